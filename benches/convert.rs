@@ -0,0 +1,108 @@
+//! BGRx to NV12 conversion throughput, `cargo bench --bench convert`.
+//!
+//! Compares `convert::bgrx_to_nv12`, NEON optimized on aarch64, with the
+//! `GstVideoConverter` that videoconvert runs, single threaded as
+//! videoconvert is by default. The largest luma difference between the two
+//! is printed along, to check that they agree.
+
+extern crate gstreamer as gst;
+extern crate gstreamer_video as gst_video;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use wayland_sink_with_video_overlay::convert::{self, Plane};
+
+const SIZES: [(&str, u32, u32); 3] = [
+    ("720p", 1280, 720),
+    ("1080p", 1920, 1080),
+    ("4K", 3840, 2160),
+];
+const RUN: Duration = Duration::from_secs(2);
+
+fn measure(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    let mut frames = 0u32;
+    while start.elapsed() < RUN {
+        f();
+        frames += 1;
+    }
+    start.elapsed() / frames
+}
+
+fn print(name: &str, method: &str, time: Duration) {
+    println!(
+        "{:>6} {:<16} {:>8.3} ms/frame {:>8.1} fps",
+        name,
+        method,
+        time.as_secs_f64() * 1e3,
+        1.0 / time.as_secs_f64()
+    );
+}
+
+fn main() {
+    gst::init().expect("Failed to initialize GStreamer");
+
+    for &(name, width, height) in &SIZES {
+        let bgrx_info = gst_video::VideoInfo::builder(gst_video::VideoFormat::Bgrx, width, height)
+            .build()
+            .unwrap();
+        let nv12_info = gst_video::VideoInfo::builder(gst_video::VideoFormat::Nv12, width, height)
+            .colorimetry(&"bt709".parse().unwrap())
+            .build()
+            .unwrap();
+
+        // A gradient, so that neighbouring pixels differ
+        let mut bgrx = gst::Buffer::with_size(bgrx_info.size()).unwrap();
+        {
+            let mut map = bgrx.get_mut().unwrap().map_writable().unwrap();
+            for (i, byte) in map.as_mut_slice().iter_mut().enumerate() {
+                *byte = (i / 4 + i % 4 * 85) as u8;
+            }
+        }
+        let src = gst_video::VideoFrame::from_buffer_readable(bgrx, &bgrx_info).unwrap();
+
+        let mut ours = vec![0u8; nv12_info.size()];
+        let time = measure(|| {
+            let (y, uv) = ours.split_at_mut(nv12_info.offset()[1]);
+            convert::bgrx_to_nv12(
+                Plane {
+                    data: src.plane_data(0).unwrap(),
+                    stride: src.plane_stride()[0] as usize,
+                },
+                Plane {
+                    data: y,
+                    stride: nv12_info.stride()[0] as usize,
+                },
+                Plane {
+                    data: uv,
+                    stride: nv12_info.stride()[1] as usize,
+                },
+                width as usize,
+                height as usize,
+            );
+            black_box(&ours);
+        });
+        print(name, "in-crate", time);
+
+        let mut converter = gst_video::VideoConverter::new(&bgrx_info, &nv12_info, None).unwrap();
+        let mut theirs = gst::Buffer::with_size(nv12_info.size()).unwrap();
+        let time = measure(|| {
+            let buffer = theirs.get_mut().unwrap();
+            let mut dest =
+                gst_video::VideoFrameRef::from_buffer_ref_writable(buffer, &nv12_info).unwrap();
+            converter.frame(&src, &mut dest);
+        });
+        print(name, "GstVideoConverter", time);
+
+        let map = theirs.map_readable().unwrap();
+        let luma = nv12_info.offset()[1];
+        let difference = ours[..luma]
+            .iter()
+            .zip(&map.as_slice()[..luma])
+            .map(|(a, b)| (*a as i32 - *b as i32).abs())
+            .max()
+            .unwrap_or(0);
+        println!("{:>6} largest luma difference {}", name, difference);
+    }
+}
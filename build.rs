@@ -0,0 +1,18 @@
+use std::env;
+use std::path::Path;
+
+use wayland_scanner::{generate_code, Side};
+
+fn main() {
+    // Protocols missing from the wayland-protocols version used by sctk
+    let out_dir = env::var("OUT_DIR").unwrap();
+    for name in &["fractional-scale-v1", "content-type-v1"] {
+        let protocol = format!("protocols/{}.xml", name);
+        println!("cargo:rerun-if-changed={}", protocol);
+        generate_code(
+            &protocol,
+            Path::new(&out_dir).join(format!("{}.rs", name.replace('-', "_"))),
+            Side::Client,
+        );
+    }
+}
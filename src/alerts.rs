@@ -0,0 +1,445 @@
+//! Alerts about what is shown and the pipeline showing it, with a severity,
+//! routed to where they should be seen.
+//!
+//! They come from:
+//!
+//! - the confidence monitor, black or frozen pictures and silent or clipping
+//!   audio,
+//! - a watchdog on the frames reaching the sink, which raises an alert when
+//!   none did for `STALL_AFTER` while playing,
+//! - network streams that keep buffering for `NETWORK_AFTER`, and resource
+//!   errors, such as an unreachable server,
+//! - the other errors and warnings of the pipeline.
+//!
+//! Each is logged, and goes to every route whose minimum severity it
+//! reaches, as set in the `[alerts]` table of the configuration file: a line
+//! over the video, a desktop notification through `notify-send`, the counts
+//! logged on exit, and the `alerts` control request.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use serde_json::{json, Value};
+use tracing::{error, info, warn};
+
+use crate::confidence;
+use crate::pipeline_state::PipelineState;
+use crate::make;
+
+/// No frame for this long while playing is a stall.
+const STALL_AFTER: Duration = Duration::from_secs(5);
+/// Buffering for this long is a network problem.
+const NETWORK_AFTER: Duration = Duration::from_secs(3);
+/// How long alerts that don't clear stay over the video.
+const SHOWN_FOR: Duration = Duration::from_secs(5);
+/// How many past alerts the control requests get.
+const RECENT: usize = 20;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown alert severity {}, expected info, warning, critical or off", _0)]
+pub struct UnknownSeverity(#[error(not(source))] String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+
+    /// The `notify-send` urgency.
+    fn urgency(self) -> &'static str {
+        match self {
+            Severity::Info => "low",
+            Severity::Warning => "normal",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The minimum severity of a route, `None` being `off`.
+pub fn parse_route(s: &str) -> Result<Option<Severity>, UnknownSeverity> {
+    match s {
+        "info" => Ok(Some(Severity::Info)),
+        "warning" => Ok(Some(Severity::Warning)),
+        "critical" => Ok(Some(Severity::Critical)),
+        "off" => Ok(None),
+        _ => Err(UnknownSeverity(s.to_owned())),
+    }
+}
+
+/// The minimum severity of the alerts each route gets, `None` for none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Routing {
+    pub osd: Option<Severity>,
+    pub notify: Option<Severity>,
+    pub metrics: Option<Severity>,
+    pub control: Option<Severity>,
+}
+
+impl Default for Routing {
+    fn default() -> Self {
+        Routing {
+            osd: Some(Severity::Warning),
+            notify: None,
+            metrics: Some(Severity::Info),
+            control: Some(Severity::Info),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Raised,
+    Cleared,
+    /// Raised, never to be cleared.
+    Once,
+}
+
+#[derive(Debug, Clone)]
+struct Alert {
+    /// Such as `black-picture` or `pipeline-error`, to clear it by.
+    kind: String,
+    severity: Severity,
+    message: String,
+    change: Change,
+}
+
+impl Alert {
+    fn to_json(&self) -> Value {
+        json!({
+            "kind": self.kind,
+            "severity": self.severity.name(),
+            "message": self.message,
+            "active": self.change == Change::Raised,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Record {
+    active: Vec<Alert>,
+    recent: VecDeque<Alert>,
+    /// Alerts raised by kind and severity, of the metrics route.
+    counts: BTreeMap<(String, Severity), u64>,
+}
+
+/// What the control requests read of the alerts, from the socket threads.
+#[derive(Clone, Default)]
+pub struct Feed(Arc<Mutex<Record>>);
+
+impl Feed {
+    /// The active alerts, the recent ones and the counts.
+    pub fn to_json(&self) -> Value {
+        let record = self.0.lock().unwrap();
+        let counts: Vec<Value> = record
+            .counts
+            .iter()
+            .map(|((kind, severity), count)| {
+                json!({ "kind": kind, "severity": severity.name(), "count": count })
+            })
+            .collect();
+        json!({
+            "active": record.active.iter().map(Alert::to_json).collect::<Vec<_>>(),
+            "recent": record.recent.iter().map(Alert::to_json).collect::<Vec<_>>(),
+            "counts": counts,
+        })
+    }
+}
+
+/// Raised and cleared alerts for a condition that lasts, such as a stall.
+#[derive(Default)]
+struct Timer {
+    since: Option<Instant>,
+    raised: bool,
+}
+
+impl Timer {
+    /// Follow the condition being `present`, returns whether to raise, or
+    /// clear, the alert.
+    fn update(&mut self, present: bool, after: Duration) -> Option<bool> {
+        match (present, self.since) {
+            (true, None) => {
+                self.since = Some(Instant::now());
+                None
+            }
+            (true, Some(since)) if !self.raised && since.elapsed() >= after => {
+                self.raised = true;
+                Some(true)
+            }
+            (false, Some(_)) => {
+                self.since = None;
+                if std::mem::replace(&mut self.raised, false) {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct Alerts {
+    routing: Routing,
+    overlay: Option<gst::Element>,
+    /// Lines over the video, with when they go away for those that don't
+    /// clear.
+    shown: Vec<(String, String, Option<Instant>)>,
+    feed: Feed,
+    /// Frames that reached the sink.
+    frames: Arc<AtomicU64>,
+    last_frames: u64,
+    stall: Timer,
+    network: Timer,
+    /// At the end of the stream, where frames stop.
+    ended: bool,
+}
+
+impl Alerts {
+    pub fn new(routing: Routing) -> Result<Self, Error> {
+        let overlay = match routing.osd {
+            Some(_) => {
+                let overlay = make("textoverlay")?;
+                overlay.set_property_from_str("halignment", "center");
+                overlay.set_property_from_str("valignment", "top");
+                overlay.set_property("font-desc", &"Sans Bold, 18")?;
+                overlay.set_property("color", &0xFFFF_6060u32)?;
+                overlay.set_property("shaded-background", &true)?;
+                overlay.set_property("silent", &true)?;
+                Some(overlay)
+            }
+            None => None,
+        };
+
+        Ok(Alerts {
+            routing,
+            overlay,
+            shown: Vec::new(),
+            feed: Feed::default(),
+            frames: Arc::new(AtomicU64::new(0)),
+            last_frames: 0,
+            stall: Timer::default(),
+            network: Timer::default(),
+            ended: false,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        self.overlay.iter().collect()
+    }
+
+    /// Watch the frames reaching `sink`, for stalls.
+    pub fn watch(&self, sink: &gst::Element) {
+        let frames = self.frames.clone();
+        let pad = sink.get_static_pad("sink").unwrap();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |_, _| {
+            frames.fetch_add(1, Ordering::Relaxed);
+            gst::PadProbeReturn::Ok
+        });
+    }
+
+    pub fn feed(&self) -> Feed {
+        self.feed.clone()
+    }
+
+    pub fn on_message(&mut self, msg: &gst::Message) {
+        use gst::MessageView;
+
+        let src = || msg.get_src().map(|src| src.get_name().to_string()).unwrap_or_default();
+        match msg.view() {
+            MessageView::Element(element) => {
+                if let Some(structure) = element.get_structure() {
+                    if structure.get_name() == confidence::ALERT {
+                        self.on_confidence(structure);
+                    }
+                }
+            }
+            MessageView::Error(err) => {
+                let error = err.get_error();
+                let kind = if error.is::<gst::ResourceError>() {
+                    "network"
+                } else {
+                    "pipeline-error"
+                };
+                let message = format!("{}: {}", src(), error);
+                self.route(kind, Severity::Critical, message, Change::Once);
+            }
+            MessageView::Warning(warning) => {
+                let message = format!("{}: {}", src(), warning.get_error());
+                self.route("pipeline-warning", Severity::Warning, message, Change::Once);
+            }
+            MessageView::Eos(_) => self.ended = true,
+            MessageView::AsyncDone(_) => self.ended = false,
+            _ => {}
+        }
+    }
+
+    fn on_confidence(&mut self, structure: &gst::StructureRef) {
+        let condition = structure.get::<&str>("condition").ok().flatten().unwrap_or("unknown");
+        let media = structure.get::<&str>("media").ok().flatten().unwrap_or("picture");
+        let seconds = structure.get_some::<f64>("seconds").unwrap_or(0.0);
+        let kind = format!("{}-{}", condition, media);
+        // Clipping is bad, no picture or sound at all worse
+        let severity = match condition {
+            "clipping" => Severity::Warning,
+            _ => Severity::Critical,
+        };
+        if structure.get_some::<bool>("active").unwrap_or(false) {
+            let message = format!("{} {} for {:.1} s", condition, media, seconds);
+            self.route(&kind, severity, message, Change::Raised);
+        } else {
+            let message = format!("{} {}, after {:.1} s", condition, media, seconds);
+            self.route(&kind, severity, message, Change::Cleared);
+        }
+    }
+
+    /// Run the watchdog and the network check, and take the lines that
+    /// timed out off the video.
+    pub fn update(&mut self, pipeline_state: &PipelineState) {
+        let frames = self.frames.load(Ordering::Relaxed);
+        let moved = frames != self.last_frames;
+        self.last_frames = frames;
+        let stalled = pipeline_state.is_playing() && !self.ended && !moved;
+        match self.stall.update(stalled, STALL_AFTER) {
+            Some(true) => {
+                let message = format!("no frame for {} s", STALL_AFTER.as_secs());
+                self.route("stall", Severity::Critical, message, Change::Raised);
+            }
+            Some(false) => {
+                let message = "frames are coming again".to_owned();
+                self.route("stall", Severity::Critical, message, Change::Cleared);
+            }
+            None => {}
+        }
+
+        let buffering = pipeline_state.buffering().is_some();
+        match self.network.update(buffering, NETWORK_AFTER) {
+            Some(true) => {
+                let message = "the stream keeps buffering".to_owned();
+                self.route("network", Severity::Warning, message, Change::Raised);
+            }
+            Some(false) => {
+                let message = "the stream is buffered".to_owned();
+                self.route("network", Severity::Warning, message, Change::Cleared);
+            }
+            None => {}
+        }
+
+        let count = self.shown.len();
+        self.shown
+            .retain(|(_, _, until)| until.map_or(true, |until| Instant::now() < until));
+        if self.shown.len() != count {
+            self.show();
+        }
+    }
+
+    /// Log `kind` and send it to its routes.
+    fn route(&mut self, kind: &str, severity: Severity, message: String, change: Change) {
+        match (change, severity) {
+            (Change::Cleared, _) => info!("Cleared: {}", message),
+            (_, Severity::Critical) => error!("Alert: {}", message),
+            (_, Severity::Warning) => warn!("Alert: {}", message),
+            (_, Severity::Info) => info!("Alert: {}", message),
+        }
+        let reaches = |route: Option<Severity>| route.map_or(false, |min| severity >= min);
+
+        if reaches(self.routing.osd) {
+            self.shown.retain(|(shown, _, _)| shown != kind);
+            match change {
+                Change::Raised => self.shown.push((kind.to_owned(), message.clone(), None)),
+                Change::Once => {
+                    let until = Some(Instant::now() + SHOWN_FOR);
+                    self.shown.push((kind.to_owned(), message.clone(), until));
+                }
+                Change::Cleared => {}
+            }
+            self.show();
+        }
+        if reaches(self.routing.notify) && change != Change::Cleared {
+            notify(severity, &message);
+        }
+
+        let alert = Alert {
+            kind: kind.to_owned(),
+            severity,
+            message,
+            change,
+        };
+        let mut record = self.feed.0.lock().unwrap();
+        if reaches(self.routing.metrics) && change != Change::Cleared {
+            *record.counts.entry((alert.kind.clone(), severity)).or_insert(0) += 1;
+        }
+        if reaches(self.routing.control) {
+            record.active.retain(|active| active.kind != alert.kind);
+            if change == Change::Raised {
+                record.active.push(alert.clone());
+            }
+            if record.recent.len() == RECENT {
+                record.recent.pop_front();
+            }
+            record.recent.push_back(alert);
+        }
+    }
+
+    fn show(&self) {
+        if let Some(overlay) = &self.overlay {
+            let lines: Vec<&str> = self.shown.iter().map(|(_, line, _)| line.as_str()).collect();
+            let _ = overlay.set_property("text", &lines.join("\n"));
+            let _ = overlay.set_property("silent", &lines.is_empty());
+        }
+    }
+
+    /// The alerts counted by the metrics route, if any were.
+    pub fn summary(&self) -> Option<String> {
+        let record = self.feed.0.lock().unwrap();
+        if record.counts.is_empty() {
+            return None;
+        }
+        let counts: Vec<String> = record
+            .counts
+            .iter()
+            .map(|((kind, severity), count)| format!("{} {} ({})", count, kind, severity))
+            .collect();
+        Some(counts.join(", "))
+    }
+}
+
+/// Show a desktop notification, without waiting for it.
+fn notify(severity: Severity, message: &str) {
+    let child = Command::new("notify-send")
+        .arg("--app-name=WaylandSink with Video Overlay")
+        .arg(format!("--urgency={}", severity.urgency()))
+        .arg(format!("Alert: {}", severity))
+        .arg(message)
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(err) => warn!("Failed to notify of an alert: {}", err),
+    }
+}
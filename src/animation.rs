@@ -0,0 +1,220 @@
+//! Small animation toolkit for UI transitions.
+//!
+//! Animations are not driven by timers: the main loop calls `tick` when a
+//! `wl_surface.frame` callback fires and keeps requesting frames as long as
+//! one of them is running, so they are synced to the compositor repaints.
+
+use std::time::{Duration, Instant};
+
+/// Easing curves, mapping linear progress in `[0, 1]` to eased progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Progress of a transition over time.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeline {
+    duration: Duration,
+    easing: Easing,
+    start: Option<Instant>,
+}
+
+impl Timeline {
+    pub fn new(duration: Duration, easing: Easing) -> Self {
+        Timeline {
+            duration,
+            easing,
+            start: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.start = Some(Instant::now());
+    }
+
+    pub fn stop(&mut self) {
+        self.start = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.start.is_some()
+    }
+
+    /// Eased progress, `1.0` once the timeline is over. The timeline stops
+    /// itself when it reaches the end.
+    pub fn progress(&mut self) -> f64 {
+        let start = match self.start {
+            Some(start) => start,
+            None => return 1.0,
+        };
+
+        let elapsed = start.elapsed();
+        if elapsed >= self.duration {
+            self.start = None;
+            return 1.0;
+        }
+
+        self.easing
+            .apply(elapsed.as_secs_f64() / self.duration.as_secs_f64())
+    }
+}
+
+/// Values that can be interpolated.
+pub trait Lerp: Copy + PartialEq {
+    fn lerp(&self, to: &Self, t: f64) -> Self;
+}
+
+impl Lerp for f64 {
+    fn lerp(&self, to: &Self, t: f64) -> Self {
+        self + (to - self) * t
+    }
+}
+
+/// A value moving towards a target along a timeline.
+#[derive(Debug, Clone, Copy)]
+pub struct Animated<T: Lerp> {
+    from: T,
+    to: T,
+    current: T,
+    timeline: Timeline,
+}
+
+impl<T: Lerp> Animated<T> {
+    pub fn new(value: T, duration: Duration, easing: Easing) -> Self {
+        Animated {
+            from: value,
+            to: value,
+            current: value,
+            timeline: Timeline::new(duration, easing),
+        }
+    }
+
+    pub fn value(&self) -> T {
+        self.current
+    }
+
+    pub fn target(&self) -> T {
+        self.to
+    }
+
+    /// Start moving towards `value`, from wherever the animation currently is.
+    pub fn animate_to(&mut self, value: T) {
+        if value == self.to {
+            return;
+        }
+
+        self.from = self.current;
+        self.to = value;
+        self.timeline.start();
+    }
+
+    /// Jump to `value`, ending the animation in progress.
+    pub fn set(&mut self, value: T) {
+        self.from = value;
+        self.to = value;
+        self.current = value;
+        self.timeline.stop();
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.timeline.is_running()
+    }
+
+    /// Advance the animation, returns whether the value changed.
+    pub fn tick(&mut self) -> bool {
+        if !self.timeline.is_running() {
+            return false;
+        }
+
+        let t = self.timeline.progress();
+        self.current = self.from.lerp(&self.to, t);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    #[test]
+    fn easings_go_from_0_to_1() {
+        for easing in &EASINGS {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+            assert_eq!(easing.apply(-1.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(2.0), 1.0, "{:?}", easing);
+            let samples: Vec<f64> = (0..=10).map(|i| easing.apply(f64::from(i) / 10.0)).collect();
+            assert!(samples.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", easing);
+        }
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn timelines_end_at_1() {
+        let mut timeline = Timeline::new(Duration::from_secs(60), Easing::Linear);
+        assert!(!timeline.is_running());
+        assert_eq!(timeline.progress(), 1.0);
+
+        timeline.start();
+        assert!(timeline.is_running());
+        assert!(timeline.progress() < 0.5);
+
+        let mut timeline = Timeline::new(Duration::from_secs(0), Easing::EaseOut);
+        timeline.start();
+        assert_eq!(timeline.progress(), 1.0);
+        assert!(!timeline.is_running());
+    }
+
+    #[test]
+    fn animated_values_reach_their_target() {
+        let mut value = Animated::new(1.0, Duration::from_secs(0), Easing::Linear);
+        assert!(!value.tick());
+
+        value.animate_to(3.0);
+        assert!(value.is_animating());
+        assert_eq!(value.target(), 3.0);
+        assert!(value.tick());
+        assert_eq!(value.value(), 3.0);
+        assert!(!value.is_animating());
+    }
+
+    #[test]
+    fn setting_a_value_ends_the_animation() {
+        let mut timeline = Timeline::new(Duration::from_secs(60), Easing::Linear);
+        timeline.start();
+        timeline.stop();
+        assert!(!timeline.is_running());
+        assert_eq!(timeline.progress(), 1.0);
+
+        let mut value = Animated::new(1.0, Duration::from_secs(60), Easing::Linear);
+        value.animate_to(3.0);
+        value.set(2.0);
+        assert!(!value.is_animating());
+        assert_eq!((value.value(), value.target()), (2.0, 2.0));
+    }
+}
@@ -0,0 +1,71 @@
+//! The color scheme of the desktop, from the settings portal.
+//!
+//! `color-scheme` of `org.freedesktop.appearance` is read once, then followed
+//! from a thread through the `SettingChanged` signal, sending whether dark is
+//! preferred to the event loop. Without a preference nothing is sent.
+
+use std::thread;
+
+use anyhow::Error;
+use sctk::reexports::calloop::channel::Sender;
+use tracing::warn;
+use zbus::fdo;
+use zvariant::{OwnedValue, Value};
+
+const PORTAL: &str = "org.freedesktop.portal.Desktop";
+const PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS: &str = "org.freedesktop.portal.Settings";
+const NAMESPACE: &str = "org.freedesktop.appearance";
+const KEY: &str = "color-scheme";
+
+/// Whether the `color-scheme` `value` prefers dark, 1, or light, 2.
+fn prefers_dark(value: &Value) -> Option<bool> {
+    match value {
+        // Read wraps the setting in one more variant
+        Value::Value(value) => prefers_dark(value),
+        Value::U32(1) => Some(true),
+        Value::U32(2) => Some(false),
+        _ => None,
+    }
+}
+
+/// Send the changes of the setting, for as long as the connection lasts.
+fn follow(connection: &zbus::Connection, sender: Sender<bool>) -> Result<(), Error> {
+    let rule = format!("type='signal',interface='{}',member='SettingChanged'", SETTINGS);
+    fdo::DBusProxy::new(connection)?.add_match(&rule)?;
+    loop {
+        let message = connection.receive_message()?;
+        let header = message.header()?;
+        if header.interface()? != Some(SETTINGS) || header.member()? != Some("SettingChanged") {
+            continue;
+        }
+        let (namespace, key, value): (String, String, OwnedValue) = message.body()?;
+        if namespace != NAMESPACE || key != KEY {
+            continue;
+        }
+        if let Some(dark) = prefers_dark(&value) {
+            if sender.send(dark).is_err() {
+                // The event loop is gone
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Send to `sender` whether the desktop prefers dark, now and whenever it
+/// changes.
+pub fn watch(sender: Sender<bool>) -> Result<(), Error> {
+    let connection = zbus::Connection::new_session()?;
+    let proxy = zbus::Proxy::new(&connection, PORTAL, PATH, SETTINGS)?;
+    let value: OwnedValue = proxy.call("Read", &(NAMESPACE, KEY))?;
+    if let Some(dark) = prefers_dark(&value) {
+        let _ = sender.send(dark);
+    }
+
+    thread::spawn(move || {
+        if let Err(err) = follow(&connection, sender) {
+            warn!("Failed to follow the color scheme: {}", err);
+        }
+    });
+    Ok(())
+}
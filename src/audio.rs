@@ -0,0 +1,249 @@
+//! Audio output with downmix, channel re-mapping and device selection.
+//!
+//! Files played through `playbin` get the output as their audio sink. The
+//! appsrc only produces video, so audio can come from a test source instead,
+//! which plays a tone on one channel at a time, announcing each channel in
+//! the log, to check how the channels are routed to the speakers of a device.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::info;
+
+use crate::confidence;
+use crate::loudness;
+use crate::options::Options;
+use crate::{make, MissingElement};
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unsupported channel count {}, expected 1, 2, 6 or 8", _0)]
+pub struct UnsupportedChannels(#[error(not(source))] u32);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid channel map {}, expected comma separated input channels", _0)]
+pub struct InvalidChannelMap(#[error(not(source))] String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown audio device {}, available: {}", name, available)]
+pub struct UnknownAudioDevice {
+    name: String,
+    available: String,
+}
+
+/// Default positions of the supported layouts, as `(channel-mask, names)`.
+fn layout(channels: u32) -> Result<(u64, &'static [&'static str]), UnsupportedChannels> {
+    match channels {
+        1 => Ok((0, &["mono"])),
+        2 => Ok((0x3, &["front left", "front right"])),
+        6 => Ok((
+            0x3f,
+            &["front left", "front right", "front center", "LFE", "rear left", "rear right"],
+        )),
+        8 => Ok((
+            0xc3f,
+            &[
+                "front left",
+                "front right",
+                "front center",
+                "LFE",
+                "rear left",
+                "rear right",
+                "side left",
+                "side right",
+            ],
+        )),
+        _ => Err(UnsupportedChannels(channels)),
+    }
+}
+
+/// The input channel each output channel takes, e.g. `1,0` swaps left and right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelMap(Vec<usize>);
+
+impl FromStr for ChannelMap {
+    type Err = InvalidChannelMap;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|channel| channel.trim().parse())
+            .collect::<Result<_, _>>()
+            .map(ChannelMap)
+            .map_err(|_| InvalidChannelMap(s.to_owned()))
+    }
+}
+
+impl ChannelMap {
+    /// `audioconvert` mix matrix for `inputs` input channels, one row per output channel.
+    fn mix_matrix(&self, inputs: u32) -> Result<gst::Array, InvalidChannelMap> {
+        let invalid = || {
+            let map: Vec<String> = self.0.iter().map(ToString::to_string).collect();
+            InvalidChannelMap(map.join(","))
+        };
+
+        let rows = self
+            .0
+            .iter()
+            .map(|&input| {
+                if input >= inputs as usize {
+                    return Err(invalid());
+                }
+                let row: Vec<gst::glib::SendValue> = (0..inputs as usize)
+                    .map(|column| (if column == input { 1.0f32 } else { 0.0 }).to_send_value())
+                    .collect();
+                Ok(gst::Array::from_owned(row).to_send_value())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(gst::Array::from_owned(rows))
+    }
+}
+
+/// Add the channel test source, playing a tone on each of `channels` in turn.
+/// Returns the `volume` element of the branch.
+pub fn add_test_branch(
+    pipeline: &gst::Pipeline,
+    channels: u32,
+    options: &Options,
+) -> Result<gst::Element, Error> {
+    let (mask, names) = layout(channels)?;
+
+    let src = make("audiotestsrc")?;
+    src.set_property("volume", &0.3f64)?;
+    let capsfilter = make("capsfilter")?;
+    let caps = gst::Caps::builder("audio/x-raw")
+        .field("format", &"F32LE")
+        .field("layout", &"interleaved")
+        .field("channels", &(channels as i32))
+        .field("channel-mask", &gst::Bitmask::new(mask))
+        .build();
+    capsfilter.set_property("caps", &caps)?;
+
+    // Silence all channels but one, moving on to the next one every second
+    let current = AtomicUsize::new(usize::MAX);
+    let pad = capsfilter.get_static_pad("src").unwrap();
+    pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+        if let Some(gst::PadProbeData::Buffer(buffer)) = &mut info.data {
+            let seconds = buffer.get_pts().seconds().unwrap_or(0) as usize;
+            let active = seconds % names.len();
+            if current.swap(active, Ordering::Relaxed) != active {
+                info!("Audio test: channel {} ({})", active, names[active]);
+            }
+
+            let buffer = buffer.make_mut();
+            if let Ok(mut map) = buffer.map_writable() {
+                for frame in map.as_mut_slice().chunks_exact_mut(4 * names.len()) {
+                    for (channel, sample) in frame.chunks_exact_mut(4).enumerate() {
+                        if channel != active {
+                            sample.copy_from_slice(&0f32.to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+        gst::PadProbeReturn::Ok
+    });
+
+    pipeline.add_many(&[&src, &capsfilter])?;
+    src.link(&capsfilter)?;
+
+    let (first, volume) = add_output(pipeline.upcast_ref(), Some(channels), options)?;
+    capsfilter.link(&first)?;
+
+    Ok(volume)
+}
+
+/// The audio sink bin for `playbin`, along with its `volume` element.
+pub fn create_sink(options: &Options) -> Result<(gst::Bin, gst::Element), Error> {
+    let bin = gst::Bin::new(Some("audio"));
+    let (first, volume) = add_output(&bin, None, options)?;
+
+    let pad = first.get_static_pad("sink").unwrap();
+    bin.add_pad(&gst::GhostPad::with_target(Some("sink"), &pad)?)?;
+
+    Ok((bin, volume))
+}
+
+/// The audio sink: the device named `name`, or the default one.
+fn create_device_sink(name: Option<&str>) -> Result<gst::Element, Error> {
+    let name = match name {
+        Some(name) => name,
+        None => return make("autoaudiosink"),
+    };
+
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Audio/Sink"), None);
+    monitor.start()?;
+    let devices = monitor.get_devices();
+    monitor.stop();
+
+    let device = devices
+        .iter()
+        .find(|device| device.get_display_name() == name)
+        .ok_or_else(|| {
+            let names: Vec<String> = devices
+                .iter()
+                .map(|device| device.get_display_name().to_string())
+                .collect();
+            UnknownAudioDevice {
+                name: name.to_owned(),
+                available: names.join(", "),
+            }
+        })?;
+
+    Ok(device
+        .create_element(None)
+        .ok_or(MissingElement("audio device sink"))?)
+}
+
+/// Add the elements playing audio with `channels` channels, re-mapped and
+/// downmixed as configured, to `bin`. Returns the first element of the
+/// branch, to link the audio to, and its `volume` element.
+fn add_output(
+    bin: &gst::Bin,
+    channels: Option<u32>,
+    options: &Options,
+) -> Result<(gst::Element, gst::Element), Error> {
+    let mut branch = Vec::new();
+
+    if let Some(map) = &options.channel_map {
+        // Without a known channel count, the map has to be a permutation
+        let channels = channels.unwrap_or(map.0.len() as u32);
+        let remap = make("audioconvert")?;
+        remap.set_property("mix-matrix", &map.mix_matrix(channels)?)?;
+        branch.push(remap);
+    }
+
+    let audioconvert = make("audioconvert")?;
+    branch.push(audioconvert);
+    if let Some(downmix) = options.downmix {
+        let (mask, _) = layout(downmix)?;
+        let capsfilter = make("capsfilter")?;
+        let caps = gst::Caps::builder("audio/x-raw")
+            .field("channels", &(downmix as i32))
+            .field("channel-mask", &gst::Bitmask::new(mask))
+            .build();
+        capsfilter.set_property("caps", &caps)?;
+        branch.push(capsfilter);
+    }
+
+    if options.loudness {
+        branch.push(loudness::meter()?);
+    }
+    // Before the volume, muting is no silence of the content
+    if options.confidence_monitor {
+        branch.push(confidence::level()?);
+    }
+    let volume = make("volume")?;
+    branch.push(volume.clone());
+    branch.push(make("audioresample")?);
+    branch.push(create_device_sink(options.audio_device.as_deref())?);
+
+    let branch: Vec<&gst::Element> = branch.iter().collect();
+    bin.add_many(&branch)?;
+    gst::Element::link_many(&branch)?;
+
+    Ok((branch[0].clone(), volume))
+}
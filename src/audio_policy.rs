@@ -0,0 +1,140 @@
+//! Which window is heard when several windows play audio.
+//!
+//! Windows register the `volume` element of their audio branch and report
+//! focus changes. With the `Focused` policy only the focused window is heard
+//! once more than one window is registered. Muting by the user goes through
+//! the policy as well, both share the `mute` property.
+
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::warn;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown audio focus policy {}, expected focused or all", _0)]
+pub struct UnknownAudioFocus(#[error(not(source))] String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioFocus {
+    /// Audio follows the keyboard focus.
+    Focused,
+    /// All windows are heard.
+    All,
+}
+
+impl FromStr for AudioFocus {
+    type Err = UnknownAudioFocus;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "focused" => Ok(AudioFocus::Focused),
+            "all" => Ok(AudioFocus::All),
+            _ => Err(UnknownAudioFocus(s.to_owned())),
+        }
+    }
+}
+
+struct Window {
+    id: usize,
+    volume: gst::Element,
+    focused: bool,
+    muted: bool,
+}
+
+struct State {
+    focus: AudioFocus,
+    windows: Vec<Window>,
+    next_id: usize,
+}
+
+impl State {
+    fn apply(&self) {
+        let exclusive = self.focus == AudioFocus::Focused && self.windows.len() > 1;
+        for window in &self.windows {
+            let mute = window.muted || (exclusive && !window.focused);
+            if let Err(err) = window.volume.set_property("mute", &mute) {
+                warn!("Failed to mute window audio: {}", err);
+            }
+        }
+    }
+}
+
+/// The policy shared by all windows.
+#[derive(Clone)]
+pub struct AudioPolicy {
+    state: Arc<Mutex<State>>,
+}
+
+impl AudioPolicy {
+    pub fn new(focus: AudioFocus) -> Self {
+        AudioPolicy {
+            state: Arc::new(Mutex::new(State {
+                focus,
+                windows: Vec::new(),
+                next_id: 0,
+            })),
+        }
+    }
+
+    /// Put the audio of a window, played through `volume`, under the policy.
+    pub fn register(&self, volume: gst::Element) -> WindowAudio {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.windows.push(Window {
+            id,
+            volume,
+            focused: false,
+            muted: false,
+        });
+        state.apply();
+
+        WindowAudio {
+            policy: self.clone(),
+            id,
+        }
+    }
+}
+
+/// The registration of a window, which leaves the policy when dropped.
+pub struct WindowAudio {
+    policy: AudioPolicy,
+    id: usize,
+}
+
+impl WindowAudio {
+    fn update<F: FnOnce(&mut Window)>(&self, f: F) {
+        let mut state = self.policy.state.lock().unwrap();
+        let id = self.id;
+        if let Some(window) = state.windows.iter_mut().find(|window| window.id == id) {
+            f(window);
+        }
+        state.apply();
+    }
+
+    pub fn set_focused(&self, focused: bool) {
+        self.update(|window| window.focused = focused);
+    }
+
+    /// Mute or unmute the window at the request of the user, returns whether
+    /// it is now muted.
+    pub fn toggle_mute(&self) -> bool {
+        let mut muted = false;
+        self.update(|window| {
+            window.muted = !window.muted;
+            muted = window.muted;
+        });
+        muted
+    }
+}
+
+impl Drop for WindowAudio {
+    fn drop(&mut self) {
+        let mut state = self.policy.state.lock().unwrap();
+        let id = self.id;
+        state.windows.retain(|window| window.id != id);
+        state.apply();
+    }
+}
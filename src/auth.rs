@@ -0,0 +1,102 @@
+//! Tokens of the control APIs, and the role each of them is given.
+//!
+//! Without tokens in the `[control]` table of the configuration file, the
+//! APIs are open to whoever can reach them, with the control role. With
+//! them, every request has to carry one: a `read-tokens` one only reads,
+//! such as the alerts, while a `control-tokens` one also drives the
+//! playback.
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid or missing token")]
+pub struct Unauthorized;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Not allowed with a read-only token")]
+pub struct Forbidden;
+
+/// What a client may do, `Read` being less than `Control`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Read,
+    Control,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Tokens(Vec<(String, Role)>);
+
+impl Tokens {
+    pub fn add(&mut self, token: String, role: Role) {
+        self.0.push((token, role));
+    }
+
+    /// Whether the APIs are open, without tokens.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `token` gives the `required` role, or more.
+    pub fn authorize(&self, token: Option<&str>, required: Role) -> Result<(), Error> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let role = token
+            .and_then(|token| {
+                // Compare them all, in the same time whatever matches
+                self.0
+                    .iter()
+                    .filter(|(known, _)| same(known.as_bytes(), token.as_bytes()))
+                    .map(|(_, role)| *role)
+                    .fold(None, |best, role| best.max(Some(role)))
+            })
+            .ok_or(Unauthorized)?;
+        if role < required {
+            return Err(Forbidden.into());
+        }
+        Ok(())
+    }
+}
+
+/// Whether `a` and `b` are equal, in a time depending only on their lengths.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_the_apis_without_tokens() {
+        let tokens = Tokens::default();
+        assert!(tokens.authorize(None, Role::Control).is_ok());
+        assert!(tokens.authorize(Some("anything"), Role::Control).is_ok());
+    }
+
+    #[test]
+    fn gives_each_token_its_role() {
+        let mut tokens = Tokens::default();
+        tokens.add("reader".into(), Role::Read);
+        tokens.add("driver".into(), Role::Control);
+        assert!(tokens.authorize(Some("reader"), Role::Read).is_ok());
+        assert!(tokens.authorize(Some("driver"), Role::Read).is_ok());
+        assert!(tokens.authorize(Some("driver"), Role::Control).is_ok());
+
+        let error = |token: Option<&str>, required: Role| {
+            tokens.authorize(token, required).unwrap_err().to_string()
+        };
+        assert_eq!(error(Some("reader"), Role::Control), "Not allowed with a read-only token");
+        assert_eq!(error(Some("drive"), Role::Read), "Invalid or missing token");
+        assert_eq!(error(None, Role::Read), "Invalid or missing token");
+    }
+
+    #[test]
+    fn takes_the_highest_role_of_a_token() {
+        let mut tokens = Tokens::default();
+        tokens.add("token".into(), Role::Control);
+        tokens.add("token".into(), Role::Read);
+        assert!(tokens.authorize(Some("token"), Role::Control).is_ok());
+    }
+}
@@ -0,0 +1,214 @@
+//! The window background around the video.
+//!
+//! The gradient is drawn once into a small buffer, which `wp_viewporter`
+//! stretches over the window: resizes only change the viewport destination,
+//! the buffer is neither reallocated nor redrawn.
+//!
+//! Under the video, the background is cleared to black so that none of the
+//! gradient peeks through letterboxing. This is a single black pixel on a
+//! subsurface right above the window surface, below the video, stretched
+//! over the video rectangle by its own viewport: the video moving around is
+//! a couple of requests as well.
+//!
+//! Both buffers are drawn by the `Uploader` thread, the window shows once
+//! they come back through `on_upload`.
+
+use anyhow::{anyhow, Error};
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::{
+    wl_compositor, wl_shm, wl_subcompositor, wl_subsurface, wl_surface,
+};
+use sctk::reexports::client::{Attached, Main};
+use sctk::reexports::protocols::viewporter::client::{wp_viewport, wp_viewporter};
+
+use crate::render_rect::Rect;
+use crate::scale::SurfaceScale;
+use crate::upload::{Upload, Uploader};
+use crate::ThemedFrameExample;
+
+/// Size of the gradient buffer, which is smooth enough to be stretched.
+const GRADIENT_SIZE: u32 = 256;
+const CLEAR: u32 = 0xFF00_0000;
+
+/// The gradient color at `(x, y)` in a buffer of `width` by `height`.
+fn gradient(x: u32, y: u32, (width, height): (u32, u32)) -> u32 {
+    let r: u32 = (((width - x) * 0xFF) / width).min(((height - y) * 0xFF) / height);
+    let g: u32 = ((x * 0xFF) / width).min(((height - y) * 0xFF) / height);
+    let b: u32 = (((width - x) * 0xFF) / width).min((y * 0xFF) / height);
+    (0xFF << 24) + (r << 16) + (g << 8) + b
+}
+
+/// A buffer on its way from the `Uploader`, then uploaded.
+enum Pixels {
+    Pending(u64),
+    Uploaded(Upload),
+}
+
+impl Pixels {
+    /// Keep `upload` if it is the one waited for.
+    fn on_upload(&mut self, upload: &mut Option<Upload>) {
+        if let Pixels::Pending(id) = *self {
+            if upload.as_ref().map(|upload| upload.id) == Some(id) {
+                *self = Pixels::Uploaded(upload.take().unwrap());
+            }
+        }
+    }
+
+    fn get(&self) -> Option<&Upload> {
+        match self {
+            Pixels::Pending(_) => None,
+            Pixels::Uploaded(upload) => Some(upload),
+        }
+    }
+}
+
+/// The black layer under the video.
+struct Clear {
+    surface: wl_surface::WlSurface,
+    subsurface: wl_subsurface::WlSubsurface,
+    viewport: Main<wp_viewport::WpViewport>,
+    pixel: Pixels,
+    mapped: bool,
+}
+
+impl Clear {
+    fn new(
+        env: &Environment<ThemedFrameExample>,
+        parent: &wl_surface::WlSurface,
+        viewporter: &wp_viewporter::WpViewporter,
+        format: wl_shm::Format,
+        uploader: &mut Uploader,
+    ) -> Result<Self, Error> {
+        let surface = env.create_surface().detach();
+        let subcompositor = env.require_global::<wl_subcompositor::WlSubcompositor>();
+        let subsurface = subcompositor.get_subsurface(&surface, parent).detach();
+        // Below the video and the other subsurfaces
+        subsurface.place_above(parent);
+
+        // Pointer events go to the window below, as with the video
+        let region = env.require_global::<wl_compositor::WlCompositor>().create_region();
+        surface.set_input_region(Some(&region));
+        region.destroy();
+
+        let viewport = viewporter.get_viewport(&surface);
+        let pixel = uploader.upload((1, 1), format, |_, _| CLEAR);
+
+        Ok(Clear {
+            surface,
+            subsurface,
+            viewport,
+            pixel: Pixels::Pending(pixel),
+            mapped: false,
+        })
+    }
+
+    /// Cover `rect`, applied with the next commit of the window surface.
+    fn set_rect(&mut self, shm: &wl_shm::WlShm, rect: Rect) {
+        // Left unmapped until the pixel is uploaded
+        let mapped = rect.width > 0 && rect.height > 0 && self.pixel.get().is_some();
+        if mapped {
+            self.subsurface.set_position(rect.x, rect.y);
+            self.viewport.set_destination(rect.width, rect.height);
+        }
+        // Moving the surface around doesn't need a new buffer. Buffers are
+        // destroyed once released, so one is made for every mapping.
+        if mapped && !self.mapped {
+            let buffer = self.pixel.get().unwrap().buffer(shm);
+            self.surface.attach(Some(&buffer), 0, 0);
+            self.surface.damage_buffer(0, 0, 1, 1);
+        } else if !mapped && self.mapped {
+            self.surface.attach(None, 0, 0);
+        }
+        self.mapped = mapped;
+        self.surface.commit();
+    }
+}
+
+impl Drop for Clear {
+    fn drop(&mut self) {
+        self.viewport.destroy();
+        self.subsurface.destroy();
+        self.surface.destroy();
+    }
+}
+
+pub struct Background {
+    shm: Attached<wl_shm::WlShm>,
+    gradient: Pixels,
+    attached: bool,
+    clear: Clear,
+    shown: Option<((u32, u32), Rect)>,
+}
+
+impl Background {
+    /// The background of `surface`, which needs wp_viewporter, drawn by
+    /// `uploader` in `format`, one of those `canvas::preferred_format` picks
+    /// for opaque buffers.
+    pub fn new(
+        env: &Environment<ThemedFrameExample>,
+        surface: &wl_surface::WlSurface,
+        format: wl_shm::Format,
+        uploader: &mut Uploader,
+    ) -> Result<Self, Error> {
+        let viewporter = env
+            .manager
+            .instantiate_exact::<wp_viewporter::WpViewporter>(1)
+            .map_err(|_| anyhow!("The compositor doesn't support wp_viewporter"))?;
+        let clear = Clear::new(env, surface, &viewporter, format, uploader)?;
+        viewporter.destroy();
+
+        let size = (GRADIENT_SIZE, GRADIENT_SIZE);
+        let gradient = uploader.upload(size, format, move |x, y| gradient(x, y, size));
+
+        Ok(Background {
+            shm: env.require_global(),
+            gradient: Pixels::Pending(gradient),
+            attached: false,
+            clear,
+            shown: None,
+        })
+    }
+
+    /// Take `upload` if it is one of the background buffers, which are
+    /// shown from the next `update`.
+    pub fn on_upload(&mut self, upload: Upload) {
+        let mut upload = Some(upload);
+        self.gradient.on_upload(&mut upload);
+        self.clear.pixel.on_upload(&mut upload);
+        if upload.is_none() {
+            // Shown by the next update, even if nothing moved
+            self.shown = None;
+        }
+    }
+
+    /// Show the background of a window of `dimensions` with the video at
+    /// `video`, if it isn't already. `scaling` holds the viewport of
+    /// `surface`.
+    pub fn update(
+        &mut self,
+        surface: &wl_surface::WlSurface,
+        scaling: &SurfaceScale,
+        dimensions: (u32, u32),
+        video: Rect,
+    ) {
+        if self.shown == Some((dimensions, video)) {
+            return;
+        }
+
+        // The gradient is attached once, the surface keeps it after the
+        // compositor released it
+        if let (false, Some(gradient)) = (self.attached, self.gradient.get()) {
+            let buffer = gradient.buffer(&self.shm);
+            surface.attach(Some(&buffer), 0, 0);
+            surface.damage_buffer(0, 0, gradient.width() as i32, gradient.height() as i32);
+            self.attached = true;
+        }
+        if let Some(viewport) = scaling.viewport() {
+            viewport.set_destination(dimensions.0 as i32, dimensions.1 as i32);
+        }
+        self.clear.set_rect(&self.shm, video);
+        surface.commit();
+
+        self.shown = Some((dimensions, video));
+    }
+}
@@ -0,0 +1,181 @@
+//! Brightness, contrast, hue and saturation of the video, adjusted live.
+//!
+//! A `videobalance` among the display filters takes the values, passing the
+//! video through untouched while they are all at their defaults. A
+//! textoverlay reads out the adjustment changed last for a couple of
+//! seconds. Keys select an adjustment and step it, or reset them all, and
+//! the `balance` control request sets one to a value.
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::warn;
+
+use crate::make;
+
+const READOUT_DURATION: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown adjustment {}, expected brightness, contrast, hue or saturation", _0)]
+pub struct UnknownAdjustment(#[error(not(source))] String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Adjustment {
+    Brightness,
+    Contrast,
+    Hue,
+    Saturation,
+}
+
+impl Adjustment {
+    const ALL: [Adjustment; 4] = [
+        Adjustment::Brightness,
+        Adjustment::Contrast,
+        Adjustment::Hue,
+        Adjustment::Saturation,
+    ];
+
+    /// The name of the adjustment, which is also its videobalance property.
+    fn name(self) -> &'static str {
+        match self {
+            Adjustment::Brightness => "brightness",
+            Adjustment::Contrast => "contrast",
+            Adjustment::Hue => "hue",
+            Adjustment::Saturation => "saturation",
+        }
+    }
+
+    /// The range of values of videobalance.
+    fn range(self) -> (f64, f64) {
+        match self {
+            Adjustment::Brightness | Adjustment::Hue => (-1.0, 1.0),
+            Adjustment::Contrast | Adjustment::Saturation => (0.0, 2.0),
+        }
+    }
+
+    fn default_value(self) -> f64 {
+        match self {
+            Adjustment::Brightness | Adjustment::Hue => 0.0,
+            Adjustment::Contrast | Adjustment::Saturation => 1.0,
+        }
+    }
+
+    fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|adjustment| *adjustment == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl FromStr for Adjustment {
+    type Err = UnknownAdjustment;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|adjustment| adjustment.name() == s)
+            .ok_or_else(|| UnknownAdjustment(s.to_owned()))
+    }
+}
+
+pub struct Balance {
+    videobalance: gst::Element,
+    overlay: gst::Element,
+    /// What the step keys change.
+    selected: Adjustment,
+    /// When the readout goes away.
+    readout_until: Option<Instant>,
+}
+
+impl Balance {
+    pub fn new() -> Result<Self, Error> {
+        let videobalance = make("videobalance")?;
+        let overlay = make("textoverlay")?;
+        overlay.set_property_from_str("halignment", "center");
+        overlay.set_property_from_str("valignment", "bottom");
+        overlay.set_property("font-desc", &"Sans, 16")?;
+        overlay.set_property("shaded-background", &true)?;
+        overlay.set_property("silent", &true)?;
+
+        Ok(Balance {
+            videobalance,
+            overlay,
+            selected: Adjustment::Brightness,
+            readout_until: None,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.videobalance, &self.overlay]
+    }
+
+    pub fn value(&self, adjustment: Adjustment) -> f64 {
+        self.videobalance
+            .get_property(adjustment.name())
+            .ok()
+            .and_then(|value| value.get_some::<f64>().ok())
+            .unwrap_or_else(|| adjustment.default_value())
+    }
+
+    /// Show the value of `adjustment`, selected on the way.
+    fn read_out(&mut self, adjustment: Adjustment) {
+        self.selected = adjustment;
+        let text = format!("{} {:+.2}", adjustment.name(), self.value(adjustment));
+        let _ = self.overlay.set_property("text", &text);
+        let _ = self.overlay.set_property("silent", &false);
+        self.readout_until = Some(Instant::now() + READOUT_DURATION);
+    }
+
+    /// Set `adjustment` to `value`, clamped to its range, without the
+    /// readout.
+    pub fn restore(&self, adjustment: Adjustment, value: f64) {
+        let (min, max) = adjustment.range();
+        let value = value.clamp(min, max);
+        if let Err(err) = self.videobalance.set_property(adjustment.name(), &value) {
+            warn!("Failed to set the {}: {}", adjustment.name(), err);
+        }
+    }
+
+    /// Set `adjustment` to `value`, clamped to its range.
+    pub fn set(&mut self, adjustment: Adjustment, value: f64) {
+        self.restore(adjustment, value);
+        self.read_out(adjustment);
+    }
+
+    /// Select the next adjustment for the step keys.
+    pub fn select_next(&mut self) {
+        self.read_out(self.selected.next());
+    }
+
+    /// Step the selected adjustment by a twentieth of its range, up or down.
+    pub fn step(&mut self, up: bool) {
+        let (min, max) = self.selected.range();
+        let step = (max - min) / 20.0;
+        let value = self.value(self.selected) + if up { step } else { -step };
+        self.set(self.selected, value);
+    }
+
+    /// Put every adjustment back to its default.
+    pub fn reset(&mut self) {
+        for adjustment in &Adjustment::ALL {
+            let _ = self
+                .videobalance
+                .set_property(adjustment.name(), &adjustment.default_value());
+        }
+        let _ = self.overlay.set_property("text", &"balance reset");
+        let _ = self.overlay.set_property("silent", &false);
+        self.readout_until = Some(Instant::now() + READOUT_DURATION);
+    }
+
+    /// Hide the readout once it has been shown long enough.
+    pub fn update(&mut self) {
+        if self.readout_until.map_or(false, |until| Instant::now() >= until) {
+            self.readout_until = None;
+            let _ = self.overlay.set_property("silent", &true);
+        }
+    }
+}
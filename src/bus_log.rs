@@ -0,0 +1,279 @@
+//! Recording of the bus messages of a run, and their replay.
+//!
+//! Each message is written on a line of its own, as the time since the
+//! recording started, the message type, the path of its source and its
+//! structure serialized the way `gst-launch-1.0` prints them, separated by
+//! tabs. Replaying the file rebuilds the messages and feeds them through the
+//! same handler as the live ones, to reproduce how a run was handled without
+//! the media or the compositor that produced it.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::glib;
+use gst::prelude::*;
+use tracing::warn;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid bus log line {}: {}", line, reason)]
+struct InvalidLine {
+    line: usize,
+    #[error(not(source))]
+    reason: String,
+}
+
+/// Writes the bus messages of a run to a file.
+pub struct BusRecorder {
+    file: BufWriter<File>,
+    start: Instant,
+}
+
+impl BusRecorder {
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        Ok(BusRecorder {
+            file: BufWriter::new(File::create(path)?),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, msg: &gst::Message) {
+        let src = msg
+            .get_src()
+            .map(|src| src.get_path_string().to_string())
+            .unwrap_or_default();
+        let structure = msg
+            .get_structure()
+            .map(|structure| structure.to_string())
+            .unwrap_or_default();
+        let line = format!(
+            "{:.6}\t{:?}\t{}\t{}",
+            self.start.elapsed().as_secs_f64(),
+            msg.get_type(),
+            src,
+            structure
+        );
+        // Flushed on every message, the recording is most useful when the
+        // run ends badly
+        if let Err(err) = writeln!(self.file, "{}", line).and_then(|_| self.file.flush()) {
+            warn!("Failed to record a bus message: {}", err);
+        }
+    }
+}
+
+/// A message of a recording.
+pub struct Recorded {
+    /// Seconds since the recording started.
+    pub time: f64,
+    /// Path of the element that posted the message.
+    pub src: String,
+    pub message: gst::Message,
+}
+
+/// Read back the messages recorded in `path`.
+///
+/// End-of-stream, error, warning and QoS messages are rebuilt as such, as
+/// they are the ones the application reacts to. The others come back as
+/// application messages holding the recorded structure. Rebuilt messages
+/// have no source, the elements they came from only existed in the
+/// recorded run.
+pub fn replay(path: &Path) -> Result<Vec<Recorded>, Error> {
+    let mut recorded = Vec::new();
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = |reason: &str| InvalidLine {
+            line: index + 1,
+            reason: reason.to_owned(),
+        };
+
+        let mut fields = line.splitn(4, '\t');
+        let mut field = || fields.next().ok_or_else(|| invalid("missing fields"));
+        let time = field()?.parse().map_err(|_| invalid("invalid time"))?;
+        let kind = field()?.to_owned();
+        let src = field()?.to_owned();
+        let structure = match field()? {
+            "" => None,
+            structure => Some(
+                gst::Structure::from_str(structure).map_err(|_| invalid("invalid structure"))?,
+            ),
+        };
+
+        let message = rebuild(&kind, structure).ok_or_else(|| invalid("unexpected message"))?;
+        recorded.push(Recorded { time, src, message });
+    }
+    Ok(recorded)
+}
+
+fn rebuild(kind: &str, structure: Option<gst::Structure>) -> Option<gst::Message> {
+    match (kind, structure) {
+        ("EOS", _) => Some(gst::message::Eos::new()),
+        ("ERROR", Some(structure)) => {
+            let (error, debug) = error_fields(&structure)?;
+            Some(error_message(&error, &debug, false))
+        }
+        ("WARNING", Some(structure)) => {
+            let (error, debug) = error_fields(&structure)?;
+            Some(error_message(&error, &debug, true))
+        }
+        ("QOS", Some(structure)) => {
+            let time = |name: &str| structure.get_some::<u64>(name).map(gst::ClockTime::from);
+            Some(
+                gst::message::Qos::builder(
+                    structure.get_some::<bool>("live").ok()?,
+                    time("running-time").ok()?,
+                    time("stream-time").ok()?,
+                    time("timestamp").ok()?,
+                    time("duration").ok()?,
+                )
+                .values(
+                    structure.get_some::<i64>("jitter").ok()?,
+                    structure.get_some::<f64>("proportion").ok()?,
+                    structure.get_some::<i32>("quality").ok()?,
+                )
+                .build(),
+            )
+        }
+        (_, Some(structure)) => Some(gst::message::Application::new(structure)),
+        (_, None) => None,
+    }
+}
+
+fn error_fields(structure: &gst::StructureRef) -> Option<(glib::Error, String)> {
+    let error = structure.get::<glib::Error>("gerror").ok()??;
+    let debug = structure.get::<String>("debug").ok()?.unwrap_or_default();
+    Some((error, debug))
+}
+
+/// An error or warning message for `error`, in the GStreamer error domain it
+/// belongs to, or as a generic core error for the others.
+fn error_message(error: &glib::Error, debug: &str, warning: bool) -> gst::Message {
+    fn build<T: gst::MessageErrorDomain>(
+        kind: T,
+        error: &glib::Error,
+        debug: &str,
+        warning: bool,
+    ) -> gst::Message {
+        let message = error.to_string();
+        if warning {
+            gst::message::Warning::builder(kind, &message).debug(debug).build()
+        } else {
+            gst::message::Error::builder(kind, &message).debug(debug).build()
+        }
+    }
+
+    if let Some(kind) = error.kind::<gst::CoreError>() {
+        build(kind, error, debug, warning)
+    } else if let Some(kind) = error.kind::<gst::LibraryError>() {
+        build(kind, error, debug, warning)
+    } else if let Some(kind) = error.kind::<gst::ResourceError>() {
+        build(kind, error, debug, warning)
+    } else if let Some(kind) = error.kind::<gst::StreamError>() {
+        build(kind, error, debug, warning)
+    } else {
+        build(gst::CoreError::Failed, error, debug, warning)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qos() -> gst::Message {
+        gst::message::Qos::builder(
+            false,
+            gst::ClockTime::from_mseconds(40),
+            gst::ClockTime::from_mseconds(40),
+            gst::ClockTime::from_mseconds(40),
+            gst::ClockTime::from_mseconds(20),
+        )
+        .values(-1500, 0.5, 1_000_000)
+        .build()
+    }
+
+    /// Record `messages` and read them back.
+    fn round_trip(messages: &[gst::Message]) -> Vec<Recorded> {
+        gst::init().unwrap();
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let mut recorder = BusRecorder::create(&path).unwrap();
+        for message in messages {
+            recorder.record(message);
+        }
+        drop(recorder);
+        replay(&path).unwrap()
+    }
+
+    #[test]
+    fn replays_in_order() {
+        gst::init().unwrap();
+        let messages = [
+            gst::message::Warning::builder(gst::CoreError::Clock, "Clock drift").build(),
+            qos(),
+            gst::message::Eos::new(),
+        ];
+        let recorded = round_trip(&messages);
+        let types: Vec<_> = recorded.iter().map(|r| r.message.get_type()).collect();
+        assert_eq!(
+            types,
+            [gst::MessageType::Warning, gst::MessageType::Qos, gst::MessageType::Eos]
+        );
+        assert!(recorded.windows(2).all(|pair| pair[0].time <= pair[1].time));
+    }
+
+    #[test]
+    fn replays_errors_in_their_domain() {
+        gst::init().unwrap();
+        let error = gst::message::Error::builder(gst::StreamError::Decode, "Corrupt frame")
+            .debug("h264parse0")
+            .build();
+        let recorded = round_trip(&[error]);
+        match recorded[0].message.view() {
+            gst::MessageView::Error(error) => {
+                let err = error.get_error();
+                assert_eq!(err.kind::<gst::StreamError>(), Some(gst::StreamError::Decode));
+                assert_eq!(err.to_string(), "Corrupt frame");
+                assert_eq!(error.get_debug().as_deref(), Some("h264parse0"));
+            }
+            _ => panic!("Expected an error, got {:?}", recorded[0].message),
+        }
+    }
+
+    #[test]
+    fn replays_qos_values() {
+        let recorded = round_trip(&[qos()]);
+        match recorded[0].message.view() {
+            gst::MessageView::Qos(qos) => {
+                assert_eq!(qos.get_values(), (-1500, 0.5, 1_000_000));
+                let (live, _, _, timestamp, duration) = qos.get();
+                assert!(!live);
+                assert_eq!(timestamp, gst::ClockTime::from_mseconds(40));
+                assert_eq!(duration, gst::ClockTime::from_mseconds(20));
+            }
+            _ => panic!("Expected a QoS message, got {:?}", recorded[0].message),
+        }
+    }
+
+    #[test]
+    fn replays_other_messages_as_application_ones() {
+        gst::init().unwrap();
+        let structure = gst::Structure::builder("level").field("rms", &-20.5f64).build();
+        let recorded = round_trip(&[gst::message::Element::new(structure.clone())]);
+        assert_eq!(recorded[0].message.get_type(), gst::MessageType::Application);
+        assert_eq!(recorded[0].message.get_structure(), Some(structure.as_ref()));
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        gst::init().unwrap();
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        std::fs::write(&path, "0.5\tEOS\t\t\nnot-a-time\tEOS\t\t\n").unwrap();
+        let err = replay(&path).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid bus log line 2: invalid time");
+    }
+}
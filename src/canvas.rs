@@ -0,0 +1,315 @@
+//! Software drawing into wl_shm buffers.
+//!
+//! A `Canvas` draws straight into the memory of a `MemPool`, which is then
+//! attached to a surface, so UI around the video can be drawn without
+//! dealing with wl_shm details. It can also draw into plain memory, for UI
+//! that is blended into the video frames instead.
+
+use std::io;
+
+use sctk::reexports::client::protocol::{wl_shm, wl_surface};
+use sctk::shm::MemPool;
+
+/// Rows of the 3x5 glyphs, most significant of the three bits on the left.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        c if c.is_ascii_lowercase() => glyph(c.to_ascii_uppercase()),
+        _ => [0; 5],
+    }
+}
+
+/// Width of `text` drawn at `scale`, glyphs being 3 pixels wide plus 1 of spacing.
+pub fn text_width(text: &str, scale: i32) -> i32 {
+    text.chars().count() as i32 * 4 * scale - scale
+}
+
+/// Text height at `scale`.
+pub fn text_height(scale: i32) -> i32 {
+    5 * scale
+}
+
+/// The 32-bit formats a `Canvas` can draw in.
+const FORMATS: [wl_shm::Format; 4] = [
+    wl_shm::Format::Xrgb8888,
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Xbgr8888,
+    wl_shm::Format::Abgr8888,
+];
+
+/// The best of the `available` formats, as advertised by wl_shm, for a
+/// buffer which needs an alpha channel or not. Without alpha, the compositor
+/// doesn't have to blend the buffer.
+pub fn preferred_format(available: &[wl_shm::Format], alpha: bool) -> wl_shm::Format {
+    let has_alpha = |format: &wl_shm::Format| {
+        *format == wl_shm::Format::Argb8888 || *format == wl_shm::Format::Abgr8888
+    };
+
+    FORMATS
+        .iter()
+        .filter(|format| available.contains(format))
+        .find(|format| has_alpha(format) == alpha)
+        .or_else(|| FORMATS.iter().find(|format| available.contains(format) && has_alpha(format)))
+        // ARGB8888 is supported by all compositors
+        .map_or(wl_shm::Format::Argb8888, |format| *format)
+}
+
+/// Bytes of `color` in memory. wl_shm formats are little-endian, the red and
+/// blue channels are swapped in the BGR ones.
+pub fn pack(format: wl_shm::Format, color: u32) -> [u8; 4] {
+    match format {
+        wl_shm::Format::Xbgr8888 | wl_shm::Format::Abgr8888 => {
+            let swapped = (color & 0xFF00_FF00) | ((color >> 16) & 0xFF) | ((color & 0xFF) << 16);
+            swapped.to_le_bytes()
+        }
+        _ => color.to_le_bytes(),
+    }
+}
+
+fn unpack(format: wl_shm::Format, bytes: [u8; 4]) -> u32 {
+    // swapping red and blue is its own inverse
+    u32::from_le_bytes(pack(format, u32::from_le_bytes(bytes)))
+}
+
+/// What a `Canvas` draws into.
+enum Pixels<'a> {
+    Pool(&'a mut MemPool),
+    Memory(&'a mut [u8]),
+}
+
+/// A drawing surface over a `MemPool`. Colors are premultiplied `0xAARRGGBB`
+/// values, whatever the pixel format of the buffer.
+pub struct Canvas<'a> {
+    pixels: Pixels<'a>,
+    width: i32,
+    height: i32,
+    format: wl_shm::Format,
+}
+
+impl<'a> Canvas<'a> {
+    /// Resize `pool` to hold a `width` x `height` ARGB8888 buffer and draw into it.
+    pub fn new(pool: &'a mut MemPool, width: u32, height: u32) -> io::Result<Canvas<'a>> {
+        Self::with_format(pool, width, height, wl_shm::Format::Argb8888)
+    }
+
+    /// Same as `new`, for a buffer in `format`, which has to be one of the
+    /// 32-bit RGB formats.
+    pub fn with_format(
+        pool: &'a mut MemPool,
+        width: u32,
+        height: u32,
+        format: wl_shm::Format,
+    ) -> io::Result<Canvas<'a>> {
+        if !FORMATS.contains(&format) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported canvas format {:?}", format),
+            ));
+        }
+
+        pool.resize((4 * width * height) as usize)?;
+        Ok(Canvas {
+            pixels: Pixels::Pool(pool),
+            width: width as i32,
+            height: height as i32,
+            format,
+        })
+    }
+
+    /// Same as `with_format`, drawing into `memory` rather than a pool. Such
+    /// a canvas has nothing to attach to a surface.
+    pub fn from_memory(
+        memory: &'a mut [u8],
+        width: u32,
+        height: u32,
+        format: wl_shm::Format,
+    ) -> io::Result<Canvas<'a>> {
+        if !FORMATS.contains(&format) || memory.len() < (4 * width * height) as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported canvas of {}x{} in {:?}", width, height, format),
+            ));
+        }
+
+        Ok(Canvas {
+            pixels: Pixels::Memory(memory),
+            width: width as i32,
+            height: height as i32,
+            format,
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn memory(&mut self) -> &mut [u8] {
+        match &mut self.pixels {
+            Pixels::Pool(pool) => &mut pool.mmap()[..],
+            Pixels::Memory(memory) => &mut memory[..],
+        }
+    }
+
+    fn offset(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        } else {
+            Some(4 * (y * self.width + x) as usize)
+        }
+    }
+
+    /// The color at `x`, `y`, transparent outside of the canvas.
+    pub fn pixel(&mut self, x: i32, y: i32) -> u32 {
+        match self.offset(x, y) {
+            Some(offset) => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(&self.memory()[offset..offset + 4]);
+                unpack(self.format, bytes)
+            }
+            None => 0,
+        }
+    }
+
+    /// Set the color at `x`, `y`, ignored outside of the canvas.
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: u32) {
+        if let Some(offset) = self.offset(x, y) {
+            let bytes = pack(self.format, color);
+            self.memory()[offset..offset + 4].copy_from_slice(&bytes);
+        }
+    }
+
+    pub fn fill(&mut self, color: u32) {
+        let (width, height) = (self.width, self.height);
+        self.fill_rect(0, 0, width, height, color);
+    }
+
+    /// Fill a rectangle, clipped to the canvas.
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u32) {
+        let (x0, x1) = (x.max(0), (x + width).min(self.width));
+        let (y0, y1) = (y.max(0), (y + height).min(self.height));
+        if x0 >= x1 {
+            return;
+        }
+
+        let bytes = pack(self.format, color);
+        let stride = 4 * self.width as usize;
+        let memory = self.memory();
+        for y in y0..y1 {
+            let line = &mut memory[y as usize * stride..(y as usize + 1) * stride];
+            for pixel in line[4 * x0 as usize..4 * x1 as usize].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&bytes);
+            }
+        }
+    }
+
+    /// Copy `pixels`, an image `width` pixels wide, with its top left corner
+    /// at `x`, `y`, clipped to the canvas.
+    pub fn blit(&mut self, x: i32, y: i32, width: u32, pixels: &[u32]) {
+        for (row, line) in pixels.chunks_exact(width as usize).enumerate() {
+            for (column, &color) in line.iter().enumerate() {
+                self.set_pixel(x + column as i32, y + row as i32, color);
+            }
+        }
+    }
+
+    /// Draw `text` with its top left corner at `x`, `y`, in the built-in
+    /// pixel font magnified `scale` times. Letters are all drawn uppercase,
+    /// characters without a glyph as spaces.
+    pub fn draw_text(&mut self, x: i32, y: i32, scale: i32, text: &str, color: u32) {
+        for (index, c) in text.chars().enumerate() {
+            let left = x + index as i32 * 4 * scale;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        self.fill_rect(
+                            left + column * scale,
+                            y + row as i32 * scale,
+                            scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Attach what was drawn to `surface`, damage and commit it.
+    pub fn attach(self, surface: &wl_surface::WlSurface) {
+        let (width, height) = (self.width, self.height);
+        self.attach_damaged(surface, &[(0, 0, width, height)]);
+    }
+
+    /// Same as `attach`, only damaging `regions`, as `(x, y, width, height)`
+    /// in buffer coordinates. They have to cover everything that differs from
+    /// the buffer attached before.
+    pub fn attach_damaged(
+        self,
+        surface: &wl_surface::WlSurface,
+        regions: &[(i32, i32, i32, i32)],
+    ) {
+        let pool = match self.pixels {
+            Pixels::Pool(pool) => pool,
+            Pixels::Memory(_) => return,
+        };
+        let stride = 4 * self.width;
+        let buffer = pool.buffer(0, self.width, self.height, stride, self.format);
+        surface.attach(Some(&buffer), 0, 0);
+        for &(x, y, width, height) in regions {
+            surface.damage_buffer(x, y, width, height);
+        }
+        surface.commit();
+    }
+}
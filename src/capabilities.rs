@@ -0,0 +1,129 @@
+//! What the compositor and the video sink support.
+//!
+//! `Capabilities::probe` lists the optional protocols among the globals of
+//! the compositor, and the formats the sink accepts. Embedders can adapt
+//! their interface to it, such as hiding a pinch hint without pointer
+//! gestures, and it serializes with serde for dashboards:
+//!
+//! ```ignore
+//! let capabilities = Capabilities::probe(&env, &sink);
+//! if !capabilities.protocols.viewporter {
+//!     // No fractional scaling, nor a background behind the video
+//! }
+//! println!("{}", serde_json::to_string(&capabilities)?);
+//! ```
+
+use gst::prelude::*;
+use sctk::environment::Environment;
+use serde::{Deserialize, Serialize};
+
+/// The interfaces of the optional protocols, as listed in `Protocols`.
+pub mod interfaces {
+    pub const VIEWPORTER: &str = "wp_viewporter";
+    pub const FRACTIONAL_SCALE: &str = "wp_fractional_scale_manager_v1";
+    pub const DMABUF: &str = "zwp_linux_dmabuf_v1";
+    pub const PRESENTATION_TIME: &str = "wp_presentation";
+    pub const IDLE_INHIBIT: &str = "zwp_idle_inhibit_manager_v1";
+    pub const DECORATION: &str = "zxdg_decoration_manager_v1";
+    pub const CONTENT_TYPE: &str = "wp_content_type_manager_v1";
+    pub const POINTER_GESTURES: &str = "zwp_pointer_gestures_v1";
+    pub const LAYER_SHELL: &str = "zwlr_layer_shell_v1";
+}
+
+/// The optional protocols the compositor offers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Protocols {
+    /// `wp_viewporter`, for cropping and scaling surfaces.
+    pub viewporter: bool,
+    /// `wp_fractional_scale_manager_v1`, for fractional output scales.
+    pub fractional_scale: bool,
+    /// `zwp_linux_dmabuf_v1`, for frames without a copy.
+    pub dmabuf: bool,
+    /// `wp_presentation`, for the time frames are shown.
+    pub presentation_time: bool,
+    /// `zwp_idle_inhibit_manager_v1`, to keep the screen on.
+    pub idle_inhibit: bool,
+    /// `zxdg_decoration_manager_v1`, for window frames by the compositor.
+    pub decoration: bool,
+    /// `wp_content_type_manager_v1`, for content type hints.
+    pub content_type: bool,
+    /// `zwp_pointer_gestures_v1`, for touchpad pinches.
+    pub pointer_gestures: bool,
+    /// `zwlr_layer_shell_v1`, for surfaces on the layers of the desktop.
+    pub layer_shell: bool,
+}
+
+/// What the sink the video is handed to supports.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sink {
+    /// The name of its factory, such as `waylandsink`.
+    pub name: String,
+    /// The raw video formats it accepts, such as `NV12`.
+    pub formats: Vec<String>,
+    /// Whether it takes dmabuf memory.
+    pub dmabuf: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub protocols: Protocols,
+    pub sink: Sink,
+}
+
+impl Capabilities {
+    /// Probe the compositor of `env` and `sink`. The sink tells its formats
+    /// best once it is handed the display.
+    pub fn probe<E>(env: &Environment<E>, sink: &gst::Element) -> Self {
+        let globals = env.manager.list();
+        let has = |interface: &str| globals.iter().any(|(_, name, _)| name == interface);
+        let protocols = Protocols {
+            viewporter: has(interfaces::VIEWPORTER),
+            fractional_scale: has(interfaces::FRACTIONAL_SCALE),
+            dmabuf: has(interfaces::DMABUF),
+            presentation_time: has(interfaces::PRESENTATION_TIME),
+            idle_inhibit: has(interfaces::IDLE_INHIBIT),
+            decoration: has(interfaces::DECORATION),
+            content_type: has(interfaces::CONTENT_TYPE),
+            pointer_gestures: has(interfaces::POINTER_GESTURES),
+            layer_shell: has(interfaces::LAYER_SHELL),
+        };
+        Capabilities {
+            protocols,
+            sink: probe_sink(sink),
+        }
+    }
+}
+
+fn probe_sink(sink: &gst::Element) -> Sink {
+    let name = sink
+        .get_factory()
+        .map(|factory| factory.get_name().to_string())
+        .unwrap_or_default();
+    let caps = match sink.get_static_pad("sink") {
+        Some(pad) => pad.query_caps(None),
+        None => gst::Caps::new_empty(),
+    };
+
+    let mut formats = Vec::new();
+    let mut dmabuf = false;
+    for (structure, features) in caps.iter_with_features() {
+        if structure.get_name() != "video/x-raw" {
+            continue;
+        }
+        dmabuf |= features.contains("memory:DMABuf");
+        if let Ok(Some(format)) = structure.get::<String>("format") {
+            formats.push(format);
+        } else if let Ok(Some(list)) = structure.get::<gst::List>("format") {
+            let values = list.as_slice().iter();
+            formats.extend(values.filter_map(|value| value.get::<String>().ok().flatten()));
+        }
+    }
+    formats.sort();
+    formats.dedup();
+
+    Sink {
+        name,
+        formats,
+        dmabuf,
+    }
+}
@@ -0,0 +1,50 @@
+//! Copying the current frame to the clipboard.
+//!
+//! The frame is encoded as PNG once, when copied, and offered as
+//! `image/png` through a data source set as the selection of the seat the
+//! copy shortcut came from. Pasting clients get it written to a pipe, on a
+//! thread so that a slow reader doesn't hold up the event loop.
+
+use std::io::Write;
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::Error;
+use sctk::data_device::DataSourceEvent;
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::wl_seat;
+use tracing::warn;
+
+use crate::snapshot;
+use crate::ThemedFrameExample;
+
+const MIME_TYPE: &str = "image/png";
+
+/// Put the frame currently displayed by `sink` on the clipboard of `seat`,
+/// `serial` being the one of the input event requesting the copy.
+pub fn copy_frame(
+    env: &Environment<ThemedFrameExample>,
+    seat: &wl_seat::WlSeat,
+    serial: u32,
+    sink: &gst::Element,
+) -> Result<(), Error> {
+    let frame = snapshot::last_frame(sink)?;
+    let png = Arc::new(snapshot::to_png(&frame)?);
+
+    let source = env.new_data_source(vec![MIME_TYPE.to_owned()], move |event, _| {
+        if let DataSourceEvent::Send { mime_type, mut pipe } = event {
+            if mime_type != MIME_TYPE {
+                return;
+            }
+            let png = png.clone();
+            thread::spawn(move || {
+                if let Err(err) = pipe.write_all(&png) {
+                    warn!("Failed to paste the frame: {}", err);
+                }
+            });
+        }
+    });
+    env.with_data_device(seat, |device| device.set_selection(&Some(source), serial))?;
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Clock of the pipeline.
+//!
+//! By default GStreamer picks the system clock, or the clock of an element
+//! providing one such as an audio sink. In broadcast facilities the display
+//! has to follow the house time instead, which is usually distributed with
+//! PTP: the pipeline is then slaved to a `GstPtpClock` of the given domain.
+
+use std::str::FromStr;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::{info, warn};
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown clock {}, expected system, monotonic, realtime or ptp", _0)]
+pub struct UnknownClock(#[error(not(source))] String);
+
+/// How long to wait for the PTP clock to synchronize before starting anyway.
+const PTP_SYNC_TIMEOUT: gst::ClockTime = gst::ClockTime(Some(10_000_000_000));
+
+/// The clock to run the pipeline on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockSource {
+    /// Whatever GStreamer selects.
+    Auto,
+    /// The system clock, as GStreamer configures it.
+    System,
+    /// A system clock on `CLOCK_MONOTONIC`, unaffected by time changes.
+    Monotonic,
+    /// A system clock on `CLOCK_REALTIME`, the wall clock time.
+    Realtime,
+    /// A PTP clock, synchronized on the domain given separately.
+    Ptp,
+}
+
+impl FromStr for ClockSource {
+    type Err = UnknownClock;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ClockSource::Auto),
+            "system" => Ok(ClockSource::System),
+            "monotonic" => Ok(ClockSource::Monotonic),
+            "realtime" => Ok(ClockSource::Realtime),
+            "ptp" => Ok(ClockSource::Ptp),
+            _ => Err(UnknownClock(s.to_owned())),
+        }
+    }
+}
+
+impl ClockSource {
+    fn create(self, ptp_domain: u32) -> Result<Option<gst::Clock>, Error> {
+        let system = |clock_type: gst::ClockType| -> Result<gst::Clock, Error> {
+            let clock = gst::glib::Object::new(
+                gst::SystemClock::static_type(),
+                &[("clock-type", &clock_type)],
+            )?;
+            Ok(clock.downcast::<gst::Clock>().unwrap())
+        };
+
+        Ok(match self {
+            ClockSource::Auto => None,
+            ClockSource::System => Some(gst::SystemClock::obtain()),
+            ClockSource::Monotonic => Some(system(gst::ClockType::Monotonic)?),
+            ClockSource::Realtime => Some(system(gst::ClockType::Realtime)?),
+            ClockSource::Ptp => {
+                let clock = gst_net::PtpClock::new(Some("ptp-clock"), ptp_domain).upcast();
+                info!("Waiting for the PTP clock of domain {} to synchronize", ptp_domain);
+                if clock.wait_for_sync(PTP_SYNC_TIMEOUT).is_err() {
+                    warn!("The PTP clock didn't synchronize, starting anyway");
+                }
+                Some(clock)
+            }
+        })
+    }
+}
+
+/// Run `pipeline` on the clock of `source`, PTP ones on `ptp_domain`.
+pub fn apply(pipeline: &gst::Pipeline, source: ClockSource, ptp_domain: u32) -> Result<(), Error> {
+    if let Some(clock) = source.create(ptp_domain)? {
+        pipeline.use_clock(Some(&clock));
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Color correction of the video for the output the window is shown on.
+//!
+//! The compositors we target don't expose the output ICC profiles through a
+//! protocol yet, so the profiles come from the command line, optionally per
+//! output name or model. The video is assumed to be sRGB and is transformed
+//! on the CPU with lcms2 before it reaches the sink.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use lcms2::{Intent, PixelFormat, Profile, Transform};
+use sctk::output::with_output_info;
+use sctk::reexports::client::protocol::wl_output;
+use tracing::warn;
+
+use crate::filter::BgrxFilter;
+
+type BgrxTransform = Transform<[u8; 4], [u8; 4]>;
+
+/// An ICC profile, for a given output or for all of them.
+#[derive(Debug, Clone)]
+pub struct OutputProfile {
+    pub output: Option<String>,
+    pub path: PathBuf,
+}
+
+impl OutputProfile {
+    /// Parse a `[OUTPUT=]FILE` specification.
+    pub fn parse(spec: &str) -> Self {
+        match spec.find('=') {
+            Some(pos) => OutputProfile {
+                output: Some(spec[..pos].to_owned()),
+                path: spec[pos + 1..].into(),
+            },
+            None => OutputProfile {
+                output: None,
+                path: spec.into(),
+            },
+        }
+    }
+
+    fn matches(&self, output: &wl_output::WlOutput) -> bool {
+        match &self.output {
+            None => true,
+            Some(wanted) => with_output_info(output, |info| {
+                &info.name == wanted || &info.model == wanted
+            })
+            .unwrap_or(false),
+        }
+    }
+}
+
+/// Applies the ICC profile of the current output to the displayed video.
+pub struct ColorCorrection {
+    profiles: Vec<OutputProfile>,
+    filter: BgrxFilter,
+    transform: Arc<Mutex<Option<BgrxTransform>>>,
+    current: Option<PathBuf>,
+}
+
+impl ColorCorrection {
+    pub fn new(profiles: &[OutputProfile]) -> Result<Self, Error> {
+        let transform = Arc::new(Mutex::new(None::<BgrxTransform>));
+
+        let filter_transform = transform.clone();
+        let filter = BgrxFilter::new(move |pixels, _| {
+            if let Some(transform) = filter_transform.lock().unwrap().as_ref() {
+                transform.transform_in_place(pixels);
+            }
+        })?;
+
+        Ok(ColorCorrection {
+            profiles: profiles.to_vec(),
+            filter,
+            transform,
+            current: None,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![self.filter.element()]
+    }
+
+    /// Switch to the profile of `output`, the one the window is now on.
+    pub fn set_output(&mut self, output: Option<&wl_output::WlOutput>) {
+        let path = output.and_then(|output| {
+            self.profiles
+                .iter()
+                .find(|profile| profile.matches(output))
+                .map(|profile| profile.path.clone())
+        });
+        if path == self.current {
+            return;
+        }
+
+        let transform = match &path {
+            Some(path) => match load_transform(path) {
+                Ok(transform) => Some(transform),
+                Err(err) => {
+                    warn!("Failed to load ICC profile {}: {}", path.display(), err);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        *self.transform.lock().unwrap() = transform;
+        self.current = path;
+    }
+}
+
+fn load_transform(path: &Path) -> Result<BgrxTransform, Error> {
+    let output = Profile::new_file(path)?;
+    let transform = Transform::new(
+        &Profile::new_srgb(),
+        PixelFormat::BGRA_8,
+        &output,
+        PixelFormat::BGRA_8,
+        Intent::Perceptual,
+    )?;
+    Ok(transform)
+}
@@ -0,0 +1,143 @@
+//! Side by side A/B comparison of two sources, for `--compare`.
+//!
+//! The main pipeline is A, on the left half of the window. B is a playbin
+//! of its own, muted, with its own sink on the right half. B runs on the
+//! clock and base time of A, so that the two show the same instant, and
+//! follows what is done to A: its state, and its seeks, picked up from the
+//! new segments at the sink of A after a flush, which covers the seek bar,
+//! the keys and the control requests alike.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use gst::prelude::*;
+use gst_video::prelude::*;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::reexports::client::Display;
+use tracing::{debug, warn};
+
+use crate::render_rect::Rect;
+use crate::{make, sink};
+
+/// A seek to repeat on B: rate, start and stop.
+type Seek = (f64, gst::ClockTime, gst::ClockTime);
+
+pub struct Comparison {
+    pipeline: gst::Pipeline,
+    overlay: gst_video::VideoOverlay,
+    /// The last seek of A, not yet repeated on B.
+    seek: Arc<Mutex<Option<Seek>>>,
+}
+
+impl Comparison {
+    /// Play `uri` as B, on a subsurface of `surface`, following A, which
+    /// renders with `sink_a`.
+    pub fn new(
+        uri: &str,
+        sink: Option<&str>,
+        display: &Display,
+        surface: &wl_surface::WlSurface,
+        sink_a: &gst::Element,
+    ) -> Result<Self, Error> {
+        let playbin = make("playbin")?;
+        let sink = sink::create(sink)?;
+        playbin.set_property("uri", &uri)?;
+        playbin.set_property("video-sink", &sink)?;
+        playbin.set_property("mute", &true)?;
+        let overlay = sink::set_window(&sink, display, surface)?;
+        let pipeline = playbin.downcast::<gst::Pipeline>().unwrap();
+        // The base time is A's, not recomputed on each start
+        pipeline.set_start_time(gst::ClockTime::none());
+
+        let seek = Arc::new(Mutex::new(None));
+        let pending = seek.clone();
+        let mut flushed = false;
+        let pad = sink_a.get_static_pad("sink").unwrap();
+        pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+            let event = match &info.data {
+                Some(gst::PadProbeData::Event(event)) => event,
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            match event.view() {
+                gst::EventView::FlushStop(_) => flushed = true,
+                gst::EventView::Segment(segment) if flushed => {
+                    flushed = false;
+                    let segment = segment.get_segment();
+                    if let Some(segment) = segment.downcast_ref::<gst::ClockTime>() {
+                        let seek = (segment.get_rate(), segment.get_start(), segment.get_stop());
+                        *pending.lock().unwrap() = Some(seek);
+                    }
+                }
+                _ => {}
+            }
+            gst::PadProbeReturn::Ok
+        });
+
+        Ok(Comparison {
+            pipeline,
+            overlay,
+            seek,
+        })
+    }
+
+    /// Put B on the right half of a window of `dimensions`, returns the left
+    /// half, for A.
+    pub fn layout(&self, (width, height): (u32, u32)) -> Rect {
+        let (width, height) = (width as i32, height as i32);
+        let half = width / 2;
+        if let Err(err) = self.overlay.set_render_rectangle(half, 0, width - half, height) {
+            warn!("Failed to place the B video: {}", err);
+        }
+        Rect::new(0, 0, half, height)
+    }
+
+    /// Bring B to the state, clock and position of `a`.
+    pub fn update(&self, a: &gst::Pipeline) {
+        if let Some((rate, start, stop)) = self.seek.lock().unwrap().take() {
+            debug!(%start, rate, "Seeking B along with A");
+            let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE;
+            let stop_type = if stop.is_some() {
+                gst::SeekType::Set
+            } else {
+                gst::SeekType::None
+            };
+            let result = self
+                .pipeline
+                .seek(rate, flags, gst::SeekType::Set, start, stop_type, stop);
+            if let Err(err) = result {
+                warn!("Failed to seek the B video: {}", err);
+            }
+        }
+
+        if let Some(clock) = a.get_clock() {
+            if self.pipeline.get_clock().as_ref() != Some(&clock) {
+                self.pipeline.use_clock(Some(&clock));
+            }
+        }
+        if self.pipeline.get_base_time() != a.get_base_time() {
+            self.pipeline.set_base_time(a.get_base_time());
+        }
+
+        let target = |pipeline: &gst::Pipeline| {
+            let (_, current, pending) = pipeline.get_state(gst::ClockTime::from_seconds(0));
+            if pending == gst::State::VoidPending {
+                current
+            } else {
+                pending
+            }
+        };
+        let state = target(a);
+        let started = matches!(state, gst::State::Paused | gst::State::Playing);
+        if started && target(&self.pipeline) != state {
+            if let Err(err) = self.pipeline.set_state(state) {
+                warn!("Failed to switch the B video to {:?}: {}", state, err);
+            }
+        }
+    }
+}
+
+impl Drop for Comparison {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
@@ -0,0 +1,119 @@
+//! On-screen controls blended into the video, for `--composed-controls`.
+//!
+//! Instead of going to a subsurface of their own, the bar of `controls` is
+//! drawn into memory and handed to an `overlaycomposition` element as a
+//! `GstVideoOverlayComposition`. The element attaches it to the frames as a
+//! meta when the sink can blend it, and blends it into them itself
+//! otherwise. The bar then is part of what the sink shows, on compositors
+//! without subsurfaces as well. It is sized after the video rather than the
+//! window, and doesn't take clicks: the keys still work.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use gst::prelude::*;
+use gst_video::{VideoOverlayComposition, VideoOverlayFormatFlags, VideoOverlayRectangle};
+use sctk::reexports::client::protocol::wl_shm;
+use tracing::warn;
+use wayland_sink_with_video_overlay::canvas::Canvas;
+
+use crate::controls::{self, Playback, BAR_HEIGHT};
+use crate::scale::Scale;
+use crate::make;
+
+/// Video height the bar is drawn at its nominal size for.
+const NOMINAL_HEIGHT: u32 = 360;
+
+pub struct ComposedControls {
+    overlay: gst::Element,
+    /// Size of the video, once known.
+    size: Arc<Mutex<Option<(u32, u32)>>>,
+    composition: Arc<Mutex<Option<VideoOverlayComposition>>>,
+    /// What the composition shows, and at which size.
+    shown: Option<(Playback, (u32, u32))>,
+}
+
+impl ComposedControls {
+    pub fn new() -> Result<Self, Error> {
+        let overlay = make("overlaycomposition")?;
+        let size = Arc::new(Mutex::new(None));
+        let composition = Arc::new(Mutex::new(None::<VideoOverlayComposition>));
+
+        let caps_size = size.clone();
+        overlay.connect("caps-changed", false, move |values| {
+            let caps = values[1].get::<gst::Caps>().ok()??;
+            let info = gst_video::VideoInfo::from_caps(&caps).ok()?;
+            *caps_size.lock().unwrap() = Some((info.width(), info.height()));
+            None
+        })?;
+        let drawn = composition.clone();
+        overlay.connect("draw", false, move |_| {
+            Some(drawn.lock().unwrap().clone().to_value())
+        })?;
+
+        Ok(ComposedControls {
+            overlay,
+            size,
+            composition,
+            shown: None,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.overlay]
+    }
+
+    /// Redraw the bar if what it shows or the size of the video changed.
+    pub fn update(&mut self, playback: Playback) {
+        let size = match *self.size.lock().unwrap() {
+            Some(size) => size,
+            None => return,
+        };
+        if self.shown == Some((playback, size)) {
+            return;
+        }
+        match draw(playback, size) {
+            Ok(composition) => *self.composition.lock().unwrap() = Some(composition),
+            Err(err) => warn!("Failed to draw the composed controls: {}", err),
+        }
+        self.shown = Some((playback, size));
+    }
+}
+
+/// The bar showing `playback` along the bottom of a video of `size`.
+fn draw(
+    playback: Playback,
+    (width, height): (u32, u32),
+) -> Result<VideoOverlayComposition, Error> {
+    let scale = Scale::Integer((height / NOMINAL_HEIGHT).max(1) as i32);
+    let bar_height = (scale.to_buffer(BAR_HEIGHT as i32) as u32).min(height);
+    let mut buffer = gst::Buffer::with_size((4 * width * bar_height) as usize)?;
+    {
+        let buffer = buffer.get_mut().unwrap();
+        {
+            let mut map = buffer.map_writable()?;
+            // ARGB8888 in wl_shm terms is BGRA in memory, as GStreamer has it
+            let format = wl_shm::Format::Argb8888;
+            let mut canvas = Canvas::from_memory(map.as_mut_slice(), width, bar_height, format)?;
+            controls::paint(&mut canvas, playback, scale, 1.0);
+        }
+        gst_video::VideoMeta::add(
+            buffer,
+            gst_video::VideoFrameFlags::empty(),
+            gst_video::VideoFormat::Bgra,
+            width,
+            bar_height,
+        );
+    }
+
+    let rectangle = VideoOverlayRectangle::new_raw(
+        &buffer,
+        0,
+        (height - bar_height) as i32,
+        width,
+        bar_height,
+        VideoOverlayFormatFlags::PREMULTIPLIED_ALPHA,
+    );
+    Ok(VideoOverlayComposition::new(&[rectangle])?)
+}
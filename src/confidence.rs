@@ -0,0 +1,299 @@
+//! Confidence monitoring of the output: sustained black frames and frozen
+//! pictures, silent and clipping audio.
+//!
+//! A probe on the sink pad samples the luma of every frame on a sparse grid.
+//! A frame is black when nearly all of its samples are dark, and frozen when
+//! its samples are those of the frame before. Once either lasts `ALERT_AFTER`
+//! of stream time, an element message is posted on the bus, and another when
+//! it ends, so that alerts go through the bus recording with the rest, and
+//! on to `alerts`.
+//!
+//! The audio branch gets a `level` element, whose messages are followed the
+//! same way: audio is silent while every channel stays below `SILENCE_DB`,
+//! and clipping while a channel peaks at full scale, which alerts sooner.
+
+use std::sync::Mutex;
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::make;
+
+/// Name of the alert messages.
+pub const ALERT: &str = "confidence-alert";
+const ALERT_AFTER: u64 = 2_000_000_000;
+/// Samples across and down each frame.
+const GRID: (usize, usize) = (32, 18);
+/// Samples darker than this are black, in 8-bit luma. Limited range black
+/// is 16.
+const BLACK_LUMA: u8 = 32;
+/// Share of the samples to be black for the frame to be.
+const BLACK_SHARE: f64 = 0.98;
+/// Audio quieter than this on every channel is silent, in dBFS RMS.
+const SILENCE_DB: f64 = -60.0;
+/// Peaks this loud are clipped, in dBFS.
+const CLIPPING_DB: f64 = -0.1;
+/// Clipping is heard much sooner than silence is noticed.
+const CLIPPING_ALERT_AFTER: u64 = 500_000_000;
+/// How often the audio levels are measured.
+const LEVEL_INTERVAL: u64 = 100_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Condition {
+    Black,
+    Frozen,
+    Silent,
+    Clipping,
+}
+
+impl Condition {
+    fn name(self) -> &'static str {
+        match self {
+            Condition::Black => "black",
+            Condition::Frozen => "frozen",
+            Condition::Silent => "silent",
+            Condition::Clipping => "clipping",
+        }
+    }
+
+    fn media(self) -> &'static str {
+        match self {
+            Condition::Black | Condition::Frozen => "picture",
+            Condition::Silent | Condition::Clipping => "audio",
+        }
+    }
+
+    /// How long the condition lasts before it is alerted about.
+    fn alert_after(self) -> u64 {
+        match self {
+            Condition::Clipping => CLIPPING_ALERT_AFTER,
+            _ => ALERT_AFTER,
+        }
+    }
+}
+
+/// How long a condition lasted, in stream time.
+#[derive(Default)]
+struct Tracker {
+    since: Option<u64>,
+    raised: bool,
+}
+
+impl Tracker {
+    /// Follow `condition` being `present` at `pts`, returns the alert to
+    /// post, whether raised or cleared, along with its duration.
+    fn update(&mut self, condition: Condition, present: bool, pts: u64) -> Option<(bool, u64)> {
+        match (present, self.since) {
+            (true, None) => {
+                self.since = Some(pts);
+                None
+            }
+            (true, Some(since)) if !self.raised && pts - since >= condition.alert_after() => {
+                self.raised = true;
+                Some((true, pts - since))
+            }
+            (false, Some(since)) => {
+                self.since = None;
+                let raised = std::mem::replace(&mut self.raised, false);
+                if raised {
+                    Some((false, pts - since))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    last_pts: Option<u64>,
+    last_samples: Vec<u8>,
+    black: Tracker,
+    frozen: Tracker,
+}
+
+impl State {
+    fn reset(&mut self) {
+        *self = State::default();
+    }
+}
+
+/// 8-bit component `component` of the pixel at `x`, `y` of `frame`.
+fn component(
+    frame: &gst_video::VideoFrameRef<&gst::BufferRef>,
+    component: usize,
+    x: usize,
+    y: usize,
+) -> Option<u8> {
+    let format_info = frame.format_info();
+    let plane = format_info.plane()[component] as usize;
+    let data = frame.plane_data(plane as u32).ok()?;
+    let stride = frame.plane_stride()[plane] as usize;
+    let pixel_stride = format_info.pixel_stride()[component] as usize;
+    let offset = format_info.poffset()[component] as usize;
+    data.get(y * stride + x * pixel_stride + offset).copied()
+}
+
+/// The luma of `frame` on the sample grid, for 8-bit YUV, gray and RGB
+/// formats.
+fn sample_luma(frame: &gst_video::VideoFrameRef<&gst::BufferRef>) -> Option<Vec<u8>> {
+    let format_info = frame.format_info();
+    if format_info.depth().iter().any(|depth| *depth != 8) {
+        return None;
+    }
+    let (width, height) = (frame.width() as usize, frame.height() as usize);
+
+    let mut samples = Vec::with_capacity(GRID.0 * GRID.1);
+    for row in 0..GRID.1 {
+        let y = (2 * row + 1) * height / (2 * GRID.1);
+        for column in 0..GRID.0 {
+            let x = (2 * column + 1) * width / (2 * GRID.0);
+            let luma = if format_info.is_rgb() {
+                let r = component(frame, 0, x, y)? as u32;
+                let g = component(frame, 1, x, y)? as u32;
+                let b = component(frame, 2, x, y)? as u32;
+                // BT.709
+                ((54 * r + 183 * g + 19 * b) >> 8) as u8
+            } else {
+                component(frame, 0, x, y)?
+            };
+            samples.push(luma);
+        }
+    }
+    Some(samples)
+}
+
+/// Post an alert about `condition` from `sink`.
+fn post(sink: &gst::Element, condition: Condition, (active, duration): (bool, u64)) {
+    let structure = gst::Structure::builder(ALERT)
+        .field("condition", &condition.name())
+        .field("media", &condition.media())
+        .field("active", &active)
+        .field("seconds", &(duration as f64 / 1e9))
+        .build();
+    let message = gst::message::Element::builder(structure).src(Some(sink)).build();
+    let _ = sink.post_message(&message);
+}
+
+/// Watch the frames reaching `sink`.
+pub fn monitor(sink: &gst::Element) {
+    let state = Mutex::new(State::default());
+    let element = sink.clone();
+    let pad = sink.get_static_pad("sink").unwrap();
+    pad.add_probe(
+        gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_DOWNSTREAM,
+        move |pad, info| {
+            let mut state = state.lock().unwrap();
+            let buffer = match &info.data {
+                Some(gst::PadProbeData::Buffer(buffer)) => buffer,
+                Some(gst::PadProbeData::Event(event)) => {
+                    // Seeks start over
+                    if event.get_type() == gst::EventType::FlushStop {
+                        state.reset();
+                    }
+                    return gst::PadProbeReturn::Ok;
+                }
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            let pts = match buffer.get_pts().nseconds() {
+                Some(pts) => pts,
+                None => return gst::PadProbeReturn::Ok,
+            };
+            if state.last_pts.map_or(false, |last| pts < last) {
+                state.reset();
+            }
+            state.last_pts = Some(pts);
+
+            let samples = pad
+                .get_current_caps()
+                .and_then(|caps| gst_video::VideoInfo::from_caps(&caps).ok())
+                .and_then(|video_info| {
+                    gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, &video_info).ok()
+                })
+                .and_then(|frame| sample_luma(&frame));
+            let samples = match samples {
+                Some(samples) => samples,
+                None => return gst::PadProbeReturn::Ok,
+            };
+
+            let dark = samples.iter().filter(|luma| **luma < BLACK_LUMA).count();
+            let black = dark as f64 >= BLACK_SHARE * samples.len() as f64;
+            // Black frames are all alike, they only count as black
+            let frozen = !black && samples == state.last_samples;
+            if let Some(alert) = state.black.update(Condition::Black, black, pts) {
+                post(&element, Condition::Black, alert);
+            }
+            if let Some(alert) = state.frozen.update(Condition::Frozen, frozen, pts) {
+                post(&element, Condition::Frozen, alert);
+            }
+            state.last_samples = samples;
+
+            gst::PadProbeReturn::Ok
+        },
+    );
+}
+
+/// The `level` element measuring the audio, to insert in the audio branch.
+pub fn level() -> Result<gst::Element, Error> {
+    let level = make("level")?;
+    level.set_property("interval", &LEVEL_INTERVAL)?;
+    level.set_property("post-messages", &true)?;
+    Ok(level)
+}
+
+/// The levels of all channels in the `name` field of a `level` message.
+fn channel_levels(structure: &gst::StructureRef, name: &str) -> Option<Vec<f64>> {
+    let levels = structure.get_some::<gst::glib::ValueArray>(name).ok()?;
+    levels.iter().map(|level| level.get_some::<f64>().ok()).collect()
+}
+
+/// Follows the `level` messages of the audio branch.
+#[derive(Default)]
+pub struct AudioMonitor {
+    last_running_time: Option<u64>,
+    silent: Tracker,
+    clipping: Tracker,
+}
+
+impl AudioMonitor {
+    pub fn on_message(&mut self, msg: &gst::Message) {
+        let structure = match msg.view() {
+            gst::MessageView::Element(element) => element.get_structure(),
+            _ => None,
+        };
+        let structure = match structure {
+            Some(structure) if structure.get_name() == "level" => structure,
+            _ => return,
+        };
+        let level = match msg.get_src().and_then(|src| src.downcast::<gst::Element>().ok()) {
+            Some(level) => level,
+            None => return,
+        };
+        let running_time = match structure.get_some::<u64>("running-time") {
+            Ok(running_time) => running_time,
+            Err(_) => return,
+        };
+        // Seeks start over
+        if self.last_running_time.map_or(false, |last| running_time < last) {
+            *self = AudioMonitor::default();
+        }
+        self.last_running_time = Some(running_time);
+
+        let rms = channel_levels(structure, "rms");
+        let peak = channel_levels(structure, "peak");
+        let (rms, peak) = match (rms, peak) {
+            (Some(rms), Some(peak)) => (rms, peak),
+            _ => return,
+        };
+        let silent = rms.iter().all(|rms| *rms < SILENCE_DB);
+        let clipping = peak.iter().any(|peak| *peak >= CLIPPING_DB);
+        if let Some(alert) = self.silent.update(Condition::Silent, silent, running_time) {
+            post(&level, Condition::Silent, alert);
+        }
+        if let Some(alert) = self.clipping.update(Condition::Clipping, clipping, running_time) {
+            post(&level, Condition::Clipping, alert);
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! Settings from `~/.config/waylandsink-overlay/config.toml`.
+//!
+//! The file, and every key in it, is optional: what isn't set keeps its
+//! built-in default.
+//!
+//! ```toml
+//! [defaults]
+//! width = 1280      # of the window
+//! height = 720
+//! fps = "30/1"      # of the synthetic video
+//! sink = "waylandsink"
+//!
+//! [theme]           # "#RRGGBB" or "#AARRGGBB"
+//! mode = "system"   # dark, light or system
+//! primary = "#222222"
+//! close = "#880000"
+//!
+//! [theme-light]     # the same colors, for the light theme
+//! primary = "#F0F0F0"
+//!
+//! [keys]            # command = key
+//! fullscreen = "F11"
+//! play-pause = "k"
+//!
+//! [late-frames]     # see `late`
+//! policy = "freeze"
+//! max-lateness-ms = 20
+//! render-delay-ms = 5
+//!
+//! [alerts]          # minimum severity of each route, see `alerts`
+//! osd = "warning"
+//! notify = "critical"
+//!
+//! [control]         # tokens of the control APIs, see `auth`
+//! control-tokens = ["..."]
+//! read-tokens = ["..."]
+//!
+//! [protocols]       # required or optional, see `protocols`
+//! viewporter = "required"
+//!
+//! [element.waylandsink]    # element properties, see `overrides`
+//! sync = false
+//!
+//! [presets.hdmi-capture]   # pipeline templates, see `presets`
+//! pipeline = "v4l2src device={device} ! videoconvert ! {sink}"
+//! device = "/dev/video2"
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use toml::Value;
+
+use crate::alerts::{self, Routing};
+use crate::auth::{Role, Tokens};
+use crate::input::Keymap;
+use crate::late::{self, Policy};
+use crate::options::Options;
+use crate::overrides::Overrides;
+use crate::presets::{Preset, Presets};
+use crate::protocols::{self, Requirement};
+
+const PATH: &str = "waylandsink-overlay/config.toml";
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid configuration value for {}", _0)]
+pub struct InvalidValue(#[error(not(source))] String);
+
+/// Which theme the window starts with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeMode {
+    Dark,
+    Light,
+    /// The color scheme of the desktop, dark until it is known.
+    System,
+}
+
+impl Default for ThemeMode {
+    fn default() -> Self {
+        ThemeMode::Dark
+    }
+}
+
+/// Colors of the window decorations, as `0xAARRGGBB`.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub primary: u32,
+    pub primary_inactive: u32,
+    pub separator: u32,
+    pub icon: u32,
+    pub icon_hovered: u32,
+    pub close: u32,
+    pub close_hovered: u32,
+    pub maximize: u32,
+    pub maximize_hovered: u32,
+    pub minimize: u32,
+    pub minimize_hovered: u32,
+    pub title: u32,
+}
+
+impl Default for Theme {
+    /// Dark, with red, green and blue buttons.
+    fn default() -> Self {
+        Theme {
+            primary: 0xFF22_2222,
+            primary_inactive: 0xFF33_3333,
+            separator: 0xFFFF_FFFF,
+            icon: 0xFFFF_FFFF,
+            icon_hovered: 0xFF22_2222,
+            close: 0xFF88_0000,
+            close_hovered: 0xFFFF_0000,
+            maximize: 0xFF00_8800,
+            maximize_hovered: 0xFF00_FF00,
+            minimize: 0xFF00_0088,
+            minimize_hovered: 0xFF00_00FF,
+            title: 0xFFD0_D0D0,
+        }
+    }
+}
+
+impl Theme {
+    /// Light, with muted buttons.
+    pub fn light() -> Self {
+        Theme {
+            primary: 0xFFF0_F0F0,
+            primary_inactive: 0xFFE0_E0E0,
+            separator: 0xFFC0_C0C0,
+            icon: 0xFF30_3030,
+            icon_hovered: 0xFFFF_FFFF,
+            close: 0xFFE0_8080,
+            close_hovered: 0xFFE0_0000,
+            maximize: 0xFF80_C080,
+            maximize_hovered: 0xFF00_A000,
+            minimize: 0xFF80_90E0,
+            minimize_hovered: 0xFF00_40E0,
+            title: 0xFF20_2020,
+        }
+    }
+}
+
+/// `#RRGGBB`, opaque, or `#AARRGGBB`.
+fn parse_color(text: &str) -> Option<u32> {
+    let hex = text.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let color = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(0xFF00_0000 | color),
+        8 => Some(color),
+        _ => None,
+    }
+}
+
+/// The `table` named `name` over `theme`.
+fn parse_theme(table_name: &str, table: &Value, theme: Theme) -> Result<Theme, InvalidValue> {
+    let mut theme = theme;
+    let mut colors = [
+        ("primary", &mut theme.primary),
+        ("primary-inactive", &mut theme.primary_inactive),
+        ("separator", &mut theme.separator),
+        ("icon", &mut theme.icon),
+        ("icon-hovered", &mut theme.icon_hovered),
+        ("close", &mut theme.close),
+        ("close-hovered", &mut theme.close_hovered),
+        ("maximize", &mut theme.maximize),
+        ("maximize-hovered", &mut theme.maximize_hovered),
+        ("minimize", &mut theme.minimize),
+        ("minimize-hovered", &mut theme.minimize_hovered),
+        ("title", &mut theme.title),
+    ];
+    for (name, color) in &mut colors {
+        if let Some(value) = table.get(*name) {
+            **color = value
+                .as_str()
+                .and_then(parse_color)
+                .ok_or_else(|| InvalidValue(format!("{}.{}", table_name, name)))?;
+        }
+    }
+    Ok(theme)
+}
+
+/// The `[late-frames]` table.
+fn parse_late_frames(table: &Value) -> Result<late::Settings, Error> {
+    let policy = match table.get("policy") {
+        Some(policy) => policy
+            .as_str()
+            .ok_or_else(|| InvalidValue("late-frames.policy".to_owned()))?
+            .parse()?,
+        None => Policy::Drop,
+    };
+    let nanoseconds = |name: &str, min: i64| match table.get(name) {
+        Some(value) => value
+            .as_integer()
+            .filter(|ms| *ms >= min)
+            .map(|ms| Some(ms * 1_000_000))
+            .ok_or_else(|| InvalidValue(format!("late-frames.{}", name))),
+        None => Ok(None),
+    };
+    // -1 is no maximum
+    let max_lateness = nanoseconds("max-lateness-ms", -1)?.map(|ns| ns.max(-1));
+    let render_delay = nanoseconds("render-delay-ms", 0)?.map(|ns| ns as u64);
+
+    Ok(late::Settings {
+        policy,
+        max_lateness,
+        render_delay,
+    })
+}
+
+/// The `[alerts]` table over the default routing.
+fn parse_alerts(table: &Value) -> Result<Routing, Error> {
+    let mut routing = Routing::default();
+    let mut routes = [
+        ("osd", &mut routing.osd),
+        ("notify", &mut routing.notify),
+        ("metrics", &mut routing.metrics),
+        ("control", &mut routing.control),
+    ];
+    for (name, route) in &mut routes {
+        if let Some(value) = table.get(*name) {
+            let value = value.as_str().ok_or_else(|| InvalidValue(format!("alerts.{}", name)))?;
+            **route = alerts::parse_route(value)?;
+        }
+    }
+    Ok(routing)
+}
+
+/// The `[protocols]` table over the default policy, every protocol being
+/// optional.
+fn parse_protocols(table: &Value) -> Result<protocols::Policy, Error> {
+    let mut policy = protocols::Policy::default();
+    for (name, value) in table.as_table().into_iter().flatten() {
+        let value = value.as_str().ok_or_else(|| InvalidValue(format!("protocols.{}", name)))?;
+        policy.set(name, value.parse::<Requirement>()?)?;
+    }
+    Ok(policy)
+}
+
+/// The tokens of the `[control]` table, each a string or a list of them.
+fn parse_tokens(table: &Value) -> Result<Tokens, Error> {
+    let mut tokens = Tokens::default();
+    for (name, role) in &[("control-tokens", Role::Control), ("read-tokens", Role::Read)] {
+        let invalid = || InvalidValue(format!("control.{}", name));
+        let values = match table.get(*name) {
+            Some(Value::String(token)) => vec![token.clone()],
+            Some(Value::Array(values)) => values
+                .iter()
+                .map(|token| token.as_str().map(str::to_owned).ok_or_else(invalid))
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(invalid().into()),
+            None => continue,
+        };
+        for token in values {
+            if token.is_empty() {
+                return Err(invalid().into());
+            }
+            tokens.add(token, *role);
+        }
+    }
+    Ok(tokens)
+}
+
+/// The built-in presets, along with those of the `[presets]` tables. Keys
+/// besides `pipeline` are the defaults of the parameters.
+fn parse_presets(table: &Value) -> Result<Presets, Error> {
+    let mut presets = Presets::default();
+    for (name, preset) in table.as_table().into_iter().flatten() {
+        let invalid = |key: &str| InvalidValue(format!("presets.{}.{}", name, key));
+        let pipeline = preset
+            .get("pipeline")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("pipeline"))?;
+        let mut built = Preset::new(name, pipeline);
+        for (key, value) in preset.as_table().into_iter().flatten() {
+            let value = match value {
+                _ if key == "pipeline" => continue,
+                Value::String(value) => value.clone(),
+                Value::Integer(value) => value.to_string(),
+                _ => return Err(invalid(key).into()),
+            };
+            built = built.param(key, &value);
+        }
+        presets.add(built);
+    }
+    Ok(presets)
+}
+
+/// The properties of the `[element.FACTORY]` tables, in their string form.
+fn parse_overrides(table: &Value) -> Result<Overrides, Error> {
+    let mut overrides = Overrides::default();
+    for (factory, properties) in table.as_table().into_iter().flatten() {
+        let properties = properties
+            .as_table()
+            .ok_or_else(|| InvalidValue(format!("element.{}", factory)))?;
+        for (property, value) in properties {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                Value::Integer(value) => value.to_string(),
+                Value::Float(value) => value.to_string(),
+                Value::Boolean(value) => value.to_string(),
+                _ => return Err(InvalidValue(format!("element.{}.{}", factory, property)).into()),
+            };
+            overrides.add(factory, property, value);
+        }
+    }
+    Ok(overrides)
+}
+
+/// What the configuration file sets besides the options.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub theme: Theme,
+    pub light_theme: Theme,
+    pub theme_mode: ThemeMode,
+    pub keymap: Keymap,
+    pub alerts: Routing,
+    pub tokens: Tokens,
+    pub presets: Presets,
+    pub overrides: Overrides,
+    pub protocols: protocols::Policy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            theme: Theme::default(),
+            light_theme: Theme::light(),
+            theme_mode: ThemeMode::default(),
+            keymap: Keymap::default(),
+            alerts: Routing::default(),
+            tokens: Tokens::default(),
+            presets: Presets::default(),
+            overrides: Overrides::default(),
+            protocols: protocols::Policy::default(),
+        }
+    }
+}
+
+impl Config {
+    /// The dark theme, or the light one.
+    pub fn theme(&self, dark: bool) -> &Theme {
+        if dark {
+            &self.theme
+        } else {
+            &self.light_theme
+        }
+    }
+
+    /// Read the configuration file, if there is one, with its `[defaults]`
+    /// going to `options`.
+    pub fn load(options: &mut Options) -> Result<Self, Error> {
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => return Err(err.into()),
+        };
+        Self::parse(&text, options)
+    }
+
+    fn parse(text: &str, options: &mut Options) -> Result<Self, Error> {
+        let config: Value = text.parse()?;
+        let empty = Value::Table(Default::default());
+        let table = |name: &str| config.get(name).unwrap_or(&empty);
+
+        let defaults = table("defaults");
+        let size = |name: &str| match defaults.get(name) {
+            Some(value) => value
+                .as_integer()
+                .filter(|size| *size > 0 && *size <= u16::MAX as i64)
+                .map(|size| Some(size as u32))
+                .ok_or_else(|| InvalidValue(format!("defaults.{}", name))),
+            None => Ok(None),
+        };
+        if let Some(width) = size("width")? {
+            options.window_size.0 = width;
+        }
+        if let Some(height) = size("height")? {
+            options.window_size.1 = height;
+        }
+        if let Some(fps) = defaults.get("fps") {
+            options.fps = fps
+                .as_str()
+                .and_then(parse_fps)
+                .or_else(|| fps.as_integer().and_then(|fps| parse_fps(&fps.to_string())))
+                .ok_or_else(|| InvalidValue("defaults.fps".to_owned()))?;
+        }
+        // --sink takes precedence
+        if let (None, Some(sink)) = (&options.sink, defaults.get("sink")) {
+            let sink = sink.as_str().ok_or_else(|| InvalidValue("defaults.sink".to_owned()))?;
+            options.sink = Some(sink.to_owned());
+        }
+
+        let theme = parse_theme("theme", table("theme"), Theme::default())?;
+        let light_theme = parse_theme("theme-light", table("theme-light"), Theme::light())?;
+        let theme_mode = match table("theme").get("mode") {
+            Some(mode) => match mode.as_str() {
+                Some("dark") => ThemeMode::Dark,
+                Some("light") => ThemeMode::Light,
+                Some("system") => ThemeMode::System,
+                _ => return Err(InvalidValue("theme.mode".to_owned()).into()),
+            },
+            None => ThemeMode::default(),
+        };
+        if let Some(late_frames) = config.get("late-frames") {
+            options.late_frames = Some(parse_late_frames(late_frames)?);
+        }
+        let alerts = parse_alerts(table("alerts"))?;
+        let tokens = parse_tokens(table("control"))?;
+        let presets = parse_presets(table("presets"))?;
+        let overrides = parse_overrides(table("element"))?;
+        let protocols = parse_protocols(table("protocols"))?;
+
+        let mut keymap = Keymap::default();
+        if let Some(keys) = table("keys").as_table() {
+            for (command, key) in keys {
+                let key = key.as_str().ok_or_else(|| InvalidValue(format!("keys.{}", command)))?;
+                keymap.bind(command, key)?;
+            }
+        }
+
+        Ok(Config {
+            theme,
+            light_theme,
+            theme_mode,
+            keymap,
+            alerts,
+            tokens,
+            presets,
+            overrides,
+            protocols,
+        })
+    }
+}
+
+/// `N/D`, or `N` frames per second.
+fn parse_fps(text: &str) -> Option<gst::Fraction> {
+    let (numer, denom) = match text.split_once('/') {
+        Some((numer, denom)) => (numer.parse().ok()?, denom.parse().ok()?),
+        None => (text.parse().ok()?, 1),
+    };
+    if numer > 0 && denom > 0 {
+        Some(gst::Fraction::new(numer, denom))
+    } else {
+        None
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    Some(gst::glib::get_user_config_dir()?.join(PATH))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("#123456"), Some(0xFF12_3456));
+        assert_eq!(parse_color("#80123456"), Some(0x8012_3456));
+        for text in &["123456", "#12345", "#12345G", "#+23456"] {
+            assert_eq!(parse_color(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn parses_defaults_and_theme() {
+        let mut options = Options::default();
+        let text = r##"
+            [defaults]
+            width = 1280
+            height = 720
+            fps = "30000/1001"
+            sink = "glimagesink"
+
+            [theme]
+            close = "#80FF0000"
+        "##;
+        let config = Config::parse(text, &mut options).unwrap();
+        assert_eq!(options.window_size, (1280, 720));
+        assert_eq!(options.fps, gst::Fraction::new(30000, 1001));
+        assert_eq!(options.sink.as_deref(), Some("glimagesink"));
+        assert_eq!(config.theme.close, 0x80FF_0000);
+        assert_eq!(config.theme.primary, Theme::default().primary);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let message = |text: &str| {
+            let mut options = Options::default();
+            Config::parse(text, &mut options).unwrap_err().to_string()
+        };
+        let invalid = |key: &str| format!("Invalid configuration value for {}", key);
+        assert_eq!(message("[defaults]\nwidth = 0"), invalid("defaults.width"));
+        assert_eq!(message("[defaults]\nfps = \"30/0\""), invalid("defaults.fps"));
+        assert_eq!(message("[theme]\nprimary = \"red\""), invalid("theme.primary"));
+    }
+}
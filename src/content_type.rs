@@ -0,0 +1,115 @@
+//! Content type hints of surfaces.
+//!
+//! With `wp_content_type_v1`, a surface tells the compositor what it shows,
+//! so that it can pick the right path for it: direct scanout and accurate
+//! timing for video, reduced latency for games. The hint is applied with the
+//! next commit of the surface, and is ignored by compositors without the
+//! protocol.
+
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::reexports::client::Main;
+
+use protocol::wp_content_type_manager_v1::WpContentTypeManagerV1;
+use protocol::wp_content_type_v1::{self, WpContentTypeV1};
+
+mod protocol {
+    #![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+    #![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+    #![allow(unknown_lints, static_mut_refs, clippy::all)]
+
+    pub(crate) use sctk::reexports::client::protocol::wl_surface;
+    pub(crate) use sctk::reexports::client::{
+        sys, AnonymousObject, Attached, Main, Proxy, ProxyMap,
+    };
+    pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+    pub(crate) use wayland_commons::smallvec;
+    pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+    pub(crate) use wayland_commons::{Interface, MessageGroup};
+
+    include!(concat!(env!("OUT_DIR"), "/content_type_v1.rs"));
+}
+
+/// What a surface shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentType {
+    None,
+    Photo,
+    Video,
+    Game,
+}
+
+impl From<ContentType> for wp_content_type_v1::Type {
+    fn from(content_type: ContentType) -> Self {
+        match content_type {
+            ContentType::None => wp_content_type_v1::Type::None,
+            ContentType::Photo => wp_content_type_v1::Type::Photo,
+            ContentType::Video => wp_content_type_v1::Type::Video,
+            ContentType::Game => wp_content_type_v1::Type::Game,
+        }
+    }
+}
+
+/// The content type of a surface.
+pub struct SurfaceContentType {
+    object: Option<Main<WpContentTypeV1>>,
+    content_type: ContentType,
+}
+
+impl SurfaceContentType {
+    /// Tag `surface` as showing `content_type`, if the compositor supports
+    /// content type hints. There can only be one per surface.
+    pub fn new<E>(
+        env: &Environment<E>,
+        surface: &wl_surface::WlSurface,
+        content_type: ContentType,
+    ) -> Self {
+        // The object outlives the global it comes from
+        let object = env
+            .manager
+            .instantiate_exact::<WpContentTypeManagerV1>(1)
+            .ok()
+            .map(|manager| {
+                let object = manager.get_surface_content_type(surface);
+                manager.destroy();
+                object
+            });
+
+        let surface_content_type = SurfaceContentType {
+            object,
+            content_type,
+        };
+        surface_content_type.apply();
+        surface_content_type
+    }
+
+    /// The content type the surface is tagged with.
+    pub fn get(&self) -> ContentType {
+        self.content_type
+    }
+
+    /// Whether the compositor gets the hint at all.
+    pub fn is_supported(&self) -> bool {
+        self.object.is_some()
+    }
+
+    /// Tag the surface as showing `content_type` from its next commit.
+    pub fn set(&mut self, content_type: ContentType) {
+        self.content_type = content_type;
+        self.apply();
+    }
+
+    fn apply(&self) {
+        if let Some(object) = &self.object {
+            object.set_content_type(self.content_type.into());
+        }
+    }
+}
+
+impl Drop for SurfaceContentType {
+    fn drop(&mut self) {
+        if let Some(object) = &self.object {
+            object.destroy();
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Line based control requests, read from stdin.
+//!
+//! One request per line:
+//!
+//! ```text
+//! preempt URI [PRIORITY]
+//! release
+//! swap-source URI
+//! play
+//! pause
+//! seek SECONDS
+//! load URI
+//! set-rect X Y WIDTH HEIGHT
+//! balance brightness|contrast|hue|saturation VALUE
+//! reset-balance
+//! dump EVERY SECONDS
+//! alerts
+//! capabilities
+//! quit
+//! ```
+//!
+//! The same requests come as JSON objects from the control socket, see
+//! `Request::from_json`.
+
+use std::convert::TryFrom;
+use std::io::BufRead;
+use std::str::FromStr;
+use std::thread;
+
+use derive_more::{Display, Error};
+use sctk::reexports::calloop::channel::Sender;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::auth::Role;
+use crate::balance::Adjustment;
+use crate::render_rect::Rect;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid control request {}", _0)]
+pub struct InvalidRequest(#[error(not(source))] String);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Show the stream at `uri` in place of the current content, if its
+    /// priority is higher.
+    Preempt { uri: String, priority: u32 },
+    /// Stop the last preempting stream and restore what it interrupted.
+    Release,
+    /// Switch the current source to `uri`, keeping the playback position.
+    SwapSource { uri: String },
+    Play,
+    Pause,
+    /// Seek to this position, in seconds.
+    Seek { position: f64 },
+    /// Play `uri` from the start in place of the current source.
+    Load { uri: String },
+    /// Move the video to `rect` of the window, until the next layout change.
+    SetRect(Rect),
+    /// Set a video adjustment, clamped to the range of videobalance.
+    SetBalance { adjustment: Adjustment, value: f64 },
+    ResetBalance,
+    /// Dump every `every`th displayed frame as a PNG for `seconds`.
+    DumpFrames { every: u32, seconds: f64 },
+    /// The active and recent alerts. The control socket answers it itself.
+    Alerts,
+    /// What the compositor and the sink support. The control socket answers
+    /// it itself.
+    Capabilities,
+    Quit,
+}
+
+impl Request {
+    /// The role a client needs for the request.
+    pub fn role(&self) -> Role {
+        match self {
+            Request::Alerts | Request::Capabilities => Role::Read,
+            _ => Role::Control,
+        }
+    }
+
+    /// Parse a request of the control socket, a JSON object naming it in
+    /// `command` along with its arguments:
+    ///
+    /// ```text
+    /// {"command": "preempt", "uri": "file:///ad.mp4", "priority": 2}
+    /// {"command": "seek", "position": 30.5}
+    /// {"command": "set-rect", "x": 0, "y": 0, "width": 640, "height": 360}
+    /// {"command": "balance", "adjustment": "contrast", "value": 1.2}
+    /// ```
+    ///
+    /// along with the `token` of the client, if the control API takes them,
+    /// see `auth`.
+    pub fn from_json(text: &str) -> Result<Self, InvalidRequest> {
+        let invalid = || InvalidRequest(text.to_owned());
+        let json: Value = serde_json::from_str(text).map_err(|_| invalid())?;
+        let string = |name: &str| json[name].as_str().map(str::to_owned).ok_or_else(invalid);
+        let int = |name: &str| {
+            json[name]
+                .as_i64()
+                .and_then(|value| i32::try_from(value).ok())
+                .ok_or_else(invalid)
+        };
+
+        match json["command"].as_str().ok_or_else(invalid)? {
+            "preempt" => Ok(Request::Preempt {
+                uri: string("uri")?,
+                priority: match json.get("priority") {
+                    Some(priority) => priority
+                        .as_u64()
+                        .and_then(|priority| u32::try_from(priority).ok())
+                        .ok_or_else(invalid)?,
+                    None => 1,
+                },
+            }),
+            "release" => Ok(Request::Release),
+            "swap-source" => Ok(Request::SwapSource { uri: string("uri")? }),
+            "play" => Ok(Request::Play),
+            "pause" => Ok(Request::Pause),
+            "seek" => Ok(Request::Seek {
+                position: json["position"].as_f64().ok_or_else(invalid)?,
+            }),
+            "load" => Ok(Request::Load { uri: string("uri")? }),
+            "set-rect" => Ok(Request::SetRect(Rect::new(
+                int("x")?,
+                int("y")?,
+                int("width")?,
+                int("height")?,
+            ))),
+            "balance" => Ok(Request::SetBalance {
+                adjustment: string("adjustment")?.parse().map_err(|_| invalid())?,
+                value: json["value"].as_f64().ok_or_else(invalid)?,
+            }),
+            "reset-balance" => Ok(Request::ResetBalance),
+            "dump" => Ok(Request::DumpFrames {
+                every: json["every"]
+                    .as_u64()
+                    .and_then(|every| u32::try_from(every).ok())
+                    .ok_or_else(invalid)?,
+                seconds: json["seconds"].as_f64().ok_or_else(invalid)?,
+            }),
+            "alerts" => Ok(Request::Alerts),
+            "capabilities" => Ok(Request::Capabilities),
+            "quit" => Ok(Request::Quit),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl FromStr for Request {
+    type Err = InvalidRequest;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidRequest(s.to_owned());
+        let words: Vec<&str> = s.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["preempt", uri] => Ok(Request::Preempt {
+                uri: (*uri).to_owned(),
+                priority: 1,
+            }),
+            ["preempt", uri, priority] => Ok(Request::Preempt {
+                uri: (*uri).to_owned(),
+                priority: priority.parse().map_err(|_| invalid())?,
+            }),
+            ["release"] => Ok(Request::Release),
+            ["swap-source", uri] => Ok(Request::SwapSource {
+                uri: (*uri).to_owned(),
+            }),
+            ["play"] => Ok(Request::Play),
+            ["pause"] => Ok(Request::Pause),
+            ["seek", position] => Ok(Request::Seek {
+                position: position.parse().map_err(|_| invalid())?,
+            }),
+            ["load", uri] => Ok(Request::Load {
+                uri: (*uri).to_owned(),
+            }),
+            ["set-rect", x, y, width, height] => {
+                let int = |word: &str| word.parse().map_err(|_| invalid());
+                Ok(Request::SetRect(Rect::new(int(x)?, int(y)?, int(width)?, int(height)?)))
+            }
+            ["balance", adjustment, value] => Ok(Request::SetBalance {
+                adjustment: adjustment.parse().map_err(|_| invalid())?,
+                value: value.parse().map_err(|_| invalid())?,
+            }),
+            ["reset-balance"] => Ok(Request::ResetBalance),
+            ["dump", every, seconds] => Ok(Request::DumpFrames {
+                every: every.parse().map_err(|_| invalid())?,
+                seconds: seconds.parse().map_err(|_| invalid())?,
+            }),
+            ["alerts"] => Ok(Request::Alerts),
+            ["capabilities"] => Ok(Request::Capabilities),
+            ["quit"] => Ok(Request::Quit),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Read requests from stdin on a thread, sending them to the event loop.
+pub fn read_stdin(sender: Sender<Request>) {
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match line.parse() {
+                Ok(request) => {
+                    if sender.send(request).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => warn!("{}", err),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lines() {
+        let request = |line: &str| line.parse::<Request>().unwrap();
+        assert_eq!(
+            request("preempt file:///ad.mp4"),
+            Request::Preempt {
+                uri: "file:///ad.mp4".into(),
+                priority: 1,
+            }
+        );
+        assert_eq!(
+            request("preempt file:///ad.mp4 3"),
+            Request::Preempt {
+                uri: "file:///ad.mp4".into(),
+                priority: 3,
+            }
+        );
+        assert_eq!(request("  release  "), Request::Release);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let invalid = [
+            "",
+            "preempt",
+            "preempt file:///ad.mp4 high",
+            "preempt file:///ad.mp4 -1",
+            "release now",
+            "stop",
+        ];
+        for line in &invalid {
+            assert!(line.parse::<Request>().is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parses_json() {
+        let request = |json: &str| Request::from_json(json).unwrap();
+        assert_eq!(
+            request(r#"{"command": "preempt", "uri": "file:///ad.mp4", "priority": 2}"#),
+            Request::Preempt {
+                uri: "file:///ad.mp4".into(),
+                priority: 2,
+            }
+        );
+        assert_eq!(
+            request(r#"{"command": "preempt", "uri": "file:///ad.mp4"}"#),
+            Request::Preempt {
+                uri: "file:///ad.mp4".into(),
+                priority: 1,
+            }
+        );
+        assert_eq!(
+            request(r#"{"command": "seek", "position": 30.5}"#),
+            Request::Seek { position: 30.5 }
+        );
+        assert_eq!(
+            request(r#"{"command": "set-rect", "x": 0, "y": 10, "width": 640, "height": 360}"#),
+            Request::SetRect(Rect::new(0, 10, 640, 360))
+        );
+        assert_eq!(request(r#"{"command": "quit"}"#), Request::Quit);
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        let invalid = [
+            "seek 30",
+            r#"{"uri": "file:///ad.mp4"}"#,
+            r#"{"command": "seek", "position": "30"}"#,
+            r#"{"command": "preempt", "uri": "file:///ad.mp4", "priority": -1}"#,
+            r#"{"command": "set-rect", "x": 0, "y": 0, "width": 4294967296, "height": 1}"#,
+        ];
+        for json in &invalid {
+            assert!(Request::from_json(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn gives_the_alerts_to_readers() {
+        assert_eq!(Request::Alerts.role(), Role::Read);
+        assert_eq!(Request::Quit.role(), Role::Control);
+        assert_eq!(
+            Request::from_json(r#"{"command": "alerts", "token": "secret"}"#).unwrap(),
+            Request::Alerts
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Control requests over a Unix socket, for scripts and remote control.
+//!
+//! The socket is `$XDG_RUNTIME_DIR/waylandsink-ctl.sock`. Clients write one
+//! JSON request per line, as parsed by `Request::from_json`, and read a
+//! reply line for each: `{"ok": true}` once the request is queued for the
+//! event loop, or `{"ok": false, "error": "..."}`. The `alerts` and
+//! `capabilities` requests are answered right away, with `{"ok": true,
+//! "alerts": ...}` and `{"ok": true, "capabilities": ...}`. With tokens set,
+//! requests carry one in `token`, of a role allowing them.
+//!
+//! An instance taking over with `--handoff` replaces the socket of the old
+//! one, which then leaves it in place on exit.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::thread;
+
+use anyhow::Error;
+use sctk::reexports::calloop::channel::Sender;
+use serde_json::{json, Value};
+use tracing::warn;
+use wayland_sink_with_video_overlay::capabilities::Capabilities;
+
+use crate::alerts::Feed;
+use crate::auth::Tokens;
+use crate::control::Request;
+use crate::runtime_socket::RuntimeSocket;
+
+const SOCKET_NAME: &str = "waylandsink-ctl.sock";
+
+/// Answer the requests of `stream` until the client hangs up.
+fn serve(
+    stream: UnixStream,
+    sender: Sender<Request>,
+    alerts: Feed,
+    capabilities: &Capabilities,
+    tokens: &Tokens,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let token = serde_json::from_str::<Value>(&line)
+            .ok()
+            .and_then(|json| json["token"].as_str().map(str::to_owned));
+        let request = Request::from_json(&line).map_err(Error::from).and_then(|request| {
+            tokens.authorize(token.as_deref(), request.role())?;
+            Ok(request)
+        });
+        let reply = match request {
+            Ok(Request::Alerts) => json!({ "ok": true, "alerts": alerts.to_json() }),
+            Ok(Request::Capabilities) => json!({ "ok": true, "capabilities": capabilities }),
+            Ok(request) => {
+                if sender.send(request).is_err() {
+                    break;
+                }
+                json!({ "ok": true })
+            }
+            Err(err) => json!({ "ok": false, "error": err.to_string() }),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// The listening socket, removed when dropped unless another instance
+/// replaced it.
+pub struct ControlSocket {
+    _socket: RuntimeSocket,
+}
+
+impl ControlSocket {
+    /// Listen for clients on a thread, sending their requests to the event
+    /// loop, and answering those for `alerts` and `capabilities`, as far as
+    /// their `tokens` allow. An instance that `took_over` replaces the socket
+    /// in use.
+    pub fn new(
+        sender: Sender<Request>,
+        alerts: Feed,
+        capabilities: Capabilities,
+        tokens: Tokens,
+        took_over: bool,
+    ) -> Result<Self, Error> {
+        let (socket, listener) = RuntimeSocket::bind(SOCKET_NAME, took_over)?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let (sender, alerts) = (sender.clone(), alerts.clone());
+                        let (capabilities, tokens) = (capabilities.clone(), tokens.clone());
+                        thread::spawn(move || {
+                            let served = serve(stream, sender, alerts, &capabilities, &tokens);
+                            if let Err(err) = served {
+                                warn!("Control client failed: {}", err);
+                            }
+                        });
+                    }
+                    Err(err) => warn!("Failed to accept a control client: {}", err),
+                }
+            }
+        });
+
+        Ok(ControlSocket { _socket: socket })
+    }
+}
@@ -0,0 +1,246 @@
+//! On-screen playback controls, drawn on a subsurface above the video.
+//!
+//! The bar along the bottom of the window holds a play/pause button, a seek
+//! bar and the playback time. It fades in when the pointer moves over the
+//! window and out again after a few seconds without activity.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::{wl_subcompositor, wl_subsurface, wl_surface};
+use sctk::shm::DoubleMemPool;
+use tracing::warn;
+use wayland_sink_with_video_overlay::canvas::{self, Canvas};
+
+use crate::animation::{Animated, Easing};
+use crate::scale::{Scale, SurfaceScale};
+use crate::{format_time, ThemedFrameExample};
+
+pub const BAR_HEIGHT: u32 = 40;
+const PADDING: i32 = 12;
+const TEXT_SCALE: i32 = 3;
+const HIDE_AFTER: Duration = Duration::from_secs(3);
+const FADE: Duration = Duration::from_millis(200);
+
+const BACKGROUND: u32 = 0xB000_0000;
+const FOREGROUND: u32 = 0xFFE0_E0E0;
+const TRACK: u32 = 0xFF50_5050;
+
+/// What the controls show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Playback {
+    pub playing: bool,
+    /// Position and duration, in seconds.
+    pub position: Option<u64>,
+    pub duration: Option<u64>,
+}
+
+/// The part of the bar at some position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hit {
+    PlayPause,
+    /// Position along the seek bar, from 0 to 1.
+    SeekBar(f64),
+}
+
+/// `color`, premultiplied, at `opacity`.
+fn fade(color: u32, opacity: f64) -> u32 {
+    let channel = |shift: u32| (((color >> shift) & 0xFF) as f64 * opacity).round() as u32;
+    channel(24) << 24 | channel(16) << 16 | channel(8) << 8 | channel(0)
+}
+
+/// Draw the bar showing `playback` over all of `canvas`, at `scale` and
+/// `opacity`. Returns the horizontal extent of the seek bar, in buffer
+/// coordinates.
+pub fn paint(canvas: &mut Canvas, playback: Playback, scale: Scale, opacity: f64) -> (i32, i32) {
+    let background = fade(BACKGROUND, opacity);
+    let foreground = fade(FOREGROUND, opacity);
+    let track_color = fade(TRACK, opacity);
+    canvas.fill(background);
+
+    // Play/pause button, in a square at the left
+    let size = canvas.height() / 2;
+    let (x, y) = (size / 2, size / 2);
+    if playback.playing {
+        canvas.fill_rect(x + size / 8, y, size / 4, size, foreground);
+        canvas.fill_rect(x + size * 5 / 8, y, size / 4, size, foreground);
+    } else {
+        for column in 0..size {
+            let inset = column / 2;
+            canvas.fill_rect(x + column, y + inset, 1, size - 2 * inset, foreground);
+        }
+    }
+
+    let position = playback.position.map(format_time).unwrap_or_default();
+    let text = match playback.duration {
+        Some(duration) => format!("{} / {}", position, format_time(duration)),
+        None => position,
+    };
+    let padding = scale.to_buffer(PADDING);
+    let text_scale = scale.to_buffer(TEXT_SCALE).max(1);
+    let text_x = canvas.width() - padding - canvas::text_width(&text, text_scale);
+    let text_y = (canvas.height() - canvas::text_height(text_scale)) / 2;
+    canvas.draw_text(text_x, text_y, text_scale, &text, foreground);
+
+    // Seek bar in between, filled up to the position when the duration is known
+    // None left on a window narrower than the button and the time
+    let start = canvas.height();
+    let track = (text_x - padding - start).max(0);
+    let thickness = scale.to_buffer(4).max(1);
+    let bar_y = (canvas.height() - thickness) / 2;
+    canvas.fill_rect(start, bar_y, track, thickness, track_color);
+    if let (Some(position), Some(duration)) = (playback.position, playback.duration) {
+        let elapsed = track as u64 * position.min(duration);
+        if let Some(filled) = elapsed.checked_div(duration) {
+            canvas.fill_rect(start, bar_y, filled as i32, thickness, foreground);
+        }
+    }
+    (start, start + track)
+}
+
+pub struct Controls {
+    surface: wl_surface::WlSurface,
+    subsurface: wl_subsurface::WlSubsurface,
+    pools: DoubleMemPool,
+    width: u32,
+    scaling: SurfaceScale,
+    // Scale of the bar as last drawn
+    scale: Scale,
+    // Horizontal extent of the seek bar, as last drawn
+    seek_bar: (i32, i32),
+    hide_at: Option<Instant>,
+    opacity: Animated<f64>,
+    // What the controls show, and what they were last drawn with
+    playback: Option<Playback>,
+    shown: Option<Playback>,
+}
+
+impl Controls {
+    pub fn new(
+        env: &Environment<ThemedFrameExample>,
+        parent: &wl_surface::WlSurface,
+    ) -> Result<Self, Error> {
+        let surface = env.create_surface().detach();
+        let subcompositor = env.require_global::<wl_subcompositor::WlSubcompositor>();
+        let subsurface = subcompositor.get_subsurface(&surface, parent).detach();
+        // Redraws of the bar don't have to wait for a commit of the window
+        subsurface.set_desync();
+
+        let pools = env.create_double_pool(|_| {})?;
+        let scaling = SurfaceScale::new(env, &surface);
+
+        Ok(Controls {
+            surface,
+            subsurface,
+            pools,
+            width: 0,
+            scaling,
+            scale: Scale::Integer(1),
+            seek_bar: (0, 0),
+            hide_at: None,
+            opacity: Animated::new(0.0, FADE, Easing::EaseOut),
+            playback: None,
+            shown: None,
+        })
+    }
+
+    pub fn surface(&self) -> &wl_surface::WlSurface {
+        &self.surface
+    }
+
+    /// Follow the size of the window. The new position is applied with the
+    /// next commit of the window surface.
+    pub fn resize(&mut self, (width, height): (u32, u32)) {
+        self.width = width;
+        self.subsurface
+            .set_position(0, height.saturating_sub(BAR_HEIGHT) as i32);
+        // Force a redraw at the new width
+        self.shown = None;
+    }
+
+    /// Fade the controls in, or keep them a while longer.
+    pub fn show(&mut self) {
+        self.hide_at = Some(Instant::now() + HIDE_AFTER);
+        self.opacity.animate_to(1.0);
+    }
+
+    /// Redraw the controls if what they show changed, and fade them out
+    /// once they timed out.
+    pub fn update(&mut self, playback: Playback) {
+        self.playback = Some(playback);
+        if self.scaling.get() != self.scale {
+            self.shown = None;
+        }
+        if let Some(hide_at) = self.hide_at {
+            if Instant::now() >= hide_at {
+                self.hide_at = None;
+                self.opacity.animate_to(0.0);
+            }
+        }
+        if self.opacity.value() > 0.0 && self.shown != Some(playback) {
+            self.redraw();
+        }
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.opacity.is_animating()
+    }
+
+    /// Advance the fade animation, returns whether more frames are needed.
+    pub fn tick(&mut self) -> bool {
+        if self.opacity.tick() {
+            if self.opacity.value() > 0.0 {
+                self.redraw();
+            } else {
+                self.shown = None;
+                self.surface.attach(None, 0, 0);
+                self.surface.commit();
+            }
+        }
+        self.opacity.is_animating()
+    }
+
+    fn redraw(&mut self) {
+        if let Some(playback) = self.playback {
+            if let Err(err) = self.draw(playback) {
+                warn!("Failed to draw the controls: {}", err);
+            }
+        }
+    }
+
+    /// Which control is at `x` on the bar.
+    pub fn hit(&self, x: f64) -> Option<Hit> {
+        let x = x as i32;
+        let (start, end) = self.seek_bar;
+        if x < BAR_HEIGHT as i32 {
+            Some(Hit::PlayPause)
+        } else if x >= start && x < end {
+            Some(Hit::SeekBar((x - start) as f64 / (end - start) as f64))
+        } else {
+            None
+        }
+    }
+
+    fn draw(&mut self, playback: Playback) -> std::io::Result<()> {
+        let pool = match self.pools.pool() {
+            Some(pool) => pool,
+            None => return Ok(()),
+        };
+
+        // Drawn in buffer coordinates, scaled from the surface ones
+        let scale = self.scaling.get();
+        let bar_height = scale.to_buffer(BAR_HEIGHT as i32);
+        let width = scale.to_buffer(self.width.max(1) as i32);
+        let mut canvas = Canvas::new(pool, width as u32, bar_height as u32)?;
+        let (start, end) = paint(&mut canvas, playback, scale, self.opacity.value());
+        // Hits come in surface coordinates
+        self.seek_bar = (scale.to_surface(start), scale.to_surface(end));
+
+        self.scaling.set_buffer(scale, (self.width.max(1), BAR_HEIGHT));
+        canvas.attach(&self.surface);
+        self.scale = scale;
+        self.shown = Some(playback);
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+//! BGRx to NV12 conversion, with a NEON path on aarch64.
+//!
+//! NV12 takes 12 bits a pixel instead of 32, which matters for the memory
+//! bandwidth of small ARM boards when the compositor can take it. This
+//! covers the one case the example needs, BT.709 limited range with the
+//! chroma of each 2x2 block averaged, rounding the same way on both paths.
+//! `benches/convert.rs` compares it with the generic converter of
+//! videoconvert.
+
+// BT.709 limited range, times 256
+const Y_R: i32 = 47;
+const Y_G: i32 = 157;
+const Y_B: i32 = 16;
+const U_R: i32 = -26;
+const U_G: i32 = -86;
+const U_B: i32 = 112;
+const V_R: i32 = 112;
+const V_G: i32 = -102;
+const V_B: i32 = -10;
+
+/// The lines of a plane, `stride` bytes apart.
+pub struct Plane<D> {
+    pub data: D,
+    pub stride: usize,
+}
+
+/// Convert the BGRx frame `src` of `width` by `height` to the `y` and `uv`
+/// planes of an NV12 frame.
+pub fn bgrx_to_nv12(
+    src: Plane<&[u8]>,
+    y: Plane<&mut [u8]>,
+    uv: Plane<&mut [u8]>,
+    width: usize,
+    height: usize,
+) {
+    let (data, stride) = (src.data, src.stride);
+    let src_line = |index: usize| &data[stride * index..][..4 * width];
+
+    for (pair, uv_line) in uv.data.chunks_mut(uv.stride).take(height.div_ceil(2)).enumerate() {
+        let top = src_line(2 * pair);
+        // The last line of odd heights makes a pair with itself
+        let bottom = src_line((2 * pair + 1).min(height - 1));
+
+        luma_line(top, &mut y.data[y.stride * 2 * pair..][..width]);
+        if 2 * pair + 1 < height {
+            luma_line(bottom, &mut y.data[y.stride * (2 * pair + 1)..][..width]);
+        }
+        chroma_line(top, bottom, &mut uv_line[..2 * width.div_ceil(2)]);
+    }
+}
+
+/// The `y`, `u` and `v` of the BGRx pixel `bgrx`, for solid colors.
+pub fn bgrx_to_yuv(bgrx: [u8; 4]) -> (u8, u8, u8) {
+    let (b, g, r) = (bgrx[0] as i32, bgrx[1] as i32, bgrx[2] as i32);
+    let (u, v) = chroma(4 * b, 4 * g, 4 * r);
+    (luma(b, g, r), u, v)
+}
+
+fn luma(b: i32, g: i32, r: i32) -> u8 {
+    (16 + ((Y_R * r + Y_G * g + Y_B * b + 128) >> 8)) as u8
+}
+
+/// `u` and `v` of the average of four pixels, from their sums.
+fn chroma(b: i32, g: i32, r: i32) -> (u8, u8) {
+    let (b, g, r) = ((b + 2) >> 2, (g + 2) >> 2, (r + 2) >> 2);
+    let u = 128 + ((U_R * r + U_G * g + U_B * b + 128) >> 8);
+    let v = 128 + ((V_R * r + V_G * g + V_B * b + 128) >> 8);
+    (u.clamp(0, 255) as u8, v.clamp(0, 255) as u8)
+}
+
+fn luma_line(src: &[u8], dst: &mut [u8]) {
+    let done = neon::luma_line(src, dst);
+    for (pixel, y) in src[4 * done..].chunks_exact(4).zip(&mut dst[done..]) {
+        *y = luma(pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+    }
+}
+
+fn chroma_line(top: &[u8], bottom: &[u8], dst: &mut [u8]) {
+    let done = neon::chroma_line(top, bottom, dst);
+    let width = top.len() / 4;
+    for (block, uv) in dst[2 * done..].chunks_exact_mut(2).enumerate() {
+        let left = 2 * (done + block);
+        // The last column of odd widths makes a pair with itself
+        let right = (left + 1).min(width - 1);
+        let sum = |channel: usize| {
+            [top, bottom]
+                .iter()
+                .map(|line| line[4 * left + channel] as i32 + line[4 * right + channel] as i32)
+                .sum::<i32>()
+        };
+        let (u, v) = chroma(sum(0), sum(1), sum(2));
+        uv[0] = u;
+        uv[1] = v;
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::*;
+
+    /// Convert the pixels of `src` to `dst` 8 at a time, returns how many
+    /// were.
+    pub fn luma_line(src: &[u8], dst: &mut [u8]) -> usize {
+        let blocks = dst.len() / 8;
+        // NEON is part of aarch64. Each block reads 32 bytes of `src` and
+        // writes 8 of `dst`, within the lengths checked by the caller.
+        unsafe {
+            for block in 0..blocks {
+                let pixels = vld4_u8(src.as_ptr().add(32 * block));
+                let sum = vmull_u8(pixels.2, vdup_n_u8(Y_R as u8));
+                let sum = vmlal_u8(sum, pixels.1, vdup_n_u8(Y_G as u8));
+                let sum = vmlal_u8(sum, pixels.0, vdup_n_u8(Y_B as u8));
+                let y = vadd_u8(vrshrn_n_u16(sum, 8), vdup_n_u8(16));
+                vst1_u8(dst.as_mut_ptr().add(8 * block), y);
+            }
+        }
+        8 * blocks
+    }
+
+    /// The sum of `r`, `g` and `b` by `weights`, rounded, offset to 128 and
+    /// saturated.
+    unsafe fn weigh(r: int16x8_t, g: int16x8_t, b: int16x8_t, weights: [i32; 3]) -> uint8x8_t {
+        let sum = vmulq_n_s16(r, weights[0] as i16);
+        let sum = vmlaq_n_s16(sum, g, weights[1] as i16);
+        let sum = vmlaq_n_s16(sum, b, weights[2] as i16);
+        vqmovun_s16(vaddq_s16(vrshrq_n_s16(sum, 8), vdupq_n_s16(128)))
+    }
+
+    /// Convert the 2x2 blocks of `top` and `bottom` to `dst`, 8 at a time,
+    /// returns how many were.
+    pub fn chroma_line(top: &[u8], bottom: &[u8], dst: &mut [u8]) -> usize {
+        // Only whole pairs of pixels, the last one of odd widths is left out
+        let blocks = (top.len() / 4 / 2).min(dst.len() / 2) / 8;
+        // Each block reads 64 bytes of `top` and `bottom` and writes 16 of
+        // `dst`, within the lengths checked above.
+        unsafe {
+            for block in 0..blocks {
+                let top = vld4q_u8(top.as_ptr().add(64 * block));
+                let bottom = vld4q_u8(bottom.as_ptr().add(64 * block));
+                // Sums of the 4 pixels of each block, then their averages
+                let average = |top: uint8x16_t, bottom: uint8x16_t| {
+                    let sum = vpadalq_u8(vpaddlq_u8(top), bottom);
+                    vreinterpretq_s16_u16(vrshrq_n_u16(sum, 2))
+                };
+                let b = average(top.0, bottom.0);
+                let g = average(top.1, bottom.1);
+                let r = average(top.2, bottom.2);
+                let u = weigh(r, g, b, [U_R, U_G, U_B]);
+                let v = weigh(r, g, b, [V_R, V_G, V_B]);
+                vst2_u8(dst.as_mut_ptr().add(16 * block), uint8x8x2_t(u, v));
+            }
+        }
+        8 * blocks
+    }
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+mod neon {
+    pub fn luma_line(_src: &[u8], _dst: &mut [u8]) -> usize {
+        0
+    }
+
+    pub fn chroma_line(_top: &[u8], _bottom: &[u8], _dst: &mut [u8]) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A BGRx line of `width` pixels of all sorts of colors, starting with
+    /// the extremes.
+    #[cfg(target_arch = "aarch64")]
+    fn line(width: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        let mut line: Vec<u8> = (0..4 * width)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect();
+        line[..8].copy_from_slice(&[0, 0, 0, 0, 255, 255, 255, 0]);
+        line
+    }
+
+    #[test]
+    fn converts_solid_colors() {
+        let (width, height) = (5, 3);
+        for bgrx in &[[0, 0, 0, 0], [255, 255, 255, 0], [0, 0, 255, 0], [200, 100, 50, 0]] {
+            let src: Vec<u8> = bgrx.iter().copied().cycle().take(4 * width * height).collect();
+            let mut y = vec![0; width * height];
+            let mut uv = vec![0; 2 * 3 * 2];
+            bgrx_to_nv12(
+                Plane {
+                    data: &src,
+                    stride: 4 * width,
+                },
+                Plane {
+                    data: &mut y,
+                    stride: width,
+                },
+                Plane {
+                    data: &mut uv,
+                    stride: 2 * 3,
+                },
+                width,
+                height,
+            );
+
+            let (luma, u, v) = bgrx_to_yuv(*bgrx);
+            assert!(y.iter().all(|&value| value == luma), "{:?}", bgrx);
+            assert!(uv.chunks_exact(2).all(|pair| pair == [u, v]), "{:?}", bgrx);
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn neon_matches_scalar() {
+        let width = 37;
+        let (top, bottom) = (line(width, 1), line(width, 2));
+
+        let mut y = vec![0; width];
+        let done = neon::luma_line(&top, &mut y);
+        assert_eq!(done, 32);
+        for (pixel, &value) in top.chunks_exact(4).zip(&y).take(done) {
+            assert_eq!(value, luma(pixel[0] as i32, pixel[1] as i32, pixel[2] as i32));
+        }
+
+        let mut uv = vec![0; 2 * width.div_ceil(2)];
+        let done = neon::chroma_line(&top, &bottom, &mut uv);
+        assert_eq!(done, 16);
+        for (block, pair) in uv.chunks_exact(2).enumerate().take(done) {
+            let sum = |channel: usize| {
+                [&top, &bottom]
+                    .iter()
+                    .map(|line| {
+                        line[8 * block + channel] as i32 + line[8 * block + 4 + channel] as i32
+                    })
+                    .sum::<i32>()
+            };
+            let (u, v) = chroma(sum(0), sum(1), sum(2));
+            assert_eq!(pair, [u, v], "block {}", block);
+        }
+    }
+}
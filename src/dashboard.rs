@@ -0,0 +1,185 @@
+//! A second window showing the internals of the pipeline: element states,
+//! queue levels and clock times, refreshed every second.
+
+use anyhow::{anyhow, Error};
+use gst::prelude::*;
+use sctk::environment::Environment;
+use sctk::shm::DoubleMemPool;
+use sctk::window::{ConceptConfig, ConceptFrame, Event as WEvent, Window};
+use tracing::warn;
+use wayland_sink_with_video_overlay::canvas::{self, Canvas};
+
+use crate::{DispatchState, ThemedFrameExample};
+
+const TITLE: &str = "Pipeline dashboard";
+const TEXT_SCALE: i32 = 2;
+const LINE_HEIGHT: i32 = 7 * TEXT_SCALE;
+const MARGIN: i32 = 8;
+
+const BACKGROUND: u32 = 0xFF10_1010;
+const FOREGROUND: u32 = 0xFFD0_D0D0;
+
+fn clock_time(time: gst::ClockTime) -> String {
+    if time.is_some() {
+        time.to_string()
+    } else {
+        "-".into()
+    }
+}
+
+/// The dashboard text for `pipeline`.
+fn describe(pipeline: &gst::Pipeline) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    let base_time = pipeline.get_base_time();
+    match pipeline.get_clock() {
+        Some(clock) => {
+            let now = clock.get_time();
+            lines.push(format!("clock {}", clock.get_name()));
+            lines.push(format!("  time {}", clock_time(now)));
+            lines.push(format!("  base time {}", clock_time(base_time)));
+            let running = match (now.nseconds(), base_time.nseconds()) {
+                (Some(now), Some(base)) => gst::ClockTime::from_nseconds(now.saturating_sub(base)),
+                _ => gst::ClockTime::none(),
+            };
+            lines.push(format!("  running time {}", clock_time(running)));
+        }
+        None => lines.push("no clock".into()),
+    }
+    let position = pipeline
+        .query_position::<gst::ClockTime>()
+        .unwrap_or_else(gst::ClockTime::none);
+    lines.push(format!("position {}", clock_time(position)));
+    lines.push(String::new());
+
+    for element in pipeline.iterate_recurse() {
+        let element = match element {
+            Ok(element) => element,
+            Err(_) => break,
+        };
+        let factory = element
+            .get_factory()
+            .map(|factory| factory.get_name().to_string())
+            .unwrap_or_default();
+        let mut line = format!(
+            "{} ({}) {:?}",
+            element.get_name(),
+            factory,
+            element.get_current_state()
+        );
+
+        if factory == "queue" {
+            let level = |name| {
+                element
+                    .get_property(name)
+                    .ok()
+                    .and_then(|value| value.get_some::<u32>().ok())
+                    .unwrap_or(0)
+            };
+            let time = element
+                .get_property("current-level-time")
+                .ok()
+                .and_then(|value| value.get_some::<u64>().ok())
+                .unwrap_or(0);
+            line += &format!(
+                " {} buffers, {} bytes, {}",
+                level("current-level-buffers"),
+                level("current-level-bytes"),
+                gst::ClockTime::from_nseconds(time)
+            );
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+pub struct Dashboard {
+    window: Window<ConceptFrame>,
+    pools: DoubleMemPool,
+    dimensions: (u32, u32),
+    closed: bool,
+}
+
+impl Dashboard {
+    /// The dashboard window, decorated with `frame_config`.
+    pub fn new(
+        env: &Environment<ThemedFrameExample>,
+        frame_config: ConceptConfig,
+    ) -> Result<Self, Error> {
+        let dimensions = (480, 360);
+        let surface = env.create_surface().detach();
+        let mut window = env.create_window::<ConceptFrame, _>(
+            surface,
+            None,
+            dimensions,
+            |evt, mut dispatch_data| {
+                let state = dispatch_data.get::<DispatchState>().unwrap();
+                crate::queue_action(&mut state.dashboard_action, evt);
+            },
+        )
+        .map_err(|err| anyhow!("Failed to create the dashboard window: {:?}", err))?;
+        window.set_title(TITLE.to_string());
+        window.set_frame_config(frame_config);
+
+        let pools = env.create_double_pool(|_| {})?;
+
+        Ok(Dashboard {
+            window,
+            pools,
+            dimensions,
+            closed: false,
+        })
+    }
+
+    /// Handle an event of the window, which closes without ending the application.
+    pub fn handle(&mut self, event: WEvent) {
+        match event {
+            WEvent::Close => {
+                self.closed = true;
+                self.window.surface().attach(None, 0, 0);
+                self.window.surface().commit();
+            }
+            WEvent::Refresh => {
+                self.window.refresh();
+                self.window.surface().commit();
+            }
+            WEvent::Configure { new_size, .. } => {
+                if let Some((w, h)) = new_size {
+                    self.window.resize(w, h);
+                    self.dimensions = (w, h);
+                }
+                self.window.refresh();
+            }
+        }
+    }
+
+    pub fn update(&mut self, pipeline: &gst::Pipeline) {
+        if self.closed {
+            return;
+        }
+        let pool = match self.pools.pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+
+        let (width, height) = self.dimensions;
+        let mut canvas = match Canvas::new(pool, width, height) {
+            Ok(canvas) => canvas,
+            Err(err) => {
+                warn!("Failed to draw the dashboard: {}", err);
+                return;
+            }
+        };
+        canvas.fill(BACKGROUND);
+        for (row, line) in describe(pipeline).iter().enumerate() {
+            let y = MARGIN + row as i32 * LINE_HEIGHT;
+            if y + canvas::text_height(TEXT_SCALE) > canvas.height() {
+                break;
+            }
+            canvas.draw_text(MARGIN, y, TEXT_SCALE, line, FOREGROUND);
+        }
+
+        canvas.attach(self.window.surface());
+    }
+}
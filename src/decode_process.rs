@@ -0,0 +1,180 @@
+//! Decoding in a child process, for `--decode-process`.
+//!
+//! The child is this same binary, run with `--decode-child`: it decodes the
+//! URI with uridecodebin and hands the raw video to an `ipcpipelinesink`
+//! writing to its stdin, one end of a socket pair. The pipeline of the window
+//! is an `ipcslavepipeline` whose `ipcpipelinesrc` reads the other end, so
+//! that nothing but frames, events and queries cross over, and a decoder
+//! crashing only takes the child down. The window then starts another child,
+//! from the start of the URI, up to `MAX_RESTARTS` times.
+//!
+//! The child has no access to the display, its environment is stripped of
+//! the Wayland, X11 and session bus addresses. `--decode-sandbox` runs it
+//! under a sandbox command on top of that, such as
+//! `bwrap --ro-bind / / --dev /dev --unshare-all --`.
+//!
+//! Only the video is decoded, the audio isn't played.
+
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::{debug, error, info, warn};
+
+use crate::make;
+
+/// How many times a crashed child is replaced before giving up.
+const MAX_RESTARTS: u32 = 5;
+
+/// The environment variables the child goes without.
+const STRIPPED_ENV: [&str; 4] = [
+    "WAYLAND_DISPLAY",
+    "WAYLAND_SOCKET",
+    "DISPLAY",
+    "DBUS_SESSION_BUS_ADDRESS",
+];
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Decoding failed: {}", _0)]
+pub struct DecodeError(#[error(not(source))] String);
+
+/// Start a child decoding `uri` on `socket`, under `sandbox` if any.
+fn spawn(uri: &str, sandbox: Option<&str>, socket: UnixStream) -> Result<Child, Error> {
+    let exe = std::env::current_exe()?;
+    let mut words = sandbox.into_iter().flat_map(str::split_whitespace);
+    let mut command = match words.next() {
+        Some(program) => {
+            let mut command = Command::new(program);
+            command.args(words).arg(exe);
+            command
+        }
+        None => Command::new(exe),
+    };
+    command.args(&["--decode-child", "--uri", uri]);
+    for name in &STRIPPED_ENV {
+        command.env_remove(name);
+    }
+    // The socket is the stdin of the child, which needs no other fd
+    // Safety: the fd is owned by `socket`, which gives it up
+    command.stdin(unsafe { Stdio::from_raw_fd(socket.into_raw_fd()) });
+    let child = command.current_dir("/").spawn()?;
+    debug!(pid = child.id(), "Started the decoder");
+    Ok(child)
+}
+
+/// The pipeline showing the video of a child decoding `uri`, into `video`,
+/// the video bin.
+pub fn create_pipeline(
+    uri: &str,
+    sandbox: Option<&str>,
+    video: &gst::Bin,
+) -> Result<gst::Pipeline, Error> {
+    let pipeline = make("ipcslavepipeline")?
+        .downcast::<gst::Pipeline>()
+        .unwrap();
+    let src = make("ipcpipelinesrc")?;
+
+    let (ours, theirs) = UnixStream::pair()?;
+    src.set_property("fdin", &ours.as_raw_fd())?;
+    src.set_property("fdout", &ours.as_raw_fd())?;
+    pipeline.add_many(&[&src, video.upcast_ref()])?;
+    src.link(video)?;
+
+    let mut child = spawn(uri, sandbox, theirs)?;
+    let (uri, sandbox) = (uri.to_owned(), sandbox.map(str::to_owned));
+    thread::spawn(move || {
+        // Our end of the socket lives as long as the child it talks to
+        let mut socket = ours;
+        let mut restarts = 0;
+        loop {
+            match child.wait() {
+                Ok(status) if status.success() => {
+                    info!("The decoder finished");
+                    return;
+                }
+                Ok(status) => warn!("The decoder exited with {}", status),
+                Err(err) => {
+                    error!("Failed to wait for the decoder: {}", err);
+                    return;
+                }
+            }
+            if restarts == MAX_RESTARTS {
+                error!("The decoder failed {} times, giving up", restarts + 1);
+                return;
+            }
+            restarts += 1;
+
+            let (new_ours, theirs) = match UnixStream::pair() {
+                Ok(pair) => pair,
+                Err(err) => {
+                    error!("Failed to restart the decoder: {}", err);
+                    return;
+                }
+            };
+            // The source reads from the new socket once started again
+            let _ = src.set_state(gst::State::Null);
+            let _ = src.set_property("fdin", &new_ours.as_raw_fd());
+            let _ = src.set_property("fdout", &new_ours.as_raw_fd());
+            drop(std::mem::replace(&mut socket, new_ours));
+            if let Err(err) = src.sync_state_with_parent() {
+                error!("Failed to restart the decoder: {}", err);
+                return;
+            }
+            child = match spawn(&uri, sandbox.as_deref(), theirs) {
+                Ok(child) => child,
+                Err(err) => {
+                    error!("Failed to restart the decoder: {}", err);
+                    return;
+                }
+            };
+            info!("Restarted the decoder ({}/{})", restarts, MAX_RESTARTS);
+        }
+    });
+
+    Ok(pipeline)
+}
+
+/// Decode `uri` to the window on stdin, until the end of the stream.
+pub fn run_child(uri: &str) -> Result<(), Error> {
+    let pipeline = gst::Pipeline::new(Some("decoder"));
+    let decodebin = make("uridecodebin")?;
+    let videoconvert = make("videoconvert")?;
+    let sink = make("ipcpipelinesink")?;
+    decodebin.set_property("uri", &uri)?;
+    sink.set_property("fdin", &0i32)?;
+    sink.set_property("fdout", &0i32)?;
+    pipeline.add_many(&[&decodebin, &videoconvert, &sink])?;
+    videoconvert.link(&sink)?;
+
+    let convert_sink = videoconvert.get_static_pad("sink").unwrap();
+    decodebin.connect_pad_added(move |_, pad| {
+        let is_video = pad
+            .get_current_caps()
+            .and_then(|caps| caps.get_structure(0).map(|s| s.get_name().starts_with("video/")))
+            .unwrap_or(false);
+        if is_video && !convert_sink.is_linked() {
+            if let Err(err) = pad.link(&convert_sink) {
+                warn!("Failed to link the decoded video: {:?}", err);
+            }
+        }
+    });
+
+    pipeline.set_state(gst::State::Playing)?;
+    let bus = pipeline.get_bus().unwrap();
+    let result = match bus.timed_pop_filtered(
+        gst::CLOCK_TIME_NONE,
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    ) {
+        Some(message) => match message.view() {
+            gst::MessageView::Error(err) => Err(DecodeError(err.get_error().to_string()).into()),
+            _ => Ok(()),
+        },
+        None => Ok(()),
+    };
+    let _ = pipeline.set_state(gst::State::Null);
+    result
+}
@@ -0,0 +1,49 @@
+//! Window decorations.
+//!
+//! Compositors offering `zxdg_decoration_manager_v1`, such as KDE or sway,
+//! draw the decorations of the window themselves, matching the rest of the
+//! desktop. The themed `ConceptFrame` is only drawn when they don't, or when
+//! client-side decorations are asked for.
+//!
+//! Borderless windows have no decorations at all, for kiosks and signage:
+//! the compositor is asked not to draw any and the frame stays hidden, so
+//! that the whole surface of the toplevel is the video.
+
+use sctk::environment::Environment;
+use sctk::reexports::protocols::unstable::xdg_decoration::v1::client::zxdg_decoration_manager_v1;
+use sctk::window::{ConceptFrame, Decorations, Window};
+use tracing::info;
+
+use crate::ThemedFrameExample;
+
+/// Who draws the decorations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecorationMode {
+    /// The compositor when it can, the frame otherwise.
+    ServerSide,
+    /// Always the frame.
+    ClientSide,
+    /// No decorations.
+    None,
+}
+
+/// Decorate `window` as `mode` asks, applied with its next refresh.
+pub fn apply(
+    env: &Environment<ThemedFrameExample>,
+    window: &mut Window<ConceptFrame>,
+    mode: DecorationMode,
+) {
+    match mode {
+        DecorationMode::ServerSide => {
+            if env
+                .get_global::<zxdg_decoration_manager_v1::ZxdgDecorationManagerV1>()
+                .is_none()
+            {
+                info!("No server-side decorations, drawing the window frame");
+            }
+            window.set_decorate(Decorations::ServerSide);
+        }
+        DecorationMode::ClientSide => window.set_decorate(Decorations::ClientSide),
+        DecorationMode::None => window.set_decorate(Decorations::None),
+    }
+}
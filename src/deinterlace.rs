@@ -0,0 +1,71 @@
+//! Deinterlacing of interlaced sources, such as DVB and capture cards.
+//!
+//! The `deinterlace` element sits right after the converter, ahead of the
+//! recording and the display filters. In `auto` mode it only deinterlaces
+//! the caps negotiated as interlaced and passes progressive video through,
+//! `on` deinterlaces everything, for sources that don't flag their fields.
+//! It only takes 8-bit video, deeper video is converted down for it.
+
+use std::str::FromStr;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::info;
+
+use crate::make;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown deinterlacing {}, expected auto, on or off", _0)]
+pub struct UnknownDeinterlacing(#[error(not(source))] String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deinterlacing {
+    /// Deinterlace interlaced caps only.
+    Auto,
+    /// Deinterlace every frame.
+    On,
+    /// Leave the video as it comes, without the element.
+    Off,
+}
+
+impl FromStr for Deinterlacing {
+    type Err = UnknownDeinterlacing;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Deinterlacing::Auto),
+            "on" => Ok(Deinterlacing::On),
+            "off" => Ok(Deinterlacing::Off),
+            _ => Err(UnknownDeinterlacing(s.to_owned())),
+        }
+    }
+}
+
+/// The deinterlacer for `deinterlacing`, if any.
+pub fn create(deinterlacing: Deinterlacing) -> Result<Option<gst::Element>, Error> {
+    let mode = match deinterlacing {
+        Deinterlacing::Auto => "auto",
+        Deinterlacing::On => "interlaced",
+        Deinterlacing::Off => return Ok(None),
+    };
+    let deinterlace = make("deinterlace")?;
+    deinterlace.set_property_from_str("mode", mode);
+
+    // Say when the video turns out to be interlaced
+    let pad = deinterlace.get_static_pad("sink").unwrap();
+    pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, |_, info| {
+        if let Some(gst::PadProbeData::Event(event)) = &info.data {
+            if let gst::EventView::Caps(caps) = event.view() {
+                let progressive = gst_video::VideoInterlaceMode::Progressive;
+                let interlaced = gst_video::VideoInfo::from_caps(caps.get_caps())
+                    .map_or(false, |info| info.interlace_mode() != progressive);
+                if interlaced {
+                    info!("Deinterlacing {}", caps.get_caps());
+                }
+            }
+        }
+        gst::PadProbeReturn::Ok
+    });
+    Ok(Some(deinterlace))
+}
@@ -0,0 +1,86 @@
+//! Video deeper than 8 bits, such as the P010 of 10-bit HDR decoders.
+//!
+//! videoconvert passes it through untouched when downstream takes it, along
+//! with its colorimetry and HDR caps. The text overlays of the display
+//! filters only blend in 8 bits though, and would have it converted down.
+//! So when the first caps of the video are deeper and the sink takes them
+//! as they are, the display filters are bypassed and the video goes
+//! straight to the sink, without the OSD and other overlays.
+//!
+//! `--force-8bit` converts to 8 bits right away instead, keeping the display
+//! filters whatever the video.
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::{debug, info, warn};
+
+use crate::make;
+
+/// The formats the display filters take.
+const EIGHT_BIT_FORMATS: [&str; 9] = [
+    "BGRx", "BGRA", "RGBx", "RGBA", "NV12", "I420", "YV12", "YUY2", "UYVY",
+];
+
+/// Whether `caps` are of raw video with more than 8 bits a component.
+pub fn is_deep(caps: &gst::CapsRef) -> bool {
+    gst_video::VideoInfo::from_caps(caps).map_or(false, |info| info.format_info().depth()[0] > 8)
+}
+
+/// A capsfilter for the 8-bit formats of the display filters, to follow the
+/// converter with.
+pub fn eight_bit_filter() -> Result<gst::Element, Error> {
+    let capsfilter = make("capsfilter")?;
+    let formats: Vec<&dyn ToSendValue> = EIGHT_BIT_FORMATS
+        .iter()
+        .map(|format| format as &dyn ToSendValue)
+        .collect();
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", &gst::List::new(&formats))
+        .build();
+    capsfilter.set_property("caps", &caps)?;
+    Ok(capsfilter)
+}
+
+/// Link `from` straight to `sink`, past the `filters` linked in between,
+/// if the first caps coming through `input`, of the video before it is
+/// converted, are deep and `sink` takes them.
+pub fn bypass_when_deep(
+    input: &gst::Pad,
+    from: &gst::Element,
+    filters: &[&gst::Element],
+    sink: &gst::Element,
+) {
+    let (first, last) = match (filters.first(), filters.last()) {
+        (Some(first), Some(last)) => ((*first).clone(), (*last).clone()),
+        _ => return,
+    };
+    let (from, sink) = (from.clone(), sink.clone());
+
+    // Before the converter negotiates its output with the filters
+    input.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+        let caps = match &info.data {
+            Some(gst::PadProbeData::Event(event)) => match event.view() {
+                gst::EventView::Caps(caps) => caps.get_caps_owned(),
+                _ => return gst::PadProbeReturn::Ok,
+            },
+            _ => return gst::PadProbeReturn::Ok,
+        };
+        let sink_pad = sink.get_static_pad("sink").unwrap();
+        if !is_deep(&caps) {
+            debug!(?caps, "8-bit video, through the display filters");
+        } else if !sink_pad.query_accept_caps(&caps) {
+            info!("The sink doesn't take {}, converting it to 8 bits", caps);
+        } else {
+            from.unlink(&first);
+            last.unlink(&sink);
+            match from.link(&sink) {
+                Ok(()) => info!("Passing {} through, without the display filters", caps),
+                Err(err) => {
+                    warn!("Failed to bypass the display filters: {}", err);
+                    let _ = from.link(&first).and_then(|_| last.link(&sink));
+                }
+            }
+        }
+        gst::PadProbeReturn::Remove
+    });
+}
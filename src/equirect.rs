@@ -0,0 +1,149 @@
+//! Viewing of 360° equirectangular video.
+//!
+//! The frames are projected onto a sphere looked at from the inside, with a
+//! GLSL shader run by `glshader`. Dragging with the pointer looks around.
+
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::warn;
+
+use crate::make;
+
+const SHADER: &str = "
+#ifdef GL_ES
+precision mediump float;
+#endif
+varying vec2 v_texcoord;
+uniform sampler2D tex;
+uniform float yaw;
+uniform float pitch;
+uniform float fov;
+uniform float aspect;
+
+const float PI = 3.14159265;
+
+void main () {
+  // Ray through this pixel of a pinhole camera looking down +z
+  float scale = tan (fov * 0.5);
+  vec2 ndc = v_texcoord * 2.0 - 1.0;
+  vec3 dir = normalize (vec3 (ndc.x * scale * aspect, -ndc.y * scale, 1.0));
+
+  float cp = cos (pitch);
+  float sp = sin (pitch);
+  dir = vec3 (dir.x, dir.y * cp + dir.z * sp, dir.z * cp - dir.y * sp);
+  float cy = cos (yaw);
+  float sy = sin (yaw);
+  dir = vec3 (dir.x * cy + dir.z * sy, dir.y, dir.z * cy - dir.x * sy);
+
+  float longitude = atan (dir.x, dir.z);
+  float latitude = asin (dir.y);
+  gl_FragColor = texture2D (tex, vec2 (longitude / (2.0 * PI) + 0.5, 0.5 - latitude / PI));
+}
+";
+
+/// Where the view looks, and the aspect ratio of the frames it is drawn in.
+#[derive(Debug, Clone, Copy)]
+struct View {
+    yaw: f32,
+    pitch: f32,
+    fov: f32,
+    aspect: f32,
+}
+
+impl View {
+    fn apply(&self, shader: &gst::Element) -> Result<(), Error> {
+        let uniforms = gst::Structure::builder("uniforms")
+            .field("yaw", &self.yaw)
+            .field("pitch", &self.pitch)
+            .field("fov", &self.fov)
+            .field("aspect", &self.aspect)
+            .build();
+        shader.set_property("uniforms", &uniforms)?;
+        Ok(())
+    }
+}
+
+/// The display aspect ratio of frames of `caps`.
+fn display_aspect(caps: &gst::CapsRef) -> Option<f32> {
+    let info = gst_video::VideoInfo::from_caps(caps).ok()?;
+    let par = info.par();
+    let (numer, denom) = (*par.numer() as f32, *par.denom() as f32);
+    if info.height() == 0 || denom == 0.0 {
+        return None;
+    }
+    Some(info.width() as f32 * numer / (info.height() as f32 * denom))
+}
+
+/// Perspective view into equirectangular frames.
+pub struct Equirect {
+    elements: Vec<gst::Element>,
+    shader: gst::Element,
+    view: Arc<Mutex<View>>,
+}
+
+impl Equirect {
+    /// `fov` is the vertical field of view, in degrees.
+    pub fn new(fov: f32) -> Result<Self, Error> {
+        let upload = make("glupload")?;
+        let colorconvert = make("glcolorconvert")?;
+        let shader = make("glshader")?;
+        let download = make("gldownload")?;
+        // gldownload produces RGBA, the following filters want BGRx
+        let videoconvert = make("videoconvert")?;
+
+        shader.set_property("fragment", &SHADER)?;
+
+        // The aspect ratio is that of the frames, known from their caps
+        let view = Arc::new(Mutex::new(View {
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: fov.to_radians(),
+            aspect: 4.0 / 3.0,
+        }));
+        view.lock().unwrap().apply(&shader)?;
+        let (caps_view, caps_shader) = (view.clone(), shader.clone());
+        let pad = upload.get_static_pad("sink").unwrap();
+        pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+            if let Some(gst::PadProbeData::Event(event)) = &info.data {
+                if let gst::EventView::Caps(caps) = event.view() {
+                    if let Some(aspect) = display_aspect(caps.get_caps()) {
+                        let mut view = caps_view.lock().unwrap();
+                        view.aspect = aspect;
+                        if let Err(err) = view.apply(&caps_shader) {
+                            warn!("Failed to update the 360° view: {}", err);
+                        }
+                    }
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
+
+        Ok(Equirect {
+            elements: vec![upload, colorconvert, shader.clone(), download, videoconvert],
+            shader,
+            view,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        self.elements.iter().collect()
+    }
+
+    /// Turn the view by a drag of `dx`, `dy`, in fractions of the window size.
+    pub fn look(&mut self, dx: f64, dy: f64) {
+        let mut view = self.view.lock().unwrap();
+        // The scene follows the pointer
+        view.yaw = (view.yaw - dx as f32 * view.fov * view.aspect) % (2.0 * PI);
+        view.pitch = (view.pitch + dy as f32 * view.fov)
+            .max(-FRAC_PI_2)
+            .min(FRAC_PI_2);
+
+        if let Err(err) = view.apply(&self.shader) {
+            warn!("Failed to update the 360° view: {}", err);
+        }
+    }
+}
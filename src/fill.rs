@@ -0,0 +1,137 @@
+//! Filling frames with a solid color, fast enough for 4K60 on modest ARM
+//! boards.
+//!
+//! The frame is filled in bands of lines that fit in the L2 cache: the first
+//! line of a band is written pixel by pixel, and the others are copies of it
+//! while it is still hot. Frames larger than the last level cache would only
+//! evict everything else on their way to the sink, they are written with
+//! non-temporal stores instead, which bypass the cache. `benches/fill.rs`
+//! measures both against the plain per-pixel loop.
+
+/// Bytes of a band of lines, about what the L2 cache of small cores holds.
+const BAND_BYTES: usize = 128 * 1024;
+/// Frames larger than this skip the cache: 4K BGRx frames are twice as
+/// large, 1080p ones half.
+pub const NON_TEMPORAL_BYTES: usize = 16 * 1024 * 1024;
+
+/// Fill the `width` first pixels of the `height` lines of `stride` bytes
+/// of `data` with `bgrx`.
+pub fn fill_bgrx(data: &mut [u8], stride: usize, width: usize, height: usize, bgrx: [u8; 4]) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let line_bytes = 4 * width;
+    assert!(line_bytes <= stride);
+    // Leave what follows the last line alone
+    let data = &mut data[..stride * (height - 1) + line_bytes];
+
+    if stride * height >= NON_TEMPORAL_BYTES && non_temporal::AVAILABLE {
+        for line in data.chunks_mut(stride) {
+            non_temporal::fill(&mut line[..line_bytes], u32::from_ne_bytes(bgrx));
+        }
+        non_temporal::fence();
+        return;
+    }
+
+    let band_lines = (BAND_BYTES / stride).max(1);
+    for band in data.chunks_mut(stride * band_lines) {
+        let (first, rest) = band.split_at_mut(stride.min(band.len()));
+        fill_line(&mut first[..line_bytes], bgrx);
+        for line in rest.chunks_mut(stride) {
+            line[..line_bytes].copy_from_slice(&first[..line_bytes]);
+        }
+    }
+}
+
+/// The per-pixel fill, which the compiler vectorizes well for a single line.
+pub fn fill_line(line: &mut [u8], bgrx: [u8; 4]) {
+    for pixel in line.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&bgrx);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod non_temporal {
+    use std::arch::x86_64::*;
+
+    pub const AVAILABLE: bool = true;
+
+    /// Fill `line`, whose length is a multiple of 4, with `pixel`.
+    pub fn fill(line: &mut [u8], pixel: u32) {
+        let pixels = line.len() / 4;
+        let ptr = line.as_mut_ptr();
+        // SSE2 is part of x86_64. Writes stay within `line`: 4 byte stores
+        // up to 16 byte alignment, 16 byte ones while whole pixels remain,
+        // and 4 byte ones for the tail.
+        unsafe {
+            let mut i = 0;
+            while i < pixels && (ptr.add(4 * i) as usize) & 15 != 0 {
+                _mm_stream_si32(ptr.add(4 * i) as *mut i32, pixel as i32);
+                i += 1;
+            }
+            let wide = _mm_set1_epi32(pixel as i32);
+            while i + 4 <= pixels {
+                _mm_stream_si128(ptr.add(4 * i) as *mut __m128i, wide);
+                i += 4;
+            }
+            while i < pixels {
+                _mm_stream_si32(ptr.add(4 * i) as *mut i32, pixel as i32);
+                i += 1;
+            }
+        }
+    }
+
+    /// Order the non-temporal stores before the frame is handed over.
+    pub fn fence() {
+        unsafe { _mm_sfence() }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod non_temporal {
+    use std::arch::asm;
+
+    pub const AVAILABLE: bool = true;
+
+    /// Fill `line`, whose length is a multiple of 4, with `pixel`.
+    pub fn fill(line: &mut [u8], pixel: u32) {
+        let (body, tail) = line.split_at_mut(line.len() / 32 * 32);
+        if !body.is_empty() {
+            // STNP of two NEON registers writes 32 bytes per iteration,
+            // exactly covering `body`. Unaligned addresses are fine on
+            // normal memory.
+            unsafe {
+                asm!(
+                    "dup v0.4s, {pixel:w}",
+                    "2:",
+                    "stnp q0, q0, [{ptr}]",
+                    "add {ptr}, {ptr}, #32",
+                    "subs {count}, {count}, #1",
+                    "b.ne 2b",
+                    pixel = in(reg) pixel,
+                    ptr = inout(reg) body.as_mut_ptr() => _,
+                    count = inout(reg) body.len() / 32 => _,
+                    out("v0") _,
+                    options(nostack),
+                );
+            }
+        }
+        super::fill_line(tail, pixel.to_ne_bytes());
+    }
+
+    /// Order the non-temporal stores before the frame is handed over.
+    pub fn fence() {
+        unsafe { asm!("dmb ishst", options(nostack, preserves_flags)) }
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod non_temporal {
+    pub const AVAILABLE: bool = false;
+
+    pub fn fill(line: &mut [u8], pixel: u32) {
+        super::fill_line(line, pixel.to_ne_bytes());
+    }
+
+    pub fn fence() {}
+}
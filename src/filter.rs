@@ -0,0 +1,58 @@
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::make;
+
+/// A CPU video filter working in place on BGRx frames.
+///
+/// This is a `capsfilter` forcing BGRx, which is also what we produce, with
+/// a buffer probe on its source pad handing every frame to `process`, along
+/// with its width.
+pub struct BgrxFilter {
+    capsfilter: gst::Element,
+}
+
+impl BgrxFilter {
+    pub fn new<F>(process: F) -> Result<Self, Error>
+    where
+        F: Fn(&mut [[u8; 4]], usize) + Send + Sync + 'static,
+    {
+        let capsfilter = make("capsfilter")?;
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("format", &"BGRx")
+            .build();
+        capsfilter.set_property("caps", &caps)?;
+
+        let pad = capsfilter.get_static_pad("src").unwrap();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |pad, info| {
+            let width = pad
+                .get_current_caps()
+                .and_then(|caps| gst_video::VideoInfo::from_caps(&caps).ok())
+                .map(|video_info| video_info.width() as usize);
+
+            if let (Some(width), Some(gst::PadProbeData::Buffer(buffer))) =
+                (width, &mut info.data)
+            {
+                let buffer = buffer.make_mut();
+                if let Ok(mut map) = buffer.map_writable() {
+                    let data = map.as_mut_slice();
+                    // BGRx lines have no padding, the frame is one run of pixels
+                    let pixels = unsafe {
+                        std::slice::from_raw_parts_mut(
+                            data.as_mut_ptr() as *mut [u8; 4],
+                            data.len() / 4,
+                        )
+                    };
+                    process(pixels, width);
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
+
+        Ok(BgrxFilter { capsfilter })
+    }
+
+    pub fn element(&self) -> &gst::Element {
+        &self.capsfilter
+    }
+}
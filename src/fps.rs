@@ -0,0 +1,95 @@
+//! Rendered and dropped frame rates, shown over the video.
+//!
+//! Rates come from the `stats` of the video sink, QoS messages of the
+//! pipeline are counted along, with the last reported jitter.
+
+use std::time::Instant;
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::make;
+
+struct Sample {
+    time: Instant,
+    rendered: u64,
+    dropped: u64,
+}
+
+pub struct Fps {
+    overlay: gst::Element,
+    shown: bool,
+    last: Option<Sample>,
+    qos_messages: u64,
+    jitter: i64,
+}
+
+fn sink_stats(sink: &gst::Element) -> Option<(u64, u64)> {
+    let stats = sink.get_property("stats").ok()?.get::<gst::Structure>().ok()??;
+    let rendered = stats.get_some::<u64>("rendered").ok()?;
+    let dropped = stats.get_some::<u64>("dropped").ok()?;
+    Some((rendered, dropped))
+}
+
+impl Fps {
+    pub fn new() -> Result<Self, Error> {
+        let overlay = make("textoverlay")?;
+        overlay.set_property_from_str("halignment", "left");
+        overlay.set_property_from_str("valignment", "bottom");
+        overlay.set_property("font-desc", &"Monospace 12")?;
+        overlay.set_property("silent", &true)?;
+
+        Ok(Fps {
+            overlay,
+            shown: false,
+            last: None,
+            qos_messages: 0,
+            jitter: 0,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.overlay]
+    }
+
+    pub fn toggle(&mut self) {
+        self.shown = !self.shown;
+        let _ = self.overlay.set_property("silent", &!self.shown);
+    }
+
+    pub fn on_qos(&mut self, qos: &gst::message::Qos) {
+        let (jitter, _, _) = qos.get_values();
+        self.qos_messages += 1;
+        self.jitter = jitter;
+    }
+
+    /// Update the rates from the statistics of `sink`.
+    pub fn update(&mut self, sink: &gst::Element) {
+        let (rendered, dropped) = match sink_stats(sink) {
+            Some(stats) => stats,
+            None => return,
+        };
+        let now = Sample {
+            time: Instant::now(),
+            rendered,
+            dropped,
+        };
+
+        if let Some(last) = &self.last {
+            let elapsed = now.time.duration_since(last.time).as_secs_f64();
+            if elapsed > 0.0 {
+                let rate = |now: u64, last: u64| now.saturating_sub(last) as f64 / elapsed;
+                let text = format!(
+                    "{:.1} fps rendered, {:.1} fps dropped\n{} QoS messages, jitter {:.1} ms",
+                    rate(now.rendered, last.rendered),
+                    rate(now.dropped, last.dropped),
+                    self.qos_messages,
+                    self.jitter as f64 / 1e6
+                );
+                let _ = self.overlay.set_property("text", &text);
+            }
+        }
+        self.last = Some(now);
+    }
+}
@@ -0,0 +1,99 @@
+//! Dumps of the displayed frames as numbered PNGs.
+//!
+//! A probe on the sink pad takes every Nth frame the sink is handed for a
+//! while, and a thread converts them and writes them to a timestamped
+//! directory next to the binary, as `frame-000001.png` and on, numbered by
+//! frame. The probe only copies references to the buffers, so that the
+//! video doesn't wait on the encoding. A dump ends by itself, or when the
+//! next one starts.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::{info, warn};
+
+use crate::snapshot;
+
+/// A dump in progress, which ends when dropped.
+pub struct FrameDump {
+    stopped: Arc<AtomicBool>,
+}
+
+impl FrameDump {
+    /// Dump every `every`th frame handed to `sink` for `duration`, returns
+    /// the dump along with the directory the frames go to.
+    pub fn start(
+        sink: &gst::Element,
+        every: u32,
+        duration: Duration,
+    ) -> Result<(Self, PathBuf), Error> {
+        let now = gst::glib::DateTime::new_now_local();
+        let name = now
+            .format("frames-%Y%m%d-%H%M%S")
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "frames".into());
+        let exe = std::env::current_exe()?;
+        let directory = exe.parent().unwrap_or_else(|| Path::new(".")).join(name);
+        std::fs::create_dir_all(&directory)?;
+
+        let (sender, frames) = mpsc::channel::<(u64, gst::Sample)>();
+        let written = directory.clone();
+        thread::spawn(move || {
+            let mut count = 0;
+            for (number, sample) in frames {
+                let path = written.join(format!("frame-{:06}.png", number));
+                let saved = snapshot::rgba_frame(&sample)
+                    .and_then(|frame| snapshot::write_png(&frame, &path));
+                match saved {
+                    Ok(()) => count += 1,
+                    Err(err) => warn!("Failed to save {}: {}", path.display(), err),
+                }
+            }
+            info!("Dumped {} frames to {}", count, written.display());
+        });
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
+        let end = Instant::now() + duration;
+        let every = u64::from(every.max(1));
+        let mut number = 0;
+        let pad = sink.get_static_pad("sink").unwrap();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |pad, info| {
+            // The sender goes with the probe, which ends the thread
+            if stop.load(Ordering::Relaxed) || Instant::now() >= end {
+                return gst::PadProbeReturn::Remove;
+            }
+            number += 1;
+            if (number - 1) % every != 0 {
+                return gst::PadProbeReturn::Ok;
+            }
+            let buffer = match &info.data {
+                Some(gst::PadProbeData::Buffer(buffer)) => buffer.clone(),
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            let caps = match pad.get_current_caps() {
+                Some(caps) => caps,
+                None => return gst::PadProbeReturn::Ok,
+            };
+            let sample = gst::Sample::builder().buffer(&buffer).caps(&caps).build();
+            match sender.send((number, sample)) {
+                Ok(()) => gst::PadProbeReturn::Ok,
+                Err(_) => gst::PadProbeReturn::Remove,
+            }
+        });
+
+        Ok((FrameDump { stopped }, directory))
+    }
+}
+
+impl Drop for FrameDump {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
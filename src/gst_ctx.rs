@@ -0,0 +1,90 @@
+//! GStreamer contexts sharing our Wayland connection with the sinks.
+//!
+//! waylandsink looks for a `GstWaylandDisplayHandleContextType` context
+//! holding the `wl_display` pointer, so that it renders on the connection of
+//! the application instead of opening its own, which would make its surfaces
+//! unusable as subsurfaces of ours.
+
+use std::ffi::c_void;
+
+use gst::glib;
+use gst::glib::translate::*;
+use gst::prelude::*;
+use sctk::reexports::client::Display;
+
+pub const WAYLAND_DISPLAY_HANDLE_CONTEXT_TYPE: &str = "GstWaylandDisplayHandleContextType";
+
+/// A `G_TYPE_POINTER` value holding `pointer`.
+///
+/// glib-rs has no safe way to set pointers, or to send them, as it can't know
+/// what they point to. GValue only copies the address around, so the value
+/// can be sent as long as what it points to can, which is the caller's
+/// business.
+pub fn pointer_value(pointer: *mut c_void) -> glib::SendValue {
+    let mut value = glib::Value::from_type(glib::Type::Pointer);
+    unsafe {
+        glib::gobject_sys::g_value_set_pointer(value.to_glib_none_mut().0, pointer);
+        glib::SendValue::from_glib_none(value.to_glib_none().0)
+    }
+}
+
+/// The context handing the `wl_display` at `handle` to waylandsink.
+pub fn display_handle_context(handle: *mut c_void) -> gst::Context {
+    let mut context = gst::Context::new(WAYLAND_DISPLAY_HANDLE_CONTEXT_TYPE, true);
+    {
+        let context = context.get_mut().unwrap();
+        let s = context.get_mut_structure();
+        s.set_value("handle", pointer_value(handle));
+    }
+    context
+}
+
+/// The context handing `display` to waylandsink.
+pub fn wayland_display_context(display: &Display) -> gst::Context {
+    // wl_display is thread safe, waylandsink uses it from its own threads
+    display_handle_context(display.c_ptr() as *mut c_void)
+}
+
+/// Make `element`, a waylandsink, render on `display`.
+pub fn set_wayland_display(element: &gst::Element, display: &Display) {
+    element.set_context(&wayland_display_context(display));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The address held by a `G_TYPE_POINTER` value.
+    fn get_pointer(value: &glib::Value) -> *mut c_void {
+        unsafe { glib::gobject_sys::g_value_get_pointer(value.to_glib_none().0) }
+    }
+
+    #[test]
+    fn pointer_value_holds_the_address() {
+        let mut target = 0u32;
+        let pointer = &mut target as *mut u32 as *mut c_void;
+        let value = pointer_value(pointer);
+        assert_eq!(value.type_(), glib::Type::Pointer);
+        assert_eq!(get_pointer(&value), pointer);
+    }
+
+    #[test]
+    fn pointer_value_holds_null() {
+        let value = pointer_value(std::ptr::null_mut());
+        assert_eq!(value.type_(), glib::Type::Pointer);
+        assert!(get_pointer(&value).is_null());
+    }
+
+    #[test]
+    fn display_handle_context_holds_the_handle() {
+        gst::init().unwrap();
+        let mut display = 0u64;
+        let handle = &mut display as *mut u64 as *mut c_void;
+        let context = display_handle_context(handle);
+        assert_eq!(context.get_context_type(), WAYLAND_DISPLAY_HANDLE_CONTEXT_TYPE);
+        assert!(context.is_persistent());
+        let value = context.get_structure().get_value("handle").unwrap();
+        assert_eq!(value.type_(), glib::Type::Pointer);
+        assert_eq!(get_pointer(value), handle);
+    }
+}
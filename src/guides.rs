@@ -0,0 +1,174 @@
+//! Broadcast guides drawn over the video: safe areas, center cross and
+//! thirds grid, as on confidence monitors.
+//!
+//! The safe areas are those of EBU R 95, 93% of the picture for action and
+//! 90% for graphics. They are taken within the picture of a given aspect
+//! ratio centered in the frame, such as the 4:3 center cut of 16:9 video,
+//! whose outline is drawn too. By default it is the frame itself.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+
+use crate::filter::BgrxFilter;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid aspect ratio {}, expected W:H or a ratio such as 2.39", _0)]
+pub struct InvalidAspectRatio(#[error(not(source))] String);
+
+const ACTION_SAFE: f64 = 0.93;
+const GRAPHICS_SAFE: f64 = 0.90;
+
+const SAFE: [u8; 4] = [0xE0, 0xE0, 0xE0, 0];
+const GRID: [u8; 4] = [0x80, 0x80, 0x80, 0];
+const PICTURE: [u8; 4] = [0x00, 0xC0, 0xFF, 0];
+
+/// A width to height ratio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AspectRatio(pub f64);
+
+impl FromStr for AspectRatio {
+    type Err = InvalidAspectRatio;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidAspectRatio(s.to_owned());
+        let ratio = match s.find(':') {
+            Some(pos) => {
+                let width: f64 = s[..pos].parse().map_err(|_| invalid())?;
+                let height: f64 = s[pos + 1..].parse().map_err(|_| invalid())?;
+                width / height
+            }
+            None => s.parse().map_err(|_| invalid())?,
+        };
+        if ratio.is_finite() && ratio > 0.0 {
+            Ok(AspectRatio(ratio))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// The largest rectangle of `aspect` centered in a frame of `width` by
+/// `height`, as `(x, y, width, height)`.
+pub fn centered(aspect: AspectRatio, width: usize, height: usize) -> (usize, usize, usize, usize) {
+    let frame = width as f64 / height as f64;
+    if aspect.0 < frame {
+        let inner = (height as f64 * aspect.0).round() as usize;
+        ((width - inner) / 2, 0, inner, height)
+    } else {
+        let inner = (width as f64 / aspect.0).round() as usize;
+        (0, (height - inner) / 2, width, inner)
+    }
+}
+
+/// Frame pixels and their width, to draw lines on.
+struct Frame<'a> {
+    pixels: &'a mut [[u8; 4]],
+    width: usize,
+    height: usize,
+    thickness: usize,
+}
+
+impl Frame<'_> {
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 4]) {
+        let right = (x + width).min(self.width);
+        let bottom = (y + height).min(self.height);
+        for y in y..bottom {
+            let line = y * self.width;
+            for pixel in &mut self.pixels[line + x.min(right)..line + right] {
+                *pixel = color;
+            }
+        }
+    }
+
+    fn outline(&mut self, (x, y, width, height): (usize, usize, usize, usize), color: [u8; 4]) {
+        let t = self.thickness;
+        self.fill(x, y, width, t, color);
+        self.fill(x, (y + height).saturating_sub(t), width, t, color);
+        self.fill(x, y, t, height, color);
+        self.fill((x + width).saturating_sub(t), y, t, height, color);
+    }
+
+    /// `area` shrunk to `fraction` of its size around its center.
+    fn inset(area: (usize, usize, usize, usize), fraction: f64) -> (usize, usize, usize, usize) {
+        let (x, y, width, height) = area;
+        let inner_width = (width as f64 * fraction).round() as usize;
+        let inner_height = (height as f64 * fraction).round() as usize;
+        (
+            x + (width - inner_width) / 2,
+            y + (height - inner_height) / 2,
+            inner_width,
+            inner_height,
+        )
+    }
+
+    fn draw(&mut self, aspect: Option<AspectRatio>) {
+        let area = match aspect {
+            Some(aspect) => centered(aspect, self.width, self.height),
+            None => (0, 0, self.width, self.height),
+        };
+        let (x, y, width, height) = area;
+        let t = self.thickness;
+
+        // Thirds, under the rest
+        for third in 1..3 {
+            self.fill(x + width * third / 3, y, t, height, GRID);
+            self.fill(x, y + height * third / 3, width, t, GRID);
+        }
+
+        if aspect.is_some() {
+            self.outline(area, PICTURE);
+        }
+        self.outline(Self::inset(area, ACTION_SAFE), SAFE);
+        self.outline(Self::inset(area, GRAPHICS_SAFE), SAFE);
+
+        let (center_x, center_y) = (x + width / 2, y + height / 2);
+        let arm = height / 20;
+        self.fill(center_x.saturating_sub(arm), center_y, 2 * arm, t, SAFE);
+        self.fill(center_x, center_y.saturating_sub(arm), t, 2 * arm, SAFE);
+    }
+}
+
+/// Draws the guides over the displayed video, unless hidden.
+pub struct Guides {
+    filter: BgrxFilter,
+    shown: Arc<AtomicBool>,
+}
+
+impl Guides {
+    /// Guides for the picture of `aspect` in the frame, or the whole frame.
+    pub fn new(aspect: Option<AspectRatio>) -> Result<Self, Error> {
+        let shown = Arc::new(AtomicBool::new(true));
+
+        let filter_shown = shown.clone();
+        let filter = BgrxFilter::new(move |pixels, width| {
+            if !filter_shown.load(Ordering::Relaxed) || width == 0 {
+                return;
+            }
+            let height = pixels.len() / width;
+            let mut frame = Frame {
+                pixels,
+                width,
+                height,
+                // Still visible once the video is scaled down to the window
+                thickness: (height / 360).max(1),
+            };
+            frame.draw(aspect);
+        })?;
+
+        Ok(Guides { filter, shown })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![self.filter.element()]
+    }
+
+    /// Hide or show the guides, returns whether they are now shown.
+    pub fn toggle(&self) -> bool {
+        !self.shown.fetch_xor(true, Ordering::Relaxed)
+    }
+}
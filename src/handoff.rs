@@ -0,0 +1,180 @@
+//! Handing playback over to a new instance, for upgrades without a gap.
+//!
+//! With `--handoff`, the player listens on
+//! `$XDG_RUNTIME_DIR/waylandsink-handoff.sock`. Another instance started
+//! with `--handoff` while it does takes over: it is sent a JSON line of what
+//! is played, where, and the window size and fullscreen state, prerolls
+//! there, and answers `ready` once it plays, at which the old instance
+//! closes. The new one then listens for the next upgrade in turn.
+//!
+//! The sockets of the old instance are left to the new one, which binds
+//! them anew while the old one still runs. They are only removed on exit by
+//! the instance that bound them.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::Instant;
+
+use anyhow::Error;
+use sctk::reexports::calloop::channel::Sender;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::runtime_socket::{self, RuntimeSocket};
+
+const SOCKET_NAME: &str = "waylandsink-handoff.sock";
+
+/// What the old instance plays, sent to the new one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    /// None for the synthetic video.
+    pub uri: Option<String>,
+    /// In seconds.
+    pub position: f64,
+    pub playing: bool,
+    pub size: (u32, u32),
+    pub fullscreen: bool,
+}
+
+impl State {
+    fn to_json(&self) -> Value {
+        json!({
+            "uri": self.uri,
+            "position": self.position,
+            "playing": self.playing,
+            "width": self.size.0,
+            "height": self.size.1,
+            "fullscreen": self.fullscreen,
+        })
+    }
+
+    fn from_json(json: &Value) -> Option<Self> {
+        let dimension = |name: &str| json[name].as_u64().map(|value| value as u32);
+        Some(State {
+            uri: json["uri"].as_str().map(str::to_owned),
+            position: json["position"].as_f64()?,
+            playing: json["playing"].as_bool()?,
+            size: (dimension("width")?, dimension("height")?),
+            fullscreen: json["fullscreen"].as_bool()?,
+        })
+    }
+
+    /// Hand the state over to the new instance on `stream`.
+    pub fn send(&self, mut stream: UnixStream) {
+        info!("Handing playback over at {:.3}s", self.position);
+        if let Err(err) = writeln!(stream, "{}", self.to_json()) {
+            warn!("Failed to hand playback over: {}", err);
+        }
+    }
+}
+
+/// A takeover of the old instance, until the new one plays.
+pub struct Takeover {
+    pub state: State,
+    stream: UnixStream,
+    received: Instant,
+}
+
+impl Takeover {
+    /// Take over from the instance listening, if there is one.
+    pub fn connect() -> Option<Self> {
+        let path = runtime_socket::path(SOCKET_NAME).ok()?;
+        let stream = UnixStream::connect(&path).ok()?;
+        let mut line = String::new();
+        if let Err(err) = BufReader::new(&stream).read_line(&mut line) {
+            warn!("Failed to take playback over: {}", err);
+            return None;
+        }
+        let state = serde_json::from_str(&line).ok().and_then(|json| State::from_json(&json));
+        match state {
+            Some(state) => {
+                info!(?state, "Taking playback over");
+                Some(Takeover {
+                    state,
+                    stream,
+                    received: Instant::now(),
+                })
+            }
+            None => {
+                warn!("Invalid playback handed over: {}", line.trim());
+                None
+            }
+        }
+    }
+
+    /// Preroll `pipeline` where the old instance is by now, which keeps
+    /// playing meanwhile.
+    pub fn preroll(&self, pipeline: &gst::Pipeline) -> Result<(), Error> {
+        let mut position = self.state.position;
+        pipeline.set_state(gst::State::Paused)?;
+        let (result, _, _) = pipeline.get_state(gst::ClockTime::from_seconds(5));
+        result?;
+        if self.state.playing {
+            position += self.received.elapsed().as_secs_f64();
+        }
+        if position > 0.0 {
+            let position = gst::ClockTime::from_nseconds((position * 1e9) as u64);
+            let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE;
+            pipeline.seek_simple(flags, position)?;
+        }
+        Ok(())
+    }
+
+    /// Let the old instance go, once the new one plays.
+    pub fn ready(mut self) {
+        if let Err(err) = writeln!(self.stream, "ready") {
+            warn!("Failed to release the old instance: {}", err);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Event {
+    /// A new instance takes over, and waits for the state on this stream.
+    Connected(UnixStream),
+    /// It plays, this instance can go.
+    Ready,
+}
+
+/// The listening socket, removed when dropped unless another instance took
+/// it over.
+pub struct Listener {
+    _socket: RuntimeSocket,
+}
+
+impl Listener {
+    /// Listen for new instances on a thread, sending their `Event`s to the
+    /// event loop. After a takeover, the socket of the old instance is
+    /// replaced rather than refused.
+    pub fn new(sender: Sender<Event>, took_over: bool) -> Result<Self, Error> {
+        let (socket, listener) = RuntimeSocket::bind(SOCKET_NAME, took_over)?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (writer, reader) = match stream.and_then(|s| Ok((s.try_clone()?, s))) {
+                    Ok(streams) => streams,
+                    Err(err) => {
+                        warn!("Failed to accept a new instance: {}", err);
+                        continue;
+                    }
+                };
+                if sender.send(Event::Connected(writer)).is_err() {
+                    break;
+                }
+                let mut line = String::new();
+                match BufReader::new(reader).read_line(&mut line) {
+                    Ok(_) if line.trim() == "ready" => {
+                        let _ = sender.send(Event::Ready);
+                        break;
+                    }
+                    // The new instance failed, this one goes on
+                    Ok(_) => warn!("The new instance didn't take over"),
+                    Err(err) => warn!("The new instance didn't take over: {}", err),
+                }
+            }
+        });
+
+        Ok(Listener { _socket: socket })
+    }
+}
@@ -0,0 +1,396 @@
+//! The HTTP endpoint, for dashboards keeping an eye on players.
+//!
+//! With `--http ADDRESS`, up to `MAX_CLIENTS` clients are served on a thread
+//! each, one request per connection:
+//!
+//! ```text
+//! GET /snapshot.jpg    the frame currently displayed, encoded as JPEG
+//! GET /capabilities.json
+//!                      what the compositor and the sink support, see
+//!                      `capabilities`
+//! GET /preview.mjpeg   the low frame rate preview of `preview`, as a
+//!                      multipart/x-mixed-replace stream of JPEGs
+//! ```
+//!
+//! With tokens set, see `auth`, requests carry a read or control one in an
+//! `Authorization: Bearer` header, or in the `token` query parameter for
+//! clients that can't set headers, such as an `<img>` of a dashboard.
+//!
+//! Built with the `tls` feature, the endpoint serves HTTPS with a certificate
+//! and key, so that the tokens don't travel in cleartext.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use tracing::{debug, warn};
+use wayland_sink_with_video_overlay::capabilities::Capabilities;
+
+use crate::auth::{Role, Tokens};
+use crate::preview::Preview;
+use crate::snapshot;
+#[cfg(not(feature = "tls"))]
+use crate::MissingFeature;
+
+const BOUNDARY: &str = "preview-frame";
+/// How long the preview may stall before it is given up on.
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a client may take to send its request, or to take a write.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The clients served at once, each on a thread.
+const MAX_CLIENTS: usize = 32;
+/// The longest request or header line, and the most headers, of a request.
+const MAX_LINE: usize = 8192;
+const MAX_HEADERS: usize = 64;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Request line or headers too long")]
+struct RequestTooLarge;
+
+#[cfg(feature = "tls")]
+#[derive(Debug, Display, Error)]
+#[display(fmt = "No private key in {}", _0)]
+struct NoPrivateKey(#[error(not(source))] String);
+
+/// A client connection, in the clear or over TLS.
+trait Stream: Read + Write + Send {}
+
+impl<S: Read + Write + Send> Stream for S {}
+
+/// The TLS configuration of the endpoint, of which there is none without
+/// the `tls` feature.
+#[cfg(feature = "tls")]
+type TlsConfig = Arc<rustls::ServerConfig>;
+#[cfg(not(feature = "tls"))]
+type TlsConfig = std::convert::Infallible;
+
+/// The configuration serving the certificate chain and the private key of
+/// the PEM files `cert` and `key`.
+#[cfg(feature = "tls")]
+fn load_tls(cert: &Path, key: &Path) -> Result<TlsConfig, Error> {
+    use std::fs::File;
+
+    use rustls_pemfile::Item;
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    let mut reader = BufReader::new(File::open(key)?);
+    let private_key = loop {
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(Item::RSAKey(der)) | Some(Item::PKCS8Key(der)) | Some(Item::ECKey(der)) => {
+                break rustls::PrivateKey(der)
+            }
+            Some(_) => {}
+            None => return Err(NoPrivateKey(key.display().to_string()).into()),
+        }
+    };
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)?;
+    Ok(Arc::new(config))
+}
+
+#[cfg(not(feature = "tls"))]
+fn load_tls(_cert: &Path, _key: &Path) -> Result<TlsConfig, Error> {
+    Err(MissingFeature("tls").into())
+}
+
+/// The connection of a client on `stream`, over TLS with `tls`.
+fn accept(stream: TcpStream, tls: Option<&TlsConfig>) -> io::Result<Box<dyn Stream>> {
+    match tls {
+        #[cfg(feature = "tls")]
+        Some(config) => {
+            let connection = rustls::ServerConnection::new(config.clone())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            Ok(Box::new(rustls::StreamOwned::new(connection, stream)))
+        }
+        #[cfg(not(feature = "tls"))]
+        Some(never) => match *never {},
+        None => Ok(Box::new(stream)),
+    }
+}
+
+/// A response to write.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Response {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain",
+            body: format!("{}\n", message).into_bytes(),
+        }
+    }
+
+    fn write(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+             Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+/// Read a line of `reader` into `line`, refusing ones over `MAX_LINE`.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    let read = reader.by_ref().take(MAX_LINE as u64).read_line(line)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, RequestTooLarge));
+    }
+    Ok(read)
+}
+
+/// The method, path, query and token of the request of `reader`, leaving
+/// the body unread.
+fn read_request(reader: &mut impl BufRead) -> io::Result<(String, String, Option<String>)> {
+    let mut line = String::new();
+    read_line(reader, &mut line)?;
+    let mut words = line.split_whitespace();
+    let method = words.next().unwrap_or_default().to_owned();
+    let target = words.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut token = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .map(str::to_owned);
+    for count in 0.. {
+        let mut header = String::new();
+        if read_line(reader, &mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, RequestTooLarge));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("authorization") {
+                if let Some(bearer) = value.trim().strip_prefix("Bearer ") {
+                    token = Some(bearer.trim().to_owned());
+                }
+            }
+        }
+    }
+    Ok((method, path.to_owned(), token))
+}
+
+fn respond(path: &str, sink: &gst::Element, capabilities: &Capabilities) -> Response {
+    match path {
+        "/snapshot.jpg" => match snapshot::last_jpeg(sink) {
+            Ok(jpeg) => Response::ok("image/jpeg", jpeg),
+            Err(err) => Response::error("503 Service Unavailable", &err.to_string()),
+        },
+        "/capabilities.json" => match serde_json::to_vec(capabilities) {
+            Ok(json) => Response::ok("application/json", json),
+            Err(err) => Response::error("500 Internal Server Error", &err.to_string()),
+        },
+        _ => Response::error("404 Not Found", "Not found"),
+    }
+}
+
+/// Write the part of the preview stream holding `jpeg`.
+fn write_part(stream: &mut impl Write, jpeg: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+        BOUNDARY,
+        jpeg.len()
+    )?;
+    stream.write_all(jpeg)?;
+    stream.write_all(b"\r\n")?;
+    stream.flush()
+}
+
+/// Write the JPEGs of `preview` to `stream` as they come, until the client
+/// hangs up or the preview stalls, such as while paused.
+fn stream_preview(stream: &mut impl Write, preview: &Preview) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        BOUNDARY
+    )?;
+    let mut sequence = 0;
+    while let Some((next, jpeg)) = preview.next(sequence, PREVIEW_TIMEOUT) {
+        sequence = next;
+        match write_part(stream, &jpeg) {
+            Ok(()) => {}
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset
+                ) =>
+            {
+                break
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+fn serve(
+    stream: Box<dyn Stream>,
+    sink: &gst::Element,
+    preview: Option<&Preview>,
+    capabilities: &Capabilities,
+    tokens: &Tokens,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let (method, path, token) = read_request(&mut reader)?;
+    let writer = reader.get_mut();
+    debug!(%method, %path, "HTTP request");
+
+    let response = if method != "GET" {
+        Response::error("405 Method Not Allowed", "Only GET is served")
+    } else if let Err(err) = tokens.authorize(token.as_deref(), Role::Read) {
+        Response::error("401 Unauthorized", &err.to_string())
+    } else if path == "/preview.mjpeg" {
+        match preview {
+            Some(preview) => return stream_preview(writer, preview),
+            None => Response::error("404 Not Found", "No preview of this pipeline"),
+        }
+    } else {
+        respond(&path, sink, capabilities)
+    };
+    response.write(writer)
+}
+
+fn set_timeouts(stream: &TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))
+}
+
+/// A client being served, counted until it is dropped.
+struct Client(Arc<AtomicUsize>);
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The listening endpoint.
+pub struct HttpServer {
+    address: SocketAddr,
+    tls: bool,
+}
+
+impl HttpServer {
+    /// Listen on `address` for clients on a thread, serving what `sink`
+    /// displays, the `preview` and the `capabilities`, as far as their
+    /// `tokens` allow, over TLS with the PEM certificate chain and key of
+    /// `tls` if given.
+    pub fn new(
+        address: &str,
+        sink: gst::Element,
+        preview: Option<Preview>,
+        capabilities: Capabilities,
+        tokens: Tokens,
+        tls: Option<(&Path, &Path)>,
+    ) -> Result<Self, Error> {
+        let tls = tls.map(|(cert, key)| load_tls(cert, key)).transpose()?;
+        let clients = Arc::new(AtomicUsize::new(0));
+        let listener = TcpListener::bind(address)?;
+        let server = HttpServer {
+            address: listener.local_addr()?,
+            tls: tls.is_some(),
+        };
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if clients.fetch_add(1, Ordering::SeqCst) >= MAX_CLIENTS {
+                            clients.fetch_sub(1, Ordering::SeqCst);
+                            warn!("Refusing an HTTP client, {} are served", MAX_CLIENTS);
+                            continue;
+                        }
+                        let client = Client(clients.clone());
+                        let (sink, tokens) = (sink.clone(), tokens.clone());
+                        let (preview, capabilities) = (preview.clone(), capabilities.clone());
+                        let tls = tls.clone();
+                        thread::spawn(move || {
+                            let _client = client;
+                            let served = set_timeouts(&stream)
+                                .and_then(|()| accept(stream, tls.as_ref()))
+                                .and_then(|stream| {
+                                    serve(stream, &sink, preview.as_ref(), &capabilities, &tokens)
+                                });
+                            if let Err(err) = served {
+                                warn!("HTTP client failed: {}", err);
+                            }
+                        });
+                    }
+                    Err(err) => warn!("Failed to accept an HTTP client: {}", err),
+                }
+            }
+        });
+
+        Ok(server)
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Whether clients are served over TLS.
+    pub fn is_tls(&self) -> bool {
+        self.tls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_token_of_a_request() {
+        let request = "GET /snapshot.jpg?size=1&token=abc HTTP/1.1\r\nHost: player\r\n\r\n";
+        let (method, path, token) = read_request(&mut request.as_bytes()).unwrap();
+        assert_eq!(method, "GET");
+        assert_eq!(path, "/snapshot.jpg");
+        assert_eq!(token.as_deref(), Some("abc"));
+
+        let request = "GET / HTTP/1.1\r\nauthorization: Bearer  def \r\n\r\n";
+        let (_, _, token) = read_request(&mut request.as_bytes()).unwrap();
+        assert_eq!(token.as_deref(), Some("def"));
+    }
+
+    #[test]
+    fn refuses_oversized_requests() {
+        let request = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(read_request(&mut request.as_bytes()).is_err());
+
+        let request = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: y\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert!(read_request(&mut request.as_bytes()).is_err());
+    }
+}
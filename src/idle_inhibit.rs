@@ -0,0 +1,79 @@
+//! Keeping the screen on while the video plays.
+//!
+//! An inhibitor of `zwp_idle_inhibit_manager_v1` exists on the window surface
+//! as long as the pipeline is playing, and is destroyed on pause, at the end
+//! of the stream and with the window. Compositors only honor it while the
+//! surface is visible.
+
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::reexports::client::Main;
+use sctk::reexports::protocols::unstable::idle_inhibit::v1::client::{
+    zwp_idle_inhibit_manager_v1, zwp_idle_inhibitor_v1,
+};
+use tracing::info;
+
+use crate::ThemedFrameExample;
+
+pub struct IdleInhibit {
+    manager: Option<Main<zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1>>,
+    surface: wl_surface::WlSurface,
+    inhibitor: Option<Main<zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1>>,
+    ended: bool,
+}
+
+impl IdleInhibit {
+    /// Inhibit idling on `surface`, if the compositor supports it.
+    pub fn new(env: &Environment<ThemedFrameExample>, surface: &wl_surface::WlSurface) -> Self {
+        let manager = env
+            .manager
+            .instantiate_exact::<zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1>(1)
+            .ok();
+        if manager.is_none() {
+            info!("No idle inhibition, the screen may blank during playback");
+        }
+
+        IdleInhibit {
+            manager,
+            surface: surface.clone(),
+            inhibitor: None,
+            ended: false,
+        }
+    }
+
+    /// Follow the end of the stream, and seeks away from it.
+    pub fn on_message(&mut self, msg: &gst::Message) {
+        match msg.view() {
+            gst::MessageView::Eos(_) => self.ended = true,
+            gst::MessageView::AsyncDone(_) => self.ended = false,
+            _ => {}
+        }
+    }
+
+    /// Inhibit idling when `playing` and not at the end of the stream.
+    pub fn update(&mut self, playing: bool) {
+        let manager = match &self.manager {
+            Some(manager) => manager,
+            None => return,
+        };
+        match (playing && !self.ended, &self.inhibitor) {
+            (true, None) => self.inhibitor = Some(manager.create_inhibitor(&self.surface)),
+            (false, Some(inhibitor)) => {
+                inhibitor.destroy();
+                self.inhibitor = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for IdleInhibit {
+    fn drop(&mut self) {
+        if let Some(inhibitor) = &self.inhibitor {
+            inhibitor.destroy();
+        }
+        if let Some(manager) = &self.manager {
+            manager.destroy();
+        }
+    }
+}
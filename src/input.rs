@@ -0,0 +1,653 @@
+use derive_more::{Display, Error};
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::{wl_keyboard, wl_pointer, wl_seat, wl_surface, wl_touch};
+use sctk::reexports::client::{Attached, DispatchData, Main};
+use sctk::reexports::protocols::unstable::pointer_gestures::v1::client::{
+    zwp_pointer_gesture_pinch_v1, zwp_pointer_gestures_v1,
+};
+use sctk::seat::keyboard::{keysyms, map_keyboard, Event as KbEvent, KeyState};
+use sctk::seat::{with_seat_data, SeatListener};
+use tracing::warn;
+
+use crate::{DispatchState, ThemedFrameExample};
+
+/// A user request, independent of the input device that triggered it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    ToggleFullscreen,
+    TogglePip,
+    ToggleOsd,
+    Snapshot,
+    /// Save the last seconds of the `--timeshift` input.
+    SaveReplay,
+    /// Dump the displayed frames as PNGs for a while.
+    DumpFrames,
+    ToggleLut,
+    TogglePlay,
+    SlowDown,
+    SpeedUp,
+    StepForward,
+    StepBackward,
+    SeekForward,
+    SeekBackward,
+    CycleSubtitles,
+    ToggleSubtitles,
+    VolumeUp,
+    VolumeDown,
+    ToggleMute,
+    ToggleFps,
+    /// Hide and show the timestamps of the frames.
+    ToggleTimeOverlay,
+    /// Hide and show the wall clock.
+    ToggleClockOverlay,
+    ToggleGuides,
+    NextTrack,
+    PreviousTrack,
+    ToggleTheme,
+    /// Select the next of brightness, contrast, hue and saturation.
+    BalanceNext,
+    BalanceUp,
+    BalanceDown,
+    BalanceReset,
+    Rotate,
+    FlipHorizontal,
+    FlipVertical,
+    ResetZoom,
+    /// Swap the main video and the `--pip-uri` one.
+    SwapPip,
+    /// Move the `--pip-uri` video to the next corner.
+    MovePip,
+    GrowPip,
+    ShrinkPip,
+    /// Ctrl+C, with the seat and serial of the key press stored in
+    /// `DispatchState::key_serial`.
+    CopyFrame,
+    /// Pointer drag over the video, in surface coordinates.
+    Drag { dx: f64, dy: f64 },
+    /// Scroll wheel over the video, in clicks, positive to zoom in.
+    Zoom { steps: f64 },
+    /// Two finger pinch over the video, by the ratio of the distances
+    /// between the fingers, above 1 to zoom in.
+    Pinch { ratio: f64 },
+    /// Touchpad pinch over the video, by the ratio of the scales before and
+    /// after, above 1 to grow it.
+    ScaleVideo { ratio: f64 },
+    /// Double click on the video, to undo the `ScaleVideo`s.
+    ResetVideoScale,
+    /// Left button press, drag and release on the on-screen controls, in
+    /// surface coordinates.
+    ControlsPress { x: f64 },
+    ControlsDrag { x: f64 },
+    ControlsRelease { x: f64 },
+}
+
+// from linux/input-event-codes.h
+const BTN_LEFT: u32 = 0x110;
+
+/// Farthest a finger can move and still tap, in surface coordinates.
+const TAP_SLOP: f64 = 10.0;
+/// Longest a tap lasts, in milliseconds.
+const TAP_TIME: u32 = 300;
+
+/// Longest time between the clicks of a double click, in milliseconds.
+const DOUBLE_CLICK_TIME: u32 = 400;
+/// Shortest horizontal swipe, in surface coordinates.
+const SWIPE_DISTANCE: f64 = 80.0;
+
+/// The commands triggered by a key, by the name given to them in the
+/// configuration file, with their default key.
+const BINDINGS: [(&str, Command, u32); 38] = [
+    ("fullscreen", Command::ToggleFullscreen, keysyms::XKB_KEY_f),
+    ("pip", Command::TogglePip, keysyms::XKB_KEY_w),
+    ("osd", Command::ToggleOsd, keysyms::XKB_KEY_o),
+    ("snapshot", Command::Snapshot, keysyms::XKB_KEY_S),
+    ("replay", Command::SaveReplay, keysyms::XKB_KEY_i),
+    ("dump-frames", Command::DumpFrames, keysyms::XKB_KEY_d),
+    ("lut", Command::ToggleLut, keysyms::XKB_KEY_l),
+    ("play-pause", Command::TogglePlay, keysyms::XKB_KEY_space),
+    ("slow-down", Command::SlowDown, keysyms::XKB_KEY_bracketleft),
+    ("speed-up", Command::SpeedUp, keysyms::XKB_KEY_bracketright),
+    ("step-forward", Command::StepForward, keysyms::XKB_KEY_period),
+    ("step-backward", Command::StepBackward, keysyms::XKB_KEY_comma),
+    ("seek-forward", Command::SeekForward, keysyms::XKB_KEY_Right),
+    ("seek-backward", Command::SeekBackward, keysyms::XKB_KEY_Left),
+    ("cycle-subtitles", Command::CycleSubtitles, keysyms::XKB_KEY_j),
+    ("subtitles", Command::ToggleSubtitles, keysyms::XKB_KEY_c),
+    ("volume-up", Command::VolumeUp, keysyms::XKB_KEY_Up),
+    ("volume-down", Command::VolumeDown, keysyms::XKB_KEY_Down),
+    ("mute", Command::ToggleMute, keysyms::XKB_KEY_m),
+    ("fps", Command::ToggleFps, keysyms::XKB_KEY_F2),
+    ("time-overlay", Command::ToggleTimeOverlay, keysyms::XKB_KEY_F3),
+    ("clock-overlay", Command::ToggleClockOverlay, keysyms::XKB_KEY_F4),
+    ("guides", Command::ToggleGuides, keysyms::XKB_KEY_g),
+    ("next", Command::NextTrack, keysyms::XKB_KEY_n),
+    ("previous", Command::PreviousTrack, keysyms::XKB_KEY_p),
+    ("theme", Command::ToggleTheme, keysyms::XKB_KEY_t),
+    ("balance-next", Command::BalanceNext, keysyms::XKB_KEY_a),
+    ("balance-up", Command::BalanceUp, keysyms::XKB_KEY_equal),
+    ("balance-down", Command::BalanceDown, keysyms::XKB_KEY_minus),
+    ("balance-reset", Command::BalanceReset, keysyms::XKB_KEY_0),
+    ("rotate", Command::Rotate, keysyms::XKB_KEY_r),
+    ("flip-horizontal", Command::FlipHorizontal, keysyms::XKB_KEY_h),
+    ("flip-vertical", Command::FlipVertical, keysyms::XKB_KEY_v),
+    ("reset-zoom", Command::ResetZoom, keysyms::XKB_KEY_z),
+    ("pip-swap", Command::SwapPip, keysyms::XKB_KEY_x),
+    ("pip-move", Command::MovePip, keysyms::XKB_KEY_k),
+    ("pip-grow", Command::GrowPip, keysyms::XKB_KEY_Page_Up),
+    ("pip-shrink", Command::ShrinkPip, keysyms::XKB_KEY_Page_Down),
+];
+
+/// Keys other than the printable ASCII characters, whose keysyms are their
+/// codes.
+const NAMED_KEYS: [(&str, u32); 12] = [
+    ("space", keysyms::XKB_KEY_space),
+    ("Up", keysyms::XKB_KEY_Up),
+    ("Down", keysyms::XKB_KEY_Down),
+    ("Left", keysyms::XKB_KEY_Left),
+    ("Right", keysyms::XKB_KEY_Right),
+    ("Home", keysyms::XKB_KEY_Home),
+    ("End", keysyms::XKB_KEY_End),
+    ("Page_Up", keysyms::XKB_KEY_Page_Up),
+    ("Page_Down", keysyms::XKB_KEY_Page_Down),
+    ("Return", keysyms::XKB_KEY_Return),
+    ("Tab", keysyms::XKB_KEY_Tab),
+    ("Escape", keysyms::XKB_KEY_Escape),
+];
+
+/// The keysym of the key named `name`, which is either a printable ASCII
+/// character, `F1` to `F12`, or one of `NAMED_KEYS`.
+fn keysym_from_name(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return if c.is_ascii_graphic() { Some(c as u32) } else { None };
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        return if (1..=12).contains(&n) {
+            Some(keysyms::XKB_KEY_F1 + n - 1)
+        } else {
+            None
+        };
+    }
+    NAMED_KEYS.iter().find(|(key, _)| *key == name).map(|(_, keysym)| *keysym)
+}
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid key binding {} = {}", command, key)]
+pub struct InvalidBinding {
+    command: String,
+    key: String,
+}
+
+/// Which key triggers which command.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(u32, Command)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: BINDINGS.iter().map(|(_, command, keysym)| (*keysym, *command)).collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Trigger the command named `command` in `BINDINGS` with the key named
+    /// `key` instead of its default one. Whatever the key triggered before
+    /// is left without a key.
+    pub fn bind(&mut self, command: &str, key: &str) -> Result<(), InvalidBinding> {
+        let invalid = || InvalidBinding {
+            command: command.to_owned(),
+            key: key.to_owned(),
+        };
+        let command = BINDINGS
+            .iter()
+            .find(|(name, _, _)| *name == command)
+            .map(|(_, command, _)| *command)
+            .ok_or_else(invalid)?;
+        let keysym = keysym_from_name(key).ok_or_else(invalid)?;
+
+        self.bindings.retain(|binding| binding.0 != keysym && binding.1 != command);
+        self.bindings.push((keysym, command));
+        Ok(())
+    }
+
+    fn command(&self, keysym: u32, ctrl: bool) -> Option<Command> {
+        if ctrl && keysym == keysyms::XKB_KEY_c {
+            return Some(Command::CopyFrame);
+        }
+        self.bindings
+            .iter()
+            .find(|binding| binding.0 == keysym)
+            .map(|binding| binding.1)
+    }
+}
+
+fn handle_keyboard_event(
+    seat: &wl_seat::WlSeat,
+    keymap: &Keymap,
+    ctrl: &mut bool,
+    event: KbEvent,
+    mut ddata: DispatchData,
+) {
+    match event {
+        KbEvent::Modifiers { modifiers } => *ctrl = modifiers.ctrl,
+        KbEvent::Key {
+            serial,
+            keysym,
+            state: KeyState::Pressed,
+            ..
+        } => {
+            if let Some(state) = ddata.get::<DispatchState>() {
+                state.key_serial = Some((seat.clone(), serial));
+                state.commands.extend(keymap.command(keysym, *ctrl));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn map_seat_keyboard(
+    seat: &Attached<wl_seat::WlSeat>,
+    name: &str,
+    keymap: &Keymap,
+) -> Option<wl_keyboard::WlKeyboard> {
+    let (keyboard_seat, keymap, mut ctrl) = (seat.detach(), keymap.clone(), false);
+    let keyboard = map_keyboard(seat, None, move |event, _, ddata| {
+        handle_keyboard_event(&keyboard_seat, &keymap, &mut ctrl, event, ddata)
+    });
+    match keyboard {
+        Ok(kbd) => Some(kbd),
+        Err(e) => {
+            warn!("Failed to map keyboard on seat {} : {:?}.", name, e);
+            None
+        }
+    }
+}
+
+/// Which of our surfaces the pointer is over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    /// Elsewhere, such as on the decorations which are left to the frame.
+    None,
+    Video,
+    Controls,
+}
+
+/// Turns left button drags over the `video` surface into `Command::Drag`s,
+/// double clicks on it into `Command::ResetVideoScale`, scrolling over it
+/// into `Command::Zoom`s, and left button drags over the `controls` into
+/// `Command::Controls*`.
+fn map_seat_pointer(
+    seat: &Attached<wl_seat::WlSeat>,
+    video: &wl_surface::WlSurface,
+    controls: &wl_surface::WlSurface,
+) -> wl_pointer::WlPointer {
+    let (video, controls) = (video.clone(), controls.clone());
+    let mut focus = Focus::None;
+    let mut position = (0.0, 0.0);
+    let mut dragging = Focus::None;
+    // Time of the last press on the video, in milliseconds
+    let mut last_click = None::<u32>;
+
+    let pointer = seat.get_pointer();
+    pointer.quick_assign(move |_, event, mut ddata| {
+        let state = match ddata.get::<DispatchState>() {
+            Some(state) => state,
+            None => return,
+        };
+        match event {
+            wl_pointer::Event::Enter {
+                surface,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                focus = if surface == video {
+                    Focus::Video
+                } else if surface == controls {
+                    Focus::Controls
+                } else {
+                    Focus::None
+                };
+                position = (surface_x, surface_y);
+                state.pointer_active |= focus != Focus::None;
+            }
+            wl_pointer::Event::Leave { .. } => {
+                focus = Focus::None;
+                dragging = Focus::None;
+            }
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                let (dx, dy) = (surface_x - position.0, surface_y - position.1);
+                position = (surface_x, surface_y);
+                state.pointer_active |= focus != Focus::None;
+                match dragging {
+                    Focus::Video => state.commands.push(Command::Drag { dx, dy }),
+                    Focus::Controls => state.commands.push(Command::ControlsDrag { x: surface_x }),
+                    Focus::None => {}
+                }
+            }
+            wl_pointer::Event::Button {
+                time,
+                button: BTN_LEFT,
+                state: button_state,
+                ..
+            } => {
+                let x = position.0;
+                if button_state == wl_pointer::ButtonState::Pressed {
+                    dragging = focus;
+                    match focus {
+                        Focus::Controls => state.commands.push(Command::ControlsPress { x }),
+                        Focus::Video => match last_click.take() {
+                            Some(last) if time.wrapping_sub(last) <= DOUBLE_CLICK_TIME => {
+                                state.commands.push(Command::ResetVideoScale);
+                            }
+                            _ => last_click = Some(time),
+                        },
+                        Focus::None => {}
+                    }
+                } else {
+                    if dragging == Focus::Controls {
+                        state.commands.push(Command::ControlsRelease { x });
+                    }
+                    dragging = Focus::None;
+                }
+            }
+            wl_pointer::Event::Axis {
+                axis: wl_pointer::Axis::VerticalScroll,
+                value,
+                ..
+            } if focus == Focus::Video => {
+                // Scrolling up zooms in, a wheel click being 10 units
+                state.commands.push(Command::Zoom { steps: -value / 10.0 });
+            }
+            _ => {}
+        }
+    });
+
+    pointer.detach()
+}
+
+/// Turns touchpad pinches of `pointer` over the `video` surface into
+/// `Command::ScaleVideo`s.
+fn map_pointer_pinch(
+    gestures: &Main<zwp_pointer_gestures_v1::ZwpPointerGesturesV1>,
+    pointer: &wl_pointer::WlPointer,
+    video: &wl_surface::WlSurface,
+) -> zwp_pointer_gesture_pinch_v1::ZwpPointerGesturePinchV1 {
+    let video = video.clone();
+    // The scale of the pinch in progress over the video, as of the last
+    // update, the compositor sending it relative to the begin
+    let mut scale = None::<f64>;
+
+    let pinch = gestures.get_pinch_gesture(pointer);
+    pinch.quick_assign(move |_, event, mut ddata| {
+        let state = match ddata.get::<DispatchState>() {
+            Some(state) => state,
+            None => return,
+        };
+        match event {
+            zwp_pointer_gesture_pinch_v1::Event::Begin { surface, .. } => {
+                scale = if surface == video { Some(1.0) } else { None };
+            }
+            zwp_pointer_gesture_pinch_v1::Event::Update { scale: after, .. } => {
+                if let Some(before) = scale {
+                    if before > 0.0 && after > 0.0 {
+                        state.commands.push(Command::ScaleVideo { ratio: after / before });
+                        scale = Some(after);
+                    }
+                }
+            }
+            zwp_pointer_gesture_pinch_v1::Event::End { .. } => scale = None,
+            _ => {}
+        }
+    });
+
+    pinch.detach()
+}
+
+/// A finger on the touchscreen.
+struct TouchPoint {
+    focus: Focus,
+    start: (f64, f64),
+    position: (f64, f64),
+    /// Time of the touch down, in milliseconds.
+    time: u32,
+}
+
+fn distance((x0, y0): (f64, f64), (x1, y1): (f64, f64)) -> f64 {
+    (x1 - x0).hypot(y1 - y0)
+}
+
+/// Turns a tap on the `video` surface into `Command::TogglePlay`, a
+/// horizontal swipe over it into `Command::SeekForward` or `SeekBackward`,
+/// and a pinch into `Command::Pinch`. Touches on the `controls` act as left
+/// button drags over them.
+fn map_seat_touch(
+    seat: &Attached<wl_seat::WlSeat>,
+    video: &wl_surface::WlSurface,
+    controls: &wl_surface::WlSurface,
+) -> wl_touch::WlTouch {
+    let (video, controls) = (video.clone(), controls.clone());
+    let mut points = Vec::<(i32, TouchPoint)>::new();
+    // Whether a second finger came down since the first one did, which makes
+    // it a pinch rather than a tap or a swipe
+    let mut pinched = false;
+
+    let touch = seat.get_touch();
+    touch.quick_assign(move |_, event, mut ddata| {
+        let state = match ddata.get::<DispatchState>() {
+            Some(state) => state,
+            None => return,
+        };
+        match event {
+            wl_touch::Event::Down {
+                time,
+                surface,
+                id,
+                x,
+                y,
+                ..
+            } => {
+                let focus = if surface == video {
+                    Focus::Video
+                } else if surface == controls {
+                    Focus::Controls
+                } else {
+                    Focus::None
+                };
+                state.pointer_active |= focus != Focus::None;
+                if focus == Focus::Controls {
+                    state.commands.push(Command::ControlsPress { x });
+                }
+                points.push((
+                    id,
+                    TouchPoint {
+                        focus,
+                        start: (x, y),
+                        position: (x, y),
+                        time,
+                    },
+                ));
+                pinched |= points.len() > 1;
+            }
+            wl_touch::Event::Motion { id, x, y, .. } => {
+                let pinch = match points.as_slice() {
+                    [(_, a), (_, b)] if a.focus == Focus::Video && b.focus == Focus::Video => {
+                        Some(distance(a.position, b.position))
+                    }
+                    _ => None,
+                };
+                let point = match points.iter_mut().find(|(point_id, _)| *point_id == id) {
+                    Some((_, point)) => point,
+                    None => return,
+                };
+                point.position = (x, y);
+                if point.focus == Focus::Controls {
+                    state.commands.push(Command::ControlsDrag { x });
+                }
+                if let (Some(before), [(_, a), (_, b)]) = (pinch, points.as_slice()) {
+                    let after = distance(a.position, b.position);
+                    if before > 0.0 && after > 0.0 {
+                        state.commands.push(Command::Pinch { ratio: after / before });
+                    }
+                }
+            }
+            wl_touch::Event::Up { time, id, .. } => {
+                let index = match points.iter().position(|(point_id, _)| *point_id == id) {
+                    Some(index) => index,
+                    None => return,
+                };
+                let (_, point) = points.remove(index);
+                let (dx, dy) = (point.position.0 - point.start.0, point.position.1 - point.start.1);
+                match point.focus {
+                    Focus::Controls => {
+                        let x = point.position.0;
+                        state.commands.push(Command::ControlsRelease { x });
+                    }
+                    Focus::Video if !pinched => {
+                        let moved = distance(point.start, point.position);
+                        if moved <= TAP_SLOP && time.wrapping_sub(point.time) <= TAP_TIME {
+                            state.commands.push(Command::TogglePlay);
+                        } else if dx.abs() >= SWIPE_DISTANCE && dx.abs() > 2.0 * dy.abs() {
+                            state.commands.push(if dx > 0.0 {
+                                Command::SeekForward
+                            } else {
+                                Command::SeekBackward
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+                if points.is_empty() {
+                    pinched = false;
+                }
+            }
+            wl_touch::Event::Cancel => {
+                points.clear();
+                pinched = false;
+            }
+            _ => {}
+        }
+    });
+
+    touch.detach()
+}
+
+#[derive(Default)]
+struct SeatInputs {
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    pointer: Option<wl_pointer::WlPointer>,
+    /// The touchpad pinches of the pointer.
+    pinch: Option<zwp_pointer_gesture_pinch_v1::ZwpPointerGesturePinchV1>,
+    touch: Option<wl_touch::WlTouch>,
+}
+
+/// The keyboards, pointers and touchscreens of all seats, turning their input
+/// into `Command`s pushed to the `DispatchState`.
+pub struct Inputs {
+    _listener: SeatListener,
+}
+
+impl Inputs {
+    pub fn new(
+        env: &Environment<ThemedFrameExample>,
+        video: &wl_surface::WlSurface,
+        controls: &wl_surface::WlSurface,
+        keymap: Keymap,
+    ) -> Inputs {
+        let mut seats = Vec::<(String, SeatInputs)>::new();
+        let gestures = env
+            .manager
+            .instantiate_exact::<zwp_pointer_gestures_v1::ZwpPointerGesturesV1>(1)
+            .ok();
+
+        // first process already existing seats
+        for seat in env.get_all_seats() {
+            if let Some((has_kbd, has_ptr, has_touch, name)) = with_seat_data(&seat, |seat_data| {
+                (
+                    seat_data.has_keyboard && !seat_data.defunct,
+                    seat_data.has_pointer && !seat_data.defunct,
+                    seat_data.has_touch && !seat_data.defunct,
+                    seat_data.name.clone(),
+                )
+            }) {
+                let pointer = if has_ptr {
+                    Some(map_seat_pointer(&seat, video, controls))
+                } else {
+                    None
+                };
+                let pinch = match (&gestures, &pointer) {
+                    (Some(gestures), Some(pointer)) => {
+                        Some(map_pointer_pinch(gestures, pointer, video))
+                    }
+                    _ => None,
+                };
+                let inputs = SeatInputs {
+                    keyboard: if has_kbd {
+                        map_seat_keyboard(&seat, &name, &keymap)
+                    } else {
+                        None
+                    },
+                    pointer,
+                    pinch,
+                    touch: if has_touch {
+                        Some(map_seat_touch(&seat, video, controls))
+                    } else {
+                        None
+                    },
+                };
+                seats.push((name, inputs));
+            }
+        }
+
+        // then setup a listener for changes
+        let (video, controls) = (video.clone(), controls.clone());
+        let listener = env.listen_for_seats(move |seat, seat_data, _| {
+            let idx = seats.iter().position(|(name, _)| name == &seat_data.name);
+            let idx = idx.unwrap_or_else(|| {
+                seats.push((seat_data.name.clone(), SeatInputs::default()));
+                seats.len() - 1
+            });
+
+            let (_, ref mut inputs) = &mut seats[idx];
+            if seat_data.has_keyboard && !seat_data.defunct {
+                if inputs.keyboard.is_none() {
+                    inputs.keyboard = map_seat_keyboard(&seat, &seat_data.name, &keymap);
+                }
+            } else if let Some(kbd) = inputs.keyboard.take() {
+                kbd.release();
+            }
+            if seat_data.has_pointer && !seat_data.defunct {
+                if inputs.pointer.is_none() {
+                    let pointer = map_seat_pointer(&seat, &video, &controls);
+                    if let Some(gestures) = &gestures {
+                        inputs.pinch = Some(map_pointer_pinch(gestures, &pointer, &video));
+                    }
+                    inputs.pointer = Some(pointer);
+                }
+            } else if let Some(ptr) = inputs.pointer.take() {
+                if let Some(pinch) = inputs.pinch.take() {
+                    pinch.destroy();
+                }
+                ptr.release();
+            }
+            if seat_data.has_touch && !seat_data.defunct {
+                if inputs.touch.is_none() {
+                    inputs.touch = Some(map_seat_touch(&seat, &video, &controls));
+                }
+            } else if let Some(touch) = inputs.touch.take() {
+                touch.release();
+            }
+        });
+
+        Inputs {
+            _listener: listener,
+        }
+    }
+}
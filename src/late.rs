@@ -0,0 +1,206 @@
+//! What becomes of the frames that reach the sink late, and how many did.
+//!
+//! A frame is late when it reaches the sink past its running time plus the
+//! latency of the pipeline. Up to `max-lateness`, it is rendered anyway.
+//! Past that, the policy decides:
+//!
+//! - `drop`, the sink default, drops it and asks upstream to skip frames,
+//!   which keeps up with the clock but stutters under load.
+//! - `render-late` renders every frame however late, so the video runs
+//!   smoothly but behind the clock.
+//! - `freeze` holds the picture still until a frame comes in time again, or
+//!   for a second at most, dropping the frames in between.
+//!
+//! Lateness is measured on the way into the sink, which is what the sink
+//! goes by when it renders right away.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+
+/// The longest freeze, before frames are shown again however late.
+const FREEZE_LIMIT: u64 = 1_000_000_000;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown late frame policy {}, expected drop, render-late or freeze", _0)]
+pub struct UnknownPolicy(#[error(not(source))] String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    Drop,
+    RenderLate,
+    Freeze,
+}
+
+impl FromStr for Policy {
+    type Err = UnknownPolicy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(Policy::Drop),
+            "render-late" => Ok(Policy::RenderLate),
+            "freeze" => Ok(Policy::Freeze),
+            _ => Err(UnknownPolicy(s.to_owned())),
+        }
+    }
+}
+
+/// The `[late-frames]` table of the configuration file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub policy: Policy,
+    /// Lateness past which frames are dropped or frozen, in nanoseconds, -1
+    /// for none. The sink default when unset.
+    pub max_lateness: Option<i64>,
+    /// Time the sink takes to render, in nanoseconds. Frames are handed to
+    /// it that much earlier.
+    pub render_delay: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    on_time: u64,
+    late: u64,
+    dropped: u64,
+    frozen: u64,
+}
+
+#[derive(Default)]
+struct State {
+    counts: Counts,
+    /// When the current freeze started, in clock time.
+    frozen_since: Option<u64>,
+}
+
+impl State {
+    /// Count a frame `lateness` nanoseconds late, returns whether to drop it
+    /// before the sink.
+    fn on_frame(&mut self, policy: Policy, max_lateness: i64, lateness: i64, now: u64) -> bool {
+        let too_late = max_lateness >= 0 && lateness > max_lateness;
+
+        if let Some(since) = self.frozen_since {
+            if lateness > 0 && now.saturating_sub(since) < FREEZE_LIMIT {
+                self.counts.frozen += 1;
+                return true;
+            }
+            self.frozen_since = None;
+        } else if too_late {
+            match policy {
+                Policy::Drop => {
+                    // Dropped by the sink itself
+                    self.counts.dropped += 1;
+                    return false;
+                }
+                Policy::Freeze => {
+                    self.frozen_since = Some(now);
+                    self.counts.frozen += 1;
+                    return true;
+                }
+                Policy::RenderLate => {}
+            }
+        }
+
+        if lateness > 0 {
+            self.counts.late += 1;
+        } else {
+            self.counts.on_time += 1;
+        }
+        false
+    }
+}
+
+pub struct LateFrames {
+    state: Arc<Mutex<State>>,
+    /// Latency of the pipeline, in nanoseconds.
+    latency: Arc<AtomicU64>,
+}
+
+impl LateFrames {
+    /// Apply `settings` to `sink`, counting the frames that reach it.
+    pub fn new(sink: &gst::Element, settings: &Settings) -> Result<Self, Error> {
+        if let Some(render_delay) = settings.render_delay {
+            sink.set_property("render-delay", &render_delay)?;
+        }
+        if let Some(max_lateness) = settings.max_lateness {
+            sink.set_property("max-lateness", &max_lateness)?;
+        }
+        let max_lateness = sink.get_property("max-lateness")?.get_some::<i64>()?;
+        // Frames past `max_lateness` are left to us, or rendered anyway
+        if settings.policy != Policy::Drop {
+            sink.set_property("max-lateness", &-1i64)?;
+        }
+        if settings.policy == Policy::RenderLate {
+            sink.set_property("qos", &false)?;
+        }
+
+        let state = Arc::new(Mutex::new(State::default()));
+        let latency = Arc::new(AtomicU64::new(0));
+        let (probe_state, probe_latency) = (state.clone(), latency.clone());
+        let (element, policy) = (sink.clone(), settings.policy);
+        let pad = sink.get_static_pad("sink").unwrap();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |pad, info| {
+            let pts = match &info.data {
+                Some(gst::PadProbeData::Buffer(buffer)) => buffer.get_pts(),
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            let running_time = pad
+                .get_sticky_event(gst::EventType::Segment, 0)
+                .and_then(|event| match event.view() {
+                    gst::EventView::Segment(segment) => segment
+                        .get_segment()
+                        .downcast_ref::<gst::format::Time>()
+                        .map(|segment| segment.to_running_time(pts)),
+                    _ => None,
+                })
+                .and_then(|time| time.nseconds());
+            let now = element.get_clock().and_then(|clock| clock.get_time().nseconds());
+            let base_time = element.get_base_time().nseconds();
+
+            let (running_time, now, base_time) = match (running_time, now, base_time) {
+                (Some(running_time), Some(now), Some(base_time)) => (running_time, now, base_time),
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            let deadline = base_time + running_time + probe_latency.load(Ordering::Relaxed);
+            let lateness = now as i64 - deadline as i64;
+
+            let drop = probe_state
+                .lock()
+                .unwrap()
+                .on_frame(policy, max_lateness, lateness, now);
+            if drop {
+                gst::PadProbeReturn::Drop
+            } else {
+                gst::PadProbeReturn::Ok
+            }
+        });
+
+        Ok(LateFrames { state, latency })
+    }
+
+    /// Follow the latency of `pipeline`, which changes as it starts and
+    /// with live sources.
+    pub fn on_message(&self, msg: &gst::Message, pipeline: &gst::Pipeline) {
+        use gst::MessageView;
+
+        if let MessageView::Latency(_) | MessageView::AsyncDone(_) = msg.view() {
+            let mut query = gst::query::Latency::new();
+            if pipeline.query(&mut query) {
+                let (live, min, _) = query.get_result();
+                let latency = if live { min.nseconds().unwrap_or(0) } else { 0 };
+                self.latency.store(latency, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let counts = &self.state.lock().unwrap().counts;
+        format!(
+            "{} frames on time, {} late, {} dropped, {} frozen",
+            counts.on_time, counts.late, counts.dropped, counts.frozen
+        )
+    }
+}
@@ -0,0 +1,105 @@
+//! Latency tracing through the GStreamer `latency` tracer.
+//!
+//! The tracer is enabled through `GST_TRACERS` before GStreamer is
+//! initialized, and its records, logged in the `GST_TRACER` category, are
+//! caught by a log function instead of going to stderr. Each record is a
+//! serialized structure: `element-latency` ones give the time a buffer spent
+//! in an element, `latency` ones the time it took from the source to the sink.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+const TRACERS: &str = "latency(flags=pipeline+element)";
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Summary {
+    count: u64,
+    total: u64,
+    max: u64,
+}
+
+impl Summary {
+    fn add(&mut self, time: u64) {
+        self.count += 1;
+        self.total += time;
+        self.max = self.max.max(time);
+    }
+}
+
+/// Enable the latency tracer, to call before `gst::init`.
+pub fn enable_tracer() {
+    std::env::set_var("GST_TRACERS", TRACERS);
+}
+
+/// What a tracer record is about, and the latency it reports in nanoseconds.
+fn parse_record(record: &str) -> Option<(String, u64)> {
+    let structure = gst::Structure::from_str(record.trim_end_matches(';')).ok()?;
+    let field = |name| structure.get::<&str>(name).ok().flatten();
+    let time = structure.get_some::<u64>("time").ok()?;
+
+    match structure.get_name() {
+        "element-latency" => Some((field("element")?.to_owned(), time)),
+        "latency" => {
+            let path = format!("{} -> {}", field("src-element")?, field("sink-element")?);
+            Some((path, time))
+        }
+        _ => None,
+    }
+}
+
+/// Per-element and end-to-end latencies collected from the tracer.
+pub struct LatencyTrace {
+    summaries: Arc<Mutex<BTreeMap<String, Summary>>>,
+}
+
+impl LatencyTrace {
+    /// Start collecting the tracer records.
+    pub fn new() -> Self {
+        let summaries = Arc::new(Mutex::new(BTreeMap::<String, Summary>::new()));
+
+        gst::debug_set_active(true);
+        gst::debug_set_threshold_for_name("GST_TRACER", gst::DebugLevel::Trace);
+        // The default log function would print every record
+        gst::debug_remove_default_log_function();
+
+        let collected = summaries.clone();
+        gst::debug_add_log_function(move |category, _, _, _, _, _, message| {
+            if category.get_name() != "GST_TRACER" {
+                return;
+            }
+            let record = match message.get() {
+                Some(record) => record,
+                None => return,
+            };
+            if let Some((what, time)) = record.to_str().ok().and_then(parse_record) {
+                collected.lock().unwrap().entry(what).or_default().add(time);
+            }
+        });
+
+        LatencyTrace { summaries }
+    }
+
+    /// One line per element, then per source and sink pair.
+    pub fn summary(&self) -> String {
+        let summaries = self.summaries.lock().unwrap();
+        if summaries.is_empty() {
+            return "no latency records".into();
+        }
+
+        let ms = |ns: u64| ns as f64 / 1e6;
+        summaries
+            .iter()
+            .map(|(what, summary)| {
+                format!(
+                    "{}: {} buffers, mean {:.3} ms, max {:.3} ms",
+                    what,
+                    summary.count,
+                    ms(summary.total / summary.count),
+                    ms(summary.max)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
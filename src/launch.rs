@@ -0,0 +1,159 @@
+//! `gst-launch-1.0` descriptions of pipelines.
+//!
+//! Bins made by the application are flattened, their elements being linked
+//! through the ghost pads. Bins made from a factory other than `bin`, such as
+//! `playbin`, are described as single elements along with their properties,
+//! element properties such as `video-sink` holding a description of their own.
+//! Elements are named only when a description refers to them, from a branch
+//! off a `tee` or into an element with several inputs.
+
+use std::collections::HashSet;
+
+use gst::prelude::*;
+
+fn factory_name(element: &gst::Element) -> String {
+    element
+        .get_factory()
+        .map(|factory| factory.get_name().to_string())
+        .unwrap_or_else(|| "bin".into())
+}
+
+/// Whether `element` is a bin of our own, to flatten.
+fn is_plain_bin(element: &gst::Element) -> bool {
+    element.is::<gst::Bin>() && ["bin", "pipeline"].contains(&factory_name(element).as_str())
+}
+
+/// The elements in `bin`, and in the plain bins it holds.
+fn leaves(bin: &gst::Bin) -> Vec<gst::Element> {
+    let mut elements = Vec::new();
+    for element in bin.get_children().into_iter().rev() {
+        if is_plain_bin(&element) {
+            elements.extend(leaves(element.downcast_ref::<gst::Bin>().unwrap()));
+        } else {
+            elements.push(element);
+        }
+    }
+    elements
+}
+
+/// What `pad` is linked to, seen through ghost pads, as element and pad.
+fn peer(pad: &gst::Pad) -> Option<(gst::Element, gst::Pad)> {
+    let mut peer = pad.get_peer()?;
+    while let Some(ghost) = peer.downcast_ref::<gst::GhostPad>() {
+        peer = ghost.get_target()?;
+    }
+    Some((peer.get_parent_element()?, peer))
+}
+
+fn linked_sink_pads(element: &gst::Element) -> usize {
+    element.get_sink_pads().iter().filter(|pad| pad.is_linked()).count()
+}
+
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || "!\"'()=,;".contains(c)) {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// `name=value` for each property that isn't left to its default.
+fn properties(element: &gst::Element) -> Vec<String> {
+    let flags = gst::glib::ParamFlags::READABLE | gst::glib::ParamFlags::WRITABLE;
+    let mut properties = Vec::new();
+
+    for pspec in element.list_properties() {
+        let name = pspec.get_name();
+        if !pspec.get_flags().contains(flags) || name == "name" || name == "parent" {
+            continue;
+        }
+        let value = match element.get_property(&name) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+
+        if let Ok(Some(child)) = value.get::<gst::Element>() {
+            let child = match child.downcast::<gst::Bin>() {
+                Ok(bin) if is_plain_bin(bin.upcast_ref()) => describe_bin(&bin),
+                Ok(bin) => describe_element(bin.upcast_ref(), false),
+                Err(child) => describe_element(&child, false),
+            };
+            properties.push(format!("{}={}", name, quote(&child)));
+            continue;
+        }
+
+        let default = pspec.get_default_value().and_then(|value| value.serialize());
+        if let Some(serialized) = value.serialize() {
+            if Some(&serialized) != default.as_ref() {
+                properties.push(format!("{}={}", name, quote(&serialized)));
+            }
+        }
+    }
+    properties
+}
+
+fn describe_element(element: &gst::Element, named: bool) -> String {
+    let mut description = vec![factory_name(element)];
+    if named {
+        description.push(format!("name={}", element.get_name()));
+    }
+    description.extend(properties(element));
+    description.join(" ")
+}
+
+/// The chain starting at `element`, pushing the branches off it to
+/// `segments`.
+fn chain(
+    element: &gst::Element,
+    visited: &mut HashSet<gst::Element>,
+    segments: &mut Vec<String>,
+) -> String {
+    visited.insert(element.clone());
+
+    let links: Vec<(gst::Pad, gst::Element)> = element
+        .get_src_pads()
+        .into_iter()
+        .filter_map(|pad| peer(&pad).map(|(peer, _)| (pad, peer)))
+        .collect();
+    let named = links.len() > 1 || linked_sink_pads(element) > 1;
+    let mut description = describe_element(element, named);
+
+    for (index, (pad, next)) in links.iter().enumerate() {
+        let link = if visited.contains(next) {
+            format!("{}.", next.get_name())
+        } else {
+            chain(next, visited, segments)
+        };
+        if index == 0 {
+            description = format!("{} ! {}", description, link);
+        } else {
+            segments.push(format!("{}.{} ! {}", element.get_name(), pad.get_name(), link));
+        }
+    }
+    description
+}
+
+fn describe_bin(bin: &gst::Bin) -> String {
+    let elements = leaves(bin);
+    let mut visited = HashSet::new();
+    let mut segments = Vec::new();
+
+    // From each source, each chain followed by its branches
+    for element in &elements {
+        if linked_sink_pads(element) == 0 && !visited.contains(element) {
+            let mut branches = Vec::new();
+            segments.push(chain(element, &mut visited, &mut branches));
+            segments.extend(branches);
+        }
+    }
+    segments.join("  ")
+}
+
+/// The `gst-launch-1.0` arguments building `pipeline`.
+pub fn describe(pipeline: &gst::Pipeline) -> String {
+    if is_plain_bin(pipeline.upcast_ref()) {
+        describe_bin(pipeline.upcast_ref())
+    } else {
+        describe_element(pipeline.upcast_ref(), false)
+    }
+}
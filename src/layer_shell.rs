@@ -0,0 +1,118 @@
+//! The video as a wlr-layer-shell surface instead of a window.
+//!
+//! On wlroots based compositors, `zwlr_layer_shell_v1` puts surfaces on the
+//! layers of an output: under the windows as a background, or above them as
+//! an overlay. The surface is anchored to all the edges of the output and
+//! sized by the compositor, which makes it a fit for kiosks and signage.
+//! Layer surfaces have no title, decorations or fullscreen state, and don't
+//! take the keyboard focus.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use derive_more::{Display, Error};
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::reexports::client::{DispatchData, Main};
+use sctk::reexports::protocols::wlr::unstable::layer_shell::v1::client::{
+    zwlr_layer_shell_v1, zwlr_layer_surface_v1,
+};
+use sctk::window::Event as WEvent;
+
+use crate::ThemedFrameExample;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown layer {}, expected background or overlay", _0)]
+pub struct UnknownLayer(#[error(not(source))] String);
+
+/// The layer the video is shown on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layer {
+    /// Under the windows.
+    Background,
+    /// Above the windows.
+    Overlay,
+}
+
+impl FromStr for Layer {
+    type Err = UnknownLayer;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "background" => Ok(Layer::Background),
+            "overlay" => Ok(Layer::Overlay),
+            _ => Err(UnknownLayer(s.to_owned())),
+        }
+    }
+}
+
+pub struct LayerSurface {
+    surface: wl_surface::WlSurface,
+    layer_surface: Main<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
+}
+
+impl LayerSurface {
+    /// Put `surface` on `layer` of the output the compositor picks. Its
+    /// configure and close events are reported to `callback` as window
+    /// events, so that both kinds of surfaces are handled the same way.
+    pub fn new(
+        env: &Environment<ThemedFrameExample>,
+        surface: wl_surface::WlSurface,
+        layer: Layer,
+        mut callback: impl FnMut(WEvent, DispatchData) + 'static,
+    ) -> Result<Self, Error> {
+        let layer_shell = env
+            .manager
+            .instantiate_exact::<zwlr_layer_shell_v1::ZwlrLayerShellV1>(1)
+            .map_err(|_| anyhow!("The compositor doesn't support wlr-layer-shell"))?;
+
+        let layer = match layer {
+            Layer::Background => zwlr_layer_shell_v1::Layer::Background,
+            Layer::Overlay => zwlr_layer_shell_v1::Layer::Overlay,
+        };
+        let layer_surface =
+            layer_shell.get_layer_surface(&surface, None, layer, "video".to_owned());
+
+        use zwlr_layer_surface_v1::{Anchor, KeyboardInteractivity};
+        layer_surface.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+        // Stretched over the output, under the panels too
+        layer_surface.set_size(0, 0);
+        layer_surface.set_exclusive_zone(-1);
+        layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+
+        layer_surface.quick_assign(move |layer_surface, event, dispatch_data| match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer_surface.ack_configure(serial);
+                let event = WEvent::Configure {
+                    new_size: Some((width, height)),
+                    states: Vec::new(),
+                };
+                callback(event, dispatch_data);
+            }
+            zwlr_layer_surface_v1::Event::Closed => callback(WEvent::Close, dispatch_data),
+            _ => {}
+        });
+
+        // The first configure comes in response to a commit without buffer
+        surface.commit();
+
+        Ok(LayerSurface {
+            surface,
+            layer_surface,
+        })
+    }
+
+    pub fn surface(&self) -> &wl_surface::WlSurface {
+        &self.surface
+    }
+}
+
+impl Drop for LayerSurface {
+    fn drop(&mut self) {
+        self.layer_surface.destroy();
+    }
+}
@@ -0,0 +1,17 @@
+//! Building blocks of the example that are reusable by embedders.
+
+extern crate gstreamer as gst;
+extern crate gstreamer_video as gst_video;
+extern crate smithay_client_toolkit as sctk;
+
+pub mod canvas;
+pub mod capabilities;
+pub mod content_type;
+pub mod convert;
+pub mod fill;
+pub mod gst_ctx;
+pub mod output;
+pub mod session;
+pub mod shutdown;
+pub mod video_bin;
+pub mod video_window;
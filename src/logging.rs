@@ -0,0 +1,27 @@
+//! Diagnostics, through `tracing`.
+//!
+//! They go to stderr, filtered by `--log-level`, or `RUST_LOG` without it,
+//! with the syntax of `tracing_subscriber::EnvFilter` for both: a level, or
+//! levels by target such as `info,wayland_sink_with_video_overlay=trace`.
+//! Pipeline errors are errors, failures the player recovers from warnings,
+//! what it does info, state changes debug, and every frame produced and bus
+//! message left unhandled a trace event.
+
+use anyhow::Error;
+use tracing_subscriber::EnvFilter;
+
+/// Without a filter, everything but the per-frame and per-message noise.
+const DEFAULT: &str = "info";
+
+/// Print the diagnostics allowed by `filter`, or `RUST_LOG` without it.
+pub fn init(filter: Option<&str>) -> Result<(), Error> {
+    let filter = match filter {
+        Some(filter) => EnvFilter::try_new(filter)?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT)),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+    Ok(())
+}
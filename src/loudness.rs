@@ -0,0 +1,58 @@
+//! EBU R128 loudness of the audio, shown over the video.
+//!
+//! The `ebur128level` element of gst-plugins-rs measures the audio before
+//! the volume is applied, and posts the momentary, short-term and integrated
+//! loudness along with the loudness range as `ebur128-level` element
+//! messages, which update the overlay.
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::make;
+
+/// How often the levels are posted.
+const INTERVAL: u64 = 200 * 1_000_000;
+
+/// The measuring element, to insert in the audio branch.
+pub fn meter() -> Result<gst::Element, Error> {
+    let meter = make("ebur128level")?;
+    meter.set_property("interval", &INTERVAL)?;
+    Ok(meter)
+}
+
+pub struct Loudness {
+    overlay: gst::Element,
+}
+
+impl Loudness {
+    pub fn new() -> Result<Self, Error> {
+        let overlay = make("textoverlay")?;
+        overlay.set_property_from_str("halignment", "right");
+        overlay.set_property_from_str("valignment", "bottom");
+        overlay.set_property("font-desc", &"Monospace 12")?;
+        overlay.set_property("shaded-background", &true)?;
+
+        Ok(Loudness { overlay })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.overlay]
+    }
+
+    /// Show the levels of an element message, if it is an `ebur128-level` one.
+    pub fn on_message(&self, structure: &gst::StructureRef) {
+        if structure.get_name() != "ebur128-level" {
+            return;
+        }
+        let level = |name: &str| structure.get_some::<f64>(name).unwrap_or(f64::NEG_INFINITY);
+        let text = format!(
+            "M {:6.1} LUFS  S {:6.1} LUFS\nI {:6.1} LUFS  LRA {:4.1} LU",
+            level("momentary-loudness"),
+            level("shortterm-loudness"),
+            level("global-loudness"),
+            structure.get_some::<f64>("loudness-range").unwrap_or(0.0),
+        );
+        let _ = self.overlay.set_property("text", &text);
+    }
+}
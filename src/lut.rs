@@ -0,0 +1,267 @@
+//! 3D LUTs in the Adobe/Resolve `.cube` format, applied on the CPU.
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+
+use crate::filter::BgrxFilter;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid cube file line {}: {}", line, reason)]
+struct InvalidCube {
+    line: usize,
+    reason: &'static str,
+}
+
+/// A 3D lookup table mapping RGB to RGB.
+#[derive(Debug, Clone)]
+pub struct Lut3d {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    // red varies fastest, then green, then blue
+    table: Vec<[f32; 3]>,
+}
+
+fn parse_triplet(words: &[&str], line: usize) -> Result<[f32; 3], InvalidCube> {
+    let invalid = || InvalidCube {
+        line,
+        reason: "expected three numbers",
+    };
+    if words.len() != 3 {
+        return Err(invalid());
+    }
+
+    let mut triplet = [0.0; 3];
+    for (value, word) in triplet.iter_mut().zip(words) {
+        *value = word.parse().map_err(|_| invalid())?;
+    }
+    Ok(triplet)
+}
+
+impl Lut3d {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        Ok(Self::parse(&fs::read_to_string(path)?)?)
+    }
+
+    fn parse(cube: &str) -> Result<Self, InvalidCube> {
+        let mut lut = Lut3d {
+            size: 0,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            table: Vec::new(),
+        };
+
+        for (index, line) in cube.lines().enumerate() {
+            let line_number = index + 1;
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            match words.first() {
+                None => {}
+                Some(word) if word.starts_with('#') => {}
+                Some(&"TITLE") => {}
+                Some(&"LUT_3D_SIZE") => {
+                    lut.size = words
+                        .get(1)
+                        .and_then(|size| size.parse().ok())
+                        .filter(|size| (2..=256).contains(size))
+                        .ok_or(InvalidCube {
+                            line: line_number,
+                            reason: "invalid LUT_3D_SIZE",
+                        })?;
+                }
+                Some(&"DOMAIN_MIN") => lut.domain_min = parse_triplet(&words[1..], line_number)?,
+                Some(&"DOMAIN_MAX") => lut.domain_max = parse_triplet(&words[1..], line_number)?,
+                // Resolve's domain, the same for the three channels
+                Some(&"LUT_3D_INPUT_RANGE") => {
+                    let range: Vec<f32> = words[1..]
+                        .iter()
+                        .filter_map(|word| word.parse().ok())
+                        .collect();
+                    match range[..] {
+                        [min, max] if words.len() == 3 && min < max => {
+                            lut.domain_min = [min; 3];
+                            lut.domain_max = [max; 3];
+                        }
+                        _ => {
+                            return Err(InvalidCube {
+                                line: line_number,
+                                reason: "invalid LUT_3D_INPUT_RANGE",
+                            })
+                        }
+                    }
+                }
+                Some(&"LUT_1D_SIZE") => {
+                    return Err(InvalidCube {
+                        line: line_number,
+                        reason: "1D LUTs are not supported",
+                    })
+                }
+                Some(_) => lut.table.push(parse_triplet(&words, line_number)?),
+            }
+        }
+
+        if lut.size == 0 || lut.table.len() != lut.size.pow(3) {
+            return Err(InvalidCube {
+                line: cube.lines().count(),
+                reason: "entry count doesn't match LUT_3D_SIZE",
+            });
+        }
+
+        Ok(lut)
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[r + self.size * (g + self.size * b)]
+    }
+
+    /// Map a color, with trilinear interpolation between the LUT entries.
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+
+        // Position of the color in the grid, split into cell and offset
+        let axis = |c: usize| {
+            let range = self.domain_max[c] - self.domain_min[c];
+            let pos = ((rgb[c] - self.domain_min[c]) / range * max)
+                .max(0.0)
+                .min(max);
+            let cell = (pos as usize).min(self.size - 2);
+            (cell, pos - cell as f32)
+        };
+        let (r, fr) = axis(0);
+        let (g, fg) = axis(1);
+        let (b, fb) = axis(2);
+
+        let mix = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+
+        let c00 = mix(self.entry(r, g, b), self.entry(r + 1, g, b), fr);
+        let c10 = mix(self.entry(r, g + 1, b), self.entry(r + 1, g + 1, b), fr);
+        let c01 = mix(self.entry(r, g, b + 1), self.entry(r + 1, g, b + 1), fr);
+        let c11 = mix(self.entry(r, g + 1, b + 1), self.entry(r + 1, g + 1, b + 1), fr);
+
+        mix(mix(c00, c10, fg), mix(c01, c11, fg), fb)
+    }
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value * 255.0).round().max(0.0).min(255.0) as u8
+}
+
+/// Applies a 3D LUT to the displayed video, unless bypassed.
+pub struct LutFilter {
+    filter: BgrxFilter,
+    bypass: Arc<AtomicBool>,
+}
+
+impl LutFilter {
+    pub fn new(lut: Lut3d) -> Result<Self, Error> {
+        let bypass = Arc::new(AtomicBool::new(false));
+
+        let filter_bypass = bypass.clone();
+        let filter = BgrxFilter::new(move |pixels, _| {
+            if filter_bypass.load(Ordering::Relaxed) {
+                return;
+            }
+            for pixel in pixels {
+                let rgb = [
+                    pixel[2] as f32 / 255.0,
+                    pixel[1] as f32 / 255.0,
+                    pixel[0] as f32 / 255.0,
+                ];
+                let [r, g, b] = lut.apply(rgb);
+                pixel[0] = to_u8(b);
+                pixel[1] = to_u8(g);
+                pixel[2] = to_u8(r);
+            }
+        })?;
+
+        Ok(LutFilter { filter, bypass })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![self.filter.element()]
+    }
+
+    /// Switch between the graded and the original video, returns whether
+    /// the LUT is now bypassed.
+    pub fn toggle_bypass(&self) -> bool {
+        !self.bypass.fetch_xor(true, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cube of size 2 mapping each color to `map` of it.
+    fn cube(header: &str, map: impl Fn([f32; 3]) -> [f32; 3]) -> String {
+        let mut cube = format!("TITLE \"test\"\n# comment\nLUT_3D_SIZE 2\n{}\n", header);
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    let [r, g, b] = map([r as f32, g as f32, b as f32]);
+                    cube.push_str(&format!("{} {} {}\n", r, g, b));
+                }
+            }
+        }
+        cube
+    }
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn parses_entries_red_fastest() {
+        let lut = Lut3d::parse(&cube("", |rgb| rgb)).unwrap();
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.entry(1, 0, 0), [1.0, 0.0, 0.0]);
+        assert_eq!(lut.entry(0, 1, 0), [0.0, 1.0, 0.0]);
+        assert_eq!(lut.entry(0, 0, 1), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn interpolates_between_entries() {
+        let lut = Lut3d::parse(&cube("", |[r, g, b]| [1.0 - r, g, b * 0.5])).unwrap();
+        assert_close(lut.apply([0.25, 0.5, 1.0]), [0.75, 0.5, 0.5]);
+        assert_close(lut.apply([2.0, -1.0, 0.0]), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn applies_the_domain() {
+        let lut = Lut3d::parse(&cube("DOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 4", |rgb| rgb)).unwrap();
+        assert_close(lut.apply([1.0, 2.0, 1.0]), [0.5, 1.0, 0.25]);
+    }
+
+    #[test]
+    fn applies_the_input_range() {
+        let lut = Lut3d::parse(&cube("LUT_3D_INPUT_RANGE -1 1", |rgb| rgb)).unwrap();
+        assert_eq!(lut.domain_min, [-1.0; 3]);
+        assert_eq!(lut.domain_max, [1.0; 3]);
+        assert_close(lut.apply([0.0, -1.0, 0.5]), [0.5, 0.0, 0.75]);
+    }
+
+    #[test]
+    fn rejects_invalid_cubes() {
+        let reason = |cube: &str| Lut3d::parse(cube).unwrap_err().reason;
+        let invalid_range = "invalid LUT_3D_INPUT_RANGE";
+        assert_eq!(reason(&cube("LUT_3D_INPUT_RANGE 1 0", |rgb| rgb)), invalid_range);
+        assert_eq!(reason(&cube("LUT_3D_INPUT_RANGE 0", |rgb| rgb)), invalid_range);
+        assert_eq!(reason("LUT_1D_SIZE 16"), "1D LUTs are not supported");
+        assert_eq!(reason("LUT_3D_SIZE 2\n0 0 0"), "entry count doesn't match LUT_3D_SIZE");
+        assert_eq!(reason("LUT_3D_SIZE 2\n0 0"), "expected three numbers");
+    }
+}
@@ -1,25 +1,204 @@
 extern crate gstreamer as gst;
 extern crate gstreamer_app as gst_app;
+extern crate gstreamer_gl as gst_gl;
+extern crate gstreamer_net as gst_net;
+#[cfg(feature = "webrtc")]
+extern crate gstreamer_sdp as gst_sdp;
 extern crate gstreamer_video as gst_video;
+#[cfg(feature = "webrtc")]
+extern crate gstreamer_webrtc as gst_webrtc;
 extern crate smithay_client_toolkit as sctk;
 
-use std::cmp::min;
-use std::ffi::c_void;
-use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use derive_more::{Display, Error};
 use gst::prelude::*;
 use gst_video::prelude::*;
-use sctk::reexports::client::Display;
-use sctk::reexports::client::protocol::{wl_shm, wl_surface};
-use sctk::shm::MemPool;
-use sctk::window::{ButtonColorSpec, ColorSpec, ConceptConfig, ConceptFrame, Event as WEvent};
+use sctk::environment::Environment;
+use sctk::reexports::calloop::channel::{self, Channel};
+use sctk::reexports::calloop::timer::{TimeoutAction, Timer};
+use sctk::reexports::calloop::EventLoop;
+use sctk::reexports::client::{DispatchData, Display};
+use sctk::reexports::client::protocol::{wl_seat, wl_surface};
+use sctk::window::{
+    ButtonColorSpec, ColorSpec, ConceptConfig, ConceptFrame, Event as WEvent, State,
+};
+use tracing::{debug, debug_span, error, info, info_span, trace, warn};
+use wayland_sink_with_video_overlay::capabilities::Capabilities;
+use wayland_sink_with_video_overlay::content_type::{ContentType, SurfaceContentType};
+use wayland_sink_with_video_overlay::output::{self, OutputTracker};
+use wayland_sink_with_video_overlay::session::{self, SessionState, Transforms};
+use wayland_sink_with_video_overlay::shutdown::ShutdownHooks;
+use wayland_sink_with_video_overlay::video_bin;
+use wayland_sink_with_video_overlay::video_window::VideoWindow;
+use wayland_sink_with_video_overlay::canvas;
+
+use alerts::Alerts;
+use audio_policy::AudioPolicy;
+use background::Background;
+use balance::{Adjustment, Balance};
+use bus_log::BusRecorder;
+use color::ColorCorrection;
+use compare::Comparison;
+use composition::ComposedControls;
+use confidence::AudioMonitor;
+use config::{Config, Theme, ThemeMode};
+use control::Request;
+use control_socket::ControlSocket;
+use controls::{Controls, Hit, Playback};
+use dashboard::Dashboard;
+use equirect::Equirect;
+use fps::Fps;
+use frame_dump::FrameDump;
+use guides::Guides;
+use handoff::Takeover;
+use http::HttpServer;
+use idle_inhibit::IdleInhibit;
+use input::{Command, Inputs};
+use late::LateFrames;
+use latency::LatencyTrace;
+use layer_shell::LayerSurface;
+use loudness::Loudness;
+use lut::{Lut3d, LutFilter};
+use matte::Matte;
+use mosaic::Mosaic;
+use nv12::Nv12Output;
+use options::Options;
+use orientation::Orientation;
+use osd::Osd;
+use pip::PipVideo;
+use pipeline_state::PipelineState;
+use preview::Preview;
+use producer::{ColorCycle, FrameProducer};
+use playlist::Playlist;
+use preempt::Preemption;
+use proxy::AutoProxy;
+use render_rect::{Rect, RenderRectangle};
+use scale::{SurfaceScale, VideoScaler};
+use sink::SurfaceGuard;
+use size_hints::SizeHints;
+use stats::{Presented, Stats};
+use stereo::Stereo;
+use still::StillFrames;
+use subtitles::Subtitles;
+use swap::SourceSwap;
+use swap_interval::SwapInterval;
+use time_overlay::TimeOverlays;
+use timecode::TimecodeOverlay;
+use timeshift::Timeshift;
+use toplevel::Toplevel;
+use transport::Transport;
+use upload::{Upload, Uploader};
+use visibility::AutoPause;
+use zoom::Zoom;
+
+mod alerts;
+mod animation;
+#[cfg(feature = "portal")]
+mod appearance;
+mod audio;
+mod audio_policy;
+mod auth;
+mod background;
+mod balance;
+mod bus_log;
+mod clipboard;
+mod clock;
+mod color;
+mod compare;
+mod composition;
+mod confidence;
+mod config;
+mod control;
+mod control_socket;
+mod controls;
+mod dashboard;
+mod decode_process;
+mod decorations;
+mod deinterlace;
+mod depth;
+mod equirect;
+mod filter;
+mod fps;
+mod frame_dump;
+mod guides;
+mod handoff;
+mod http;
+mod idle_inhibit;
+mod input;
+mod launch;
+mod late;
+mod latency;
+mod layer_shell;
+mod logging;
+mod loudness;
+mod lut;
+mod matte;
+mod mosaic;
+#[cfg(feature = "mpris")]
+mod mpris;
+mod nv12;
+mod options;
+mod orientation;
+mod osd;
+mod pip;
+mod pipeline_state;
+mod playlist;
+mod preempt;
+mod presets;
+mod preview;
+mod producer;
+mod protocols;
+mod proxy;
+mod record;
+mod render_rect;
+mod replay;
+mod runtime_socket;
+mod scale;
+mod shm;
+mod sink;
+mod size_hints;
+mod snapshot;
+mod source_settings;
+mod stats;
+mod stereo;
+mod still;
+mod stream;
+mod subtitles;
+mod swap;
+mod swap_interval;
+mod time_overlay;
+mod timecode;
+mod timeshift;
+mod toplevel;
+mod transport;
+mod upload;
+mod visibility;
+#[cfg(feature = "webrtc")]
+mod webrtc;
+mod zoom;
 
 #[derive(Debug, Display, Error)]
 #[display(fmt = "Missing element {}", _0)]
 struct MissingElement(#[error(not(source))] &'static str);
 
+/// The element of the factory `name`, or `MissingElement`.
+fn make(name: &'static str) -> Result<gst::Element, Error> {
+    Ok(gst::ElementFactory::make(name, None).map_err(|_| MissingElement(name))?)
+}
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "No video overlay sink in the pipeline, such as waylandsink")]
+struct NoOverlaySink;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Built without the {} feature", _0)]
+struct MissingFeature(#[error(not(source))] &'static str);
+
 #[derive(Debug, Display, Error)]
 #[display(fmt = "Received error from {}: {} (debug: {:?})", src, error, debug)]
 struct ErrorMessage {
@@ -31,56 +210,114 @@ struct ErrorMessage {
 
 const WIDTH: usize = 640;
 const HEIGHT: usize = 480;
-const GST_WAYLAND_DISPLAY_HANDLE_CONTEXT_TYPE: &str = "GstWaylandDisplayHandleContextType";
+const TITLE: &str = "Themed frame";
+const TITLE_INTERVAL: Duration = Duration::from_millis(500);
+const DASHBOARD_INTERVAL: Duration = Duration::from_secs(1);
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+/// How far the seek keys go, in seconds.
+const SEEK_STEP: f64 = 10.0;
+/// Longest frame dump a control request starts, in seconds.
+const MAX_DUMP_SECONDS: f64 = 3600.0;
 
 sctk::default_environment!(ThemedFrameExample, desktop);
 
-fn create_pipeline(surface: &wl_surface::WlSurface, display: Display) -> Result<gst::Pipeline, Error> {
-    gst::init()?;
-
-    let pipeline = gst::Pipeline::new(None);
-
-    let src = gst::ElementFactory::make("appsrc", None)
-        .map_err(|_| MissingElement("appsrc"))?;
-    let videoconvert = gst::ElementFactory::make("videoconvert", None)
-        .map_err(|_| MissingElement("videoconvert"))?;
-    let sink = gst::ElementFactory::make("waylandsink", None)
-        .map_err(|_| MissingElement("waylandsink"))?;
-
-    let mut context = gst::Context::new(GST_WAYLAND_DISPLAY_HANDLE_CONTEXT_TYPE, true);
-    {
-        let context = context.get_mut().unwrap();
-        let s = context.get_mut_structure();
-        #[allow(clippy::cast_ptr_alignment)]
-            let value = unsafe {
-            use gst::glib::translate::*;
-            use std::mem;
-
-            let handle = display.c_ptr();
-            let mut value = mem::MaybeUninit::zeroed();
-            gst::gobject_sys::g_value_init(value.as_mut_ptr(), gst::gobject_sys::G_TYPE_POINTER);
-            gst::gobject_sys::g_value_set_pointer(value.as_mut_ptr(), handle as *mut c_void);
-            gst::glib::SendValue::from_glib_none(&value.assume_init() as *const _)
-        };
-        s.set_value("handle", value);
+/// State shared with the Wayland callbacks through their `DispatchData`.
+#[derive(Default)]
+struct DispatchState {
+    next_action: Option<WEvent>,
+    commands: Vec<Command>,
+    frame_done: bool,
+    title_tick: bool,
+    /// The pointer moved over the window since the last loop iteration.
+    pointer_active: bool,
+    requests: Vec<Request>,
+    dashboard_action: Option<WEvent>,
+    /// Events of the `--window` windows, with their index.
+    window_actions: Vec<(usize, WEvent)>,
+    dashboard_tick: bool,
+    presented: Vec<Presented>,
+    #[cfg(feature = "mpris")]
+    mpris_calls: Vec<mpris::Call>,
+    /// Whether the desktop last said it prefers dark.
+    #[cfg(feature = "portal")]
+    prefers_dark: Option<bool>,
+    /// The seat and serial of the last key press, to set the selection with.
+    key_serial: Option<(wl_seat::WlSeat, u32)>,
+    /// Buffers the upload thread finished.
+    uploads: Vec<Upload>,
+    /// A new instance taking over.
+    handoffs: Vec<handoff::Event>,
+}
+
+/// Keep the last window event in priority order: Close > Configure > Refresh.
+fn queue_action(next_action: &mut Option<WEvent>, evt: WEvent) {
+    let replace = match (&evt, &*next_action) {
+        (_, &None)
+        | (_, &Some(WEvent::Refresh))
+        | (&WEvent::Configure { .. }, &Some(WEvent::Configure { .. }))
+        | (&WEvent::Close, _) => true,
+        _ => false,
+    };
+    if replace {
+        *next_action = Some(evt);
     }
-    sink.set_context(&context);
+}
 
-    pipeline.add_many(&[&src, &videoconvert, &sink])?;
-    gst::Element::link_many(&[&src, &videoconvert, &sink])?;
+/// The pipeline built by `create_pipeline`, along with the parts of it the
+/// event loop works with.
+struct Player {
+    pipeline: gst::Pipeline,
+    /// The recording of a live URI, with `--timeshift`.
+    timeshift: Option<Timeshift>,
+    /// The grid of the URIs, with `--mosaic`.
+    mosaic: Option<Mosaic>,
+    video: gst::Bin,
+    sink: gst::Element,
+    /// Number of frames produced by the appsrc so far.
+    frames: Arc<AtomicU64>,
+    /// The `volume` element of the audio branch, if there is one.
+    volume: Option<gst::Element>,
+    /// The preview of the HTTP endpoint, if it is served.
+    preview: Option<Preview>,
+}
+
+impl Player {
+    /// The whole video path, from the converter through the display filters
+    /// and branches to the sink, as a bin with a `sink` ghost pad.
+    ///
+    /// Once removed from `pipeline`, it can be added to a larger one and fed
+    /// from there, while the sink keeps rendering on the window and the
+    /// overlay keeps being driven from here.
+    #[allow(dead_code)]
+    pub fn as_bin(&self) -> &gst::Bin {
+        &self.video
+    }
+}
 
+/// The synthetic source at `fps`, drawn by `producer`, along with its count
+/// of produced frames. With `timecode`, frames are stamped with a timecode
+/// jam-synced to the wall clock.
+fn create_appsrc(
+    timecode: bool,
+    fps: gst::Fraction,
+    mut producer: Box<dyn FrameProducer>,
+) -> Result<(gst::Element, Arc<AtomicU64>), Error> {
+    let src = make("appsrc")?;
 
     let appsrc = src
+        .clone()
         .dynamic_cast::<gst_app::AppSrc>()
         .expect("Source element is expected to be an appsrc!");
 
     // Specify the format we want to provide as application into the pipeline
-    // by creating a video info with the given format and creating caps from it for the appsrc element.
-    let video_info =
-        gst_video::VideoInfo::builder(gst_video::VideoFormat::Bgrx, WIDTH as u32, HEIGHT as u32)
-            .fps(gst::Fraction::new(2, 1))
-            .build()
-            .expect("Failed to create video info");
+    // by creating a video info with the format of the producer and creating caps from it
+    // for the appsrc element.
+    let video_info = producer::video_info(&*producer, WIDTH as u32, HEIGHT as u32, fps)?;
+    let mut timecode = if timecode {
+        Some(timecode::jam_synced(fps)?)
+    } else {
+        None
+    };
 
     appsrc.set_caps(Some(&video_info.to_caps().unwrap()));
     appsrc.set_property_format(gst::Format::Time);
@@ -93,13 +330,16 @@ fn create_pipeline(surface: &wl_surface::WlSurface, display: Display) -> Result<
     // is not really needed here. It is *not required* to use the
     // need-data callback.
     let mut i = 0;
+    let (numer, denom) = (*fps.numer() as u64, *fps.denom() as u64);
+    let frames = Arc::new(AtomicU64::new(0));
+    let produced = frames.clone();
     appsrc.set_callbacks(
         // Since our appsrc element operates in pull mode (it asks us to provide data),
         // we add a handler for the need-data callback and provide new data from there.
-        // In our case, we told gstreamer that we do 2 frames per second. While the
-        // buffers of all elements of the pipeline are still empty, this will be called
-        // a couple of times until all of them are filled. After this initial period,
-        // this handler will be called (on average) twice per second.
+        // In our case, we told gstreamer that we do `fps` frames per second, 2 unless
+        // configured otherwise. While the buffers of all elements of the pipeline are still
+        // empty, this will be called a couple of times until all of them are filled. After
+        // this initial period, this handler will be called (on average) `fps` times per second.
         gst_app::AppSrcCallbacks::builder()
             .need_data(move |appsrc, _| {
                 // We only produce 50 frames
@@ -108,20 +348,20 @@ fn create_pipeline(surface: &wl_surface::WlSurface, display: Display) -> Result<
                     return;
                 }
 
-                println!("Producing frame {}", i);
-
-                let r = if i % 2 == 0 { 0 } else { 255 };
-                let g = if i % 3 == 0 { 0 } else { 255 };
-                let b = if i % 5 == 0 { 0 } else { 255 };
+                trace!(frame = i, "Producing frame");
 
-                // Create the buffer that can hold exactly one BGRx frame.
+                // Create the buffer that can hold exactly one frame.
                 let mut buffer = gst::Buffer::with_size(video_info.size()).unwrap();
                 {
                     let buffer = buffer.get_mut().unwrap();
                     // For each frame we produce, we set the timestamp when it should be displayed
                     // (pts = presentation time stamp)
                     // The autovideosink will use this information to display the frame at the right time.
-                    buffer.set_pts(i * 500 * gst::MSECOND);
+                    let pts = i * 1_000_000_000 * denom / numer;
+                    buffer.set_pts(gst::ClockTime::from_nseconds(pts));
+                    if let Some(timecode) = &mut timecode {
+                        timecode::stamp(buffer, timecode);
+                    }
 
                     // At this point, buffer is only a reference to an existing memory region somewhere.
                     // When we want to access its content, we have to map it while requesting the required
@@ -131,31 +371,13 @@ fn create_pipeline(surface: &wl_surface::WlSurface, display: Display) -> Result<
                         gst_video::VideoFrameRef::from_buffer_ref_writable(buffer, &video_info)
                             .unwrap();
 
-                    // Remember some values from the frame for later usage
-                    let width = vframe.width() as usize;
-                    let height = vframe.height() as usize;
-
-                    // Each line of the first plane has this many bytes
-                    let stride = vframe.plane_stride()[0] as usize;
-
-                    // Iterate over each of the height many lines of length stride
-                    for line in vframe
-                        .plane_data_mut(0)
-                        .unwrap()
-                        .chunks_exact_mut(stride)
-                        .take(height)
-                    {
-                        // Iterate over each pixel of 4 bytes in that line
-                        for pixel in line[..(4 * width)].chunks_exact_mut(4) {
-                            pixel[0] = b;
-                            pixel[1] = g;
-                            pixel[2] = r;
-                            pixel[3] = 0;
-                        }
+                    if let Err(err) = producer.fill(i, &mut vframe) {
+                        warn!("Failed to produce frame {}: {}", i, err);
                     }
                 }
 
                 i += 1;
+                produced.store(i, Ordering::Relaxed);
 
                 // appsrc already handles the error here
                 let _ = appsrc.push_buffer(buffer);
@@ -163,217 +385,1710 @@ fn create_pipeline(surface: &wl_surface::WlSurface, display: Display) -> Result<
             .build(),
     );
 
-    // Use the platform-specific sink to create our overlay.
-    // Since we only use the video_overlay in the closure below, we need a weak reference.
-    // !!ATTENTION!!:
-    // It might seem appealing to use .clone() here, because that greatly
-    // simplifies the code within the callback. What this actually does, however, is creating
-    // a memory leak.
-    let video_overlay = sink
-        .dynamic_cast::<gst_video::VideoOverlay>()
-        .unwrap()
-        .downgrade();
+    Ok((src, frames))
+}
+
+/// The video path as a bin, along with its sink.
+fn create_video_bin(
+    options: &Options,
+    display_filters: &[&gst::Element],
+) -> Result<(gst::Bin, gst::Element, Option<Preview>), Error> {
+    let _setup = debug_span!("video_bin").entered();
+    let videoconvert = make("videoconvert")?;
+    let sink = sink::create(options.sink.as_deref())?;
 
-    // Here we temporarily retrieve a strong reference on the video-overlay from the
-    // weak reference that we moved into the closure.
-    let video_overlay = video_overlay.upgrade().unwrap();
+    // Extra branches such as the recording hang off a tee after the converter
+    let tee = if options.has_branches() {
+        let tee = make("tee")?;
+        let queue = make("queue")?;
+        Some((tee, queue))
+    } else {
+        None
+    };
 
-    #[allow(clippy::cast_ptr_alignment)]
-        unsafe {
-        // Here we ask native window handle we got assigned for
-        // our video region from the window system, and then we will
-        // pass this unique identifier to the overlay provided by our
-        // sink - so the sink can then arrange the overlay.
-        let native = surface.as_ref().c_ptr();
-        video_overlay.set_window_handle(native as usize);
+    let deinterlace = deinterlace::create(options.deinterlace)?;
+    let eight_bit = if options.force_8bit {
+        Some(depth::eight_bit_filter()?)
+    } else {
+        None
+    };
+    let mut chain = vec![&videoconvert];
+    chain.extend(&deinterlace);
+    chain.extend(&eight_bit);
+    if let Some((tee, queue)) = &tee {
+        chain.push(tee);
+        chain.push(queue);
     }
-    video_overlay.set_render_rectangle(0, 0, WIDTH as i32, HEIGHT as i32).unwrap();
+    let unfiltered = *chain.last().unwrap();
+    // Grading, color correction and OSD only apply to what's displayed
+    chain.extend(display_filters);
+    chain.push(&sink);
 
-    Ok(pipeline)
-}
+    // The video path is a bin, so that it can also be the video sink of a playbin
+    let video = video_bin::create("video", &chain)?;
+    let names: Vec<_> = chain.iter().map(|element| element.get_name()).collect();
+    debug!(?names, "Linked the video chain");
+    let pad = videoconvert.get_static_pad("sink").unwrap();
+    if eight_bit.is_none() {
+        depth::bypass_when_deep(&pad, unfiltered, display_filters, &sink);
+    }
 
-fn main() {
-    let (env, display, mut queue) = sctk::new_default_environment!(ThemedFrameExample, desktop)
-        .expect("Unable to connect to a Wayland compositor");
+    if let (Some((tee, _)), Some(path)) = (&tee, &options.record) {
+        record::add_branch(&video, tee, path)?;
+    }
+    if let (Some((tee, _)), Some(destination)) = (&tee, &options.stream) {
+        let (path, target) = (options.stream_sdp.clone(), destination.clone());
+        let publish = move |sdp: String| match &path {
+            Some(path) => {
+                if let Err(err) = std::fs::write(path, sdp) {
+                    warn!("Failed to write the SDP to {}: {}", path.display(), err);
+                }
+            }
+            None => println!("Streaming to {}:{}\n{}", target.host, target.port, sdp),
+        };
+        stream::add_branch(&video, tee, destination, options.stream_codec, publish)?;
+    }
+    if let (Some((tee, _)), Some(peer)) = (&tee, &options.webrtc_peer) {
+        #[cfg(feature = "webrtc")]
+        webrtc::add_branch(&video, tee, &options.webrtc_server, peer)?;
+        #[cfg(not(feature = "webrtc"))]
+        {
+            let _ = (tee, peer);
+            return Err(MissingFeature("webrtc").into());
+        }
+    }
+    // The preview is served by the HTTP endpoint
+    let preview = match (&tee, &options.http) {
+        (Some((tee, _)), Some(_)) => Some(Preview::add_branch(&video, tee)?),
+        _ => None,
+    };
 
-    let mut dimensions = (640u32, 480u32);
+    Ok((video, sink, preview))
+}
 
+/// A `--window` window of `dimensions` playing `uri`, decorated with
+/// `frame_config`, along with the `volume` element of its audio. Its events
+/// are queued under `index`.
+fn open_video_window(
+    env: &Environment<ThemedFrameExample>,
+    display: &Display,
+    index: usize,
+    uri: &str,
+    options: &Options,
+    dimensions: (u32, u32),
+    frame_config: ConceptConfig,
+) -> Result<(VideoWindow, gst::Element), Error> {
     let surface = env.create_surface().detach();
-
     let mut window = env
         .create_window::<ConceptFrame, _>(
             surface,
             None,
             dimensions,
             move |evt, mut dispatch_data| {
-                let next_action = dispatch_data.get::<Option<WEvent>>().unwrap();
-                // Keep last event in priority order : Close > Configure > Refresh
-                let replace = match (&evt, &*next_action) {
-                    (_, &None)
-                    | (_, &Some(WEvent::Refresh))
-                    | (&WEvent::Configure { .. }, &Some(WEvent::Configure { .. }))
-                    | (&WEvent::Close, _) => true,
-                    _ => false,
-                };
-                if replace {
-                    *next_action = Some(evt);
-                }
+                let state = dispatch_data.get::<DispatchState>().unwrap();
+                state.window_actions.push((index, evt));
             },
         )
-        .expect("Failed to create a window !");
+        .map_err(|err| anyhow!("Failed to create a window for {}: {:?}", uri, err))?;
+    let title = uri.rsplit('/').next().unwrap_or(uri);
+    window.set_title(title.to_string());
+    window.set_frame_config(frame_config);
+    let pools = env.create_double_pool(|_| {})?;
 
-    window.set_title("Themed frame".to_string());
-    window.set_frame_config(create_frame_config());
+    let sink = sink::create(options.sink.as_deref())?;
+    let overlay = sink::set_window(&sink, display, window.surface())?;
+    let (audio, volume) = audio::create_sink(options)?;
+    let needs_configure = env.get_shell().unwrap().needs_configure();
+    let video_window = VideoWindow::new(
+        window,
+        pools,
+        uri,
+        overlay,
+        Some(audio.upcast_ref()),
+        dimensions,
+        needs_configure,
+    )?;
+    Ok((video_window, volume))
+}
 
-    let mut pools = env.create_double_pool(|_| {}).expect("Failed to create a memory pool !");
+/// The pipeline of the gst-launch-1.0 `description`, shown with its video
+/// overlay sink, the waylandsink if there are several.
+fn create_launch_pipeline(description: &str) -> Result<Player, Error> {
+    let _setup = info_span!("pipeline_setup", %description).entered();
+    // A single element isn't put in a pipeline
+    let pipeline = match gst::parse_launch(description)?.downcast::<gst::Pipeline>() {
+        Ok(pipeline) => pipeline,
+        Err(element) => {
+            let pipeline = gst::Pipeline::new(None);
+            pipeline.add(&element)?;
+            pipeline
+        }
+    };
 
-    if !env.get_shell().unwrap().needs_configure() {
-        // initial draw to bootstrap on wl_shell
-        if let Some(pool) = pools.pool() {
-            redraw(pool, window.surface(), dimensions).expect("Failed to draw")
+    let mut overlays = Vec::new();
+    for element in pipeline.iterate_recurse() {
+        let element = match element {
+            Ok(element) => element,
+            Err(_) => break,
+        };
+        if element.dynamic_cast_ref::<gst_video::VideoOverlay>().is_some() {
+            overlays.push(element);
+        }
+    }
+    let is_waylandsink = |element: &&gst::Element| {
+        element
+            .get_factory()
+            .map_or(false, |factory| factory.get_name() == "waylandsink")
+    };
+    let sink = overlays
+        .iter()
+        .find(is_waylandsink)
+        .or_else(|| overlays.first())
+        .cloned()
+        .ok_or(NoOverlaySink)?;
+    debug!(sink = %sink.get_name(), "Showing the video of the pipeline");
+
+    Ok(Player {
+        // The whole description is the video path
+        video: pipeline.clone().upcast(),
+        pipeline,
+        timeshift: None,
+        mosaic: None,
+        sink,
+        frames: Arc::new(AtomicU64::new(0)),
+        volume: None,
+        preview: None,
+    })
+}
+
+/// The pipeline, without the Wayland side: the sink has to be handed the
+/// window with `sink::set_window`.
+fn create_pipeline(options: &Options, display_filters: &[&gst::Element]) -> Result<Player, Error> {
+    if let Some(description) = &options.pipeline {
+        return create_launch_pipeline(description);
+    }
+    let _setup = info_span!("pipeline_setup", uri = ?options.uri).entered();
+    let (video, sink, preview) = create_video_bin(options, display_filters)?;
+
+    let (pipeline, timeshift, mosaic, frames, volume) = match &options.uri {
+        Some(uri) if options.decode_process => {
+            let sandbox = options.decode_sandbox.as_deref();
+            let pipeline = decode_process::create_pipeline(uri, sandbox, &video)?;
+            (pipeline, None, None, Arc::new(AtomicU64::new(0)), None)
+        }
+        Some(uri) if options.mosaic => {
+            let uris = if options.playlist.is_empty() {
+                std::slice::from_ref(uri)
+            } else {
+                &options.playlist
+            };
+            let (pipeline, mosaic) = mosaic::create_pipeline(uris, &video)?;
+            mosaic.layout(options.window_size);
+            (pipeline, None, Some(mosaic), Arc::new(AtomicU64::new(0)), None)
+        }
+        Some(uri) if options.timeshift.is_some() && timeshift::is_live(uri) => {
+            let minutes = options.timeshift.unwrap();
+            let (audio, volume) = audio::create_sink(options)?;
+            let (pipeline, timeshift) =
+                timeshift::create_pipeline(uri, minutes, options.replay, &video, &audio)?;
+            (pipeline, Some(timeshift), None, Arc::new(AtomicU64::new(0)), Some(volume))
+        }
+        Some(uri) => {
+            if options.timeshift.is_some() {
+                warn!("Only live RTSP and UDP inputs are time-shifted, playing {} as is", uri);
+            }
+            let playbin = make("playbin")?;
+            playbin.set_property("uri", uri)?;
+            playbin.set_property("video-sink", &video)?;
+            let (audio, volume) = audio::create_sink(options)?;
+            playbin.set_property("audio-sink", &audio)?;
+            if let Some(subtitles) = &options.subtitles {
+                playbin.set_property("suburi", subtitles)?;
+            }
+            let pipeline = playbin.downcast::<gst::Pipeline>().unwrap();
+            (pipeline, None, None, Arc::new(AtomicU64::new(0)), Some(volume))
+        }
+        None => {
+            let pipeline = gst::Pipeline::new(None);
+            let (src, frames) = match &options.shm_socket {
+                Some(path) => {
+                    let caps = options.shm_caps.as_deref().unwrap_or(shm::DEFAULT_CAPS);
+                    println!("Showing the video of {}", shm::producer_hint(path, caps));
+                    (shm::create_source(path, caps)?, Arc::new(AtomicU64::new(0)))
+                }
+                None => {
+                    let producer = Box::new(ColorCycle::new(options.source_format));
+                    create_appsrc(options.timecode_generate, options.fps, producer)?
+                }
+            };
+            pipeline.add_many(&[&src, video.upcast_ref()])?;
+            src.link(&video)?;
+
+            let volume = match options.audio_test {
+                Some(channels) => Some(audio::add_test_branch(&pipeline, channels, options)?),
+                None => None,
+            };
+            (pipeline, None, None, frames, volume)
+        }
+    };
+
+    Ok(Player {
+        pipeline,
+        timeshift,
+        mosaic,
+        video,
+        sink,
+        frames,
+        volume,
+        preview,
+    })
+}
+
+fn main() {
+    let mut options = Options::from_args().expect("Failed to parse arguments");
+    logging::init(options.log_level.as_deref()).expect("Failed to set up logging");
+    let config = Config::load(&mut options).expect("Failed to load the configuration");
+    source_settings::restore(&mut options);
+    if let (None, Some(spec)) = (&options.pipeline, &options.preset) {
+        let description = config.presets.describe(spec, options.sink.as_deref());
+        options.pipeline = Some(description.expect("Failed to set up the preset"));
+    }
+
+    if options.trace_latency {
+        latency::enable_tracer();
+    }
+    gst::init().expect("Failed to initialize GStreamer");
+    // The old instance goes on until this one plays
+    let mut takeover = if options.handoff {
+        Takeover::connect()
+    } else {
+        None
+    };
+    if let Some(takeover) = &takeover {
+        if takeover.state.uri.is_some() {
+            options.uri = takeover.state.uri.clone();
+        }
+        options.window_size = takeover.state.size;
+    }
+    if options.decode_child {
+        let uri = options.uri.as_deref().expect("The decoder needs a URI");
+        decode_process::run_child(uri).expect("Failed to decode");
+        return;
+    }
+    let mut hooks = ShutdownHooks::default();
+    if options.trace_latency {
+        let latency_trace = LatencyTrace::new();
+        hooks.on_pipeline_null(move || info!("Latency:\n{}", latency_trace.summary()));
+    }
+
+    let mut osd = Osd::new(&options).expect("Failed to create the OSD");
+    let mut color = if options.icc_profiles.is_empty() {
+        None
+    } else {
+        Some(ColorCorrection::new(&options.icc_profiles).expect("Failed to set up color correction"))
+    };
+
+    let lut = options.lut.as_ref().map(|path| {
+        let lut = Lut3d::load(path).expect("Failed to load the LUT");
+        LutFilter::new(lut).expect("Failed to set up the LUT")
+    });
+
+    let stereo = options
+        .stereo
+        .map(|layout| Stereo::new(layout, options.eye).expect("Failed to set up stereo display"));
+
+    let mut equirect = options
+        .equirect
+        .map(|fov| Equirect::new(fov).expect("Failed to set up the 360° view"));
+
+    let mut display_filters = Vec::new();
+    if let Some(stereo) = &stereo {
+        display_filters.extend(stereo.elements());
+    }
+    if let Some(equirect) = &equirect {
+        display_filters.extend(equirect.elements());
+    }
+    // Downscaling makes the CPU filters after it cheaper too
+    let mut auto_proxy = if options.auto_proxy {
+        Some(AutoProxy::new().expect("Failed to set up the proxy switching"))
+    } else {
+        None
+    };
+    if let Some(auto_proxy) = &auto_proxy {
+        display_filters.extend(auto_proxy.elements());
+    }
+    if let Some(lut) = &lut {
+        display_filters.extend(lut.elements());
+    }
+    if let Some(color) = &color {
+        display_filters.extend(color.elements());
+    }
+    let mut orientation = Orientation::new().expect("Failed to set up the video orientation");
+    display_filters.extend(orientation.elements());
+    let mut zoom = Zoom::new().expect("Failed to set up the zoom");
+    display_filters.extend(zoom.elements());
+    let matte = if options.desqueeze.is_some() || options.matte.is_some() {
+        Some(Matte::new(options.desqueeze, options.matte).expect("Failed to set up the matte"))
+    } else {
+        None
+    };
+    if let Some(matte) = &matte {
+        display_filters.extend(matte.elements());
+    }
+    let guides = if options.guides {
+        Some(Guides::new(options.guides_aspect).expect("Failed to set up the guides"))
+    } else {
+        None
+    };
+    if let Some(guides) = &guides {
+        display_filters.extend(guides.elements());
+    }
+    let mut balance = Balance::new().expect("Failed to set up the video balance");
+    display_filters.extend(balance.elements());
+    display_filters.extend(osd.elements());
+    let mut fps = Fps::new().expect("Failed to create the FPS overlay");
+    display_filters.extend(fps.elements());
+    let timecode_overlay = if options.timecode {
+        Some(TimecodeOverlay::new().expect("Failed to create the timecode overlay"))
+    } else {
+        None
+    };
+    if let Some(timecode_overlay) = &timecode_overlay {
+        display_filters.extend(timecode_overlay.elements());
+    }
+    let mut time_overlays = TimeOverlays::new(options.time_overlay, options.clock_overlay)
+        .expect("Failed to create the time overlays");
+    display_filters.extend(time_overlays.elements());
+    let loudness = if options.loudness {
+        Some(Loudness::new().expect("Failed to create the loudness overlay"))
+    } else {
+        None
+    };
+    if let Some(loudness) = &loudness {
+        display_filters.extend(loudness.elements());
+    }
+    let mut alerts = Alerts::new(config.alerts.clone()).expect("Failed to set up the alerts");
+    display_filters.extend(alerts.elements());
+    let mut composed_controls = if options.composed_controls {
+        Some(ComposedControls::new().expect("Failed to create the composed controls"))
+    } else {
+        None
+    };
+    if let Some(composed_controls) = &composed_controls {
+        display_filters.extend(composed_controls.elements());
+    }
+    let mut video_scaler = VideoScaler::new().expect("Failed to create the video scaler");
+    display_filters.extend(video_scaler.elements());
+    let nv12 = if options.nv12 {
+        Some(Nv12Output::new().expect("Failed to set up the NV12 conversion"))
+    } else {
+        None
+    };
+    display_filters.extend(nv12.as_ref().map(Nv12Output::element));
+
+    let Player {
+        pipeline,
+        mut timeshift,
+        mosaic,
+        sink,
+        frames,
+        volume,
+        preview,
+        ..
+    } = create_pipeline(&options, &display_filters).unwrap();
+    config
+        .overrides
+        .clone()
+        .apply(&pipeline)
+        .expect("Failed to set up the element properties");
+
+    if options.dry_run {
+        dry_run(&pipeline, &sink);
+        return;
+    }
+    if let Some(path) = &options.replay_bus {
+        replay_bus(path, &pipeline, &mut fps, loudness.as_ref(), &mut alerts);
+        return;
+    }
+    clock::apply(&pipeline, options.clock, options.ptp_domain)
+        .expect("Failed to set up the clock");
+    let playlist = if options.playlist.len() > 1 && !options.mosaic {
+        let uris = options.playlist.clone();
+        Some(Playlist::new(&pipeline, uris).expect("Failed to set up the playlist"))
+    } else {
+        None
+    };
+    if options.confidence_monitor {
+        confidence::monitor(&sink);
+    }
+    let mut audio_monitor = if options.confidence_monitor && volume.is_some() {
+        Some(AudioMonitor::default())
+    } else {
+        None
+    };
+    let late_frames = options.late_frames.as_ref().map(|settings| {
+        LateFrames::new(&sink, settings).expect("Failed to set up the late frame policy")
+    });
+    let still_frames = if options.still_frames {
+        Some(StillFrames::new(&sink))
+    } else {
+        None
+    };
+    let swap_interval = if options.swap_interval > 1 {
+        Some(SwapInterval::new(&sink, options.swap_interval))
+    } else {
+        None
+    };
+    alerts.watch(&sink);
+    let mut bus_recorder = options
+        .record_bus
+        .as_ref()
+        .map(|path| BusRecorder::create(path).expect("Failed to create the bus recording"));
+
+    let (env, display, queue) = sctk::new_default_environment!(ThemedFrameExample, desktop)
+        .expect("Unable to connect to a Wayland compositor");
+    if let Err(err) = protocols::check(&config.protocols, &env) {
+        error!("{}", err);
+        std::process::exit(1);
+    }
+
+    let mut dimensions = options.window_size;
+    let mut dark_theme = config.theme_mode != ThemeMode::Light;
+
+    let surface = env.create_surface().detach();
+
+    let callback = move |evt, mut dispatch_data: DispatchData| {
+        let next_action = &mut dispatch_data.get::<DispatchState>().unwrap().next_action;
+        queue_action(next_action, evt);
+    };
+    let mut window = match options.layer_shell {
+        Some(layer) => Toplevel::Layer(
+            LayerSurface::new(&env, surface, layer, callback)
+                .expect("Failed to create a layer surface !"),
+        ),
+        None => {
+            let mut window = env
+                .create_window::<ConceptFrame, _>(surface, None, dimensions, callback)
+                .expect("Failed to create a window !");
+            window.set_frame_config(create_frame_config(config.theme(dark_theme)));
+            decorations::apply(&env, &mut window, options.decorations);
+            Toplevel::Window(window)
         }
+    };
+
+    window.set_title(TITLE.to_string());
+    if takeover.as_ref().map_or(false, |takeover| takeover.state.fullscreen) {
+        window.set_fullscreen(true);
+    }
+
+    let mut controls =
+        Controls::new(&env, window.surface()).expect("Failed to create the controls");
+    controls.resize(dimensions);
+
+    let _inputs = Inputs::new(&env, window.surface(), controls.surface(), config.keymap.clone());
+
+    // The gradient is opaque, spare the compositor from blending it
+    let background_format = canvas::preferred_format(&env.shm_formats(), false);
+    let window_scale = SurfaceScale::new(&env, window.surface());
+    let content_type = SurfaceContentType::new(&env, window.surface(), ContentType::Video);
+    if !content_type.is_supported() {
+        info!("No content type hints, the compositor doesn't know it shows a video");
+    }
+    let (upload_sender, uploads): (_, Channel<Upload>) = channel::channel();
+    let mut uploader = Uploader::new(upload_sender);
+    let mut background =
+        Background::new(&env, window.surface(), background_format, &mut uploader)
+            .expect("Failed to create the background");
+
+    if !window.needs_configure(&env) {
+        // initial draw to bootstrap on wl_shell
+        let video = video_rect(dimensions, false);
+        background.update(window.surface(), &window_scale, dimensions, video);
         window.refresh();
     }
 
-    let pipeline = create_pipeline(window.surface(), display).unwrap();
+    let overlay = sink::set_window(&sink, &display, window.surface())
+        .expect("Failed to hand the window to the sink");
+    let surface_guard = SurfaceGuard::new(&sink, window.surface());
+    let mut size_hints = SizeHints::new(&sink, options.lock_aspect);
+    osd.start();
+
+    let mut pip = false;
+    let mut render_rect =
+        RenderRectangle::new(overlay, video_rect(dimensions, pip), options.rect_animation);
+
+    let mut stats = if options.stats {
+        Some(Stats::new(&env, &sink).expect("Failed to set up the statistics"))
+    } else {
+        None
+    };
+    let mut stats_printed = Instant::now();
+    let mut output_tracker = OutputTracker::new(window.surface());
+    let mut idle_inhibit = IdleInhibit::new(&env, window.surface());
+    let mut pipeline_state = PipelineState::new();
+    let mut auto_pause = AutoPause::new(window.surface());
+
+    let subtitles = options
+        .uri
+        .as_ref()
+        .map(|_| Subtitles::new(pipeline.clone().upcast()));
 
-    pipeline.set_state(gst::State::Playing).unwrap();
+    let audio_policy = AudioPolicy::new(options.audio_focus);
+    let window_audio = volume.clone().map(|volume| audio_policy.register(volume));
 
+    if let Some(takeover) = &takeover {
+        if let Err(err) = takeover.preroll(&pipeline) {
+            warn!("Failed to resume where the old instance is: {}", err);
+        }
+    }
+    if takeover.as_ref().map_or(true, |takeover| takeover.state.playing) {
+        surface_guard.set_state(&pipeline, gst::State::Playing).unwrap();
+    }
+
+    // No GLib main loop runs, the bus is polled from the event loop instead
+    // of being watched
     let bus = pipeline
         .get_bus()
         .expect("Pipeline without bus. Shouldn't happen!");
 
-    gst::glib::MainContext::default().acquire();
-
-    bus.add_watch_local(move |bus, msg| {
-        use gst::MessageView;
-
-        match msg.view() {
-            MessageView::Eos(eos) => {
-                println!("Eos: {:#?}\n{:#?}", bus, eos);
+    let mut event_loop =
+        EventLoop::<DispatchState>::try_new().expect("Failed to create the event loop");
+    sctk::WaylandSource::new(queue)
+        .quick_insert(event_loop.handle())
+        .expect("Failed to insert the Wayland source");
+    event_loop
+        .handle()
+        .insert_source(Timer::from_duration(TITLE_INTERVAL), |_, _, state| {
+            state.title_tick = true;
+            TimeoutAction::ToDuration(TITLE_INTERVAL)
+        })
+        .expect("Failed to insert the title timer");
+    event_loop
+        .handle()
+        .insert_source(uploads, |event, _, state| {
+            if let channel::Event::Msg(upload) = event {
+                state.uploads.push(upload);
+            }
+        })
+        .expect("Failed to insert the uploads");
+    let mut dashboard = if options.dashboard {
+        event_loop
+            .handle()
+            .insert_source(Timer::from_duration(DASHBOARD_INTERVAL), |_, _, state| {
+                state.dashboard_tick = true;
+                TimeoutAction::ToDuration(DASHBOARD_INTERVAL)
+            })
+            .expect("Failed to insert the dashboard timer");
+        let frame_config = create_frame_config(config.theme(dark_theme));
+        Some(Dashboard::new(&env, frame_config).expect("Failed to create the dashboard"))
+    } else {
+        None
+    };
+    // Closed windows leave an empty slot, their events come by index
+    let mut video_windows = Vec::new();
+    for uri in &options.windows {
+        let frame_config = create_frame_config(config.theme(dark_theme));
+        let index = video_windows.len();
+        match open_video_window(&env, &display, index, uri, &options, dimensions, frame_config) {
+            Ok((video_window, volume)) => {
+                video_windows.push(Some((video_window, audio_policy.register(volume))))
             }
-            MessageView::Error(err) => {
-                eprintln!("Error: {:#?}\n{:#?}", bus, err);
-                pipeline.set_state(gst::State::Null).unwrap();
+            Err(err) => warn!("Failed to open a window for {}: {}", uri, err),
+        }
+    }
+    let capabilities = Capabilities::probe(&env, &sink);
+    debug!(?capabilities, "Probed the compositor and the sink");
+    let mut _control_socket = None;
+    if options.control_stdin || options.control_socket {
+        let (sender, requests): (_, Channel<Request>) = channel::channel();
+        if options.control_stdin {
+            control::read_stdin(sender.clone());
+        }
+        if options.control_socket {
+            let (feed, tokens) = (alerts.feed(), config.tokens.clone());
+            let took_over = takeover.is_some();
+            match ControlSocket::new(sender, feed, capabilities.clone(), tokens, took_over) {
+                Ok(socket) => _control_socket = Some(socket),
+                Err(err) => warn!("Failed to listen for control requests: {}", err),
             }
+        }
+        event_loop
+            .handle()
+            .insert_source(requests, |event, _, state| {
+                if let channel::Event::Msg(request) = event {
+                    state.requests.push(request);
+                }
+            })
+            .expect("Failed to insert the control requests");
+    }
+    let mut _handoff = None;
+    if options.handoff {
+        let (sender, events): (_, Channel<handoff::Event>) = channel::channel();
+        match handoff::Listener::new(sender, takeover.is_some()) {
+            Ok(listener) => _handoff = Some(listener),
+            Err(err) => warn!("Failed to listen for a new instance: {}", err),
+        }
+        event_loop
+            .handle()
+            .insert_source(events, |event, _, state| {
+                if let channel::Event::Msg(event) = event {
+                    state.handoffs.push(event);
+                }
+            })
+            .expect("Failed to insert the handoff events");
+    }
+    let _http = options.http.as_deref().and_then(|address| {
+        let (capabilities, tokens) = (capabilities.clone(), config.tokens.clone());
+        let tls = match (&options.http_cert, &options.http_key) {
+            (Some(cert), Some(key)) => Some((cert.as_path(), key.as_path())),
+            (None, None) => None,
             _ => {
-                println!("Unhandled: {:#?}\n{:#?}", bus, msg);
+                warn!("Not serving HTTP on {}, --http-cert goes with --http-key", address);
+                return None;
+            }
+        };
+        let preview = preview.clone();
+        match HttpServer::new(address, sink.clone(), preview, capabilities, tokens, tls) {
+            Ok(server) => {
+                info!("Serving HTTP on {}", server.address());
+                let exposed = !server.address().ip().is_loopback();
+                if exposed && !server.is_tls() && !config.tokens.is_empty() {
+                    warn!("Tokens travel in cleartext on {}, see --http-cert", address);
+                }
+                Some(server)
+            }
+            Err(err) => {
+                warn!("Failed to serve HTTP on {}: {}", address, err);
+                None
             }
         }
-        gst::glib::Continue(true)
-    })
-        .expect("Failed to add bus watch");
+    });
+
+    #[cfg(feature = "mpris")]
+    let mpris = {
+        let (sender, calls): (_, Channel<mpris::Call>) = channel::channel();
+        event_loop
+            .handle()
+            .insert_source(calls, |event, _, state| {
+                if let channel::Event::Msg(call) = event {
+                    state.mpris_calls.push(call);
+                }
+            })
+            .expect("Failed to insert the MPRIS calls");
+        match mpris::Mpris::new(sender) {
+            Ok(mpris) => Some(mpris),
+            Err(err) => {
+                warn!("Failed to serve MPRIS: {}", err);
+                None
+            }
+        }
+    };
+
+    #[cfg(feature = "portal")]
+    if config.theme_mode == ThemeMode::System {
+        let (sender, schemes): (_, Channel<bool>) = channel::channel();
+        event_loop
+            .handle()
+            .insert_source(schemes, |event, _, state| {
+                if let channel::Event::Msg(dark) = event {
+                    state.prefers_dark = Some(dark);
+                }
+            })
+            .expect("Failed to insert the color scheme changes");
+        if let Err(err) = appearance::watch(sender) {
+            warn!("Failed to follow the color scheme: {}", err);
+        }
+    }
+    #[cfg(not(feature = "portal"))]
+    if config.theme_mode == ThemeMode::System {
+        info!("Following the color scheme of the desktop needs the portal feature");
+    }
 
-    let mut next_action = None;
+    let mut state = DispatchState::default();
+    let mut transport = Transport::new();
+    if let Some(path) = options.session.as_ref().filter(|path| path.exists()) {
+        let restored = restore_session(
+            path,
+            &pipeline,
+            &surface_guard,
+            &mut transport,
+            &mut orientation,
+            &mut zoom,
+            &balance,
+        );
+        match restored {
+            Ok(()) => info!("Restored the session of {}", path.display()),
+            Err(err) => warn!("Failed to restore the session of {}: {}", path.display(), err),
+        }
+    }
+    let mut preemption = Preemption::new(options.sink.clone());
+    let mut pip_video = options.pip_uri.as_ref().and_then(|uri| {
+        let surface = window.surface();
+        match PipVideo::new(&env, &display, surface, uri, options.sink.as_deref()) {
+            Ok(mut pip_video) => {
+                pip_video.layout(dimensions, surface, controls.surface());
+                surface.commit();
+                Some(pip_video)
+            }
+            Err(err) => {
+                warn!("Failed to play {}: {}", uri, err);
+                None
+            }
+        }
+    });
+    let mut source_swap = SourceSwap::new();
+    let mut frame_dump = None;
+    let comparison = options.compare.as_ref().and_then(|uri| {
+        let sink_b = options.sink.as_deref();
+        match Comparison::new(uri, sink_b, &display, window.surface(), &sink) {
+            Ok(comparison) => {
+                render_rect.animate_to(comparison.layout(dimensions));
+                Some(comparison)
+            }
+            Err(err) => {
+                warn!("Failed to play {} for the comparison: {}", uri, err);
+                None
+            }
+        }
+    });
+    let mut fullscreen = false;
+    let mut frame_pending = false;
 
     loop {
-        match next_action.take() {
-            Some(WEvent::Close) => break,
+        match state.next_action.take() {
+            Some(WEvent::Close) => {
+                if let Some(stats) = &stats {
+                    info!("Stats: {}", stats.summary());
+                }
+                if let Some(late_frames) = &late_frames {
+                    info!("Late frames: {}", late_frames.summary());
+                }
+                if let Some(still_frames) = &still_frames {
+                    info!("Still frames: {}", still_frames.summary());
+                }
+                if let Some(swap_interval) = &swap_interval {
+                    info!("Swap interval: {}", swap_interval.summary());
+                }
+                if let Some(summary) = alerts.summary() {
+                    info!("Alerts: {}", summary);
+                }
+                if let Some(path) = &options.session {
+                    let transforms = transforms(&orientation, &zoom, &balance);
+                    let state = session::save_state(&pipeline, transforms);
+                    if let Err(err) = save_session(path, &state) {
+                        warn!("Failed to save the session to {}: {}", path.display(), err);
+                    }
+                }
+                hooks.before_stop();
+                shutdown(&pipeline);
+                hooks.pipeline_null();
+                break;
+            }
             Some(WEvent::Refresh) => {
                 window.refresh();
                 window.surface().commit();
             }
             Some(WEvent::Configure { new_size, states }) => {
-                if let Some((w, h)) = new_size {
+                if let Some(size) = new_size {
+                    let (w, h) = size_hints.fit(size, &states);
                     window.resize(w, h);
                     dimensions = (w, h)
                 }
-                println!("Window states: {:?}", states);
+                debug!(?states, "Window states");
+                fullscreen = states.contains(&State::Fullscreen);
+                if let Some(window_audio) = &window_audio {
+                    window_audio.set_focused(states.contains(&State::Activated));
+                }
+                if states.contains(&State::Activated) {
+                    auto_pause.activated(&pipeline, &surface_guard);
+                }
                 window.refresh();
-                if let Some(pool) = pools.pool() {
-                    redraw(pool, window.surface(), dimensions).expect("Failed to draw")
+                if let Some(pip_video) = &mut pip_video {
+                    pip_video.layout(dimensions, window.surface(), controls.surface());
+                }
+                let rect = main_video_rect(dimensions, pip || preemption.is_active(), &pip_video);
+                render_rect.animate_to(rect);
+                if let Some(mosaic) = &mosaic {
+                    mosaic.layout(dimensions);
+                }
+                if let Some(comparison) = &comparison {
+                    render_rect.animate_to(comparison.layout(dimensions));
                 }
+                preemption.set_rect(video_rect(dimensions, false));
+                controls.resize(dimensions);
             }
             None => {}
         }
 
-        queue.dispatch(&mut next_action, |_, _, _| {}).unwrap();
+        for command in state.commands.drain(..) {
+            if !matches!(command, Command::StepForward | Command::StepBackward) {
+                transport.forward(&pipeline);
+            }
+            match command {
+                Command::ToggleFullscreen => {
+                    window.set_fullscreen(!fullscreen);
+                }
+                Command::TogglePip => {
+                    pip = !pip;
+                    let rect =
+                        main_video_rect(dimensions, pip || preemption.is_active(), &pip_video);
+                    render_rect.animate_to(rect);
+                }
+                Command::ToggleTheme => {
+                    dark_theme = !dark_theme;
+                    window.set_frame_config(create_frame_config(config.theme(dark_theme)));
+                    window.refresh();
+                }
+                Command::ToggleOsd => osd.toggle(),
+                Command::ToggleFps => fps.toggle(),
+                Command::ToggleTimeOverlay => {
+                    let shown = time_overlays.toggle_time();
+                    info!("Timestamps {}", if shown { "shown" } else { "hidden" });
+                }
+                Command::ToggleClockOverlay => {
+                    let shown = time_overlays.toggle_clock();
+                    info!("Wall clock {}", if shown { "shown" } else { "hidden" });
+                }
+                Command::ToggleGuides => {
+                    if let Some(guides) = &guides {
+                        let shown = guides.toggle();
+                        info!("Guides {}", if shown { "shown" } else { "hidden" });
+                    }
+                }
+                Command::NextTrack => {
+                    if let Some(playlist) = &playlist {
+                        playlist.next(&pipeline, &surface_guard);
+                    }
+                }
+                Command::PreviousTrack => {
+                    if let Some(playlist) = &playlist {
+                        playlist.previous(&pipeline, &surface_guard);
+                    }
+                }
+                Command::Snapshot => match snapshot::save(&sink) {
+                    Ok(path) => info!("Saved snapshot to {}", path.display()),
+                    Err(err) => warn!("Failed to save snapshot: {}", err),
+                },
+                Command::SaveReplay => match &timeshift {
+                    Some(timeshift) => match timeshift.save_replay() {
+                        Ok(path) => info!("Saving the replay to {}", path.display()),
+                        Err(err) => warn!("Failed to save the replay: {}", err),
+                    },
+                    None => info!("Instant replays need a --timeshift live input"),
+                },
+                Command::DumpFrames => {
+                    let (every, duration) = (options.dump_every, options.dump_duration);
+                    dump_frames(&mut frame_dump, &sink, every, duration);
+                }
+                Command::CopyFrame => {
+                    if let Some((seat, serial)) = &state.key_serial {
+                        match clipboard::copy_frame(&env, seat, *serial, &sink) {
+                            Ok(()) => info!("Copied the frame to the clipboard"),
+                            Err(err) => warn!("Failed to copy the frame: {}", err),
+                        }
+                    }
+                }
+                Command::ToggleLut => {
+                    if let Some(lut) = &lut {
+                        let bypassed = lut.toggle_bypass();
+                        info!("LUT {}", if bypassed { "bypassed" } else { "applied" });
+                    }
+                }
+                Command::TogglePlay => toggle_play(&pipeline, &surface_guard),
+                Command::SlowDown => {
+                    transport.slow_down(&pipeline);
+                    state.title_tick = true;
+                }
+                Command::SpeedUp => {
+                    transport.speed_up(&pipeline);
+                    state.title_tick = true;
+                }
+                Command::StepForward => transport.step(&pipeline, true),
+                Command::StepBackward => transport.step(&pipeline, false),
+                Command::SeekBackward => seek_by(&pipeline, -SEEK_STEP),
+                Command::SeekForward => seek_by(&pipeline, SEEK_STEP),
+                Command::VolumeUp | Command::VolumeDown => {
+                    if let Some(volume) = &volume {
+                        let step = if command == Command::VolumeUp { 0.1 } else { -0.1 };
+                        change_volume(volume, step);
+                    }
+                }
+                Command::ToggleMute => {
+                    if let Some(window_audio) = &window_audio {
+                        let muted = window_audio.toggle_mute();
+                        info!("Audio {}", if muted { "muted" } else { "unmuted" });
+                    }
+                }
+                Command::CycleSubtitles => {
+                    if let Some(subtitles) = &subtitles {
+                        match subtitles.cycle() {
+                            Ok((_, 0)) => info!("No subtitles"),
+                            Ok((index, count)) => info!("Subtitles {}/{}", index + 1, count),
+                            Err(err) => warn!("Failed to switch subtitles: {}", err),
+                        }
+                    }
+                }
+                Command::ToggleSubtitles => {
+                    if let Some(subtitles) = &subtitles {
+                        match subtitles.toggle() {
+                            Ok(shown) => info!("Subtitles {}", if shown { "on" } else { "off" }),
+                            Err(err) => warn!("Failed to toggle subtitles: {}", err),
+                        }
+                    }
+                }
+                Command::BalanceNext => balance.select_next(),
+                Command::BalanceUp => balance.step(true),
+                Command::BalanceDown => balance.step(false),
+                Command::BalanceReset => balance.reset(),
+                Command::Rotate | Command::FlipHorizontal | Command::FlipVertical => {
+                    match command {
+                        Command::Rotate => orientation.rotate(),
+                        Command::FlipHorizontal => orientation.flip_horizontal(),
+                        _ => orientation.flip_vertical(),
+                    }
+                    // The sink letterboxes the turned video anew
+                    render_rect.refresh();
+                }
+                Command::ControlsPress { x } => match controls.hit(x) {
+                    Some(Hit::PlayPause) => toggle_play(&pipeline, &surface_guard),
+                    Some(Hit::SeekBar(fraction)) => seek(&pipeline, fraction, false),
+                    None => {}
+                },
+                Command::ControlsDrag { x } => {
+                    if let Some(Hit::SeekBar(fraction)) = controls.hit(x) {
+                        seek(&pipeline, fraction, true);
+                    }
+                }
+                Command::ControlsRelease { x } => {
+                    if let Some(Hit::SeekBar(fraction)) = controls.hit(x) {
+                        seek(&pipeline, fraction, false);
+                    }
+                }
+                Command::Drag { dx, dy } => {
+                    if let Some(equirect) = &mut equirect {
+                        equirect.look(dx / dimensions.0 as f64, dy / dimensions.1 as f64);
+                    } else {
+                        let rect = render_rect.rect();
+                        zoom.pan((dx / rect.width as f64, dy / rect.height as f64));
+                    }
+                }
+                Command::Zoom { steps } => zoom.zoom(steps),
+                Command::Pinch { ratio } => zoom.scale(ratio),
+                Command::ResetZoom => zoom.reset(),
+                Command::ScaleVideo { ratio } => render_rect.scale_by(ratio),
+                Command::ResetVideoScale => render_rect.reset_scale(),
+                Command::SwapPip | Command::MovePip | Command::GrowPip | Command::ShrinkPip => {
+                    if let Some(pip_video) = &mut pip_video {
+                        match command {
+                            Command::SwapPip => pip_video.swap(),
+                            Command::MovePip => pip_video.next_corner(),
+                            Command::GrowPip => pip_video.resize(true),
+                            _ => pip_video.resize(false),
+                        }
+                        pip_video.layout(dimensions, window.surface(), controls.surface());
+                        window.surface().commit();
+                    }
+                    let rect =
+                        main_video_rect(dimensions, pip || preemption.is_active(), &pip_video);
+                    render_rect.animate_to(rect);
+                }
+            }
+        }
+
+        #[cfg(feature = "portal")]
+        if let Some(dark) = state.prefers_dark.take() {
+            if dark != dark_theme {
+                dark_theme = dark;
+                window.set_frame_config(create_frame_config(config.theme(dark_theme)));
+                window.refresh();
+            }
+        }
+
+        #[cfg(feature = "mpris")]
+        for call in state.mpris_calls.drain(..) {
+            let playlist = playlist.as_ref();
+            handle_mpris_call(call, &pipeline, &surface_guard, playlist, &mut state.next_action);
+        }
+
+        for request in state.requests.drain(..) {
+            match request {
+                Request::Preempt { uri, priority } => {
+                    let surface = window.surface();
+                    let rect = video_rect(dimensions, false);
+                    match preemption.preempt(&uri, priority, &display, surface, &pipeline, rect) {
+                        Ok(true) => render_rect.animate_to(video_rect(dimensions, true)),
+                        Ok(false) => info!("Ignoring {}, priority {} is too low", uri, priority),
+                        Err(err) => warn!("Failed to play {}: {}", uri, err),
+                    }
+                }
+                Request::Release => {
+                    if let Err(err) = preemption.release(&pipeline, &surface_guard) {
+                        warn!("Failed to resume playback: {}", err);
+                    }
+                    let rect =
+                        main_video_rect(dimensions, pip || preemption.is_active(), &pip_video);
+                    render_rect.animate_to(rect);
+                }
+                Request::SwapSource { uri } => {
+                    if let Err(err) = source_swap.swap(&pipeline, &uri) {
+                        warn!("Failed to swap to {}: {}", uri, err);
+                    }
+                }
+                Request::Play | Request::Pause => {
+                    let target = if request == Request::Play {
+                        gst::State::Playing
+                    } else {
+                        gst::State::Paused
+                    };
+                    if let Err(err) = surface_guard.set_state(&pipeline, target) {
+                        warn!("Failed to switch to {:?}: {}", target, err);
+                    }
+                }
+                Request::Seek { position } => seek_to(&pipeline, position),
+                Request::Load { uri } => {
+                    if let Err(err) = playlist::retarget(&pipeline, &uri, &surface_guard) {
+                        warn!("Failed to load {}: {}", uri, err);
+                    }
+                }
+                Request::SetRect(rect) => render_rect.animate_to(rect),
+                Request::SetBalance { adjustment, value } => balance.set(adjustment, value),
+                Request::ResetBalance => balance.reset(),
+                Request::DumpFrames { every, seconds } => {
+                    // Also keeps NaN and infinities out of the duration
+                    let duration = Duration::from_secs_f64(seconds.max(0.0).min(MAX_DUMP_SECONDS));
+                    dump_frames(&mut frame_dump, &sink, every, duration);
+                }
+                Request::Alerts => info!("Alerts: {}", alerts.feed().to_json()),
+                Request::Capabilities => info!("Capabilities: {:?}", capabilities),
+                Request::Quit => queue_action(&mut state.next_action, WEvent::Close),
+            }
+        }
+
+        // Animations are stepped on frame callbacks, and the last step is
+        // committed so that the subsurface position is applied
+        let animating = if state.frame_done {
+            state.frame_done = false;
+            frame_pending = false;
+            let animating = render_rect.tick() | osd.tick() | controls.tick();
+            if !animating {
+                window.surface().commit();
+            }
+            animating
+        } else {
+            render_rect.is_animating() || osd.is_animating() || controls.is_animating()
+        };
+        // The background follows the video along animations and resizes,
+        // the video is only rescaled once it settled
+        for upload in state.uploads.drain(..) {
+            background.on_upload(upload);
+        }
+        background.update(window.surface(), &window_scale, dimensions, render_rect.rect());
+        if !animating {
+            video_scaler.set(render_rect.rect(), window_scale.get());
+        }
+
+        // Statistics need a commit, and so a frame, on every refresh
+        if (animating || stats.is_some()) && !frame_pending {
+            if let Some(stats) = &stats {
+                stats.request_feedback(window.surface());
+            }
+            request_frame(window.surface());
+            frame_pending = true;
+        }
+
+        if let Some(stats) = &mut stats {
+            for presented in state.presented.drain(..) {
+                stats.on_presented(presented);
+            }
+            if stats_printed.elapsed() >= STATS_INTERVAL {
+                stats_printed = Instant::now();
+                info!("Stats: {}", stats.summary());
+            }
+        }
+
+        // Outputs differ in scale and refresh rate, the video is laid out
+        // again for the new one
+        if let Some(info) = output_tracker.poll() {
+            let refresh = output::refresh_rate(&info)
+                .map(|refresh| format!("{:.2} Hz", refresh as f64 / 1000.0))
+                .unwrap_or_else(|| "unknown refresh rate".to_owned());
+            info!(
+                "On output {} ({} {}), scale {}, {}",
+                info.name, info.make, info.model, info.scale_factor, refresh
+            );
+            render_rect.refresh();
+            if let Some(swap_interval) = &swap_interval {
+                if let Some(rate) = output::refresh_rate(&info) {
+                    swap_interval.set_refresh_rate(rate);
+                }
+            }
+            if let Some(color) = &mut color {
+                color.set_output(output_tracker.output().as_ref());
+            }
+        }
+
+        for (index, event) in state.window_actions.drain(..) {
+            let slot = match video_windows.get_mut(index) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            if let WEvent::Close = event {
+                // Stops the pipeline, destroys the window and leaves the audio policy
+                *slot = None;
+            } else if let Some((video_window, window_audio)) = slot {
+                video_window.handle(event);
+                window_audio.set_focused(video_window.is_activated());
+            }
+        }
+        for (video_window, _) in video_windows.iter().flatten() {
+            video_window.update();
+        }
+        if let Some(dashboard) = &mut dashboard {
+            if let Some(event) = state.dashboard_action.take() {
+                dashboard.handle(event);
+            }
+            if state.dashboard_tick {
+                state.dashboard_tick = false;
+                dashboard.update(&pipeline);
+            }
+        }
+
+        if state.pointer_active {
+            state.pointer_active = false;
+            controls.show();
+        }
+
+        while let Some(msg) = bus.pop() {
+            if let Some(bus_recorder) = &mut bus_recorder {
+                bus_recorder.record(&msg);
+            }
+            idle_inhibit.on_message(&msg);
+            pipeline_state.on_message(&msg, &pipeline, &surface_guard);
+            transport.on_message(&msg, &pipeline);
+            source_swap.on_message(&msg, &pipeline, &surface_guard);
+            if let Some(playlist) = &playlist {
+                playlist.on_message(&msg, &pipeline, &surface_guard);
+            }
+            if let Some(auto_proxy) = &mut auto_proxy {
+                auto_proxy.on_message(&msg);
+            }
+            if let Some(late_frames) = &late_frames {
+                late_frames.on_message(&msg, &pipeline);
+            }
+            if let Some(audio_monitor) = &mut audio_monitor {
+                audio_monitor.on_message(&msg);
+            }
+            alerts.on_message(&msg);
+            handle_message(&msg, &pipeline, &mut fps, loudness.as_ref());
+        }
+
+        for event in state.handoffs.drain(..) {
+            match event {
+                handoff::Event::Connected(stream) => {
+                    let (_, current, _) = pipeline.get_state(gst::ClockTime::from_seconds(0));
+                    let position = pipeline
+                        .query_position::<gst::ClockTime>()
+                        .and_then(|position| position.nseconds())
+                        .unwrap_or(0);
+                    let uri = pipeline
+                        .find_property("current-uri")
+                        .and_then(|_| pipeline.get_property("current-uri").ok())
+                        .and_then(|uri| uri.get::<String>().ok().flatten());
+                    let handed_over = handoff::State {
+                        uri,
+                        position: position as f64 / 1e9,
+                        playing: current == gst::State::Playing,
+                        size: dimensions,
+                        fullscreen,
+                    };
+                    handed_over.send(stream);
+                }
+                handoff::Event::Ready => {
+                    info!("The new instance took over");
+                    queue_action(&mut state.next_action, WEvent::Close);
+                }
+            }
+        }
+        // This instance plays, or is paused as the old one was: it can go
+        let resumed = |takeover: &Takeover| pipeline_state.is_playing() || !takeover.state.playing;
+        if takeover.as_ref().map_or(false, resumed) {
+            if let Some(takeover) = takeover.take() {
+                takeover.ready();
+            }
+        }
+
+        auto_pause.update(&pipeline, &surface_guard);
+        if preemption.update(&pipeline, &surface_guard) {
+            let rect = main_video_rect(dimensions, pip || preemption.is_active(), &pip_video);
+            render_rect.animate_to(rect);
+        }
+        alerts.update(&pipeline_state);
+        balance.update();
+        if let Some(timeshift) = &mut timeshift {
+            timeshift.update(&pipeline);
+        }
+        if let Some(pip_video) = &pip_video {
+            pip_video.update();
+        }
+        if let Some(comparison) = &comparison {
+            comparison.update(&pipeline);
+        }
+        if let Some(size) = size_hints.update(&mut window, dimensions) {
+            // Laid out like a configure event, unless one is already pending
+            if !matches!(state.next_action, Some(WEvent::Configure { .. })) {
+                let states = size_hints.states().to_vec();
+                let configure = WEvent::Configure { new_size: Some(size), states };
+                queue_action(&mut state.next_action, configure);
+            }
+        }
+        if let Some(auto_proxy) = &mut auto_proxy {
+            auto_proxy.update(&pipeline);
+        }
+        let playback = playback(&pipeline, pipeline_state.is_playing());
+        #[cfg(feature = "mpris")]
+        if let Some(mpris) = &mpris {
+            mpris.update(&playback, transport.rate(), &pipeline);
+        }
+        idle_inhibit.update(playback.playing);
+        if state.title_tick {
+            state.title_tick = false;
+            let frames = frames.load(Ordering::Relaxed);
+            let title = playback_title(&playback, &pipeline_state, frames, transport.rate());
+            window.set_title(title);
+            fps.update(&sink);
+        }
+        match &mut composed_controls {
+            Some(composed_controls) => composed_controls.update(playback),
+            None => controls.update(playback),
+        }
+        // A fade of the controls starting now is stepped from the next frame
+        if controls.is_animating() && !frame_pending {
+            request_frame(window.surface());
+            frame_pending = true;
+        }
+
+        display.flush().unwrap();
+        event_loop.dispatch(None, &mut state).unwrap();
+    }
+
+    drop(window);
+    display.flush().unwrap();
+    hooks.surface_destroyed();
+}
+
+/// Check that `pipeline` gets ready, which also has auto-detected elements
+/// pick their implementation, and print it as a `gst-launch-1.0` line. The
+/// sink is left out of the state change as it needs a Wayland connection.
+fn dry_run(pipeline: &gst::Pipeline, sink: &gst::Element) {
+    sink.set_locked_state(true);
+    let ready = pipeline.set_state(gst::State::Ready);
+    println!("gst-launch-1.0 {}", launch::describe(pipeline));
+    if let Err(err) = ready {
+        error!("The pipeline failed to get ready: {}", err);
     }
+    pipeline.set_state(gst::State::Null).unwrap();
+}
+
+/// React to a message of the pipeline bus, a live or a replayed one.
+fn handle_message(
+    msg: &gst::Message,
+    pipeline: &gst::Pipeline,
+    fps: &mut Fps,
+    loudness: Option<&Loudness>,
+) {
+    use gst::MessageView;
+
+    let src = || msg.get_src().map(|src| src.get_path_string().to_string()).unwrap_or_default();
+    match msg.view() {
+        MessageView::Eos(_) => info!(src = %src(), "End of stream"),
+        MessageView::Error(err) => {
+            error!(src = %src(), debug = ?err.get_debug(), "{}", err.get_error());
+            pipeline.set_state(gst::State::Null).unwrap();
+        }
+        MessageView::Warning(warning) => {
+            warn!(src = %src(), debug = ?warning.get_debug(), "{}", warning.get_error());
+        }
+        MessageView::Info(info) => {
+            info!(src = %src(), debug = ?info.get_debug(), "{}", info.get_error());
+        }
+        MessageView::StateChanged(state) => debug!(
+            src = %src(),
+            old = ?state.get_old(),
+            new = ?state.get_current(),
+            "State changed"
+        ),
+        MessageView::Qos(qos) => fps.on_qos(qos),
+        MessageView::Element(element) => match (loudness, element.get_structure()) {
+            // Raised by `alerts`
+            (_, Some(structure)) if structure.get_name() == confidence::ALERT => {}
+            (Some(loudness), Some(structure)) => loudness.on_message(structure),
+            _ => trace!(src = %src(), message = ?msg, "Unhandled bus message"),
+        },
+        _ => trace!(src = %src(), message = ?msg, "Unhandled bus message"),
+    }
+}
+
+/// Feed the messages recorded in `path` through `handle_message`, in order
+/// and without waiting in between, so that runs replay the same way every
+/// time.
+fn replay_bus(
+    path: &Path,
+    pipeline: &gst::Pipeline,
+    fps: &mut Fps,
+    loudness: Option<&Loudness>,
+    alerts: &mut Alerts,
+) {
+    let recorded = bus_log::replay(path).expect("Failed to read the bus recording");
+    for recorded in recorded {
+        info!(time = recorded.time, src = %recorded.src, "Replaying");
+        alerts.on_message(&recorded.message);
+        handle_message(&recorded.message, pipeline, fps, loudness);
+    }
+}
+
+/// Stop the pipeline, giving it a chance to drain so that muxers such as the
+/// recording one can finalize their files.
+fn shutdown(pipeline: &gst::Pipeline) {
+    if pipeline.send_event(gst::event::Eos::new()) {
+        let bus = pipeline.get_bus().expect("Pipeline without bus. Shouldn't happen!");
+        bus.timed_pop_filtered(
+            5 * gst::SECOND,
+            &[gst::MessageType::Eos, gst::MessageType::Error],
+        );
+    }
+    pipeline.set_state(gst::State::Null).unwrap();
+}
+
+fn playback(pipeline: &gst::Pipeline, playing: bool) -> Playback {
+    Playback {
+        playing,
+        position: pipeline
+            .query_position::<gst::ClockTime>()
+            .and_then(|position| position.seconds()),
+        duration: pipeline
+            .query_duration::<gst::ClockTime>()
+            .and_then(|duration| duration.seconds()),
+    }
+}
+
+fn toggle_play(pipeline: &gst::Pipeline, surface_guard: &SurfaceGuard) {
+    let (_, current, _) = pipeline.get_state(gst::ClockTime::from_seconds(0));
+    let target = if current == gst::State::Playing {
+        gst::State::Paused
+    } else {
+        gst::State::Playing
+    };
+    if let Err(err) = surface_guard.set_state(pipeline, target) {
+        warn!("Failed to switch to {:?}: {}", target, err);
+    }
+}
+
+/// Seek `pipeline` to `seconds` from the start.
+fn seek_to(pipeline: &gst::Pipeline, seconds: f64) {
+    let position = gst::ClockTime::from_nseconds((seconds.max(0.0) * 1e9) as u64);
+    let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE;
+    if let Err(err) = pipeline.seek_simple(flags, position) {
+        warn!("Failed to seek to {}: {}", position, err);
+    }
+}
+
+/// Start dumping every `every`th frame of `sink` for `duration`, in place of
+/// the `dump` in progress if any.
+fn dump_frames(dump: &mut Option<FrameDump>, sink: &gst::Element, every: u32, duration: Duration) {
+    *dump = None;
+    match FrameDump::start(sink, every, duration) {
+        Ok((started, directory)) => {
+            info!("Dumping frames to {}", directory.display());
+            *dump = Some(started);
+        }
+        Err(err) => warn!("Failed to dump frames: {}", err),
+    }
+}
+
+/// Seek `pipeline` by `seconds` from the current position, back when
+/// negative.
+fn seek_by(pipeline: &gst::Pipeline, seconds: f64) {
+    if let Some(position) = pipeline.query_position::<gst::ClockTime>().and_then(|p| p.nseconds()) {
+        seek_to(pipeline, position as f64 / 1e9 + seconds);
+    }
+}
+
+/// How the video is shown, for the session.
+fn transforms(orientation: &Orientation, zoom: &Zoom, balance: &Balance) -> Transforms {
+    let (rotation, mirrored) = orientation.get();
+    let (zoom, zoom_center) = zoom.get();
+    Transforms {
+        rotation,
+        mirrored,
+        zoom,
+        zoom_center,
+        brightness: balance.value(Adjustment::Brightness),
+        contrast: balance.value(Adjustment::Contrast),
+        hue: balance.value(Adjustment::Hue),
+        saturation: balance.value(Adjustment::Saturation),
+    }
+}
+
+fn save_session(path: &Path, state: &SessionState) -> Result<(), Error> {
+    std::fs::write(path, serde_json::to_string_pretty(state)?)?;
+    Ok(())
+}
+
+/// Bring the pipeline and the transforms back to the session saved at `path`.
+fn restore_session(
+    path: &Path,
+    pipeline: &gst::Pipeline,
+    surface_guard: &SurfaceGuard,
+    transport: &mut Transport,
+    orientation: &mut Orientation,
+    zoom: &mut Zoom,
+    balance: &Balance,
+) -> Result<(), Error> {
+    let state: SessionState = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    if session::restore_state(pipeline, &state)? {
+        surface_guard.set_state(pipeline, gst::State::Playing)?;
+    }
+    transport.set_rate(state.rate);
+    let transforms = state.transforms;
+    orientation.set(transforms.rotation, transforms.mirrored);
+    zoom.set(transforms.zoom, transforms.zoom_center);
+    balance.restore(Adjustment::Brightness, transforms.brightness);
+    balance.restore(Adjustment::Contrast, transforms.contrast);
+    balance.restore(Adjustment::Hue, transforms.hue);
+    balance.restore(Adjustment::Saturation, transforms.saturation);
+    Ok(())
+}
+
+/// Carry out a method call of the MPRIS interface.
+#[cfg(feature = "mpris")]
+fn handle_mpris_call(
+    call: mpris::Call,
+    pipeline: &gst::Pipeline,
+    surface_guard: &SurfaceGuard,
+    playlist: Option<&Playlist>,
+    next_action: &mut Option<WEvent>,
+) {
+    use mpris::Call;
+
+    let set_state = |target| {
+        if let Err(err) = surface_guard.set_state(pipeline, target) {
+            warn!("Failed to switch to {:?}: {}", target, err);
+        }
+    };
+
+    match call {
+        Call::Next => {
+            if let Some(playlist) = playlist {
+                playlist.next(pipeline, surface_guard);
+            }
+        }
+        Call::Previous => {
+            if let Some(playlist) = playlist {
+                playlist.previous(pipeline, surface_guard);
+            }
+        }
+        Call::Pause => set_state(gst::State::Paused),
+        Call::PlayPause => toggle_play(pipeline, surface_guard),
+        Call::Stop => {
+            set_state(gst::State::Paused);
+            seek_to(pipeline, 0.0);
+        }
+        Call::Play => set_state(gst::State::Playing),
+        Call::Seek(offset) => {
+            let position = pipeline
+                .query_position::<gst::ClockTime>()
+                .and_then(|position| position.nseconds())
+                .unwrap_or(0);
+            seek_to(pipeline, position as f64 / 1e9 + offset);
+        }
+        Call::SetPosition(seconds) => seek_to(pipeline, seconds),
+        Call::Quit => queue_action(next_action, WEvent::Close),
+    }
+}
+
+fn change_volume(volume: &gst::Element, step: f64) {
+    let current = volume
+        .get_property("volume")
+        .ok()
+        .and_then(|value| value.get_some::<f64>().ok())
+        .unwrap_or(1.0);
+    let new = (current + step).max(0.0).min(1.0);
+    match volume.set_property("volume", &new) {
+        Ok(()) => info!("Volume {:.0}%", new * 100.0),
+        Err(err) => warn!("Failed to change the volume: {}", err),
+    }
+}
+
+/// Seek to `fraction` of the duration, if it is known. Scrubbing seeks to the
+/// nearest keyframe in trick mode to keep up with the pointer, otherwise the
+/// seek is accurate.
+fn seek(pipeline: &gst::Pipeline, fraction: f64, scrub: bool) {
+    let duration = match pipeline.query_duration::<gst::ClockTime>().and_then(|d| d.nseconds()) {
+        Some(duration) => duration,
+        None => return,
+    };
+    let fraction = fraction.max(0.0).min(1.0);
+    let position = gst::ClockTime::from_nseconds((duration as f64 * fraction) as u64);
+
+    let flags = if scrub {
+        gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT | gst::SeekFlags::TRICKMODE
+    } else {
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE
+    };
+    if let Err(err) = pipeline.seek_simple(flags, position) {
+        warn!("Failed to seek to {}: {}", position, err);
+    }
+}
+
+/// The window title with the title and artist from the tags, the playback
+/// progress, as position and duration when the latter is known, or as the
+/// frame count for the live appsrc, and the rate unless it's normal.
+fn playback_title(
+    playback: &Playback,
+    pipeline_state: &PipelineState,
+    frames: u64,
+    rate: f64,
+) -> String {
+    let mut title = TITLE.to_owned();
+    if let Some(media_title) = pipeline_state.media_title() {
+        title += &format!(" \u{2014} {}", media_title);
+    }
+    match (playback.position, playback.duration) {
+        (Some(position), Some(duration)) => {
+            title += &format!(" \u{2014} {} / {}", format_time(position), format_time(duration))
+        }
+        _ => title += &format!(" \u{2014} frame {}", frames),
+    }
+    if (rate - 1.0).abs() > f64::EPSILON {
+        title += &format!(" ({}x)", rate);
+    }
+    if let Some(percent) = pipeline_state.buffering() {
+        title += &format!(", buffering {}%", percent);
+    }
+    title
+}
+
+/// `mm:ss`, or `h:mm:ss` from an hour on.
+fn format_time(seconds: u64) -> String {
+    let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{:02}:{:02}", m, s)
+    }
+}
+
+/// Where the video goes in a window of the given size.
+fn video_rect((w, h): (u32, u32), pip: bool) -> Rect {
+    let (w, h) = (w as i32, h as i32);
+    if pip {
+        // a third of the window, in the bottom right corner
+        let margin = 16;
+        Rect::new(w - w / 3 - margin, h - h / 3 - margin, w / 3, h / 3)
+    } else {
+        Rect::new(0, 0, w, h)
+    }
+}
+
+/// Where the main video goes, in the corner of the `--pip-uri` one when the
+/// two are swapped.
+fn main_video_rect((w, h): (u32, u32), pip: bool, pip_video: &Option<PipVideo>) -> Rect {
+    match pip_video {
+        Some(pip_video) if pip_video.is_swapped() => pip_video.corner((w, h)),
+        _ => video_rect((w, h), pip),
+    }
+}
+
+fn request_frame(surface: &wl_surface::WlSurface) {
+    surface.frame().quick_assign(|_, _, mut ddata| {
+        if let Some(state) = ddata.get::<DispatchState>() {
+            state.frame_done = true;
+        }
+    });
+    surface.commit();
 }
 
 // The frame configuration we will use in this example
-fn create_frame_config() -> ConceptConfig {
-    let icon_spec = ButtonColorSpec {
-        hovered: ColorSpec::identical([0xFF, 0x22, 0x22, 0x22].into()),
-        idle: ColorSpec::identical([0xFF, 0xff, 0xff, 0xff].into()),
+/// The decorations in the colors of `theme`.
+fn create_frame_config(theme: &Theme) -> ConceptConfig {
+    let color = |argb: u32| ColorSpec::identical(argb.to_be_bytes().into());
+    let button = |idle: u32, hovered: u32| ButtonColorSpec {
+        hovered: color(hovered),
+        idle: color(idle),
         disabled: ColorSpec::invisible(),
     };
+    let icon_spec = button(theme.icon, theme.icon_hovered);
 
     ConceptConfig {
-        // dark theme
         primary_color: ColorSpec {
-            active: [0xFF, 0x22, 0x22, 0x22].into(),
-            inactive: [0xFF, 0x33, 0x33, 0x33].into(),
+            active: theme.primary.to_be_bytes().into(),
+            inactive: theme.primary_inactive.to_be_bytes().into(),
         },
-        // white separation line
-        secondary_color: ColorSpec::identical([0xFF, 0xFF, 0xFF, 0xFF].into()),
-        // red close button
-        close_button: Some((
-            // icon
-            icon_spec,
-            // button background
-            ButtonColorSpec {
-                hovered: ColorSpec::identical([0xFF, 0xFF, 0x00, 0x00].into()),
-                idle: ColorSpec::identical([0xFF, 0x88, 0x00, 0x00].into()),
-                disabled: ColorSpec::invisible(),
-            },
-        )),
-        // green maximize button
-        maximize_button: Some((
-            // icon
-            icon_spec,
-            // button background
-            ButtonColorSpec {
-                hovered: ColorSpec::identical([0xFF, 0x00, 0xFF, 0x00].into()),
-                idle: ColorSpec::identical([0xFF, 0x00, 0x88, 0x00].into()),
-                disabled: ColorSpec::invisible(),
-            },
-        )),
-        // blue minimize button
-        minimize_button: Some((
-            // icon
-            icon_spec,
-            // button background
-            ButtonColorSpec {
-                hovered: ColorSpec::identical([0xFF, 0x00, 0x00, 0xFF].into()),
-                idle: ColorSpec::identical([0xFF, 0x00, 0x00, 0x88].into()),
-                disabled: ColorSpec::invisible(),
-            },
-        )),
+        // separation line
+        secondary_color: color(theme.separator),
+        // icon and button background
+        close_button: Some((icon_spec, button(theme.close, theme.close_hovered))),
+        maximize_button: Some((icon_spec, button(theme.maximize, theme.maximize_hovered))),
+        minimize_button: Some((icon_spec, button(theme.minimize, theme.minimize_hovered))),
         // same font as default
         title_font: Some(("sans".into(), 17.0)),
-        // clear text over dark background
-        title_color: ColorSpec::identical([0xFF, 0xD0, 0xD0, 0xD0].into()),
-    }
-}
-
-
-fn redraw(
-    pool: &mut MemPool,
-    surface: &wl_surface::WlSurface,
-    (buf_x, buf_y): (u32, u32),
-) -> Result<(), ::std::io::Error> {
-    // resize the pool if relevant
-    pool.resize((4 * buf_x * buf_y) as usize).expect("Failed to resize the memory pool.");
-    // write the contents, a nice color gradient =)
-    pool.seek(SeekFrom::Start(0))?;
-    {
-        let mut writer = BufWriter::new(&mut *pool);
-        for i in 0..(buf_x * buf_y) {
-            let x = (i % buf_x) as u32;
-            let y = (i / buf_x) as u32;
-            let r: u32 = min(((buf_x - x) * 0xFF) / buf_x, ((buf_y - y) * 0xFF) / buf_y);
-            let g: u32 = min((x * 0xFF) / buf_x, ((buf_y - y) * 0xFF) / buf_y);
-            let b: u32 = min(((buf_x - x) * 0xFF) / buf_x, (y * 0xFF) / buf_y);
-            let pixel: u32 = (0xFF << 24) + (r << 16) + (g << 8) + b;
-            writer.write_all(&pixel.to_ne_bytes())?;
-        }
-        writer.flush()?;
-    }
-    // get a buffer and attach it
-    let new_buffer =
-        pool.buffer(0, buf_x as i32, buf_y as i32, 4 * buf_x as i32, wl_shm::Format::Argb8888);
-    surface.attach(Some(&new_buffer), 0, 0);
-    surface.commit();
-    Ok(())
+        title_color: color(theme.title),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Record `messages` to a bus log and replay it through the handlers of
+    /// the bus, returns the state `pipeline` is left in.
+    fn replay_through_handlers(messages: &[gst::Message], pipeline: &gst::Pipeline) -> gst::State {
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let mut recorder = BusRecorder::create(&path).unwrap();
+        for message in messages {
+            recorder.record(message);
+        }
+        drop(recorder);
+
+        let mut fps = Fps::new().unwrap();
+        for recorded in bus_log::replay(&path).unwrap() {
+            handle_message(&recorded.message, pipeline, &mut fps, None);
+        }
+        let (_, state, _) = pipeline.get_state(gst::ClockTime::from_seconds(1));
+        state
+    }
+
+    #[test]
+    fn replayed_warnings_leave_the_pipeline_running() {
+        gst::init().unwrap();
+        let pipeline = gst::Pipeline::new(None);
+        pipeline.set_state(gst::State::Paused).unwrap();
+        let messages = [
+            gst::message::Warning::builder(gst::CoreError::Clock, "Clock drift").build(),
+            gst::message::Eos::new(),
+        ];
+        assert_eq!(replay_through_handlers(&messages, &pipeline), gst::State::Paused);
+    }
+
+    #[test]
+    fn replayed_error_stops_the_pipeline() {
+        gst::init().unwrap();
+        let pipeline = gst::Pipeline::new(None);
+        pipeline.set_state(gst::State::Paused).unwrap();
+        let messages = [
+            gst::message::Warning::builder(gst::CoreError::Clock, "Clock drift").build(),
+            gst::message::Error::builder(gst::StreamError::Decode, "Corrupt frame").build(),
+        ];
+        assert_eq!(replay_through_handlers(&messages, &pipeline), gst::State::Null);
+    }
+
+    #[test]
+    fn formats_times() {
+        assert_eq!(format_time(0), "00:00");
+        assert_eq!(format_time(59), "00:59");
+        assert_eq!(format_time(61), "01:01");
+        assert_eq!(format_time(3599), "59:59");
+        assert_eq!(format_time(3600), "1:00:00");
+        assert_eq!(format_time(36_061), "10:01:01");
+    }
 }
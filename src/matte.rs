@@ -0,0 +1,122 @@
+//! Anamorphic de-squeeze and mattes, to preview cinema footage as framed.
+//!
+//! Anamorphic lenses squeeze the picture horizontally, by 2x or 1.33x. The
+//! de-squeeze only changes the pixel aspect ratio of the video, which the
+//! sink then shows stretched back. Mattes black out what lies outside of the
+//! picture of the given aspect ratio, such as 2.39:1 within a 16:9 frame, as
+//! seen after the de-squeeze.
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::filter::BgrxFilter;
+use crate::guides::{self, AspectRatio};
+use crate::make;
+
+const MATTE: [u8; 4] = [0, 0, 0, 0];
+
+/// `value` as a fraction, precise to the thousandth.
+fn to_fraction(value: f64) -> gst::Fraction {
+    gst::Fraction::new((value * 1000.0).round() as i32, 1000)
+}
+
+/// Black out what lies outside of the picture of `aspect` in the `pixels`
+/// of a frame `width` wide.
+fn draw(pixels: &mut [[u8; 4]], width: usize, aspect: AspectRatio) {
+    if width == 0 {
+        return;
+    }
+    let height = pixels.len() / width;
+    let (x, y, inner_width, inner_height) = guides::centered(aspect, width, height);
+    for (line, pixels) in pixels.chunks_exact_mut(width).enumerate() {
+        if line < y || line >= y + inner_height {
+            pixels.iter_mut().for_each(|pixel| *pixel = MATTE);
+        } else {
+            pixels[..x].iter_mut().for_each(|pixel| *pixel = MATTE);
+            pixels[x + inner_width..].iter_mut().for_each(|pixel| *pixel = MATTE);
+        }
+    }
+}
+
+pub struct Matte {
+    capssetter: Option<gst::Element>,
+    filter: Option<BgrxFilter>,
+}
+
+impl Matte {
+    /// De-squeeze the video by `desqueeze` horizontally, and matte it to
+    /// `aspect`.
+    pub fn new(desqueeze: Option<f64>, aspect: Option<AspectRatio>) -> Result<Self, Error> {
+        let capssetter = match desqueeze {
+            Some(desqueeze) => {
+                let capssetter = make("capssetter")?;
+                let caps = gst::Caps::builder("video/x-raw")
+                    .field("pixel-aspect-ratio", &to_fraction(desqueeze))
+                    .build();
+                capssetter.set_property("caps", &caps)?;
+                Some(capssetter)
+            }
+            None => None,
+        };
+
+        let filter = match aspect {
+            Some(aspect) => {
+                // The matte is drawn on the squeezed pixels
+                let stored = AspectRatio(aspect.0 / desqueeze.unwrap_or(1.0));
+                Some(BgrxFilter::new(move |pixels, width| draw(pixels, width, stored))?)
+            }
+            None => None,
+        };
+
+        Ok(Matte { capssetter, filter })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        let mut elements = Vec::new();
+        elements.extend(&self.capssetter);
+        elements.extend(self.filter.as_ref().map(BgrxFilter::element));
+        elements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `frame`, `width` pixels wide, is matted outside of the
+    /// picture at `(x, y, width, height)` only.
+    fn is_matte(frame: &[[u8; 4]], width: usize, picture: (usize, usize, usize, usize)) -> bool {
+        let (x, y, picture_width, picture_height) = picture;
+        frame.iter().enumerate().all(|(i, pixel)| {
+            let (column, line) = (i % width, i / width);
+            let inside = (x..x + picture_width).contains(&column)
+                && (y..y + picture_height).contains(&line);
+            (*pixel == MATTE) != inside
+        })
+    }
+
+    #[test]
+    fn mattes_wider_pictures_at_the_top_and_bottom() {
+        let mut frame = vec![[0xFF; 4]; 16 * 9];
+        draw(&mut frame, 16, AspectRatio(2.0));
+        assert!(is_matte(&frame, 16, (0, 0, 16, 8)));
+
+        let mut frame = vec![[0xFF; 4]; 16 * 9];
+        draw(&mut frame, 16, AspectRatio(16.0 / 3.0));
+        assert!(is_matte(&frame, 16, (0, 3, 16, 3)));
+    }
+
+    #[test]
+    fn mattes_narrower_pictures_on_the_sides() {
+        let mut frame = vec![[0xFF; 4]; 16 * 9];
+        draw(&mut frame, 16, AspectRatio(1.0));
+        assert!(is_matte(&frame, 16, (3, 0, 9, 9)));
+    }
+
+    #[test]
+    fn rounds_the_desqueeze_to_the_thousandth() {
+        assert_eq!(to_fraction(2.0), gst::Fraction::new(2000, 1000));
+        assert_eq!(to_fraction(4.0 / 3.0), gst::Fraction::new(1333, 1000));
+    }
+}
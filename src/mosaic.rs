@@ -0,0 +1,150 @@
+//! Several videos in a grid, for `--mosaic`.
+//!
+//! Each URI is decoded by a uridecodebin of its own into a pad of a
+//! `compositor`, whose output goes through the video bin as the video of a
+//! single source would. The compositor draws at the size of the window, and
+//! `Mosaic::layout` reflows the tiles when it changes: the grid takes the
+//! number of columns that makes the tiles largest at 16:9, and each video
+//! keeps its aspect ratio within its tile. Only the video is played.
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::{debug, warn};
+
+use crate::{make, MissingElement};
+
+/// Aspect ratio the grid is laid out for.
+const TILE_ASPECT: f64 = 16.0 / 9.0;
+
+/// The columns and rows of the grid of `count` tiles in `(width, height)`
+/// that makes 16:9 tiles largest.
+fn grid(count: usize, (width, height): (u32, u32)) -> (usize, usize) {
+    let count = count.max(1);
+    let tile_width = |columns: usize| {
+        let rows = (count + columns - 1) / columns;
+        let width = width as f64 / columns as f64;
+        width.min(height as f64 / rows as f64 * TILE_ASPECT)
+    };
+    let columns = (1..=count)
+        .max_by(|a, b| tile_width(*a).partial_cmp(&tile_width(*b)).unwrap())
+        .unwrap();
+    (columns, (count + columns - 1) / columns)
+}
+
+pub struct Mosaic {
+    capsfilter: gst::Element,
+    pads: Vec<gst::Pad>,
+}
+
+impl Mosaic {
+    /// Lay the tiles out in a window of `dimensions`, the size the
+    /// compositor then draws at.
+    pub fn layout(&self, (width, height): (u32, u32)) {
+        let (width, height) = (width.max(1), height.max(1));
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("width", &(width as i32))
+            .field("height", &(height as i32))
+            .build();
+        if let Err(err) = self.capsfilter.set_property("caps", &caps) {
+            warn!("Failed to resize the mosaic: {}", err);
+        }
+
+        let (columns, rows) = grid(self.pads.len(), (width, height));
+        let (tile_width, tile_height) = (width as usize / columns, height as usize / rows);
+        debug!(columns, rows, "Mosaic layout");
+        for (index, pad) in self.pads.iter().enumerate() {
+            let (column, row) = (index % columns, index / columns);
+            let properties: [(&str, i32); 4] = [
+                ("xpos", (column * tile_width) as i32),
+                ("ypos", (row * tile_height) as i32),
+                ("width", tile_width as i32),
+                ("height", tile_height as i32),
+            ];
+            for (name, value) in &properties {
+                if let Err(err) = pad.set_property(*name, value) {
+                    warn!("Failed to lay out the mosaic: {}", err);
+                }
+            }
+        }
+    }
+}
+
+/// Decode `uri` into a new pad of `compositor`, in `pipeline`.
+fn add_source(
+    pipeline: &gst::Pipeline,
+    compositor: &gst::Element,
+    uri: &str,
+) -> Result<gst::Pad, Error> {
+    let decodebin = make("uridecodebin")?;
+    decodebin.set_property("uri", &uri)?;
+    let videoconvert = make("videoconvert")?;
+    let queue = make("queue")?;
+    pipeline.add_many(&[&decodebin, &videoconvert, &queue])?;
+    videoconvert.link(&queue)?;
+
+    let pad = compositor
+        .get_request_pad("sink_%u")
+        .ok_or(MissingElement("compositor sink pad"))?;
+    queue.get_static_pad("src").unwrap().link(&pad)?;
+    if pad.find_property("sizing-policy").is_some() {
+        pad.set_property_from_str("sizing-policy", "keep-aspect-ratio");
+    }
+
+    let convert_weak = videoconvert.downgrade();
+    let uri = uri.to_owned();
+    decodebin.connect_pad_added(move |_, src| {
+        let is_video = src
+            .get_current_caps()
+            .and_then(|caps| caps.get_structure(0).map(|s| s.get_name().starts_with("video/")))
+            .unwrap_or(false);
+        let sink = match convert_weak.upgrade() {
+            Some(videoconvert) if is_video => videoconvert.get_static_pad("sink").unwrap(),
+            _ => return,
+        };
+        if sink.is_linked() {
+            return;
+        }
+        if let Err(err) = src.link(&sink) {
+            warn!("Failed to add {} to the mosaic: {:?}", uri, err);
+        }
+    });
+    Ok(pad)
+}
+
+/// The pipeline showing `uris` in a grid, into `video`, the video bin.
+pub fn create_pipeline(
+    uris: &[String],
+    video: &gst::Bin,
+) -> Result<(gst::Pipeline, Mosaic), Error> {
+    let pipeline = gst::Pipeline::new(None);
+    let compositor = make("compositor")?;
+    compositor.set_property_from_str("background", "black");
+    let capsfilter = make("capsfilter")?;
+    pipeline.add_many(&[&compositor, &capsfilter, video.upcast_ref()])?;
+    gst::Element::link_many(&[&compositor, &capsfilter, video.upcast_ref()])?;
+
+    let pads = uris
+        .iter()
+        .map(|uri| add_source(&pipeline, &compositor, uri))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((pipeline, Mosaic { capsfilter, pads }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn makes_the_tiles_largest() {
+        assert_eq!(grid(1, (1920, 1080)), (1, 1));
+        assert_eq!(grid(3, (1920, 1080)), (2, 2));
+        assert_eq!(grid(4, (1920, 1080)), (2, 2));
+        assert_eq!(grid(4, (3840, 540)), (4, 1));
+        assert_eq!(grid(3, (540, 1920)), (1, 3));
+    }
+
+    #[test]
+    fn lays_out_at_least_one_tile() {
+        assert_eq!(grid(0, (1920, 1080)), (1, 1));
+    }
+}
@@ -0,0 +1,331 @@
+//! The MPRIS D-Bus interface, for media keys and desktop media applets.
+//!
+//! `org.mpris.MediaPlayer2` and its `Player` interface are served on the
+//! session bus from a thread, which sends the method calls to the event
+//! loop. Properties are read from the status the event loop keeps up to
+//! date, which also signals the changes of playback status and metadata.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::Error;
+use gst::prelude::*;
+use sctk::reexports::calloop::channel::Sender;
+use tracing::warn;
+use zbus::{dbus_interface, fdo};
+use zvariant::{ObjectPath, Value};
+
+use crate::controls::Playback;
+use crate::transport::RATES;
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+const TRACK_ID: &str = "/org/mpris/MediaPlayer2/Track/0";
+
+/// A method call of the `Player` interface, or `Quit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Call {
+    Next,
+    Previous,
+    Pause,
+    PlayPause,
+    Stop,
+    Play,
+    /// Seek forward or backward by this many seconds.
+    Seek(f64),
+    /// Seek to this position, in seconds.
+    SetPosition(f64),
+    Quit,
+}
+
+/// What the properties are read from.
+#[derive(Debug, Default, Clone)]
+struct Status {
+    playing: bool,
+    /// Position and duration, in microseconds.
+    position: i64,
+    duration: Option<i64>,
+    rate: f64,
+    uri: Option<String>,
+}
+
+impl Status {
+    fn playback_status(&self) -> &'static str {
+        if self.playing {
+            "Playing"
+        } else {
+            "Paused"
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, Value<'static>> {
+        let mut metadata = HashMap::new();
+        let track_id = ObjectPath::try_from(TRACK_ID).unwrap();
+        metadata.insert("mpris:trackid".to_owned(), Value::from(track_id));
+        if let Some(duration) = self.duration {
+            metadata.insert("mpris:length".to_owned(), Value::from(duration));
+        }
+        if let Some(uri) = &self.uri {
+            let title = uri.rsplit('/').next().unwrap_or(uri).to_owned();
+            metadata.insert("xesam:title".to_owned(), Value::from(title));
+            metadata.insert("xesam:url".to_owned(), Value::from(uri.clone()));
+        }
+        metadata
+    }
+}
+
+struct Root {
+    calls: Sender<Call>,
+}
+
+#[dbus_interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {
+        let _ = self.calls.send(Call::Quit);
+    }
+
+    #[dbus_interface(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn identity(&self) -> String {
+        "WaylandSink with Video Overlay".to_owned()
+    }
+
+    #[dbus_interface(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[dbus_interface(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+struct Player {
+    calls: Sender<Call>,
+    status: Arc<Mutex<Status>>,
+}
+
+impl Player {
+    fn send(&self, call: Call) {
+        let _ = self.calls.send(call);
+    }
+}
+
+#[dbus_interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {
+        self.send(Call::Next);
+    }
+
+    fn previous(&self) {
+        self.send(Call::Previous);
+    }
+
+    fn pause(&self) {
+        self.send(Call::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.send(Call::PlayPause);
+    }
+
+    fn stop(&self) {
+        self.send(Call::Stop);
+    }
+
+    fn play(&self) {
+        self.send(Call::Play);
+    }
+
+    fn seek(&self, offset: i64) {
+        self.send(Call::Seek(offset as f64 / 1e6));
+    }
+
+    fn set_position(&self, track_id: ObjectPath, position: i64) {
+        if track_id.as_str() == TRACK_ID {
+            self.send(Call::SetPosition(position as f64 / 1e6));
+        }
+    }
+
+    fn open_uri(&self, _uri: &str) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported("Opening URIs is not supported".to_owned()))
+    }
+
+    #[dbus_interface(property)]
+    fn playback_status(&self) -> String {
+        self.status.lock().unwrap().playback_status().to_owned()
+    }
+
+    #[dbus_interface(property)]
+    fn rate(&self) -> f64 {
+        self.status.lock().unwrap().rate
+    }
+
+    #[dbus_interface(property)]
+    fn metadata(&self) -> HashMap<String, Value<'static>> {
+        self.status.lock().unwrap().metadata()
+    }
+
+    #[dbus_interface(property)]
+    fn position(&self) -> i64 {
+        self.status.lock().unwrap().position
+    }
+
+    #[dbus_interface(property)]
+    fn minimum_rate(&self) -> f64 {
+        RATES[0]
+    }
+
+    #[dbus_interface(property)]
+    fn maximum_rate(&self) -> f64 {
+        RATES[RATES.len() - 1]
+    }
+
+    #[dbus_interface(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn can_seek(&self) -> bool {
+        self.status.lock().unwrap().duration.is_some()
+    }
+
+    #[dbus_interface(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// Handle the method calls and property reads, for as long as the
+/// connection lasts.
+fn serve(
+    connection: &zbus::Connection,
+    calls: Sender<Call>,
+    status: Arc<Mutex<Status>>,
+) -> Result<(), Error> {
+    let path = ObjectPath::try_from(PATH)?;
+    let mut server = zbus::ObjectServer::new(connection);
+    server.at(
+        &path,
+        Root {
+            calls: calls.clone(),
+        },
+    )?;
+    server.at(&path, Player { calls, status })?;
+    loop {
+        if let Err(err) = server.try_handle_next() {
+            warn!("Failed to handle an MPRIS call: {}", err);
+        }
+    }
+}
+
+pub struct Mpris {
+    connection: zbus::Connection,
+    status: Arc<Mutex<Status>>,
+}
+
+impl Mpris {
+    /// Serve the interfaces on the session bus, sending the method calls to
+    /// `calls`.
+    pub fn new(calls: Sender<Call>) -> Result<Self, Error> {
+        let connection = zbus::Connection::new_session()?;
+        let name = format!(
+            "org.mpris.MediaPlayer2.wayland_sink_with_video_overlay.instance{}",
+            std::process::id()
+        );
+        let flags = fdo::RequestNameFlags::DoNotQueue.into();
+        fdo::DBusProxy::new(&connection)?.request_name(&name, flags)?;
+
+        let status = Arc::new(Mutex::new(Status {
+            rate: 1.0,
+            ..Status::default()
+        }));
+        let (server_connection, player_status) = (connection.clone(), status.clone());
+        thread::spawn(move || {
+            if let Err(err) = serve(&server_connection, calls, player_status) {
+                warn!("Failed to serve MPRIS: {}", err);
+            }
+        });
+
+        Ok(Mpris { connection, status })
+    }
+
+    /// Follow the playback of the event loop, signalling what changed.
+    pub fn update(&self, playback: &Playback, rate: f64, pipeline: &gst::Pipeline) {
+        // playbin knows what it plays, the synthetic source has no URI
+        let uri = pipeline
+            .find_property("current-uri")
+            .and_then(|_| pipeline.get_property("current-uri").ok())
+            .and_then(|uri| uri.get::<String>().ok().flatten());
+        let seconds = |seconds: u64| seconds as i64 * 1_000_000;
+        let new = Status {
+            playing: playback.playing,
+            position: playback.position.map(seconds).unwrap_or(0),
+            duration: playback.duration.map(seconds),
+            rate,
+            uri,
+        };
+
+        let old = std::mem::replace(&mut *self.status.lock().unwrap(), new.clone());
+        let mut changed = HashMap::new();
+        if new.playing != old.playing {
+            changed.insert("PlaybackStatus", Value::from(new.playback_status()));
+        }
+        if new.duration != old.duration || new.uri != old.uri {
+            changed.insert("Metadata", Value::from(new.metadata()));
+        }
+        if new.rate != old.rate {
+            changed.insert("Rate", Value::from(new.rate));
+        }
+        if changed.is_empty() {
+            return;
+        }
+
+        let body = (PLAYER, changed, Vec::<&str>::new());
+        let signal = self.connection.emit_signal(
+            None,
+            PATH,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+            &body,
+        );
+        if let Err(err) = signal {
+            warn!("Failed to signal the MPRIS changes: {}", err);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Handing NV12 to the sink, converted by `convert::bgrx_to_nv12`.
+//!
+//! The conversion happens between an appsink and an appsrc in a bin, which
+//! takes the place of a `videoconvert` in front of the sink. The appsink
+//! doesn't sync, the sink does, and the preroll frame is pushed through
+//! right away so that the sink can preroll too. Flushes stop at the
+//! appsink, so this suits sources that aren't seeked, like the synthetic
+//! one.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::warn;
+use wayland_sink_with_video_overlay::convert::{self, Plane};
+
+use crate::make;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Sample without caps")]
+struct NoCaps;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Sample without a buffer")]
+struct NoBuffer;
+
+#[derive(Default)]
+struct State {
+    /// The BGRx input and NV12 output of the current caps.
+    infos: Option<(gst_video::VideoInfo, gst_video::VideoInfo)>,
+    /// The timestamp of the last pushed frame, as the preroll one comes
+    /// again once playing.
+    last_pts: Option<gst::ClockTime>,
+}
+
+impl State {
+    /// Convert the frame of `sample` and push it to `appsrc`.
+    fn push(&mut self, sample: &gst::Sample, appsrc: &gst_app::AppSrc) -> Result<(), Error> {
+        let caps = sample.get_caps().ok_or(NoCaps)?;
+        let input = gst_video::VideoInfo::from_caps(caps)?;
+        if self.infos.as_ref().map(|(current, _)| current) != Some(&input) {
+            let output = gst_video::VideoInfo::builder(
+                gst_video::VideoFormat::Nv12,
+                input.width(),
+                input.height(),
+            )
+            .fps(input.fps())
+            .par(input.par())
+            .colorimetry(&"bt709".parse()?)
+            .build()?;
+            appsrc.set_caps(Some(&output.to_caps()?));
+            self.infos = Some((input, output));
+        }
+        let (input, output) = self.infos.as_ref().unwrap();
+
+        let buffer = sample.get_buffer().ok_or(NoBuffer)?;
+        let pts = buffer.get_pts();
+        if pts.is_some() && self.last_pts == Some(pts) {
+            return Ok(());
+        }
+        self.last_pts = Some(pts);
+
+        let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, input)?;
+        let mut converted = gst::Buffer::with_size(output.size())?;
+        {
+            let converted = converted.get_mut().unwrap();
+            converted.set_pts(pts);
+            converted.set_duration(buffer.get_duration());
+
+            let mut map = converted.map_writable()?;
+            let (y, uv) = map.as_mut_slice().split_at_mut(output.offset()[1]);
+            convert::bgrx_to_nv12(
+                Plane {
+                    data: frame.plane_data(0)?,
+                    stride: frame.plane_stride()[0] as usize,
+                },
+                Plane {
+                    data: y,
+                    stride: output.stride()[0] as usize,
+                },
+                Plane {
+                    data: uv,
+                    stride: output.stride()[1] as usize,
+                },
+                input.width() as usize,
+                input.height() as usize,
+            );
+        }
+
+        appsrc.push_buffer(converted)?;
+        Ok(())
+    }
+}
+
+pub struct Nv12Output {
+    bin: gst::Bin,
+}
+
+impl Nv12Output {
+    pub fn new() -> Result<Self, Error> {
+        let appsink = make("appsink")?
+            .dynamic_cast::<gst_app::AppSink>()
+            .unwrap();
+        let appsrc = make("appsrc")?
+            .dynamic_cast::<gst_app::AppSrc>()
+            .unwrap();
+
+        appsink.set_caps(Some(
+            &gst::Caps::builder("video/x-raw")
+                .field("format", &"BGRx")
+                .build(),
+        ));
+        appsink.set_property("sync", &false)?;
+        appsrc.set_property_format(gst::Format::Time);
+
+        let bin = gst::Bin::new(Some("nv12"));
+        bin.add_many(&[appsink.upcast_ref(), appsrc.upcast_ref()])?;
+        let sink_pad = appsink.get_static_pad("sink").unwrap();
+        bin.add_pad(&gst::GhostPad::new(Some("sink"), &sink_pad)?)?;
+        let src_pad = appsrc.get_static_pad("src").unwrap();
+        bin.add_pad(&gst::GhostPad::new(Some("src"), &src_pad)?)?;
+
+        let eos_appsrc = appsrc.clone();
+        let state = Arc::new(Mutex::new(State::default()));
+        let convert = move |appsink: &gst_app::AppSink, preroll: bool| {
+            let sample = if preroll {
+                appsink.pull_preroll()
+            } else {
+                appsink.pull_sample()
+            }
+            .map_err(|_| gst::FlowError::Eos)?;
+            match state.lock().unwrap().push(&sample, &appsrc) {
+                Ok(()) => Ok(gst::FlowSuccess::Ok),
+                Err(err) => {
+                    warn!("Failed to convert to NV12: {}", err);
+                    Err(gst::FlowError::Error)
+                }
+            }
+        };
+        let convert = Arc::new(convert);
+        let (preroll, sample) = (convert.clone(), convert);
+
+        appsink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_preroll(move |appsink| preroll(appsink, true))
+                .new_sample(move |appsink| sample(appsink, false))
+                .eos(move |_| {
+                    let _ = eos_appsrc.end_of_stream();
+                })
+                .build(),
+        );
+
+        Ok(Nv12Output { bin })
+    }
+
+    /// The element to link in front of the sink.
+    pub fn element(&self) -> &gst::Element {
+        self.bin.upcast_ref()
+    }
+}
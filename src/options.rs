@@ -0,0 +1,584 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+
+use crate::audio::ChannelMap;
+use crate::audio_policy::AudioFocus;
+use crate::clock::ClockSource;
+use crate::color::OutputProfile;
+use crate::decorations::DecorationMode;
+use crate::deinterlace::Deinterlacing;
+use crate::guides::AspectRatio;
+use crate::late;
+use crate::layer_shell::Layer;
+use crate::playlist;
+use crate::producer;
+use crate::stereo::{Eye, Layout};
+use crate::stream::{Codec, Destination};
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid argument {}", _0)]
+pub struct InvalidArgument(#[error(not(source))] pub String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Missing value for {}", _0)]
+pub struct MissingValue(#[error(not(source))] pub String);
+
+/// URIs are taken as is, anything else as a path to a local file.
+fn to_uri(location: &str) -> Result<String, Error> {
+    if location.contains("://") {
+        return Ok(location.to_owned());
+    }
+    let path = Path::new(location).canonicalize()?;
+    Ok(gst::glib::filename_to_uri(&path, None)?.to_string())
+}
+
+/// Command line options of the example.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Play this URI instead of the synthetic video.
+    pub uri: Option<String>,
+    /// Run this gst-launch-1.0 description instead, showing the video of its
+    /// waylandsink, or of another video overlay sink in it. The display
+    /// filters, such as the OSD, are not part of it.
+    pub pipeline: Option<String>,
+    /// Run the pipeline of this preset, `NAME[:KEY=VALUE,...]`, as with
+    /// `pipeline`.
+    pub preset: Option<String>,
+    /// URIs played in turn, starting with `uri`, when there are several.
+    pub playlist: Vec<String>,
+    /// Show the URIs of the playlist together in a grid instead.
+    pub mosaic: bool,
+    /// Decode the URI in a child process, showing the frames it sends.
+    pub decode_process: bool,
+    /// Command the decoding child is run under, such as a bwrap line.
+    pub decode_sandbox: Option<String>,
+    /// Be that child, decoding the URI to the socket on stdin.
+    pub decode_child: bool,
+    /// Record a live RTSP or UDP URI for this many minutes back, to pause
+    /// and rewind it.
+    pub timeshift: Option<u32>,
+    /// How much of the time-shifted input an instant replay saves.
+    pub replay: Duration,
+    /// External subtitles for the URI.
+    pub subtitles: Option<String>,
+    /// Also play this URI, muted, in a corner of the window.
+    pub pip_uri: Option<String>,
+    /// Play this URI muted on the right half of the window, along with the
+    /// main one on the left half, for comparisons.
+    pub compare: Option<String>,
+    /// Play each of these URIs in a window of its own.
+    pub windows: Vec<String>,
+    /// Show a clock widget in the OSD, formatted with this `g_date_time_format` string.
+    pub osd_clock: Option<String>,
+    /// HTTP JSON sources shown in the OSD, as `URL#/json/pointer`.
+    pub osd_json: Vec<String>,
+    /// How often the HTTP JSON sources are polled.
+    pub osd_json_interval: Duration,
+    /// Duration of the render rectangle animations.
+    pub rect_animation: Duration,
+    /// Also encode the video into this Matroska file.
+    pub record: Option<PathBuf>,
+    /// Frame dumps take one displayed frame in this many.
+    pub dump_every: u32,
+    /// How long frame dumps go on.
+    pub dump_duration: Duration,
+    /// ICC profiles of the outputs, to color correct the video for.
+    pub icc_profiles: Vec<OutputProfile>,
+    /// Also send the video as RTP to this destination.
+    pub stream: Option<Destination>,
+    pub stream_codec: Codec,
+    /// Write the session description of the stream to this file.
+    pub stream_sdp: Option<PathBuf>,
+    /// Grade the displayed video with this `.cube` 3D LUT.
+    pub lut: Option<PathBuf>,
+    /// Publish the video over WebRTC to this peer.
+    pub webrtc_peer: Option<String>,
+    /// WebSocket URL of the WebRTC signalling server.
+    pub webrtc_server: String,
+    /// How stereo content is packed, if the video is stereoscopic.
+    pub stereo: Option<Layout>,
+    /// The view of stereo content to display.
+    pub eye: Eye,
+    /// Show the video as 360° equirectangular, with this vertical field of view in degrees.
+    pub equirect: Option<f32>,
+    /// Play a tone on each of this many channels in turn.
+    pub audio_test: Option<u32>,
+    /// Downmix the audio to this many channels.
+    pub downmix: Option<u32>,
+    /// Re-map the audio channels before downmixing.
+    pub channel_map: Option<ChannelMap>,
+    /// Which windows are heard when several play audio.
+    pub audio_focus: AudioFocus,
+    /// Play audio on the device with this display name.
+    pub audio_device: Option<String>,
+    /// Stretch the video horizontally by this anamorphic squeeze factor.
+    pub desqueeze: Option<f64>,
+    /// Black out what lies outside of the picture of this aspect ratio.
+    pub matte: Option<AspectRatio>,
+    /// Draw safe areas, a center cross and a thirds grid over the video.
+    pub guides: bool,
+    /// Aspect ratio of the picture the guides are drawn for.
+    pub guides_aspect: Option<AspectRatio>,
+    /// Blend the on-screen controls into the video rather than drawing them
+    /// on a subsurface.
+    pub composed_controls: bool,
+    /// Keep the aspect ratio of the video while resizing the window.
+    pub lock_aspect: bool,
+    /// Show the EBU R128 loudness of the audio.
+    pub loudness: bool,
+    /// Show the timecode of the frames.
+    pub timecode: bool,
+    /// Show the timestamp of the frames.
+    pub time_overlay: bool,
+    /// Show the local time the frames go through.
+    pub clock_overlay: bool,
+    /// Stamp the synthetic frames with a timecode jam-synced to the wall clock.
+    pub timecode_generate: bool,
+    /// Show the video of the shmsink at this socket instead of the synthetic
+    /// video.
+    pub shm_socket: Option<String>,
+    /// Caps of the video of the shmsink.
+    pub shm_caps: Option<String>,
+    /// Format the synthetic frames are produced in.
+    pub source_format: producer::Format,
+    /// Show the video on this layer of wlr-layer-shell instead of in a window.
+    pub layer_shell: Option<Layer>,
+    /// Who draws the window decorations.
+    pub decorations: DecorationMode,
+    /// Clock the pipeline runs on.
+    pub clock: ClockSource,
+    /// PTP domain of the clock, when it is a PTP one.
+    pub ptp_domain: u32,
+    /// Whether interlaced video is deinterlaced.
+    pub deinterlace: Deinterlacing,
+    /// Convert the video to 8 bits before the display filters, instead of
+    /// passing deeper video through to the sink without them.
+    pub force_8bit: bool,
+    /// Hand NV12 to the sink, converted by the in-crate converter.
+    pub nv12: bool,
+    /// Downscale the video while frames are late, and scale it back up after.
+    pub auto_proxy: bool,
+    /// Alert on sustained black or frozen video, silent or clipping audio.
+    pub confidence_monitor: bool,
+    /// Print display latency statistics.
+    pub stats: bool,
+    /// Skip the frames repeating the previous one, rather than commit them.
+    pub still_frames: bool,
+    /// Let frames through to the sink at most every this many refreshes of
+    /// the output.
+    pub swap_interval: u32,
+    /// Show the pipeline dashboard window.
+    pub dashboard: bool,
+    /// Only build the pipeline and print it as a gst-launch-1.0 line.
+    pub dry_run: bool,
+    /// Print the latency of each element and of the whole pipeline on exit.
+    pub trace_latency: bool,
+    /// Write all the bus messages of the run to this file.
+    pub record_bus: Option<PathBuf>,
+    /// Only feed the bus messages recorded in this file through the handlers.
+    pub replay_bus: Option<PathBuf>,
+    /// Filter of the diagnostics, in the syntax of `RUST_LOG`, which it
+    /// overrides.
+    pub log_level: Option<String>,
+    /// Read control requests from stdin.
+    pub control_stdin: bool,
+    /// Take JSON control requests on `$XDG_RUNTIME_DIR/waylandsink-ctl.sock`.
+    pub control_socket: bool,
+    /// Restore the session saved in this file, if any, and save it there
+    /// on exit.
+    pub session: Option<PathBuf>,
+    /// Take playback over from the instance listening for upgrades, if any,
+    /// and listen for the next one.
+    pub handoff: bool,
+    /// Serve snapshots and a preview over HTTP on this address, such as
+    /// `0.0.0.0:8080`.
+    pub http: Option<String>,
+    /// Serve HTTPS with the certificate chain of this PEM file, along with
+    /// `http_key`.
+    pub http_cert: Option<PathBuf>,
+    /// The private key of `http_cert`, a PEM file.
+    pub http_key: Option<PathBuf>,
+    /// Initial size of the window, from the configuration file.
+    pub window_size: (u32, u32),
+    /// Frame rate of the synthetic video, from the configuration file.
+    pub fps: gst::Fraction,
+    /// Make this sink, from `--sink` or the configuration file, instead of
+    /// the first of waylandsink, glimagesink and autovideosink that can be.
+    pub sink: Option<String>,
+    /// What becomes of late frames, from the configuration file.
+    pub late_frames: Option<late::Settings>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            uri: None,
+            pipeline: None,
+            preset: None,
+            playlist: Vec::new(),
+            mosaic: false,
+            decode_process: false,
+            decode_sandbox: None,
+            decode_child: false,
+            timeshift: None,
+            replay: Duration::from_secs(30),
+            subtitles: None,
+            pip_uri: None,
+            compare: None,
+            windows: Vec::new(),
+            osd_clock: None,
+            osd_json: Vec::new(),
+            osd_json_interval: Duration::from_secs(60),
+            rect_animation: Duration::from_millis(250),
+            dump_every: 1,
+            dump_duration: Duration::from_secs(5),
+            record: None,
+            icc_profiles: Vec::new(),
+            stream: None,
+            stream_codec: Codec::H264,
+            stream_sdp: None,
+            lut: None,
+            webrtc_peer: None,
+            webrtc_server: "ws://127.0.0.1:8443".into(),
+            stereo: None,
+            eye: Eye::Left,
+            equirect: None,
+            audio_test: None,
+            downmix: None,
+            channel_map: None,
+            audio_focus: AudioFocus::Focused,
+            audio_device: None,
+            desqueeze: None,
+            matte: None,
+            guides: false,
+            guides_aspect: None,
+            composed_controls: false,
+            lock_aspect: false,
+            loudness: false,
+            timecode: false,
+            time_overlay: false,
+            clock_overlay: false,
+            timecode_generate: false,
+            shm_socket: None,
+            shm_caps: None,
+            source_format: producer::Format::Bgrx,
+            layer_shell: None,
+            decorations: DecorationMode::ServerSide,
+            clock: ClockSource::Auto,
+            ptp_domain: 0,
+            deinterlace: Deinterlacing::Off,
+            force_8bit: false,
+            nv12: false,
+            auto_proxy: false,
+            confidence_monitor: false,
+            stats: false,
+            still_frames: false,
+            swap_interval: 1,
+            dashboard: false,
+            trace_latency: false,
+            dry_run: false,
+            record_bus: None,
+            replay_bus: None,
+            log_level: None,
+            control_stdin: false,
+            control_socket: false,
+            session: None,
+            handoff: false,
+            http: None,
+            http_cert: None,
+            http_key: None,
+            window_size: (640, 480),
+            fps: gst::Fraction::new(2, 1),
+            sink: None,
+            late_frames: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args() -> Result<Options, Error> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, Error> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| MissingValue(arg.clone()));
+
+            match arg.as_str() {
+                "--uri" => options.uri = Some(to_uri(&value()?)?),
+                "--pipeline" => options.pipeline = Some(value()?),
+                "--preset" => options.preset = Some(value()?),
+                "--decode-process" => options.decode_process = true,
+                "--decode-sandbox" => options.decode_sandbox = Some(value()?),
+                "--decode-child" => options.decode_child = true,
+                "--timeshift" => {
+                    let minutes = value()?;
+                    let minutes = minutes.parse().map_err(|_| InvalidArgument(minutes))?;
+                    options.timeshift = Some(minutes);
+                }
+                "--replay-seconds" => {
+                    let secs = value()?;
+                    let secs = secs.parse().map_err(|_| InvalidArgument(secs))?;
+                    options.replay = Duration::from_secs(secs);
+                }
+                "--subtitles" => options.subtitles = Some(to_uri(&value()?)?),
+                "--pip-uri" => options.pip_uri = Some(to_uri(&value()?)?),
+                "--compare" => options.compare = Some(to_uri(&value()?)?),
+                "--window" => options.windows.push(to_uri(&value()?)?),
+                "--mosaic" => options.mosaic = true,
+                "--osd-clock" => options.osd_clock = Some("%H:%M:%S".into()),
+                "--osd-clock-format" => options.osd_clock = Some(value()?),
+                "--osd-json" => options.osd_json.push(value()?),
+                "--osd-json-interval" => {
+                    let secs = value()?;
+                    let secs = secs.parse().map_err(|_| InvalidArgument(secs))?;
+                    options.osd_json_interval = Duration::from_secs(secs);
+                }
+                "--rect-animation-ms" => {
+                    let ms = value()?;
+                    let ms = ms.parse().map_err(|_| InvalidArgument(ms))?;
+                    options.rect_animation = Duration::from_millis(ms);
+                }
+                "--record" => options.record = Some(value()?.into()),
+                "--dump-every" => {
+                    let every = value()?;
+                    options.dump_every = every.parse().map_err(|_| InvalidArgument(every))?;
+                }
+                "--dump-seconds" => {
+                    let secs = value()?;
+                    let secs = secs.parse().map_err(|_| InvalidArgument(secs))?;
+                    options.dump_duration = Duration::from_secs(secs);
+                }
+                "--icc-profile" => options.icc_profiles.push(OutputProfile::parse(&value()?)),
+                "--stream" => options.stream = Some(value()?.parse()?),
+                "--stream-codec" => options.stream_codec = value()?.parse()?,
+                "--stream-sdp" => options.stream_sdp = Some(value()?.into()),
+                "--lut" => options.lut = Some(value()?.into()),
+                "--webrtc-peer" => options.webrtc_peer = Some(value()?),
+                "--webrtc-server" => options.webrtc_server = value()?,
+                "--stereo" => options.stereo = Some(value()?.parse()?),
+                "--eye" => options.eye = value()?.parse()?,
+                "--equirect" => options.equirect = Some(90.0),
+                "--equirect-fov" => {
+                    let fov = value()?;
+                    let fov = fov.parse().map_err(|_| InvalidArgument(fov))?;
+                    options.equirect = Some(fov);
+                }
+                "--audio-test" => {
+                    let channels = value()?;
+                    let channels = channels.parse().map_err(|_| InvalidArgument(channels))?;
+                    options.audio_test = Some(channels);
+                }
+                "--downmix" => {
+                    let channels = value()?;
+                    let channels = match channels.as_str() {
+                        "mono" => 1,
+                        "stereo" => 2,
+                        _ => channels.parse().map_err(|_| InvalidArgument(channels))?,
+                    };
+                    options.downmix = Some(channels);
+                }
+                "--channel-map" => options.channel_map = Some(value()?.parse()?),
+                "--audio-focus" => options.audio_focus = value()?.parse()?,
+                "--audio-device" => options.audio_device = Some(value()?),
+                "--desqueeze" => {
+                    let factor = value()?;
+                    match factor.parse::<f64>() {
+                        Ok(parsed) if parsed > 0.0 => options.desqueeze = Some(parsed),
+                        _ => return Err(InvalidArgument(factor).into()),
+                    }
+                }
+                "--matte" => options.matte = Some(value()?.parse()?),
+                "--guides" => options.guides = true,
+                "--guides-aspect" => {
+                    options.guides = true;
+                    options.guides_aspect = Some(value()?.parse()?);
+                }
+                "--composed-controls" => options.composed_controls = true,
+                "--lock-aspect" => options.lock_aspect = true,
+                "--loudness" => options.loudness = true,
+                "--timecode" => options.timecode = true,
+                "--time-overlay" => options.time_overlay = true,
+                "--clock-overlay" => options.clock_overlay = true,
+                "--timecode-generate" => options.timecode_generate = true,
+                "--shm-socket" => options.shm_socket = Some(value()?),
+                "--shm-caps" => options.shm_caps = Some(value()?),
+                "--source-format" => options.source_format = value()?.parse()?,
+                "--layer-shell" => options.layer_shell = Some(value()?.parse()?),
+                "--ssd" => options.decorations = DecorationMode::ServerSide,
+                "--csd" => options.decorations = DecorationMode::ClientSide,
+                "--borderless" => options.decorations = DecorationMode::None,
+                "--deinterlace" => options.deinterlace = value()?.parse()?,
+                "--force-8bit" => options.force_8bit = true,
+                "--nv12" => options.nv12 = true,
+                "--sink" => options.sink = Some(value()?),
+                "--auto-proxy" => options.auto_proxy = true,
+                "--clock" => options.clock = value()?.parse()?,
+                "--ptp-domain" => {
+                    let domain = value()?;
+                    let domain = domain.parse().map_err(|_| InvalidArgument(domain))?;
+                    options.ptp_domain = domain;
+                }
+                "--confidence-monitor" => options.confidence_monitor = true,
+                "--stats" => options.stats = true,
+                "--still-frames" => options.still_frames = true,
+                "--swap-interval" => {
+                    let interval = value()?;
+                    options.swap_interval =
+                        interval.parse().map_err(|_| InvalidArgument(interval))?;
+                }
+                "--dashboard" => options.dashboard = true,
+                "--trace-latency" => options.trace_latency = true,
+                "--dry-run" => options.dry_run = true,
+                "--record-bus" => options.record_bus = Some(value()?.into()),
+                "--replay-bus" => options.replay_bus = Some(value()?.into()),
+                "--log-level" => options.log_level = Some(value()?),
+                "--control-stdin" => options.control_stdin = true,
+                "--control-socket" => options.control_socket = true,
+                "--session" => options.session = Some(value()?.into()),
+                "--handoff" => options.handoff = true,
+                "--http" => options.http = Some(value()?),
+                "--http-cert" => options.http_cert = Some(value()?.into()),
+                "--http-key" => options.http_key = Some(value()?.into()),
+                location if !location.starts_with("--") => {
+                    if playlist::is_m3u(location) {
+                        for entry in playlist::read_m3u(Path::new(location))? {
+                            options.playlist.push(to_uri(&entry)?);
+                        }
+                    } else {
+                        options.playlist.push(to_uri(location)?);
+                    }
+                }
+                _ => return Err(InvalidArgument(arg.clone()).into()),
+            }
+        }
+
+        // Files given as arguments play after the --uri one
+        if !options.playlist.is_empty() {
+            if let Some(uri) = options.uri.take() {
+                options.playlist.insert(0, uri);
+            }
+            options.uri = Some(options.playlist[0].clone());
+        }
+
+        Ok(options)
+    }
+
+    /// Whether the video is sent somewhere besides the window.
+    pub fn has_branches(&self) -> bool {
+        self.record.is_some()
+            || self.stream.is_some()
+            || self.webrtc_peer.is_some()
+            || self.http.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, Error> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_the_osd_options() {
+        let options = parse(&[
+            "--osd-clock",
+            "--osd-json",
+            "http://a/#/temperature",
+            "--osd-json",
+            "http://b/#/humidity",
+            "--osd-json-interval",
+            "5",
+        ])
+        .unwrap();
+        assert_eq!(options.osd_clock.as_deref(), Some("%H:%M:%S"));
+        assert_eq!(options.osd_json, ["http://a/#/temperature", "http://b/#/humidity"]);
+        assert_eq!(options.osd_json_interval, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let message = |args: &[&str]| parse(args).unwrap_err().to_string();
+        assert_eq!(message(&["--osd-json"]), "Missing value for --osd-json");
+        assert_eq!(message(&["--osd-json-interval", "soon"]), "Invalid argument soon");
+        assert_eq!(message(&["--unknown"]), "Invalid argument --unknown");
+    }
+
+    #[test]
+    fn parses_the_rect_animation_duration() {
+        assert_eq!(parse(&[]).unwrap().rect_animation, Duration::from_millis(250));
+        let options = parse(&["--rect-animation-ms", "100"]).unwrap();
+        assert_eq!(options.rect_animation, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn parses_the_downmix() {
+        assert_eq!(parse(&["--downmix", "mono"]).unwrap().downmix, Some(1));
+        assert_eq!(parse(&["--downmix", "stereo"]).unwrap().downmix, Some(2));
+        assert_eq!(parse(&["--downmix", "6"]).unwrap().downmix, Some(6));
+        assert!(parse(&["--downmix", "surround"]).is_err());
+    }
+
+    #[test]
+    fn keeps_uris_as_they_are() {
+        let options = parse(&["--uri", "https://example.com/a.mp4"]).unwrap();
+        assert_eq!(options.uri.as_deref(), Some("https://example.com/a.mp4"));
+        assert_eq!(
+            parse(&["--uri"]).unwrap_err().to_string(),
+            "Missing value for --uri"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_desqueeze_factors() {
+        assert_eq!(parse(&["--desqueeze", "2"]).unwrap().desqueeze, Some(2.0));
+        for factor in &["0", "-2", "wide"] {
+            let message = parse(&["--desqueeze", factor]).unwrap_err().to_string();
+            assert_eq!(message, format!("Invalid argument {}", factor));
+        }
+    }
+
+    #[test]
+    fn plays_the_uri_before_the_playlist() {
+        let options = parse(&["https://b", "--uri", "https://a", "https://c"]).unwrap();
+        assert_eq!(options.playlist, ["https://a", "https://b", "https://c"]);
+        assert_eq!(options.uri.as_deref(), Some("https://a"));
+    }
+
+    #[test]
+    fn parses_the_timeshift_window() {
+        assert_eq!(parse(&["--timeshift", "10"]).unwrap().timeshift, Some(10));
+        assert_eq!(
+            parse(&["--timeshift", "ten"]).unwrap_err().to_string(),
+            "Invalid argument ten"
+        );
+    }
+
+    #[test]
+    fn opens_a_window_per_uri() {
+        let options =
+            parse(&["--window", "rtsp://camera/1", "--window", "rtsp://camera/2"]).unwrap();
+        assert_eq!(options.windows, ["rtsp://camera/1", "rtsp://camera/2"]);
+    }
+
+    #[test]
+    fn parses_the_dump_options() {
+        let options = parse(&[]).unwrap();
+        assert_eq!((options.dump_every, options.dump_duration), (1, Duration::from_secs(5)));
+        let options = parse(&["--dump-every", "5", "--dump-seconds", "2"]).unwrap();
+        assert_eq!((options.dump_every, options.dump_duration), (5, Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn parses_lock_aspect() {
+        assert!(!parse(&[]).unwrap().lock_aspect);
+        assert!(parse(&["--lock-aspect"]).unwrap().lock_aspect);
+    }
+}
@@ -0,0 +1,93 @@
+//! Rotation and mirroring of the video, changed at runtime.
+//!
+//! A `videoflip` among the display filters turns the video, ahead of the
+//! overlays so that they stay upright. Rotations and flips are applied to
+//! the video as shown, and add up to one of the eight methods of videoflip.
+//! Rotating by a quarter turn swaps the aspect ratio, so the caller lays the
+//! video out again afterwards.
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::info;
+
+use crate::make;
+
+pub struct Orientation {
+    videoflip: gst::Element,
+    /// Quarter turns clockwise, applied after `mirrored`.
+    turns: u8,
+    /// Whether the video is flipped horizontally before it is turned.
+    mirrored: bool,
+}
+
+impl Orientation {
+    pub fn new() -> Result<Self, Error> {
+        let videoflip = make("videoflip")?;
+
+        Ok(Orientation {
+            videoflip,
+            turns: 0,
+            mirrored: false,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.videoflip]
+    }
+
+    /// Turn the video a quarter clockwise.
+    pub fn rotate(&mut self) {
+        self.turns = (self.turns + 1) % 4;
+        self.apply();
+    }
+
+    /// Mirror the video left to right.
+    pub fn flip_horizontal(&mut self) {
+        // Flipping then turning is turning the other way then flipping
+        self.turns = (4 - self.turns) % 4;
+        self.mirrored = !self.mirrored;
+        self.apply();
+    }
+
+    /// Mirror the video top to bottom, which is a horizontal flip turned
+    /// upside down.
+    pub fn flip_vertical(&mut self) {
+        self.turns = (6 - self.turns) % 4;
+        self.mirrored = !self.mirrored;
+        self.apply();
+    }
+
+    /// The quarter turns clockwise, and whether the video is flipped
+    /// horizontally before.
+    pub fn get(&self) -> (u8, bool) {
+        (self.turns, self.mirrored)
+    }
+
+    pub fn set(&mut self, turns: u8, mirrored: bool) {
+        self.turns = turns % 4;
+        self.mirrored = mirrored;
+        self.apply();
+    }
+
+    /// The videoflip method for the current orientation.
+    fn method(&self) -> &'static str {
+        match (self.turns, self.mirrored) {
+            (0, false) => "none",
+            (1, false) => "clockwise",
+            (2, false) => "rotate-180",
+            (3, false) => "counterclockwise",
+            (0, true) => "horizontal-flip",
+            (1, true) => "upper-right-diagonal",
+            (2, true) => "vertical-flip",
+            _ => "upper-left-diagonal",
+        }
+    }
+
+    fn apply(&self) {
+        let method = self.method();
+        info!("Video orientation {}", method);
+        // videoflip renegotiates its caps from the next frame
+        self.videoflip.set_property_from_str("method", method);
+    }
+}
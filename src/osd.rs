@@ -0,0 +1,235 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::warn;
+
+use crate::animation::{Animated, Easing};
+use crate::options::Options;
+use crate::make;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid JSON source {}, expected URL#/json/pointer", _0)]
+struct InvalidJsonSource(#[error(not(source))] String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "No value at {} in response from {}", pointer, url)]
+struct MissingJsonValue {
+    url: String,
+    pointer: String,
+}
+
+/// Something that produces the text shown by an OSD widget.
+///
+/// Sources are polled from a dedicated thread, so they are free to block.
+pub trait DataSource: Send {
+    /// Time to wait between two polls.
+    fn interval(&self) -> Duration;
+
+    /// Produce the current text of the widget.
+    fn poll(&mut self) -> Result<String, Error>;
+}
+
+/// The local wall clock.
+pub struct ClockSource {
+    format: String,
+}
+
+impl ClockSource {
+    pub fn new(format: &str) -> Self {
+        ClockSource {
+            format: format.to_owned(),
+        }
+    }
+}
+
+impl DataSource for ClockSource {
+    fn interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn poll(&mut self) -> Result<String, Error> {
+        let now = gst::glib::DateTime::new_now_local();
+        Ok(now.format(&self.format).map(|s| s.to_string()).unwrap_or_default())
+    }
+}
+
+/// A value extracted with a JSON pointer from a periodically fetched HTTP resource,
+/// e.g. the temperature of a weather service.
+pub struct HttpJsonSource {
+    url: String,
+    pointer: String,
+    interval: Duration,
+}
+
+impl HttpJsonSource {
+    /// Parse a `URL#/json/pointer` specification.
+    pub fn new(spec: &str, interval: Duration) -> Result<Self, Error> {
+        let pos = spec
+            .rfind("#/")
+            .ok_or_else(|| InvalidJsonSource(spec.to_owned()))?;
+
+        Ok(HttpJsonSource {
+            url: spec[..pos].to_owned(),
+            pointer: spec[pos + 1..].to_owned(),
+            interval,
+        })
+    }
+}
+
+impl DataSource for HttpJsonSource {
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn poll(&mut self) -> Result<String, Error> {
+        let json: serde_json::Value = ureq::get(&self.url)
+            .timeout(Duration::from_secs(10))
+            .call()?
+            .into_json()?;
+
+        match json.pointer(&self.pointer) {
+            Some(serde_json::Value::String(s)) => Ok(s.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => Err(MissingJsonValue {
+                url: self.url.clone(),
+                pointer: self.pointer.clone(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// A `textoverlay` element whose text is kept up to date from a data source.
+struct Widget {
+    overlay: gst::Element,
+    source: Box<dyn DataSource>,
+    halignment: &'static str,
+}
+
+/// The on-screen display drawn over the video.
+///
+/// Each widget is a `textoverlay` element chained in front of the sink.
+/// The whole OSD can be faded in and out.
+pub struct Osd {
+    widgets: Vec<Widget>,
+    overlays: Vec<gst::Element>,
+    opacity: Animated<f64>,
+    // Dropping the senders wakes up and stops the polling threads
+    stop: Vec<mpsc::Sender<()>>,
+}
+
+impl Osd {
+    pub fn new(options: &Options) -> Result<Osd, Error> {
+        let mut osd = Osd {
+            widgets: Vec::new(),
+            overlays: Vec::new(),
+            opacity: Animated::new(1.0, Duration::from_millis(300), Easing::EaseOut),
+            stop: Vec::new(),
+        };
+
+        if let Some(format) = &options.osd_clock {
+            osd.add_widget(Box::new(ClockSource::new(format)), "right", "top")?;
+        }
+        for spec in &options.osd_json {
+            let source = HttpJsonSource::new(spec, options.osd_json_interval)?;
+            osd.add_widget(Box::new(source), "left", "top")?;
+        }
+
+        Ok(osd)
+    }
+
+    fn add_widget(
+        &mut self,
+        source: Box<dyn DataSource>,
+        halignment: &'static str,
+        valignment: &'static str,
+    ) -> Result<(), Error> {
+        let overlay = make("textoverlay")?;
+
+        // Stack widgets sharing a corner below each other
+        let row = self
+            .widgets
+            .iter()
+            .filter(|w| w.halignment == halignment)
+            .count();
+
+        overlay.set_property_from_str("halignment", halignment);
+        overlay.set_property_from_str("valignment", valignment);
+        overlay.set_property("font-desc", &"Sans, 18")?;
+        overlay.set_property("shaded-background", &true)?;
+        overlay.set_property("deltay", &(row as i32 * 36))?;
+
+        self.overlays.push(overlay.clone());
+        self.widgets.push(Widget {
+            overlay,
+            source,
+            halignment,
+        });
+        Ok(())
+    }
+
+    /// The elements to link between the converter and the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        self.overlays.iter().collect()
+    }
+
+    /// Fade the OSD out if it is visible, in otherwise.
+    pub fn toggle(&mut self) {
+        let target = if self.opacity.target() > 0.0 { 0.0 } else { 1.0 };
+        self.opacity.animate_to(target);
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.opacity.is_animating()
+    }
+
+    /// Advance the fade animation, returns whether more frames are needed.
+    pub fn tick(&mut self) -> bool {
+        if self.opacity.tick() {
+            self.apply_opacity();
+        }
+        self.opacity.is_animating()
+    }
+
+    fn apply_opacity(&self) {
+        let opacity = self.opacity.value();
+        let alpha = (opacity * 255.0).round() as u32;
+
+        for overlay in &self.overlays {
+            let _ = overlay.set_property("color", &(alpha << 24 | 0x00ff_ffff));
+            let _ = overlay.set_property("outline-color", &(alpha << 24));
+            let _ = overlay.set_property("shading-value", &((opacity * 80.0) as u32));
+            let _ = overlay.set_property("silent", &(alpha == 0));
+        }
+    }
+
+    /// Start polling the data sources, each in its own thread.
+    pub fn start(&mut self) {
+        for Widget { overlay, source, .. } in self.widgets.drain(..) {
+            let (tx, rx) = mpsc::channel();
+            self.stop.push(tx);
+
+            thread::spawn(move || run_widget(overlay, source, rx));
+        }
+    }
+}
+
+fn run_widget(overlay: gst::Element, mut source: Box<dyn DataSource>, stop: mpsc::Receiver<()>) {
+    loop {
+        match source.poll() {
+            Ok(text) => {
+                let _ = overlay.set_property("text", &text);
+            }
+            Err(err) => warn!("OSD source failed: {}", err),
+        }
+
+        match stop.recv_timeout(source.interval()) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            _ => break,
+        }
+    }
+}
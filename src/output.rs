@@ -0,0 +1,58 @@
+//! The output a surface is shown on.
+//!
+//! sctk follows `wl_surface.enter` and `leave` for the surfaces it creates.
+//! A surface can span several outputs, the first one it entered is taken as
+//! the one it is on.
+
+use sctk::output::{with_output_info, OutputInfo};
+use sctk::reexports::client::protocol::{wl_output, wl_surface};
+
+/// The output `surface` is on, if it is shown at all.
+pub fn surface_output(surface: &wl_surface::WlSurface) -> Option<wl_output::WlOutput> {
+    sctk::get_surface_outputs(surface).into_iter().next()
+}
+
+/// What the compositor advertised about `output`.
+pub fn output_info(output: &wl_output::WlOutput) -> Option<OutputInfo> {
+    with_output_info(output, OutputInfo::clone)
+}
+
+/// The refresh rate of the current mode of an output, in mHz.
+pub fn refresh_rate(info: &OutputInfo) -> Option<i32> {
+    info.modes
+        .iter()
+        .find(|mode| mode.is_current)
+        .map(|mode| mode.refresh_rate)
+}
+
+/// Follows the output a surface is on.
+pub struct OutputTracker {
+    surface: wl_surface::WlSurface,
+    current: Option<u32>,
+}
+
+impl OutputTracker {
+    pub fn new(surface: &wl_surface::WlSurface) -> Self {
+        OutputTracker {
+            surface: surface.clone(),
+            current: None,
+        }
+    }
+
+    /// The output the surface is on.
+    pub fn output(&self) -> Option<wl_output::WlOutput> {
+        surface_output(&self.surface)
+    }
+
+    /// The output the surface moved to since the last call, if it did. The
+    /// surface leaving all outputs isn't reported, it stays where it was as
+    /// far as we are concerned.
+    pub fn poll(&mut self) -> Option<OutputInfo> {
+        let info = self.output().as_ref().and_then(output_info)?;
+        if self.current == Some(info.id) {
+            return None;
+        }
+        self.current = Some(info.id);
+        Some(info)
+    }
+}
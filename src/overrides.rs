@@ -0,0 +1,63 @@
+//! Properties of elements set from the `[element.FACTORY]` tables of the
+//! configuration file, such as `sync = false` for waylandsink or
+//! `bitrate = 4000` for x264enc.
+//!
+//! They are set on every element of that factory in the pipeline, those it
+//! is built with and those added later on, such as the decoders of playbin,
+//! from their string form as with gst-launch-1.0.
+
+use std::collections::BTreeMap;
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::{debug, warn};
+
+/// The properties to set, by factory.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides(BTreeMap<String, Vec<(String, String)>>);
+
+impl Overrides {
+    pub fn add(&mut self, factory: &str, property: &str, value: String) {
+        self.0
+            .entry(factory.to_owned())
+            .or_default()
+            .push((property.to_owned(), value));
+    }
+
+    fn apply_to(&self, element: &gst::Element) {
+        let factory = match element.get_factory() {
+            Some(factory) => factory.get_name(),
+            None => return,
+        };
+        for (property, value) in self.0.get(factory.as_str()).into_iter().flatten() {
+            if element.find_property(property).is_none() {
+                warn!("{} has no property {}", factory, property);
+                continue;
+            }
+            debug!(element = %element.get_name(), %property, %value, "Overriding");
+            element.set_property_from_str(property, value);
+        }
+    }
+
+    /// Set the properties on the elements of `pipeline`, now and as they
+    /// are added.
+    pub fn apply(self, pipeline: &gst::Pipeline) -> Result<(), Error> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        for element in pipeline.iterate_recurse() {
+            let element = match element {
+                Ok(element) => element,
+                Err(_) => break,
+            };
+            self.apply_to(&element);
+        }
+
+        pipeline.connect("deep-element-added", false, move |values| {
+            let element = values[2].get::<gst::Element>().ok()??;
+            self.apply_to(&element);
+            None
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+//! A second video in a corner of the window, for `--pip-uri`.
+//!
+//! The second source plays in its own pipeline and sink, muted, on a
+//! subsurface of the window placed in a corner above the main video. The
+//! subsurface holds a black buffer of its size, which the sink renders its
+//! own subsurface over. Keys move it from corner to corner, resize it, and
+//! swap the two videos: the second one then fills the window below the main
+//! one, which takes its corner.
+
+use anyhow::Error;
+use gst::prelude::*;
+use gst_video::prelude::*;
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::{wl_compositor, wl_subcompositor, wl_subsurface, wl_surface};
+use sctk::reexports::client::Display;
+use sctk::shm::DoubleMemPool;
+use tracing::warn;
+use wayland_sink_with_video_overlay::canvas::Canvas;
+
+use crate::render_rect::Rect;
+use crate::{make, sink, ThemedFrameExample};
+
+const MARGIN: i32 = 16;
+const BACKGROUND: u32 = 0xFF00_0000;
+/// Sizes of the corner, in fractions of the window.
+const MIN_SIZE: f64 = 0.2;
+const MAX_SIZE: f64 = 0.6;
+const SIZE_STEP: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+}
+
+impl Corner {
+    /// The next corner clockwise.
+    fn next(self) -> Self {
+        match self {
+            Corner::TopLeft => Corner::TopRight,
+            Corner::TopRight => Corner::BottomRight,
+            Corner::BottomRight => Corner::BottomLeft,
+            Corner::BottomLeft => Corner::TopLeft,
+        }
+    }
+}
+
+pub struct PipVideo {
+    surface: wl_surface::WlSurface,
+    subsurface: wl_subsurface::WlSubsurface,
+    pools: DoubleMemPool,
+    pipeline: gst::Pipeline,
+    overlay: gst_video::VideoOverlay,
+    corner: Corner,
+    /// Width and height, in fractions of the window.
+    size: f64,
+    swapped: bool,
+    /// Where the surface is, as last laid out.
+    rect: Option<Rect>,
+}
+
+impl PipVideo {
+    /// Play `uri` on a subsurface of `parent`, with the `sink` factory if
+    /// given.
+    pub fn new(
+        env: &Environment<ThemedFrameExample>,
+        display: &Display,
+        parent: &wl_surface::WlSurface,
+        uri: &str,
+        sink: Option<&str>,
+    ) -> Result<Self, Error> {
+        let surface = env.create_surface().detach();
+        let subcompositor = env.require_global::<wl_subcompositor::WlSubcompositor>();
+        let subsurface = subcompositor.get_subsurface(&surface, parent).detach();
+        // Pointer events go to the window below, as with the video
+        let region = env.require_global::<wl_compositor::WlCompositor>().create_region();
+        surface.set_input_region(Some(&region));
+        region.destroy();
+        let pools = env.create_double_pool(|_| {})?;
+
+        let playbin = make("playbin")?;
+        let sink = sink::create(sink)?;
+        playbin.set_property("uri", &uri)?;
+        playbin.set_property("video-sink", &sink)?;
+        // Only the main video is heard
+        playbin.set_property("mute", &true)?;
+        let overlay = sink::set_window(&sink, display, &surface)?;
+        let pipeline = playbin.downcast::<gst::Pipeline>().unwrap();
+        pipeline.set_state(gst::State::Playing)?;
+
+        Ok(PipVideo {
+            surface,
+            subsurface,
+            pools,
+            pipeline,
+            overlay,
+            corner: Corner::BottomRight,
+            size: 0.3,
+            swapped: false,
+            rect: None,
+        })
+    }
+
+    pub fn is_swapped(&self) -> bool {
+        self.swapped
+    }
+
+    /// The corner of the smaller video, in a window of `dimensions`.
+    pub fn corner(&self, (width, height): (u32, u32)) -> Rect {
+        let (width, height) = (width as i32, height as i32);
+        let w = (width as f64 * self.size) as i32;
+        let h = (height as f64 * self.size) as i32;
+        let (left, top) = (MARGIN, MARGIN);
+        let (right, bottom) = (width - w - MARGIN, height - h - MARGIN);
+        match self.corner {
+            Corner::TopLeft => Rect::new(left, top, w, h),
+            Corner::TopRight => Rect::new(right, top, w, h),
+            Corner::BottomRight => Rect::new(right, bottom, w, h),
+            Corner::BottomLeft => Rect::new(left, bottom, w, h),
+        }
+    }
+
+    pub fn next_corner(&mut self) {
+        self.corner = self.corner.next();
+    }
+
+    /// Grow or shrink the corner by a step.
+    pub fn resize(&mut self, grow: bool) {
+        let step = if grow { SIZE_STEP } else { -SIZE_STEP };
+        self.size = (self.size + step).clamp(MIN_SIZE, MAX_SIZE);
+    }
+
+    /// Put the second video in the corner, or behind the main one.
+    pub fn swap(&mut self) {
+        self.swapped = !self.swapped;
+    }
+
+    /// Place the second video in a window of `dimensions`, above or below
+    /// the main video and the other subsurfaces but `top`, the controls.
+    /// Applied with the next commit of the window surface.
+    pub fn layout(
+        &mut self,
+        dimensions: (u32, u32),
+        parent: &wl_surface::WlSurface,
+        top: &wl_surface::WlSurface,
+    ) {
+        let rect = if self.swapped {
+            self.subsurface.place_above(parent);
+            Rect::new(0, 0, dimensions.0 as i32, dimensions.1 as i32)
+        } else {
+            self.subsurface.place_below(top);
+            self.corner(dimensions)
+        };
+        self.subsurface.set_position(rect.x, rect.y);
+        let size = |rect: Rect| (rect.width, rect.height);
+        let resized = self.rect.map(size) != Some(size(rect));
+        self.rect = Some(rect);
+        if !resized {
+            return;
+        }
+
+        // The black under the video, of the size of the surface
+        let pool = match self.pools.pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        match Canvas::new(pool, rect.width.max(1) as u32, rect.height.max(1) as u32) {
+            Ok(mut canvas) => {
+                canvas.fill(BACKGROUND);
+                canvas.attach(&self.surface);
+            }
+            Err(err) => warn!("Failed to draw under the second video: {}", err),
+        }
+        if let Err(err) = self.overlay.set_render_rectangle(0, 0, rect.width, rect.height) {
+            warn!("Failed to place the second video: {}", err);
+        }
+    }
+
+    /// Handle the messages of the second pipeline, looping it at its end.
+    pub fn update(&self) {
+        let bus = match self.pipeline.get_bus() {
+            Some(bus) => bus,
+            None => return,
+        };
+        while let Some(msg) = bus.pop() {
+            match msg.view() {
+                gst::MessageView::Eos(_) => {
+                    let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT;
+                    let start = gst::ClockTime::from_seconds(0);
+                    if let Err(err) = self.pipeline.seek_simple(flags, start) {
+                        warn!("Failed to loop the second video: {}", err);
+                    }
+                }
+                gst::MessageView::Error(err) => {
+                    warn!("Second video: {}", err.get_error());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for PipVideo {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+        self.subsurface.destroy();
+        self.surface.destroy();
+    }
+}
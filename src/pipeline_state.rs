@@ -0,0 +1,134 @@
+//! What the bus tells about the pipeline as a whole: its state, the title
+//! and artist of what it plays, and buffering.
+//!
+//! The state follows the `StateChanged` messages of the pipeline itself,
+//! rather than being queried. Network sources post `Buffering` messages, on
+//! which a non-live pipeline is paused until its buffers are full again and
+//! then resumes, if it was playing. A lost clock, such as that of an audio
+//! device going away, is replaced by pausing and playing again, and a change
+//! of latency by recalculating it.
+
+use gst::prelude::*;
+use tracing::{debug, info, warn};
+
+use crate::sink::SurfaceGuard;
+
+#[derive(Debug, Default)]
+pub struct PipelineState {
+    playing: bool,
+    title: Option<String>,
+    artist: Option<String>,
+    /// Percent of the buffering in progress.
+    buffering: Option<i32>,
+    /// Play again once buffering is done.
+    resume: bool,
+}
+
+impl PipelineState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow `msg`, switching `pipeline` through `surface_guard`.
+    pub fn on_message(
+        &mut self,
+        msg: &gst::Message,
+        pipeline: &gst::Pipeline,
+        surface_guard: &SurfaceGuard,
+    ) {
+        use gst::MessageView;
+
+        match msg.view() {
+            MessageView::StateChanged(state) => {
+                let from_pipeline = msg
+                    .get_src()
+                    .map_or(false, |src| &src == pipeline.upcast_ref::<gst::Object>());
+                if from_pipeline {
+                    self.playing = state.get_current() == gst::State::Playing;
+                }
+            }
+            // A new stream, of a playlist entry or a swapped source, has
+            // tags of its own
+            MessageView::StreamStart(_) => {
+                self.title = None;
+                self.artist = None;
+            }
+            MessageView::Tag(tag) => {
+                let tags = tag.get_tags();
+                // Streams of the same media tag the same, keep the first
+                if self.title.is_none() {
+                    self.title = tags
+                        .get::<gst::tags::Title>()
+                        .and_then(|title| title.get().map(ToOwned::to_owned));
+                }
+                if self.artist.is_none() {
+                    self.artist = tags
+                        .get::<gst::tags::Artist>()
+                        .and_then(|artist| artist.get().map(ToOwned::to_owned));
+                }
+            }
+            MessageView::Buffering(buffering) => {
+                if is_live(pipeline) {
+                    return;
+                }
+                let percent = buffering.get_percent();
+                match (percent < 100, self.buffering.is_some()) {
+                    (true, false) => {
+                        debug!(percent, "Buffering");
+                        self.resume = self.playing;
+                        set_state(pipeline, surface_guard, gst::State::Paused);
+                    }
+                    (false, true) => {
+                        debug!("Buffering done");
+                        if std::mem::replace(&mut self.resume, false) {
+                            set_state(pipeline, surface_guard, gst::State::Playing);
+                        }
+                    }
+                    _ => {}
+                }
+                self.buffering = if percent < 100 { Some(percent) } else { None };
+            }
+            MessageView::ClockLost(_) => {
+                info!("Lost the clock, selecting a new one");
+                set_state(pipeline, surface_guard, gst::State::Paused);
+                set_state(pipeline, surface_guard, gst::State::Playing);
+            }
+            MessageView::Latency(_) => {
+                if let Err(err) = pipeline.recalculate_latency() {
+                    warn!("Failed to recalculate the latency: {}", err);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// `artist – title`, or either of them, from the tags.
+    pub fn media_title(&self) -> Option<String> {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => Some(format!("{} \u{2013} {}", artist, title)),
+            (None, Some(title)) => Some(title.clone()),
+            (Some(artist), None) => Some(artist.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Percent of the buffering in progress, if any.
+    pub fn buffering(&self) -> Option<i32> {
+        self.buffering
+    }
+}
+
+fn is_live(pipeline: &gst::Pipeline) -> bool {
+    let mut query = gst::query::Latency::new();
+    pipeline.query(&mut query) && query.get_result().0
+}
+
+fn set_state(pipeline: &gst::Pipeline, surface_guard: &SurfaceGuard, state: gst::State) {
+    if let Err(err) = surface_guard.set_state(pipeline, state) {
+        warn!("Failed to switch to {:?}: {}", state, err);
+    }
+}
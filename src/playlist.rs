@@ -0,0 +1,184 @@
+//! Playing several files or URIs in turn.
+//!
+//! playbin asks for what comes next with `about-to-finish`, shortly before
+//! the end of the current entry, and switches to it without a gap when its
+//! URI is set from there. The switch shows up on the bus as a stream start.
+//! Skipping, and entries that end without asking, take a trip through the
+//! ready state to re-target playbin.
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::{info, warn};
+
+use crate::sink::SurfaceGuard;
+
+/// The entries of the M3U playlist at `path`, relative paths being taken
+/// from its directory.
+pub fn read_m3u(path: &Path) -> Result<Vec<String>, Error> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if line.contains("://") || Path::new(line).is_absolute() {
+                line.to_owned()
+            } else {
+                dir.join(line).to_string_lossy().into_owned()
+            }
+        })
+        .collect())
+}
+
+/// Whether `location` is a local M3U playlist, from its extension. Remote
+/// ones are left to playbin, as they are HLS streams.
+pub fn is_m3u(location: &str) -> bool {
+    let location = location.to_ascii_lowercase();
+    !location.contains("://") && (location.ends_with(".m3u") || location.ends_with(".m3u8"))
+}
+
+/// Have `playbin` play `uri` from the start, unless `surface_guard` refuses
+/// to.
+pub fn retarget(
+    playbin: &gst::Pipeline,
+    uri: &str,
+    surface_guard: &SurfaceGuard,
+) -> Result<(), Error> {
+    playbin.set_state(gst::State::Ready)?;
+    playbin.set_property("uri", &uri)?;
+    surface_guard.set_state(playbin, gst::State::Playing)?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Position {
+    current: usize,
+    /// The entry set from `about-to-finish`, until it starts.
+    queued: Option<usize>,
+}
+
+pub struct Playlist {
+    uris: Arc<Vec<String>>,
+    position: Arc<Mutex<Position>>,
+}
+
+impl Playlist {
+    /// Play `uris` in turn on `playbin`, which starts with the first one.
+    pub fn new(playbin: &gst::Pipeline, uris: Vec<String>) -> Result<Self, Error> {
+        let uris = Arc::new(uris);
+        let position = Arc::new(Mutex::new(Position::default()));
+
+        let (next_uris, next_position) = (uris.clone(), position.clone());
+        playbin.connect("about-to-finish", false, move |values| {
+            let playbin = values[0].get::<gst::Element>().ok()??;
+            let mut position = next_position.lock().unwrap();
+            let next = position.current + 1;
+            if let Some(uri) = next_uris.get(next) {
+                position.queued = Some(next);
+                let _ = playbin.set_property("uri", uri);
+            }
+            None
+        })?;
+
+        Ok(Playlist { uris, position })
+    }
+
+    /// Follow the gapless switches, and go on with the next entry when one
+    /// ends without it.
+    pub fn on_message(
+        &self,
+        msg: &gst::Message,
+        pipeline: &gst::Pipeline,
+        surface_guard: &SurfaceGuard,
+    ) {
+        use gst::MessageView;
+
+        match msg.view() {
+            MessageView::StreamStart(_) => {
+                let mut position = self.position.lock().unwrap();
+                if let Some(queued) = position.queued.take() {
+                    position.current = queued;
+                    drop(position);
+                    self.announce(queued);
+                }
+            }
+            MessageView::Eos(_) => {
+                let current = self.position.lock().unwrap().current;
+                if current + 1 < self.uris.len() {
+                    self.play(pipeline, current + 1, surface_guard);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn next(&self, pipeline: &gst::Pipeline, surface_guard: &SurfaceGuard) {
+        let current = self.position.lock().unwrap().current;
+        if current + 1 < self.uris.len() {
+            self.play(pipeline, current + 1, surface_guard);
+        }
+    }
+
+    pub fn previous(&self, pipeline: &gst::Pipeline, surface_guard: &SurfaceGuard) {
+        let current = self.position.lock().unwrap().current;
+        if current > 0 {
+            self.play(pipeline, current - 1, surface_guard);
+        }
+    }
+
+    /// Re-target `pipeline` to the entry at `index`.
+    fn play(&self, pipeline: &gst::Pipeline, index: usize, surface_guard: &SurfaceGuard) {
+        {
+            let mut position = self.position.lock().unwrap();
+            position.current = index;
+            position.queued = None;
+        }
+
+        match retarget(pipeline, &self.uris[index], surface_guard) {
+            Ok(()) => self.announce(index),
+            Err(err) => warn!("Failed to play {}: {}", self.uris[index], err),
+        }
+    }
+
+    fn announce(&self, index: usize) {
+        info!("Playing {}/{}: {}", index + 1, self.uris.len(), self.uris[index]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_m3u_entries() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let text = "#EXTM3U\n\
+                    #EXTINF:10,First\n\
+                    first.mp4\n\
+                    \n  /videos/second.mp4  \n\
+                    https://example.com/third.m3u8\n";
+        fs::write(file.path(), text).unwrap();
+
+        let first = file.path().parent().unwrap().join("first.mp4");
+        assert_eq!(
+            read_m3u(file.path()).unwrap(),
+            [
+                first.to_str().unwrap(),
+                "/videos/second.mp4",
+                "https://example.com/third.m3u8",
+            ]
+        );
+    }
+
+    #[test]
+    fn tells_local_playlists() {
+        assert!(is_m3u("list.m3u"));
+        assert!(is_m3u("/videos/LIST.M3U8"));
+        assert!(!is_m3u("https://example.com/live.m3u8"));
+        assert!(!is_m3u("video.mp4"));
+    }
+}
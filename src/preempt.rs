@@ -0,0 +1,164 @@
+//! Priority based preemption of the displayed content.
+//!
+//! A stream requested with a higher priority than what is shown, such as a
+//! doorbell camera, takes over the window: the interrupted content is paused
+//! and shrunk to a corner until the stream is released, or ends, then
+//! restored to the state it was in. The content of the window itself has
+//! priority 0.
+
+use anyhow::Error;
+use gst::prelude::*;
+use gst_video::prelude::*;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::reexports::client::Display;
+use tracing::{info, warn};
+
+use crate::render_rect::Rect;
+use crate::sink::{self, SurfaceGuard};
+use crate::make;
+
+struct Interruption {
+    priority: u32,
+    pipeline: gst::Pipeline,
+    overlay: gst_video::VideoOverlay,
+    /// The state of what it interrupted, restored on release.
+    resume: gst::State,
+}
+
+impl Drop for Interruption {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
+
+#[derive(Default)]
+pub struct Preemption {
+    // Interruptions by increasing priority, the last one is shown
+    stack: Vec<Interruption>,
+    /// The sink the streams are shown with, as for the content.
+    sink: Option<String>,
+}
+
+impl Preemption {
+    pub fn new(sink: Option<String>) -> Self {
+        Preemption {
+            stack: Vec::new(),
+            sink,
+        }
+    }
+
+    /// Whether the content of the window is interrupted.
+    pub fn is_active(&self) -> bool {
+        !self.stack.is_empty()
+    }
+
+    fn shown<'a>(&'a self, content: &'a gst::Pipeline) -> &'a gst::Pipeline {
+        self.stack
+            .last()
+            .map_or(content, |interruption| &interruption.pipeline)
+    }
+
+    /// Play `uri` over `content` and the previous interruptions if `priority`
+    /// is higher than theirs, returns whether it did.
+    pub fn preempt(
+        &mut self,
+        uri: &str,
+        priority: u32,
+        display: &Display,
+        surface: &wl_surface::WlSurface,
+        content: &gst::Pipeline,
+        rect: Rect,
+    ) -> Result<bool, Error> {
+        let current = self.stack.last().map_or(0, |interruption| interruption.priority);
+        if priority <= current {
+            return Ok(false);
+        }
+
+        let playbin = make("playbin")?;
+        let sink = sink::create(self.sink.as_deref())?;
+        playbin.set_property("uri", &uri)?;
+        playbin.set_property("video-sink", &sink)?;
+        let overlay = sink::set_window(&sink, display, surface)?;
+        overlay.set_render_rectangle(rect.x, rect.y, rect.width, rect.height)?;
+
+        let shown = self.shown(content);
+        let (_, current, pending) = shown.get_state(gst::ClockTime::from_seconds(0));
+        let resume = match pending {
+            gst::State::VoidPending => current,
+            pending => pending,
+        };
+        shown.set_state(gst::State::Paused)?;
+
+        let pipeline = playbin.downcast::<gst::Pipeline>().unwrap();
+        pipeline.set_state(gst::State::Playing)?;
+        self.stack.push(Interruption {
+            priority,
+            pipeline,
+            overlay,
+            resume,
+        });
+
+        Ok(true)
+    }
+
+    /// Stop the last interruption and bring what it interrupted back to
+    /// the state it was in, the content through `surface_guard`.
+    pub fn release(
+        &mut self,
+        content: &gst::Pipeline,
+        surface_guard: &SurfaceGuard,
+    ) -> Result<(), Error> {
+        let resume = match self.stack.pop() {
+            Some(interruption) => interruption.resume,
+            None => return Ok(()),
+        };
+        match self.stack.last() {
+            Some(interruption) => {
+                interruption.pipeline.set_state(resume)?;
+            }
+            None => {
+                surface_guard.set_state(content, resume)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Release the last interruption once it ends or fails, returns whether
+    /// it did.
+    pub fn update(&mut self, content: &gst::Pipeline, surface_guard: &SurfaceGuard) -> bool {
+        let bus = match self.stack.last().and_then(|interruption| interruption.pipeline.get_bus()) {
+            Some(bus) => bus,
+            None => return false,
+        };
+        let mut ended = false;
+        while let Some(msg) = bus.pop() {
+            match msg.view() {
+                gst::MessageView::Eos(_) => {
+                    info!("The interruption ended");
+                    ended = true;
+                }
+                gst::MessageView::Error(err) => {
+                    warn!("The interruption failed: {}", err.get_error());
+                    ended = true;
+                }
+                _ => {}
+            }
+        }
+        if !ended {
+            return false;
+        }
+        if let Err(err) = self.release(content, surface_guard) {
+            warn!("Failed to resume playback: {}", err);
+        }
+        true
+    }
+
+    /// Place the interruptions, following the size of the window.
+    pub fn set_rect(&self, rect: Rect) {
+        for interruption in &self.stack {
+            let _ = interruption
+                .overlay
+                .set_render_rectangle(rect.x, rect.y, rect.width, rect.height);
+        }
+    }
+}
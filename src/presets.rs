@@ -0,0 +1,161 @@
+//! Named pipeline templates, for `--preset`.
+//!
+//! A preset is a gst-launch-1.0 description with `{name}` parameters, run as
+//! with `--pipeline`. Its parameters have defaults, which `--preset
+//! NAME:KEY=VALUE,...` overrides. Tables of `[presets]` in the configuration
+//! file add presets, or replace the built-in ones:
+//!
+//! ```toml
+//! [presets.hdmi-capture]
+//! pipeline = "v4l2src device={device} ! videoconvert ! {sink}"
+//! device = "/dev/video2"
+//! ```
+//!
+//! `{sink}` is the video sink, waylandsink unless the preset or `--sink`
+//! says otherwise.
+
+use std::collections::BTreeMap;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown preset {}, expected one of {}", name, known)]
+pub struct UnknownPreset {
+    name: String,
+    known: String,
+}
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Preset {} needs a value for {}", preset, param)]
+pub struct MissingParam {
+    preset: String,
+    param: String,
+}
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid preset parameter {}, expected KEY=VALUE", _0)]
+pub struct InvalidParam(#[error(not(source))] String);
+
+/// A pipeline template and the values of its parameters.
+#[derive(Debug, Clone)]
+pub struct Preset {
+    name: String,
+    template: String,
+    params: BTreeMap<String, String>,
+}
+
+impl Preset {
+    pub fn new(name: &str, template: &str) -> Self {
+        Preset {
+            name: name.to_owned(),
+            template: template.to_owned(),
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Set parameter `key` to `value`.
+    pub fn param(mut self, key: &str, value: &str) -> Self {
+        self.params.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    /// The description, with the parameters replaced by their values.
+    pub fn build(&self) -> Result<String, Error> {
+        let mut description = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            description.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| InvalidParam(rest[start..].to_owned()))?;
+            let key = &rest[start + 1..end];
+            let value = self.params.get(key).ok_or_else(|| MissingParam {
+                preset: self.name.clone(),
+                param: key.to_owned(),
+            })?;
+            description.push_str(value);
+            rest = &rest[end + 1..];
+        }
+        description.push_str(rest);
+        Ok(description)
+    }
+}
+
+/// The presets by name.
+#[derive(Debug, Clone)]
+pub struct Presets(BTreeMap<String, Preset>);
+
+impl Default for Presets {
+    fn default() -> Self {
+        let mut presets = Presets(BTreeMap::new());
+        // Keep only the last frame, and show it as soon as it comes
+        presets.add(
+            Preset::new(
+                "camera-lowlatency",
+                "v4l2src device={device} \
+                 ! video/x-raw,width={width},height={height},framerate={fps} \
+                 ! queue max-size-buffers=1 leaky=downstream ! videoconvert \
+                 ! {sink} sync=false",
+            )
+            .param("device", "/dev/video0")
+            .param("width", "1280")
+            .param("height", "720")
+            .param("fps", "30/1"),
+        );
+        // Buffer ahead and scale with the best filter
+        presets.add(Preset::new(
+            "file-quality",
+            "filesrc location={location} ! decodebin ! queue max-size-time=2000000000 \
+             ! videoconvert ! videoscale method=lanczos ! {sink}",
+        ));
+        presets.add(
+            Preset::new(
+                "rtsp-monitor",
+                "rtspsrc location={uri} latency={latency} ! decodebin ! videoconvert \
+                 ! {sink} sync=false",
+            )
+            .param("latency", "200"),
+        );
+        presets.add(
+            Preset::new(
+                "test-4k",
+                "videotestsrc pattern={pattern} is-live=true \
+                 ! video/x-raw,width=3840,height=2160,framerate={fps} ! videoconvert ! {sink}",
+            )
+            .param("pattern", "smpte")
+            .param("fps", "60/1"),
+        );
+        presets
+    }
+}
+
+impl Presets {
+    /// Add `preset`, replacing the one of the same name.
+    pub fn add(&mut self, preset: Preset) {
+        self.0.insert(preset.name.clone(), preset);
+    }
+
+    /// The description of the preset of `spec`, `NAME[:KEY=VALUE,...]`,
+    /// rendering on `sink`.
+    pub fn describe(&self, spec: &str, sink: Option<&str>) -> Result<String, Error> {
+        let (name, params) = spec.split_once(':').unwrap_or((spec, ""));
+        let mut preset = self.0.get(name).cloned().ok_or_else(|| UnknownPreset {
+            name: name.to_owned(),
+            known: self.0.keys().cloned().collect::<Vec<_>>().join(", "),
+        })?;
+        if let Some(sink) = sink {
+            preset = preset.param("sink", sink);
+        } else if !preset.params.contains_key("sink") {
+            preset = preset.param("sink", "waylandsink");
+        }
+        for param in params.split(',').filter(|param| !param.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| InvalidParam(param.to_owned()))?;
+            preset = preset.param(key, value);
+        }
+        preset.build()
+    }
+}
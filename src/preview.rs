@@ -0,0 +1,103 @@
+//! A low frame rate MJPEG preview of the video, for the `/preview.mjpeg` of
+//! the HTTP endpoint.
+//!
+//! A branch off the tee encodes 2 frames per second, scaled down to 640
+//! pixels wide, as JPEG into an appsink. The last JPEG is kept, and the
+//! clients waiting for the next one woken when it comes. The branch leaks
+//! rather than holding up the displayed video when the encoder lags.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::make;
+
+const FPS: i32 = 2;
+const WIDTH: i32 = 640;
+
+#[derive(Default)]
+struct Latest {
+    jpeg: Option<Arc<Vec<u8>>>,
+    /// Number of JPEGs encoded so far.
+    sequence: u64,
+}
+
+/// The last JPEG of the preview, shared with the HTTP clients.
+#[derive(Clone, Default)]
+pub struct Preview(Arc<(Mutex<Latest>, Condvar)>);
+
+impl Preview {
+    /// Add the preview branch to `tee` of `bin`.
+    pub fn add_branch(bin: &gst::Bin, tee: &gst::Element) -> Result<Self, Error> {
+        let queue = make("queue")?;
+        let videorate = make("videorate")?;
+        let videoscale = make("videoscale")?;
+        let videoconvert = make("videoconvert")?;
+        let capsfilter = make("capsfilter")?;
+        let jpegenc = make("jpegenc")?;
+        let appsink = make("appsink")?;
+
+        queue.set_property_from_str("leaky", "downstream");
+        queue.set_property("max-size-buffers", &1u32)?;
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("framerate", &gst::Fraction::new(FPS, 1))
+            .field("width", &WIDTH)
+            .build();
+        capsfilter.set_property("caps", &caps)?;
+        appsink.set_property("sync", &false)?;
+        appsink.set_property("max-buffers", &1u32)?;
+        appsink.set_property("drop", &true)?;
+
+        let preview = Preview::default();
+        let latest = preview.clone();
+        let appsink = appsink.dynamic_cast::<gst_app::AppSink>().unwrap();
+        appsink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |appsink| {
+                    let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let buffer = sample.get_buffer().ok_or(gst::FlowError::Error)?;
+                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+                    latest.set(map.as_slice().to_vec());
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        let branch = [
+            &queue,
+            &videorate,
+            &videoscale,
+            &videoconvert,
+            &capsfilter,
+            &jpegenc,
+            appsink.upcast_ref(),
+        ];
+        bin.add_many(&branch)?;
+        gst::Element::link_many(&branch)?;
+        tee.link(&queue)?;
+
+        Ok(preview)
+    }
+
+    fn set(&self, jpeg: Vec<u8>) {
+        let (latest, changed) = &*self.0;
+        let mut latest = latest.lock().unwrap();
+        latest.jpeg = Some(Arc::new(jpeg));
+        latest.sequence += 1;
+        changed.notify_all();
+    }
+
+    /// The first JPEG encoded after the `after`th, with its number, waiting
+    /// up to `timeout` for it.
+    pub fn next(&self, after: u64, timeout: Duration) -> Option<(u64, Arc<Vec<u8>>)> {
+        let (latest, changed) = &*self.0;
+        let latest = latest.lock().unwrap();
+        let (latest, _) = changed
+            .wait_timeout_while(latest, timeout, |latest| latest.sequence <= after)
+            .unwrap();
+        let jpeg = latest.jpeg.clone().filter(|_| latest.sequence > after)?;
+        Some((latest.sequence, jpeg))
+    }
+}
@@ -0,0 +1,162 @@
+//! The frames of the synthetic video, in the format their producer declares.
+//!
+//! The appsrc is given the caps of that format, so that waylandsink gets
+//! NV12 or I420 straight from it, when the compositor takes them, instead of
+//! the BGRx converted by videoconvert. Planar frames are filled plane by
+//! plane, through the strides and offsets of the frame, as GStreamer pads
+//! their lines and planes as it likes.
+
+use std::str::FromStr;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use wayland_sink_with_video_overlay::{convert, fill};
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown source format {}, expected BGRx, RGBA, NV12 or I420", _0)]
+pub struct UnknownFormat(#[error(not(source))] String);
+
+/// The formats the synthetic video can be produced in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Bgrx,
+    Rgba,
+    Nv12,
+    I420,
+}
+
+impl Format {
+    pub fn video_format(self) -> gst_video::VideoFormat {
+        match self {
+            Format::Bgrx => gst_video::VideoFormat::Bgrx,
+            Format::Rgba => gst_video::VideoFormat::Rgba,
+            Format::Nv12 => gst_video::VideoFormat::Nv12,
+            Format::I420 => gst_video::VideoFormat::I420,
+        }
+    }
+
+    fn is_yuv(self) -> bool {
+        matches!(self, Format::Nv12 | Format::I420)
+    }
+}
+
+impl FromStr for Format {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "bgrx" => Ok(Format::Bgrx),
+            "rgba" => Ok(Format::Rgba),
+            "nv12" => Ok(Format::Nv12),
+            "i420" => Ok(Format::I420),
+            _ => Err(UnknownFormat(s.to_owned())),
+        }
+    }
+}
+
+/// Draws the frames of the appsrc.
+pub trait FrameProducer: Send {
+    /// The format of the frames, which the caps are made from.
+    fn format(&self) -> Format;
+
+    /// Draw frame `index` into `frame`, mapped with the video info of
+    /// `format`.
+    fn fill(
+        &mut self,
+        index: u64,
+        frame: &mut gst_video::VideoFrameRef<&mut gst::BufferRef>,
+    ) -> Result<(), Error>;
+}
+
+/// The video info of the frames of `producer`.
+pub fn video_info(
+    producer: &dyn FrameProducer,
+    width: u32,
+    height: u32,
+    fps: gst::Fraction,
+) -> Result<gst_video::VideoInfo, Error> {
+    let format = producer.format();
+    let builder = gst_video::VideoInfo::builder(format.video_format(), width, height).fps(fps);
+    // The colors are converted as `convert` does
+    let colorimetry = gst_video::VideoColorimetry::from_str("bt709")?;
+    let info = if format.is_yuv() {
+        builder.colorimetry(&colorimetry).build()?
+    } else {
+        builder.build()?
+    };
+    Ok(info)
+}
+
+/// Set the first `width` bytes of the first `lines` lines of `plane`,
+/// `stride` bytes apart, to `value`.
+fn fill_plane(plane: &mut [u8], stride: usize, width: usize, lines: usize, value: u8) {
+    for line in plane.chunks_mut(stride).take(lines) {
+        for byte in &mut line[..width] {
+            *byte = value;
+        }
+    }
+}
+
+/// Flashing solid colors, changing from frame to frame.
+pub struct ColorCycle {
+    format: Format,
+}
+
+impl ColorCycle {
+    pub fn new(format: Format) -> Self {
+        ColorCycle { format }
+    }
+
+    fn bgrx(index: u64) -> [u8; 4] {
+        let r = if index % 2 == 0 { 0 } else { 255 };
+        let g = if index % 3 == 0 { 0 } else { 255 };
+        let b = if index % 5 == 0 { 0 } else { 255 };
+        [b, g, r, 0]
+    }
+}
+
+impl FrameProducer for ColorCycle {
+    fn format(&self) -> Format {
+        self.format
+    }
+
+    fn fill(
+        &mut self,
+        index: u64,
+        frame: &mut gst_video::VideoFrameRef<&mut gst::BufferRef>,
+    ) -> Result<(), Error> {
+        let width = frame.width() as usize;
+        let height = frame.height() as usize;
+        let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+        let stride = |plane: usize| frame.plane_stride()[plane] as usize;
+        let (stride0, stride1) = (stride(0), stride(1));
+        let stride2 = if self.format == Format::I420 { stride(2) } else { 0 };
+
+        let bgrx = Self::bgrx(index);
+        let (y, u, v) = convert::bgrx_to_yuv(bgrx);
+        match self.format {
+            Format::Bgrx => {
+                fill::fill_bgrx(frame.plane_data_mut(0)?, stride0, width, height, bgrx);
+            }
+            Format::Rgba => {
+                let [b, g, r, _] = bgrx;
+                fill::fill_bgrx(frame.plane_data_mut(0)?, stride0, width, height, [r, g, b, 255]);
+            }
+            Format::Nv12 => {
+                fill_plane(frame.plane_data_mut(0)?, stride0, width, height, y);
+                for line in frame.plane_data_mut(1)?.chunks_mut(stride1).take(chroma_height) {
+                    for uv in line[..2 * chroma_width].chunks_exact_mut(2) {
+                        uv[0] = u;
+                        uv[1] = v;
+                    }
+                }
+            }
+            Format::I420 => {
+                fill_plane(frame.plane_data_mut(0)?, stride0, width, height, y);
+                fill_plane(frame.plane_data_mut(1)?, stride1, chroma_width, chroma_height, u);
+                fill_plane(frame.plane_data_mut(2)?, stride2, chroma_width, chroma_height, v);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+//! Which optional Wayland protocols a deployment requires.
+//!
+//! The application runs without any of these, in a degraded way:
+//!
+//! - without `wp_viewporter`, there is no background gradient and fractional
+//!   scales are rounded up,
+//! - without `zwp_linux_dmabuf_v1`, the sink copies every frame to shared
+//!   memory,
+//! - without `zwp_idle_inhibit_manager_v1`, the screen can blank during
+//!   playback,
+//! - without `zxdg_decoration_manager_v1`, the window frame is drawn by the
+//!   application.
+//!
+//! The `[protocols]` table of the configuration file marks each of them
+//! `required` or `optional`, the default. `check` reports what the
+//! compositor offers, and fails when a required one is missing, so that a
+//! kiosk or a signage player doesn't start degraded unnoticed.
+
+use std::str::FromStr;
+
+use derive_more::{Display, Error};
+use sctk::environment::Environment;
+use tracing::{error, info, warn};
+use wayland_sink_with_video_overlay::capabilities::interfaces;
+
+use crate::ThemedFrameExample;
+
+/// The protocols the policy is about, among those of `Capabilities`: the
+/// name in the configuration file, the interface, and what goes without it.
+const PROTOCOLS: [(&str, &str, &str); 4] = [
+    ("viewporter", interfaces::VIEWPORTER, "no background, fractional scales rounded up"),
+    ("dmabuf", interfaces::DMABUF, "frames copied to shared memory"),
+    ("idle-inhibit", interfaces::IDLE_INHIBIT, "the screen can blank during playback"),
+    ("decoration", interfaces::DECORATION, "window frame drawn client-side"),
+];
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown protocol {}, expected viewporter, dmabuf, idle-inhibit or decoration", _0)]
+pub struct UnknownProtocol(#[error(not(source))] String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown protocol requirement {}, expected required or optional", _0)]
+pub struct UnknownRequirement(#[error(not(source))] String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "The compositor lacks the required {}", _0)]
+pub struct MissingProtocols(#[error(not(source))] String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Requirement {
+    Required,
+    Optional,
+}
+
+impl FromStr for Requirement {
+    type Err = UnknownRequirement;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "required" => Ok(Requirement::Required),
+            "optional" => Ok(Requirement::Optional),
+            _ => Err(UnknownRequirement(s.to_owned())),
+        }
+    }
+}
+
+/// The `[protocols]` table of the configuration file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    /// The interfaces of the required protocols.
+    required: Vec<&'static str>,
+}
+
+impl Policy {
+    /// Set the requirement of the protocol named `name` in the configuration
+    /// file.
+    pub fn set(&mut self, name: &str, requirement: Requirement) -> Result<(), UnknownProtocol> {
+        let interface = PROTOCOLS
+            .iter()
+            .find(|(protocol, _, _)| *protocol == name)
+            .map(|(_, interface, _)| *interface)
+            .ok_or_else(|| UnknownProtocol(name.to_owned()))?;
+        self.required.retain(|required| *required != interface);
+        if requirement == Requirement::Required {
+            self.required.push(interface);
+        }
+        Ok(())
+    }
+}
+
+/// Report which of the protocols the compositor of `env` offers, failing
+/// with those of them `policy` requires and it lacks.
+pub fn check(
+    policy: &Policy,
+    env: &Environment<ThemedFrameExample>,
+) -> Result<(), MissingProtocols> {
+    let globals = env.manager.list();
+    let mut missing = Vec::new();
+    for (_, interface, without) in &PROTOCOLS {
+        let required = policy.required.contains(interface);
+        if globals.iter().any(|(_, name, _)| name == interface) {
+            info!(required, "The compositor offers {}", interface);
+        } else if required {
+            error!("The compositor lacks {}, which is required", interface);
+            missing.push(*interface);
+        } else {
+            warn!("The compositor lacks {}: {}", interface, without);
+        }
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(MissingProtocols(missing.join(", ")))
+    }
+}
@@ -0,0 +1,119 @@
+//! Switching to lower resolutions while the pipeline can't keep up.
+//!
+//! Late frames show up as QoS messages. When they keep coming, the video is
+//! downscaled right after decoding, halving its size at each step, so that
+//! the filters and the sink have fewer pixels to go through. Adaptive
+//! streams also get a lower `connection-speed` on playbin, for the demuxer
+//! to pick a lighter rendition. Once no frame has been late for a while,
+//! the full resolution comes back a step at a time.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::{info, warn};
+
+use crate::make;
+
+/// How long late frames are counted for before deciding on a switch.
+const WINDOW: Duration = Duration::from_secs(2);
+/// How many late frames in a window make for a QoS storm.
+const STORM: u32 = 10;
+/// How long without late frames before stepping back up.
+const HEADROOM: Duration = Duration::from_secs(10);
+/// The connection speed playbin is limited to at each step, in kbps, with
+/// 0 for no limit.
+const CONNECTION_SPEEDS: [u64; 3] = [0, 2500, 1000];
+
+pub struct AutoProxy {
+    videoscale: gst::Element,
+    capsfilter: gst::Element,
+    /// How many times the video is halved.
+    level: usize,
+    window_start: Instant,
+    late: u32,
+    last_late: Instant,
+}
+
+impl AutoProxy {
+    pub fn new() -> Result<Self, Error> {
+        let videoscale = make("videoscale")?;
+        let capsfilter = make("capsfilter")?;
+
+        let now = Instant::now();
+        Ok(AutoProxy {
+            videoscale,
+            capsfilter,
+            level: 0,
+            window_start: now,
+            late: 0,
+            last_late: now,
+        })
+    }
+
+    /// The elements to link in front of the sink, the earlier the better.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.videoscale, &self.capsfilter]
+    }
+
+    /// Count the late frames from the QoS messages of the pipeline.
+    pub fn on_message(&mut self, msg: &gst::Message) {
+        if let gst::MessageView::Qos(qos) = msg.view() {
+            let (jitter, _, _) = qos.get_values();
+            if jitter > 0 {
+                self.late += 1;
+                self.last_late = Instant::now();
+            }
+        }
+    }
+
+    /// Step the resolution down on QoS storms and back up with headroom.
+    pub fn update(&mut self, pipeline: &gst::Pipeline) {
+        if self.window_start.elapsed() < WINDOW {
+            return;
+        }
+        let storm = self.late >= STORM;
+        self.window_start = Instant::now();
+        self.late = 0;
+
+        if storm && self.level + 1 < CONNECTION_SPEEDS.len() {
+            self.level += 1;
+            info!("Decoding can't keep up, switching to 1/{} resolution", 1 << self.level);
+            self.apply(pipeline);
+        } else if !storm && self.level > 0 && self.last_late.elapsed() >= HEADROOM {
+            self.level -= 1;
+            // Give the new level a full period to prove itself
+            self.last_late = Instant::now();
+            info!("Headroom is back, switching to 1/{} resolution", 1 << self.level);
+            self.apply(pipeline);
+        }
+    }
+
+    /// The size of the decoded video, before any downscaling.
+    fn source_size(&self) -> Option<(i32, i32)> {
+        let caps = self.videoscale.get_static_pad("sink")?.get_current_caps()?;
+        let info = gst_video::VideoInfo::from_caps(&caps).ok()?;
+        Some((info.width() as i32, info.height() as i32))
+    }
+
+    fn apply(&self, pipeline: &gst::Pipeline) {
+        if pipeline.find_property("connection-speed").is_some() {
+            let speed = CONNECTION_SPEEDS[self.level];
+            if let Err(err) = pipeline.set_property("connection-speed", &speed) {
+                warn!("Failed to limit the connection speed: {}", err);
+            }
+        }
+
+        let caps = match (self.level, self.source_size()) {
+            (0, _) | (_, None) => gst::Caps::new_any(),
+            (level, Some((width, height))) => gst::Caps::builder("video/x-raw")
+                // Even sizes, which all formats can be scaled to
+                .field("width", &((width >> level).max(2) & !1))
+                .field("height", &((height >> level).max(2) & !1))
+                .build(),
+        };
+        if let Err(err) = self.capsfilter.set_property("caps", &caps) {
+            warn!("Failed to scale the video: {}", err);
+        }
+    }
+}
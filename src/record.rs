@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::{make, MissingElement};
+
+/// Encoders tried in order for the recording branch.
+const ENCODERS: &[&str] = &["x264enc", "vp9enc"];
+
+fn make_encoder() -> Result<gst::Element, Error> {
+    let name = ENCODERS
+        .iter()
+        .copied()
+        .find(|name| gst::ElementFactory::find(name).is_some())
+        .ok_or(MissingElement("x264enc or vp9enc"))?;
+
+    let encoder = make(name)?;
+    match name {
+        // The frames are produced live, don't let the encoder buffer them up
+        "x264enc" => encoder.set_property_from_str("tune", "zerolatency"),
+        "vp9enc" => encoder.set_property("deadline", &1i64)?,
+        _ => {}
+    }
+
+    Ok(encoder)
+}
+
+/// Add a branch to `tee` encoding the video into a Matroska file at `path`.
+///
+/// The file is only finalized once the branch receives EOS, so the pipeline
+/// must be shut down by sending EOS rather than by going straight to `Null`.
+pub fn add_branch(bin: &gst::Bin, tee: &gst::Element, path: &Path) -> Result<(), Error> {
+    let queue = make("queue")?;
+    let videoconvert = make("videoconvert")?;
+    let encoder = make_encoder()?;
+    let mux = make("matroskamux")?;
+    let filesink = make("filesink")?;
+
+    let location = path.to_string_lossy().into_owned();
+    filesink.set_property("location", &location)?;
+
+    let branch = [&queue, &videoconvert, &encoder, &mux, &filesink];
+    bin.add_many(&branch)?;
+    gst::Element::link_many(&branch)?;
+    tee.link(&queue)?;
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use gst_video::prelude::*;
+use tracing::warn;
+
+use crate::animation::{Animated, Easing, Lerp};
+
+/// A rectangle in surface coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+impl Rect {
+    /// The rectangle `scale` times this size, with the same center.
+    fn scaled(&self, scale: f64) -> Rect {
+        let width = (self.width as f64 * scale).round() as i32;
+        let height = (self.height as f64 * scale).round() as i32;
+        Rect {
+            x: self.x + (self.width - width) / 2,
+            y: self.y + (self.height - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+impl Lerp for Rect {
+    fn lerp(&self, to: &Rect, t: f64) -> Rect {
+        let mix = |a: i32, b: i32| a + ((b - a) as f64 * t).round() as i32;
+        Rect {
+            x: mix(self.x, to.x),
+            y: mix(self.y, to.y),
+            width: mix(self.width, to.width),
+            height: mix(self.height, to.height),
+        }
+    }
+}
+
+/// Drives the render rectangle of a video overlay, animating every change
+/// over `duration` instead of snapping to the new position.
+///
+/// The animation is stepped by `tick`, which is meant to be called from
+/// `wl_surface.frame` callbacks so that it runs at the compositor pace.
+///
+/// The video can be scaled down about the center of the rectangle it is
+/// laid out in, by a touchpad pinch. The scale stays across layouts, and
+/// never takes the video past the rectangle, which is at most the window.
+pub struct RenderRectangle {
+    overlay: gst_video::VideoOverlay,
+    rect: Animated<Rect>,
+    /// Where the video is laid out, before scaling.
+    layout: Rect,
+    scale: f64,
+}
+
+/// The smallest scale of the video in its rectangle.
+const MIN_SCALE: f64 = 0.2;
+
+impl RenderRectangle {
+    pub fn new(overlay: gst_video::VideoOverlay, rect: Rect, duration: Duration) -> Self {
+        let render_rect = RenderRectangle {
+            overlay,
+            rect: Animated::new(rect, duration, Easing::EaseInOut),
+            layout: rect,
+            scale: 1.0,
+        };
+        render_rect.apply();
+        render_rect
+    }
+
+    /// Move the video to `rect`.
+    pub fn animate_to(&mut self, rect: Rect) {
+        self.layout = rect;
+        self.rect.animate_to(rect.scaled(self.scale));
+    }
+
+    /// Scale the video by `ratio` right away, following a pinch.
+    pub fn scale_by(&mut self, ratio: f64) {
+        self.scale = (self.scale * ratio).max(MIN_SCALE).min(1.0);
+        self.rect.set(self.layout.scaled(self.scale));
+        self.apply();
+    }
+
+    /// Bring the video back to its full rectangle.
+    pub fn reset_scale(&mut self) {
+        self.scale = 1.0;
+        self.rect.animate_to(self.layout);
+    }
+
+    /// Where the video currently is, along the animation.
+    pub fn rect(&self) -> Rect {
+        self.rect.value()
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.rect.is_animating()
+    }
+
+    /// Advance the animation, returns whether more frames are needed.
+    pub fn tick(&mut self) -> bool {
+        if self.rect.tick() {
+            self.apply();
+        }
+        self.is_animating()
+    }
+
+    /// Lay the video out again at the current rectangle, e.g. once the
+    /// window moved to an output of a different scale.
+    pub fn refresh(&self) {
+        self.apply();
+        self.overlay.expose();
+    }
+
+    fn apply(&self) {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = self.rect.value();
+        if let Err(err) = self.overlay.set_render_rectangle(x, y, width, height) {
+            warn!("Failed to set the render rectangle: {}", err);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Instant replay of a time-shifted live input.
+//!
+//! A branch off the recording keeps the last seconds of the MPEG-TS in
+//! memory, as the buffers themselves. `save` writes them out as a
+//! timestamped `.ts` file next to the binary, from a thread, so that the
+//! window goes on showing the live input meanwhile. The file starts at a
+//! packet boundary, and players pick up from the next program tables and
+//! key frame.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::{info, warn};
+
+use crate::make;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Nothing recorded yet")]
+struct NothingRecorded;
+
+/// The last seconds of the recording.
+#[derive(Clone)]
+pub struct Replay {
+    buffers: Arc<Mutex<VecDeque<(Instant, gst::Buffer)>>>,
+}
+
+impl Replay {
+    /// Add the replay branch, keeping `duration` of the stream, to `tee`
+    /// of `bin`.
+    pub fn add_branch(
+        bin: &gst::Bin,
+        tee: &gst::Element,
+        duration: Duration,
+    ) -> Result<Self, Error> {
+        let queue = make("queue")?;
+        let appsink = make("appsink")?;
+        appsink.set_property("sync", &false)?;
+
+        let replay = Replay {
+            buffers: Arc::new(Mutex::new(VecDeque::new())),
+        };
+        let buffers = replay.buffers.clone();
+        let appsink = appsink.dynamic_cast::<gst_app::AppSink>().unwrap();
+        appsink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |appsink| {
+                    let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let buffer = sample.get_buffer_owned().ok_or(gst::FlowError::Error)?;
+                    let now = Instant::now();
+                    let mut buffers = buffers.lock().unwrap();
+                    buffers.push_back((now, buffer));
+                    while let Some((received, _)) = buffers.front() {
+                        if now.duration_since(*received) <= duration {
+                            break;
+                        }
+                        buffers.pop_front();
+                    }
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        bin.add_many(&[&queue, appsink.upcast_ref()])?;
+        queue.link(&appsink)?;
+        tee.link(&queue)?;
+
+        Ok(replay)
+    }
+
+    /// Write what is kept of the stream as a timestamped `.ts` file next to
+    /// the binary, returns the path of the file, which is written in the
+    /// background.
+    pub fn save(&self) -> Result<PathBuf, Error> {
+        let buffers: Vec<gst::Buffer> = self
+            .buffers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, buffer)| buffer.clone())
+            .collect();
+        if buffers.is_empty() {
+            return Err(NothingRecorded.into());
+        }
+
+        let now = gst::glib::DateTime::new_now_local();
+        let name = now
+            .format("replay-%Y%m%d-%H%M%S.ts")
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "replay.ts".into());
+        let exe = std::env::current_exe()?;
+        let path = exe.parent().unwrap_or_else(|| Path::new(".")).join(name);
+
+        let file = File::create(&path)?;
+        let written = path.clone();
+        thread::spawn(move || match write(file, &buffers) {
+            Ok(()) => info!("Saved the replay to {}", written.display()),
+            Err(err) => warn!("Failed to save the replay to {}: {}", written.display(), err),
+        });
+        Ok(path)
+    }
+}
+
+fn write(file: File, buffers: &[gst::Buffer]) -> Result<(), Error> {
+    let mut writer = BufWriter::new(file);
+    for buffer in buffers {
+        writer.write_all(buffer.map_readable()?.as_slice())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
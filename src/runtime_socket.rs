@@ -0,0 +1,59 @@
+//! Unix sockets in `$XDG_RUNTIME_DIR`, of the control requests and the
+//! handoff.
+//!
+//! A socket left behind by a crash is replaced, one in use by another
+//! instance is refused, unless this one took playback over from it. Each is
+//! removed on exit by the instance that bound it, and left in place once
+//! another one replaced it.
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+
+/// The path of the socket `name` in `$XDG_RUNTIME_DIR`.
+pub fn path(name: &str) -> Result<PathBuf, Error> {
+    let dir = env::var_os("XDG_RUNTIME_DIR").ok_or_else(|| anyhow!("XDG_RUNTIME_DIR is unset"))?;
+    Ok(PathBuf::from(dir).join(name))
+}
+
+/// The inode of the socket at `path`, to tell whether it is still ours.
+fn inode(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.ino())
+}
+
+/// A bound socket, removed when dropped unless another instance replaced
+/// it.
+pub struct RuntimeSocket {
+    path: PathBuf,
+    inode: Option<u64>,
+}
+
+impl RuntimeSocket {
+    /// Bind the socket `name`, replacing that of the instance this one
+    /// `took_over`.
+    pub fn bind(name: &str, took_over: bool) -> Result<(Self, UnixListener), Error> {
+        let path = path(name)?;
+        // A socket left behind by a crash doesn't answer, one in use does
+        if path.exists() {
+            if !took_over && UnixStream::connect(&path).is_ok() {
+                return Err(anyhow!("{} is in use by another instance", path.display()));
+            }
+            fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        let inode = inode(&path);
+        Ok((RuntimeSocket { path, inode }, listener))
+    }
+}
+
+impl Drop for RuntimeSocket {
+    fn drop(&mut self) {
+        if inode(&self.path) == self.inode {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Output scales, applied to our surfaces and to the video.
+//!
+//! Integer scales come from the `wl_output`s the surfaces are on, and are
+//! applied with `wl_surface.set_buffer_scale`. Compositors supporting
+//! `wp_fractional_scale_v1` send a preferred scale in 120ths instead, which is
+//! applied with `wp_viewporter`: the buffer has the exact pixel count of the
+//! surface on the output, and the viewport destination is the surface size.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use anyhow::Error;
+use gst::prelude::*;
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::reexports::client::Main;
+use sctk::reexports::protocols::viewporter::client::{wp_viewport, wp_viewporter};
+use tracing::warn;
+
+use crate::render_rect::Rect;
+use crate::{make, ThemedFrameExample};
+
+use protocol::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1;
+use protocol::wp_fractional_scale_v1;
+
+mod protocol {
+    #![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+    #![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+    #![allow(unknown_lints, static_mut_refs, clippy::all)]
+
+    pub(crate) use sctk::reexports::client::protocol::wl_surface;
+    pub(crate) use sctk::reexports::client::{
+        sys, AnonymousObject, Attached, Main, Proxy, ProxyMap,
+    };
+    pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+    pub(crate) use wayland_commons::smallvec;
+    pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+    pub(crate) use wayland_commons::{Interface, MessageGroup};
+
+    include!(concat!(env!("OUT_DIR"), "/fractional_scale_v1.rs"));
+}
+
+/// How many buffer pixels a surface pixel takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scale {
+    Integer(i32),
+    /// In 120ths.
+    Fractional(u32),
+}
+
+impl Scale {
+    /// A length in surface coordinates, in buffer pixels.
+    pub fn to_buffer(self, length: i32) -> i32 {
+        match self {
+            Scale::Integer(scale) => length * scale,
+            Scale::Fractional(scale) => ((length as i64 * scale as i64 + 60) / 120) as i32,
+        }
+    }
+
+    /// A length in buffer pixels, in surface coordinates.
+    pub fn to_surface(self, length: i32) -> i32 {
+        match self {
+            Scale::Integer(scale) => length / scale,
+            Scale::Fractional(scale) => {
+                ((length as i64 * 120 + scale as i64 / 2) / scale as i64) as i32
+            }
+        }
+    }
+
+    /// `rect` in buffer coordinates. Its edges are scaled rather than its
+    /// size, so that adjacent rectangles stay adjacent.
+    pub fn rect_to_buffer(self, rect: Rect) -> Rect {
+        let (x, y) = (self.to_buffer(rect.x), self.to_buffer(rect.y));
+        let right = self.to_buffer(rect.x + rect.width);
+        let bottom = self.to_buffer(rect.y + rect.height);
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
+
+struct Fractional {
+    fractional_scale: Main<wp_fractional_scale_v1::WpFractionalScaleV1>,
+    preferred: Rc<Cell<Option<u32>>>,
+}
+
+/// The scale to draw a surface at, and the means to apply it.
+pub struct SurfaceScale {
+    surface: wl_surface::WlSurface,
+    viewport: Option<Main<wp_viewport::WpViewport>>,
+    fractional: Option<Fractional>,
+}
+
+impl SurfaceScale {
+    pub fn new(env: &Environment<ThemedFrameExample>, surface: &wl_surface::WlSurface) -> Self {
+        // The objects outlive the globals they come from
+        let viewport = env
+            .manager
+            .instantiate_exact::<wp_viewporter::WpViewporter>(1)
+            .ok()
+            .map(|viewporter| {
+                let viewport = viewporter.get_viewport(surface);
+                viewporter.destroy();
+                viewport
+            });
+
+        let manager = env.manager.instantiate_exact::<WpFractionalScaleManagerV1>(1);
+        let fractional = match (manager, &viewport) {
+            (Ok(manager), Some(_)) => {
+                let preferred = Rc::new(Cell::new(None));
+                let fractional_scale = manager.get_fractional_scale(surface);
+                let received = preferred.clone();
+                fractional_scale.quick_assign(move |_, event, _| {
+                    let wp_fractional_scale_v1::Event::PreferredScale { scale } = event;
+                    received.set(Some(scale));
+                });
+                manager.destroy();
+
+                Some(Fractional {
+                    fractional_scale,
+                    preferred,
+                })
+            }
+            _ => None,
+        };
+
+        SurfaceScale {
+            surface: surface.clone(),
+            viewport,
+            fractional,
+        }
+    }
+
+    /// The scale preferred by the compositor, or the highest one of the
+    /// outputs the surface is on.
+    pub fn get(&self) -> Scale {
+        match self.fractional.as_ref().and_then(|fractional| fractional.preferred.get()) {
+            Some(scale) => Scale::Fractional(scale),
+            None => Scale::Integer(sctk::get_surface_scale_factor(&self.surface)),
+        }
+    }
+
+    /// The viewport of the surface, when the compositor supports wp_viewporter.
+    /// There can only be one per surface.
+    pub fn viewport(&self) -> Option<&wp_viewport::WpViewport> {
+        self.viewport.as_ref().map(|viewport| &***viewport)
+    }
+
+    /// Set up the surface for buffers drawn at `scale` for a surface of
+    /// `width` by `height`, applied with the next commit.
+    pub fn set_buffer(&self, scale: Scale, (width, height): (u32, u32)) {
+        match (scale, &self.viewport) {
+            (Scale::Fractional(_), Some(viewport)) => {
+                self.surface.set_buffer_scale(1);
+                viewport.set_destination(width as i32, height as i32);
+            }
+            (Scale::Integer(scale), viewport) => {
+                self.surface.set_buffer_scale(scale);
+                if let Some(viewport) = viewport {
+                    // Unset
+                    viewport.set_destination(-1, -1);
+                }
+            }
+            (Scale::Fractional(_), None) => unreachable!("fractional scale without a viewport"),
+        }
+    }
+}
+
+impl Drop for SurfaceScale {
+    fn drop(&mut self) {
+        if let Some(fractional) = &self.fractional {
+            fractional.fractional_scale.destroy();
+        }
+        if let Some(viewport) = &self.viewport {
+            viewport.destroy();
+        }
+    }
+}
+
+/// Scales the video to the pixel count of the render rectangle on outputs
+/// with a fractional scale, so that the compositor shows it as is instead of
+/// resampling it. Borders keep the aspect ratio, as waylandsink would.
+pub struct VideoScaler {
+    videoscale: gst::Element,
+    capsfilter: gst::Element,
+    size: Option<(i32, i32)>,
+}
+
+impl VideoScaler {
+    pub fn new() -> Result<Self, Error> {
+        let videoscale = make("videoscale")?;
+        videoscale.set_property("add-borders", &true)?;
+        let capsfilter = make("capsfilter")?;
+
+        Ok(VideoScaler {
+            videoscale,
+            capsfilter,
+            size: None,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.videoscale, &self.capsfilter]
+    }
+
+    /// Follow the render rectangle `rect` on a surface at `scale`. Integer
+    /// scales leave the video as is, waylandsink sizes its buffers for them.
+    pub fn set(&mut self, rect: Rect, scale: Scale) {
+        let size = match scale {
+            Scale::Fractional(_) => {
+                let rect = scale.rect_to_buffer(rect);
+                Some((rect.width.max(1), rect.height.max(1)))
+            }
+            Scale::Integer(_) => None,
+        };
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+
+        let caps = match size {
+            Some((width, height)) => gst::Caps::builder("video/x-raw")
+                .field("width", &width)
+                .field("height", &height)
+                .field("pixel-aspect-ratio", &gst::Fraction::new(1, 1))
+                .build(),
+            None => gst::Caps::new_any(),
+        };
+        if let Err(err) = self.capsfilter.set_property("caps", &caps) {
+            warn!("Failed to scale the video: {}", err);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Playback state to persist across sessions.
+//!
+//! `save_state` captures what a pipeline plays, where and how fast, and the
+//! tracks a playbin selected, along with the `Transforms` the application
+//! applies to the video. The `SessionState` serializes with serde, to
+//! wherever the application keeps its sessions, and `restore_state` brings
+//! a pipeline back to it, paused, telling whether to resume it:
+//!
+//! ```ignore
+//! let state = session::save_state(&pipeline, transforms);
+//! std::fs::write(path, serde_json::to_string(&state)?)?;
+//!
+//! let state: SessionState = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+//! if session::restore_state(&pipeline, &state)? {
+//!     pipeline.set_state(gst::State::Playing)?;
+//! }
+//! ```
+
+use anyhow::Error;
+use gst::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How long `restore_state` waits for the pipeline to preroll.
+const PREROLL_TIMEOUT: gst::ClockTime = gst::ClockTime(Some(5_000_000_000));
+
+/// The streams a playbin plays, by index, -1 for none.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tracks {
+    pub video: i32,
+    pub audio: i32,
+    pub text: i32,
+}
+
+/// How the application shows the video, which the pipeline doesn't know.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transforms {
+    /// Quarter turns clockwise, after `mirrored`.
+    pub rotation: u8,
+    /// Flipped horizontally.
+    pub mirrored: bool,
+    pub zoom: f64,
+    /// Center of the zoomed region, in fractions of the video size.
+    pub zoom_center: (f64, f64),
+    pub brightness: f64,
+    pub contrast: f64,
+    pub hue: f64,
+    pub saturation: f64,
+}
+
+impl Default for Transforms {
+    fn default() -> Self {
+        Transforms {
+            rotation: 0,
+            mirrored: false,
+            zoom: 1.0,
+            zoom_center: (0.5, 0.5),
+            brightness: 0.0,
+            contrast: 1.0,
+            hue: 0.0,
+            saturation: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    /// The URI a playbin plays, None for other pipelines.
+    pub source: Option<String>,
+    /// In seconds.
+    pub position: Option<f64>,
+    pub rate: f64,
+    pub playing: bool,
+    pub tracks: Option<Tracks>,
+    #[serde(default)]
+    pub transforms: Transforms,
+}
+
+fn int_property(pipeline: &gst::Pipeline, name: &str) -> Option<i32> {
+    pipeline.find_property(name)?;
+    pipeline.get_property(name).ok()?.get_some::<i32>().ok()
+}
+
+/// The state of `pipeline`, shown with `transforms`.
+pub fn save_state(pipeline: &gst::Pipeline, transforms: Transforms) -> SessionState {
+    let source = pipeline
+        .find_property("current-uri")
+        .and_then(|_| pipeline.get_property("current-uri").ok())
+        .and_then(|uri| uri.get::<String>().ok().flatten());
+    let position = pipeline
+        .query_position::<gst::ClockTime>()
+        .and_then(|position| position.nseconds())
+        .map(|nseconds| nseconds as f64 / 1e9);
+    let mut query = gst::Query::new_segment(gst::Format::Time);
+    let rate = if pipeline.query(&mut query) {
+        match query.view() {
+            gst::QueryView::Segment(segment) => segment.get_result().0,
+            _ => 1.0,
+        }
+    } else {
+        1.0
+    };
+    let (_, current, _) = pipeline.get_state(gst::ClockTime::from_seconds(0));
+    let tracks = match (
+        int_property(pipeline, "current-video"),
+        int_property(pipeline, "current-audio"),
+        int_property(pipeline, "current-text"),
+    ) {
+        (Some(video), Some(audio), Some(text)) => Some(Tracks { video, audio, text }),
+        _ => None,
+    };
+
+    SessionState {
+        source,
+        position,
+        rate,
+        playing: current == gst::State::Playing,
+        tracks,
+        transforms,
+    }
+}
+
+/// Bring `pipeline` back to `state`, but for its transforms, which are the
+/// caller's. A playbin is switched to the source of the state if it plays
+/// another one. The pipeline is left paused there, returns whether it was
+/// playing, for the caller to resume it once it can render.
+pub fn restore_state(pipeline: &gst::Pipeline, state: &SessionState) -> Result<bool, Error> {
+    if let Some(source) = &state.source {
+        if pipeline.find_property("uri").is_some() {
+            pipeline.set_state(gst::State::Ready)?;
+            pipeline.set_property("uri", source)?;
+        }
+    }
+    pipeline.set_state(gst::State::Paused)?;
+    let (result, _, _) = pipeline.get_state(PREROLL_TIMEOUT);
+    result?;
+
+    if let Some(tracks) = &state.tracks {
+        for (name, index) in &[
+            ("current-video", tracks.video),
+            ("current-audio", tracks.audio),
+            ("current-text", tracks.text),
+        ] {
+            if pipeline.find_property(name).is_some() {
+                pipeline.set_property(*name, index)?;
+            }
+        }
+    }
+    let position = gst::ClockTime::from_nseconds((state.position.unwrap_or(0.0) * 1e9) as u64);
+    pipeline.seek(
+        state.rate,
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        gst::SeekType::Set,
+        position,
+        gst::SeekType::None,
+        gst::ClockTime::none(),
+    )?;
+    Ok(state.playing)
+}
@@ -0,0 +1,52 @@
+//! Showing the video of another process, written to a `shmsink`.
+//!
+//! With `--shm-socket PATH`, the window shows what comes through the shmsink
+//! listening at PATH, in place of the synthetic video and through the same
+//! display filters, so that producers are decoupled from the window. Shared
+//! memory carries no caps: the producer has to send those of `--shm-caps`,
+//! which the start-up prints a producer line for, such as
+//!
+//! ```text
+//! gst-launch-1.0 videotestsrc is-live=true ! videoconvert \
+//!     ! video/x-raw,format=BGRx,width=640,height=480,framerate=30/1 \
+//!     ! shmsink socket-path=/tmp/waylandsink.shm wait-for-connection=false
+//! ```
+//!
+//! The producer has to be started first, and the window errors out once it
+//! goes away.
+
+use std::str::FromStr;
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::make;
+
+pub const DEFAULT_CAPS: &str = "video/x-raw,format=BGRx,width=640,height=480,framerate=30/1";
+
+/// A bin reading video of `caps` from the shmsink at `path`, with a `src`
+/// ghost pad.
+pub fn create_source(path: &str, caps: &str) -> Result<gst::Element, Error> {
+    let shmsrc = make("shmsrc")?;
+    let capsfilter = make("capsfilter")?;
+    shmsrc.set_property("socket-path", &path)?;
+    shmsrc.set_property("is-live", &true)?;
+    // The buffers come without timestamps
+    shmsrc.set_property("do-timestamp", &true)?;
+    capsfilter.set_property("caps", &gst::Caps::from_str(caps)?)?;
+
+    let bin = gst::Bin::new(Some("shm"));
+    bin.add_many(&[&shmsrc, &capsfilter])?;
+    shmsrc.link(&capsfilter)?;
+    let pad = capsfilter.get_static_pad("src").unwrap();
+    bin.add_pad(&gst::GhostPad::with_target(Some("src"), &pad)?)?;
+    Ok(bin.upcast())
+}
+
+/// The end of a gst-launch-1.0 pipeline feeding the source at `path`.
+pub fn producer_hint(path: &str, caps: &str) -> String {
+    format!(
+        "... ! videoconvert ! {} ! shmsink socket-path={} wait-for-connection=false",
+        caps, path
+    )
+}
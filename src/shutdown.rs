@@ -0,0 +1,161 @@
+//! Hooks run as an application shuts its video down.
+//!
+//! Resources of an embedder can depend on the pipeline running, or on the
+//! `wl_surface` the video is shown on, such as a subsurface of their own or
+//! a buffer attached to it. `ShutdownHooks` runs what releases them at the
+//! right point, in three phases that always come in this order:
+//!
+//! 1. before the pipeline is stopped, while it still runs,
+//! 2. once the pipeline is in the null state,
+//! 3. once the surface is destroyed.
+//!
+//! The application calls `before_stop`, `pipeline_null` and
+//! `surface_destroyed` as it goes through them. A phase runs the ones before
+//! it that didn't run yet, and each phase runs once, so hooks keep their
+//! order however the shutdown goes, early returns included: dropping the
+//! `ShutdownHooks` runs whatever is left. Within a phase, hooks run in the
+//! order they were registered in.
+//!
+//! ```ignore
+//! let mut hooks = ShutdownHooks::default();
+//! hooks.on_pipeline_null(move || recorder.finish());
+//! hooks.on_surface_destroyed(move || overlay_surface.destroy());
+//!
+//! hooks.before_stop();
+//! pipeline.set_state(gst::State::Null)?;
+//! hooks.pipeline_null();
+//! drop(window);
+//! hooks.surface_destroyed();
+//! ```
+
+type Hook = Box<dyn FnOnce()>;
+
+/// The shutdown phases, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    BeforeStop,
+    PipelineNull,
+    SurfaceDestroyed,
+}
+
+#[derive(Default)]
+pub struct ShutdownHooks {
+    before_stop: Vec<Hook>,
+    pipeline_null: Vec<Hook>,
+    surface_destroyed: Vec<Hook>,
+    /// The last phase that ran.
+    done: Option<Phase>,
+}
+
+impl ShutdownHooks {
+    /// Run `hook` before the pipeline is stopped.
+    pub fn on_before_stop(&mut self, hook: impl FnOnce() + 'static) {
+        self.before_stop.push(Box::new(hook));
+    }
+
+    /// Run `hook` once the pipeline is in the null state.
+    pub fn on_pipeline_null(&mut self, hook: impl FnOnce() + 'static) {
+        self.pipeline_null.push(Box::new(hook));
+    }
+
+    /// Run `hook` once the surface of the video is destroyed.
+    pub fn on_surface_destroyed(&mut self, hook: impl FnOnce() + 'static) {
+        self.surface_destroyed.push(Box::new(hook));
+    }
+
+    /// The pipeline is about to be stopped.
+    pub fn before_stop(&mut self) {
+        self.run_until(Phase::BeforeStop);
+    }
+
+    /// The pipeline is in the null state.
+    pub fn pipeline_null(&mut self) {
+        self.run_until(Phase::PipelineNull);
+    }
+
+    /// The surface of the video is destroyed.
+    pub fn surface_destroyed(&mut self) {
+        self.run_until(Phase::SurfaceDestroyed);
+    }
+
+    /// Run the phases up to `last` that didn't run yet.
+    fn run_until(&mut self, last: Phase) {
+        let phases = [Phase::BeforeStop, Phase::PipelineNull, Phase::SurfaceDestroyed];
+        for &phase in phases.iter().filter(|&&phase| phase <= last) {
+            if self.done.map_or(false, |done| phase <= done) {
+                continue;
+            }
+            self.done = Some(phase);
+            let hooks = match phase {
+                Phase::BeforeStop => &mut self.before_stop,
+                Phase::PipelineNull => &mut self.pipeline_null,
+                Phase::SurfaceDestroyed => &mut self.surface_destroyed,
+            };
+            for hook in hooks.drain(..) {
+                hook();
+            }
+        }
+    }
+}
+
+impl Drop for ShutdownHooks {
+    fn drop(&mut self) {
+        self.run_until(Phase::SurfaceDestroyed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    /// Hooks logging their name to `log`, registered out of order.
+    fn hooks(log: &Rc<RefCell<Vec<&'static str>>>) -> ShutdownHooks {
+        let hook = |name: &'static str| {
+            let log = log.clone();
+            move || log.borrow_mut().push(name)
+        };
+        let mut hooks = ShutdownHooks::default();
+        hooks.on_surface_destroyed(hook("surface"));
+        hooks.on_pipeline_null(hook("null 1"));
+        hooks.on_before_stop(hook("stop"));
+        hooks.on_pipeline_null(hook("null 2"));
+        hooks
+    }
+
+    #[test]
+    fn runs_the_phases_in_order_once() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut hooks = hooks(&log);
+        hooks.before_stop();
+        assert_eq!(*log.borrow(), ["stop"]);
+        hooks.pipeline_null();
+        hooks.pipeline_null();
+        assert_eq!(*log.borrow(), ["stop", "null 1", "null 2"]);
+        hooks.surface_destroyed();
+        assert_eq!(*log.borrow(), ["stop", "null 1", "null 2", "surface"]);
+        drop(hooks);
+        assert_eq!(log.borrow().len(), 4);
+    }
+
+    #[test]
+    fn runs_skipped_phases_first() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut hooks = hooks(&log);
+        hooks.pipeline_null();
+        assert_eq!(*log.borrow(), ["stop", "null 1", "null 2"]);
+        hooks.before_stop();
+        assert_eq!(log.borrow().len(), 3);
+    }
+
+    #[test]
+    fn runs_what_is_left_when_dropped() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut hooks = hooks(&log);
+        hooks.before_stop();
+        drop(hooks);
+        assert_eq!(*log.borrow(), ["stop", "null 1", "null 2", "surface"]);
+    }
+}
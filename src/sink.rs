@@ -0,0 +1,215 @@
+//! The video sinks, and how each is handed our Wayland connection and
+//! surface.
+//!
+//! waylandsink takes the `wl_display` in a `GstWaylandDisplayHandleContextType`
+//! context. glimagesink takes it wrapped in a `GstGLDisplayWayland`, in a
+//! `gst.gl.GLDisplay` context, and makes the `wl_egl_window` of its
+//! subsurface itself from the `wl_surface` handed as window handle. Both,
+//! and any other video overlay, are handed the window handle the same way.
+//!
+//! autovideosink opens the sinks it tries before anything can be handed to
+//! them, so they would render on connections of their own. Instead,
+//! `autovideosink` picks a sink as it does, the video sink of highest rank,
+//! among those that are video overlays.
+//!
+//! Without `--sink`, or when it can't be made, waylandsink, glimagesink and
+//! autovideosink are tried in turn: distributions ship waylandsink in
+//! gst-plugins-bad, which is not always installed.
+//!
+//! A sink rendering on a `wl_surface` that was destroyed in the meantime is
+//! a protocol error, which ends the connection. `SurfaceGuard` refuses to
+//! start playing once the surface is gone.
+
+use std::cmp::Reverse;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::glib::translate::ToGlib;
+use gst::prelude::*;
+use gst_gl::ContextGLExt;
+use gst_video::prelude::*;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::reexports::client::Display;
+use tracing::{info, warn};
+use wayland_sink_with_video_overlay::gst_ctx;
+
+const FALLBACK: [&str; 3] = ["waylandsink", "glimagesink", "autovideosink"];
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "No usable video sink, tried {}", _0)]
+struct NoSink(#[error(not(source))] String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "{} is not a video overlay", _0)]
+struct NotAnOverlay(#[error(not(source))] String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "The surface of {} was destroyed, not playing", _0)]
+pub struct SurfaceDestroyed(#[error(not(source))] String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Failed to make {}", _0)]
+struct NotMade(#[error(not(source))] String);
+
+/// How a sink is made to render on a subsurface of ours.
+pub trait SinkBackend {
+    /// Hand `display` to `sink`, before it opens a connection of its own.
+    fn set_display(&self, sink: &gst::Element, display: &Display) -> Result<(), Error>;
+
+    /// Hand `surface` to the video overlay of the sink, to render on a
+    /// subsurface of it.
+    fn set_window_handle(
+        &self,
+        overlay: &gst_video::VideoOverlay,
+        surface: &wl_surface::WlSurface,
+    ) {
+        #[allow(clippy::cast_ptr_alignment)]
+        unsafe {
+            overlay.set_window_handle(surface.as_ref().c_ptr() as usize);
+        }
+    }
+}
+
+/// waylandsink, and sinks of other names looking for the same context.
+struct WaylandDisplayHandle;
+
+impl SinkBackend for WaylandDisplayHandle {
+    fn set_display(&self, sink: &gst::Element, display: &Display) -> Result<(), Error> {
+        gst_ctx::set_wayland_display(sink, display);
+        Ok(())
+    }
+}
+
+/// glimagesink, looking for a GL display.
+struct GlDisplay;
+
+impl SinkBackend for GlDisplay {
+    fn set_display(&self, sink: &gst::Element, display: &Display) -> Result<(), Error> {
+        // The GL display only borrows our connection, which outlives the sink
+        let gl_display =
+            unsafe { gst_gl::GLDisplayWayland::with_display(display.c_ptr() as usize)? };
+        let mut context = gst::Context::new(gst_gl::GL_DISPLAY_CONTEXT_TYPE, true);
+        {
+            let context = context.get_mut().unwrap();
+            context.set_gl_display(&gl_display);
+        }
+        sink.set_context(&context);
+        Ok(())
+    }
+}
+
+fn backend(sink: &gst::Element) -> Box<dyn SinkBackend> {
+    let factory = sink.get_factory().map(|factory| factory.get_name());
+    match factory.as_deref() {
+        Some("glimagesink") => Box::new(GlDisplay),
+        _ => Box::new(WaylandDisplayHandle),
+    }
+}
+
+/// The name of the video sink of highest rank that is a video overlay.
+fn best_overlay_sink() -> Option<String> {
+    let mut factories = gst::ElementFactory::list_get_elements(
+        gst::ElementFactoryListType::VIDEO_SINK,
+        gst::Rank::Marginal,
+    )
+    .into_iter()
+    .filter(|factory| factory.has_interface("GstVideoOverlay"))
+    .collect::<Vec<_>>();
+    factories.sort_by_key(|factory| Reverse(factory.get_rank().to_glib()));
+    factories.first().map(|factory| factory.get_name().to_string())
+}
+
+/// The sink `name`, which has to be a video overlay, autovideosink standing
+/// for the best ranked one.
+fn make_overlay_sink(name: &str) -> Result<gst::Element, Error> {
+    let name = if name == "autovideosink" {
+        let best = best_overlay_sink().ok_or_else(|| NotMade(name.to_owned()))?;
+        info!("autovideosink picked {}", best);
+        best
+    } else {
+        name.to_owned()
+    };
+    let sink = gst::ElementFactory::make(&name, None).map_err(|_| NotMade(name.clone()))?;
+    if sink.dynamic_cast_ref::<gst_video::VideoOverlay>().is_none() {
+        return Err(NotAnOverlay(name).into());
+    }
+    Ok(sink)
+}
+
+/// The `preferred` sink, or else the first of waylandsink, glimagesink and
+/// autovideosink that can be made, to hand our Wayland connection with
+/// `set_window` before it starts.
+pub fn create(preferred: Option<&str>) -> Result<gst::Element, Error> {
+    let mut tried = Vec::new();
+    for name in preferred.into_iter().chain(FALLBACK.iter().copied()) {
+        if tried.contains(&name) {
+            continue;
+        }
+        match make_overlay_sink(name) {
+            Ok(sink) => {
+                if !tried.is_empty() {
+                    info!("Using {}", name);
+                }
+                return Ok(sink);
+            }
+            Err(err) if preferred == Some(name) => warn!("{}, falling back", err),
+            Err(err) => info!("{}", err),
+        }
+        tried.push(name);
+    }
+    Err(NoSink(tried.join(", ")).into())
+}
+
+/// Render the video of `sink` on a subsurface of `surface`, on our Wayland
+/// connection, returning the video overlay to place it with.
+pub fn set_window(
+    sink: &gst::Element,
+    display: &Display,
+    surface: &wl_surface::WlSurface,
+) -> Result<gst_video::VideoOverlay, Error> {
+    let backend = backend(sink);
+    backend.set_display(sink, display)?;
+
+    let overlay = sink
+        .clone()
+        .dynamic_cast::<gst_video::VideoOverlay>()
+        .map_err(|sink| NotAnOverlay(sink.get_name().to_string()))?;
+    backend.set_window_handle(&overlay, surface);
+    Ok(overlay)
+}
+
+/// Tracks whether the surface handed to a sink is still alive, and keeps
+/// the sink from playing once it isn't.
+pub struct SurfaceGuard {
+    sink: gst::Element,
+    surface: wl_surface::WlSurface,
+}
+
+impl SurfaceGuard {
+    /// Guard `sink`, which renders on `surface`.
+    pub fn new(sink: &gst::Element, surface: &wl_surface::WlSurface) -> Self {
+        SurfaceGuard {
+            sink: sink.clone(),
+            surface: surface.clone(),
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.surface.as_ref().is_alive()
+    }
+
+    /// Switch `pipeline`, which holds the sink, to `state`. Playing is
+    /// refused with `SurfaceDestroyed` once the surface is gone, and the
+    /// sink is then locked in its state, so that nothing else starts it.
+    pub fn set_state(
+        &self,
+        pipeline: &gst::Pipeline,
+        state: gst::State,
+    ) -> Result<gst::StateChangeSuccess, Error> {
+        if state == gst::State::Playing && !self.is_alive() {
+            self.sink.set_locked_state(true);
+            return Err(SurfaceDestroyed(self.sink.get_name().to_string()).into());
+        }
+        Ok(pipeline.set_state(state)?)
+    }
+}
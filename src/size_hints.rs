@@ -0,0 +1,201 @@
+//! Size limits of the window from the size of the video, and aspect locked
+//! resizing, for `--lock-aspect`.
+//!
+//! Once the video size is known from the caps at the sink, and whenever it
+//! changes, the window gets a minimum size, the video scaled down to
+//! `MIN_SIDE` pixels on its shorter side, so that it can't be squashed into
+//! a strip, and a maximum size, `MAX_SCALE` times the video. Both apply to
+//! floating windows only, compositors ignore them when maximizing or going
+//! fullscreen.
+//!
+//! With `--lock-aspect`, the size of each configure event of a floating
+//! window is fit to the aspect ratio of the video, within the size the
+//! compositor suggested, which is a maximum while resizing. The window is
+//! fit again once the video size is first known, for the configure events
+//! that came before the caps.
+
+use std::sync::{Arc, Mutex};
+
+use gst::prelude::*;
+use sctk::window::State;
+use tracing::debug;
+
+use crate::toplevel::Toplevel;
+
+/// The shorter side of the smallest window, in surface coordinates.
+const MIN_SIDE: f64 = 120.0;
+/// The largest window, in video sizes.
+const MAX_SCALE: u32 = 4;
+
+/// The size the video is displayed at, from `caps`, with square pixels.
+fn display_size(caps: &gst::CapsRef) -> Option<(u32, u32)> {
+    let info = gst_video::VideoInfo::from_caps(caps).ok()?;
+    let par = info.par();
+    let (numer, denom) = (*par.numer() as u64, *par.denom() as u64);
+    if numer == 0 || denom == 0 || info.width() == 0 || info.height() == 0 {
+        return None;
+    }
+    let width = u64::from(info.width()) * numer / denom;
+    Some((width.max(1) as u32, info.height()))
+}
+
+pub struct SizeHints {
+    lock_aspect: bool,
+    /// The display size of the video, from the last caps event at the sink.
+    caps_size: Arc<Mutex<Option<(u32, u32)>>>,
+    /// The display size of the video the limits were set for.
+    video: Option<(u32, u32)>,
+    /// The minimum and maximum size of the window.
+    limits: Option<((u32, u32), (u32, u32))>,
+    /// The states of the last configure event.
+    states: Vec<State>,
+}
+
+impl SizeHints {
+    /// Limit the size of the window to that of the video handed to `sink`,
+    /// and keep its aspect ratio with `lock_aspect`.
+    pub fn new(sink: &gst::Element, lock_aspect: bool) -> Self {
+        let caps_size = Arc::new(Mutex::new(None));
+        let pad = sink.get_static_pad("sink").unwrap();
+        let size = caps_size.clone();
+        pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+            if let Some(gst::PadProbeData::Event(event)) = &info.data {
+                if let gst::EventView::Caps(caps) = event.view() {
+                    if let Some(display_size) = display_size(caps.get_caps()) {
+                        *size.lock().unwrap() = Some(display_size);
+                    }
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
+        SizeHints {
+            lock_aspect,
+            caps_size,
+            video: None,
+            limits: None,
+            states: Vec::new(),
+        }
+    }
+
+    /// Follow the size of the video, setting the limits of `window` when it
+    /// changes. The first time, `window` is also resized from its current
+    /// `size`, and the new size is returned to lay the window out again.
+    pub fn update(&mut self, window: &mut Toplevel, size: (u32, u32)) -> Option<(u32, u32)> {
+        let video = match *self.caps_size.lock().unwrap() {
+            Some(video) if Some(video) != self.video => video,
+            _ => return None,
+        };
+        let first = self.video.is_none();
+        self.video = Some(video);
+
+        let (width, height) = video;
+        let scale = (MIN_SIDE / f64::from(width.min(height))).min(1.0);
+        let min = (
+            (f64::from(width) * scale).round().max(1.0) as u32,
+            (f64::from(height) * scale).round().max(1.0) as u32,
+        );
+        let max = (width.saturating_mul(MAX_SCALE), height.saturating_mul(MAX_SCALE));
+        debug!(?min, ?max, "Limiting the window size to the video");
+        window.set_size_limits(min, max);
+        self.limits = Some((min, max));
+
+        if !first {
+            return None;
+        }
+        let states = self.states.clone();
+        let fitted = self.fit(size, &states);
+        if fitted == size {
+            return None;
+        }
+        debug!(?fitted, "Fitting the window to the video");
+        window.resize(fitted.0, fitted.1);
+        Some(fitted)
+    }
+
+    /// The states of the last configure event.
+    pub fn states(&self) -> &[State] {
+        &self.states
+    }
+
+    /// The size to commit for a configure event suggesting `size` for a
+    /// window in `states`, within the size limits.
+    pub fn fit(&mut self, (width, height): (u32, u32), states: &[State]) -> (u32, u32) {
+        self.states = states.to_vec();
+        let floating = states
+            .iter()
+            .all(|state| matches!(state, State::Activated | State::Resizing));
+        if !floating {
+            return (width, height);
+        }
+
+        let (width, height) = match self.video {
+            Some((video_width, video_height)) if self.lock_aspect => {
+                let aspect = f64::from(video_width) / f64::from(video_height);
+                let fitted_width = (f64::from(height) * aspect).round() as u32;
+                if fitted_width <= width {
+                    (fitted_width.max(1), height)
+                } else {
+                    (width, (f64::from(width) / aspect).round().max(1.0) as u32)
+                }
+            }
+            _ => (width, height),
+        };
+        match self.limits {
+            Some((min, max)) => (width.max(min.0).min(max.0), height.max(min.1).min(max.1)),
+            None => (width, height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hints for a 1920x1080 video, with the limits `update` sets for it.
+    fn hints(lock_aspect: bool) -> SizeHints {
+        SizeHints {
+            lock_aspect,
+            caps_size: Arc::new(Mutex::new(Some((1920, 1080)))),
+            video: Some((1920, 1080)),
+            limits: Some(((213, 120), (7680, 4320))),
+            states: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fits_floating_windows_to_the_video() {
+        let mut hints = hints(true);
+        assert_eq!(hints.fit((960, 900), &[State::Activated]), (960, 540));
+        assert_eq!(hints.fit((2000, 540), &[]), (960, 540));
+        assert_eq!(hints.fit((100, 100), &[State::Resizing]), (213, 120));
+    }
+
+    #[test]
+    fn keeps_windows_within_the_limits() {
+        let mut hints = hints(false);
+        assert_eq!(hints.fit((800, 800), &[]), (800, 800));
+        assert_eq!(hints.fit((100, 100), &[]), (213, 120));
+        assert_eq!(hints.fit((9000, 9000), &[]), (7680, 4320));
+    }
+
+    #[test]
+    fn leaves_maximized_and_fullscreen_windows_alone() {
+        let mut hints = hints(true);
+        assert_eq!(hints.fit((100, 100), &[State::Maximized]), (100, 100));
+        assert_eq!(hints.states(), [State::Maximized]);
+        assert_eq!(hints.fit((9000, 100), &[State::Fullscreen]), (9000, 100));
+    }
+
+    #[test]
+    fn displays_non_square_pixels_square() {
+        gst::init().unwrap();
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("format", &"BGRx")
+            .field("width", &720i32)
+            .field("height", &576i32)
+            .field("pixel-aspect-ratio", &gst::Fraction::new(16, 15))
+            .field("framerate", &gst::Fraction::new(25, 1))
+            .build();
+        assert_eq!(display_size(&caps), Some((768, 576)));
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "No frame rendered yet")]
+struct NoFrame;
+
+/// A tightly packed RGBA copy of a video frame.
+pub struct RgbaFrame {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// The `last-sample` property of `sink`, which reading doesn't disturb
+/// playback.
+fn last_sample(sink: &gst::Element) -> Result<gst::Sample, Error> {
+    Ok(sink
+        .get_property("last-sample")?
+        .get::<gst::Sample>()?
+        .ok_or(NoFrame)?)
+}
+
+/// Grab the frame currently displayed by `sink`, converted to RGBA.
+pub fn last_frame(sink: &gst::Element) -> Result<RgbaFrame, Error> {
+    rgba_frame(&last_sample(sink)?)
+}
+
+/// The frame of `sample`, converted to RGBA.
+pub fn rgba_frame(sample: &gst::Sample) -> Result<RgbaFrame, Error> {
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", &"RGBA")
+        .build();
+    let sample = gst_video::convert_sample(sample, &caps, 5 * gst::SECOND)?;
+
+    let buffer = sample.get_buffer().ok_or(NoFrame)?;
+    let info = gst_video::VideoInfo::from_caps(sample.get_caps().ok_or(NoFrame)?)?;
+    let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, &info)?;
+
+    let width = frame.width() as usize;
+    let height = frame.height() as usize;
+    let stride = frame.plane_stride()[0] as usize;
+
+    // Drop the padding at the end of the lines
+    let mut data = Vec::with_capacity(4 * width * height);
+    for line in frame.plane_data(0)?.chunks(stride).take(height) {
+        data.extend_from_slice(&line[..4 * width]);
+    }
+
+    Ok(RgbaFrame {
+        width: width as u32,
+        height: height as u32,
+        data,
+    })
+}
+
+/// The frame currently displayed by `sink`, encoded as JPEG.
+pub fn last_jpeg(sink: &gst::Element) -> Result<Vec<u8>, Error> {
+    let caps = gst::Caps::new_simple("image/jpeg", &[]);
+    let sample = gst_video::convert_sample(&last_sample(sink)?, &caps, 5 * gst::SECOND)?;
+    let buffer = sample.get_buffer().ok_or(NoFrame)?;
+    Ok(buffer.map_readable()?.as_slice().to_vec())
+}
+
+fn encode_png<W: Write>(frame: &RgbaFrame, output: W) -> Result<(), Error> {
+    let mut encoder = png::Encoder::new(output, frame.width, frame.height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&frame.data)?;
+
+    Ok(())
+}
+
+/// Encode `frame` as a PNG file at `path`.
+pub fn write_png(frame: &RgbaFrame, path: &Path) -> Result<(), Error> {
+    let file = File::create(path)?;
+    encode_png(frame, BufWriter::new(file))
+}
+
+/// Encode `frame` as PNG data in memory.
+pub fn to_png(frame: &RgbaFrame) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    encode_png(frame, &mut data)?;
+    Ok(data)
+}
+
+/// Save the frame currently displayed by `sink` as a timestamped PNG next
+/// to the binary, returns the path of the file.
+pub fn save(sink: &gst::Element) -> Result<PathBuf, Error> {
+    let frame = last_frame(sink)?;
+
+    // With the milliseconds, snapshots taken within the same second don't
+    // overwrite each other
+    let now = gst::glib::DateTime::new_now_local();
+    let name = now
+        .format("snapshot-%Y%m%d-%H%M%S")
+        .map(|s| format!("{}-{:03}.png", s, now.get_microsecond() / 1000))
+        .unwrap_or_else(|| "snapshot.png".into());
+
+    let exe = std::env::current_exe()?;
+    let path = exe.parent().unwrap_or_else(|| Path::new(".")).join(name);
+
+    write_png(&frame, &path)?;
+    Ok(path)
+}
@@ -0,0 +1,115 @@
+//! Display settings remembered per source.
+//!
+//! The settings given on the command line for a URI are saved in a JSON
+//! store in the user data directory, keyed by URI, and applied again when
+//! the URI is played without them. Settings given explicitly win over the
+//! saved ones, and are saved in turn.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+use serde_json::{json, Map, Value};
+use tracing::{info, warn};
+
+use crate::guides::AspectRatio;
+use crate::options::Options;
+
+const STORE: &str = "wayland-sink-with-video-overlay/sources.json";
+
+/// The settings of one source, unset ones are left to the defaults.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct SourceSettings {
+    desqueeze: Option<f64>,
+    matte: Option<f64>,
+    lut: Option<PathBuf>,
+}
+
+impl SourceSettings {
+    fn from_options(options: &Options) -> Self {
+        SourceSettings {
+            desqueeze: options.desqueeze,
+            matte: options.matte.map(|matte| matte.0),
+            lut: options.lut.clone(),
+        }
+    }
+
+    fn from_json(value: &Value) -> Self {
+        SourceSettings {
+            desqueeze: value["desqueeze"].as_f64(),
+            matte: value["matte"].as_f64(),
+            lut: value["lut"].as_str().map(PathBuf::from),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "desqueeze": self.desqueeze,
+            "matte": self.matte,
+            "lut": self.lut.as_ref().map(|lut| lut.to_string_lossy()),
+        })
+    }
+
+    /// These settings, with the unset ones taken from `fallback`.
+    fn or(self, fallback: SourceSettings) -> Self {
+        SourceSettings {
+            desqueeze: self.desqueeze.or(fallback.desqueeze),
+            matte: self.matte.or(fallback.matte),
+            lut: self.lut.or(fallback.lut),
+        }
+    }
+
+    fn apply(&self, options: &mut Options) {
+        options.desqueeze = self.desqueeze;
+        options.matte = self.matte.map(AspectRatio);
+        options.lut = self.lut.clone();
+    }
+}
+
+fn store_path() -> Option<PathBuf> {
+    Some(gst::glib::get_user_data_dir()?.join(STORE))
+}
+
+fn load(path: &Path) -> Map<String, Value> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+        .and_then(|value| value.as_object().cloned())
+        .unwrap_or_default()
+}
+
+fn save(path: &Path, sources: &Map<String, Value>) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(sources)?)?;
+    Ok(())
+}
+
+/// Complete `options` with the settings saved for the URI they play, and
+/// save those they give.
+pub fn restore(options: &mut Options) {
+    let (uri, path) = match (&options.uri, store_path()) {
+        (Some(uri), Some(path)) => (uri.clone(), path),
+        _ => return,
+    };
+
+    let mut sources = load(&path);
+    let saved = sources
+        .get(&uri)
+        .map(SourceSettings::from_json)
+        .unwrap_or_default();
+    let settings = SourceSettings::from_options(options).or(saved.clone());
+    settings.apply(options);
+
+    if settings == saved {
+        if settings != SourceSettings::default() {
+            info!("Applying the saved settings of {}", uri);
+        }
+        return;
+    }
+    sources.insert(uri, settings.to_json());
+    if let Err(err) = save(&path, &sources) {
+        warn!("Failed to save the settings of the source: {}", err);
+    }
+}
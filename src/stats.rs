@@ -0,0 +1,170 @@
+//! Display latency statistics through the presentation-time protocol.
+//!
+//! The video is shown on a subsurface of the window that waylandsink commits
+//! on its own, so presentation feedback is requested for the window surface,
+//! committed on every frame callback while the statistics are enabled. Each
+//! buffer reaching the sink is matched to the first presentation at or after
+//! its scheduled display time: the difference is the display latency, and
+//! every refresh period it spans beyond the first is a missed vblank.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use gst::prelude::*;
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::reexports::client::Main;
+use sctk::reexports::protocols::presentation_time::client::{
+    wp_presentation, wp_presentation_feedback,
+};
+use tracing::warn;
+
+use crate::{DispatchState, ThemedFrameExample};
+
+// from linux/time.h
+const CLOCK_MONOTONIC: u32 = 1;
+
+/// A presentation of the window surface.
+#[derive(Debug, Clone, Copy)]
+pub struct Presented {
+    /// `CLOCK_MONOTONIC` time, in nanoseconds.
+    pub time: u64,
+    /// Refresh period of the output, in nanoseconds, 0 if unknown.
+    pub refresh: u32,
+    /// Vblank counter of the output.
+    pub seq: u64,
+}
+
+pub struct Stats {
+    presentation: Main<wp_presentation::WpPresentation>,
+    // Clock times at which the buffers reaching the sink are to be displayed
+    scheduled: Arc<Mutex<VecDeque<u64>>>,
+    frames: u64,
+    total_latency: u64,
+    max_latency: u64,
+    missed_vblanks: u64,
+    last_seq: Option<u64>,
+    skipped_seq: u64,
+}
+
+impl Stats {
+    pub fn new(env: &Environment<ThemedFrameExample>, sink: &gst::Element) -> Result<Self, Error> {
+        let presentation = env
+            .manager
+            .instantiate_exact::<wp_presentation::WpPresentation>(1)?;
+        presentation.quick_assign(|_, event, _| {
+            if let wp_presentation::Event::ClockId { clk_id } = event {
+                if clk_id != CLOCK_MONOTONIC {
+                    warn!("Presentation clock {} isn't CLOCK_MONOTONIC, stats are off", clk_id);
+                }
+            }
+        });
+
+        let scheduled = Arc::new(Mutex::new(VecDeque::new()));
+        let queue = scheduled.clone();
+        let element = sink.clone();
+        let pad = sink.get_static_pad("sink").unwrap();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |pad, info| {
+            let pts = match &info.data {
+                Some(gst::PadProbeData::Buffer(buffer)) => buffer.get_pts(),
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            let running_time = pad
+                .get_sticky_event(gst::EventType::Segment, 0)
+                .and_then(|event| match event.view() {
+                    gst::EventView::Segment(segment) => segment
+                        .get_segment()
+                        .downcast_ref::<gst::format::Time>()
+                        .map(|segment| segment.to_running_time(pts)),
+                    _ => None,
+                });
+
+            let base_time = element.get_base_time().nseconds();
+            if let (Some(running_time), Some(base_time)) =
+                (running_time.and_then(|time| time.nseconds()), base_time)
+            {
+                queue.lock().unwrap().push_back(base_time + running_time);
+            }
+            gst::PadProbeReturn::Ok
+        });
+
+        Ok(Stats {
+            presentation,
+            scheduled,
+            frames: 0,
+            total_latency: 0,
+            max_latency: 0,
+            missed_vblanks: 0,
+            last_seq: None,
+            skipped_seq: 0,
+        })
+    }
+
+    /// Ask for feedback on the next commit of `surface`.
+    pub fn request_feedback(&self, surface: &wl_surface::WlSurface) {
+        let feedback = self.presentation.feedback(surface);
+        feedback.quick_assign(|_, event, mut ddata| {
+            if let wp_presentation_feedback::Event::Presented {
+                tv_sec_hi,
+                tv_sec_lo,
+                tv_nsec,
+                refresh,
+                seq_hi,
+                seq_lo,
+                ..
+            } = event
+            {
+                let seconds = (u64::from(tv_sec_hi) << 32) | u64::from(tv_sec_lo);
+                if let Some(state) = ddata.get::<DispatchState>() {
+                    state.presented.push(Presented {
+                        time: seconds * 1_000_000_000 + u64::from(tv_nsec),
+                        refresh,
+                        seq: (u64::from(seq_hi) << 32) | u64::from(seq_lo),
+                    });
+                }
+            }
+        });
+    }
+
+    /// Account for the buffers displayed by `presented`.
+    pub fn on_presented(&mut self, presented: Presented) {
+        if let Some(last_seq) = self.last_seq {
+            self.skipped_seq += presented.seq.saturating_sub(last_seq + 1);
+        }
+        self.last_seq = Some(presented.seq);
+
+        let mut scheduled = self.scheduled.lock().unwrap();
+        while let Some(&time) = scheduled.front() {
+            if time > presented.time {
+                break;
+            }
+            scheduled.pop_front();
+
+            let latency = presented.time - time;
+            self.frames += 1;
+            self.total_latency += latency;
+            self.max_latency = self.max_latency.max(latency);
+            if presented.refresh > 0 {
+                self.missed_vblanks += latency / u64::from(presented.refresh);
+            }
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let average = if self.frames > 0 {
+            self.total_latency / self.frames
+        } else {
+            0
+        };
+        format!(
+            "{} frames, display latency avg {:.1} ms max {:.1} ms, {} missed vblanks, \
+             {} vblanks without feedback",
+            self.frames,
+            average as f64 / 1e6,
+            self.max_latency as f64 / 1e6,
+            self.missed_vblanks,
+            self.skipped_seq
+        )
+    }
+}
@@ -0,0 +1,163 @@
+//! Display of stereoscopic content packed in a single frame.
+//!
+//! Side-by-side and top-bottom frames otherwise show both views squished next
+//! to each other. We either crop to one eye and scale it back to the full
+//! frame size, or first merge both eyes into a red/cyan anaglyph.
+
+use std::str::FromStr;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::warn;
+
+use crate::filter::BgrxFilter;
+use crate::make;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown stereo {}", _0)]
+pub struct UnknownStereoMode(#[error(not(source))] String);
+
+/// How the two views are packed in a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    SideBySide,
+    TopBottom,
+}
+
+impl FromStr for Layout {
+    type Err = UnknownStereoMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sbs" | "side-by-side" => Ok(Layout::SideBySide),
+            "tb" | "top-bottom" => Ok(Layout::TopBottom),
+            _ => Err(UnknownStereoMode(s.to_owned())),
+        }
+    }
+}
+
+/// What is shown of the stereo pair.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Eye {
+    Left,
+    Right,
+    Anaglyph,
+}
+
+impl FromStr for Eye {
+    type Err = UnknownStereoMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Eye::Left),
+            "right" => Ok(Eye::Right),
+            "anaglyph" => Ok(Eye::Anaglyph),
+            _ => Err(UnknownStereoMode(s.to_owned())),
+        }
+    }
+}
+
+/// Merge both views into a red/cyan anaglyph, written over the left (or top) view.
+fn merge_anaglyph(pixels: &mut [[u8; 4]], width: usize, layout: Layout) {
+    let height = pixels.len() / width;
+
+    match layout {
+        Layout::SideBySide => {
+            for line in pixels.chunks_exact_mut(width) {
+                let (left, right) = line.split_at_mut(width / 2);
+                for (l, r) in left.iter_mut().zip(right.iter()) {
+                    // BGRx: keep the red of the left eye, green and blue of the right one
+                    l[0] = r[0];
+                    l[1] = r[1];
+                }
+            }
+        }
+        Layout::TopBottom => {
+            let (top, bottom) = pixels.split_at_mut(width * (height / 2));
+            for (t, b) in top.iter_mut().zip(bottom.iter()) {
+                t[0] = b[0];
+                t[1] = b[1];
+            }
+        }
+    }
+}
+
+/// The elements selecting one view of stereo content.
+pub struct Stereo {
+    anaglyph: Option<BgrxFilter>,
+    elements: Vec<gst::Element>,
+}
+
+impl Stereo {
+    /// Set up the display of frames packed with `layout`. The crop and the
+    /// size the view is scaled back to follow the caps of the frames.
+    pub fn new(layout: Layout, eye: Eye) -> Result<Self, Error> {
+        let anaglyph = if eye == Eye::Anaglyph {
+            Some(BgrxFilter::new(move |pixels, width| {
+                merge_anaglyph(pixels, width, layout)
+            })?)
+        } else {
+            None
+        };
+
+        let videocrop = make("videocrop")?;
+        let property = match (layout, eye) {
+            (Layout::SideBySide, Eye::Right) => "left",
+            (Layout::SideBySide, _) => "right",
+            (Layout::TopBottom, Eye::Right) => "top",
+            (Layout::TopBottom, _) => "bottom",
+        };
+
+        // Scale the view back to the frame size to undo the squeeze
+        let videoscale = make("videoscale")?;
+        let capsfilter = make("capsfilter")?;
+
+        // Set up for the size of the frames before they get to the crop
+        let (crop, filter) = (videocrop.clone(), capsfilter.clone());
+        let pad = videocrop.get_static_pad("sink").unwrap();
+        pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+            let caps = match &info.data {
+                Some(gst::PadProbeData::Event(event)) => match event.view() {
+                    gst::EventView::Caps(caps) => caps.get_caps_owned(),
+                    _ => return gst::PadProbeReturn::Ok,
+                },
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            let video_info = match gst_video::VideoInfo::from_caps(&caps) {
+                Ok(video_info) => video_info,
+                Err(_) => return gst::PadProbeReturn::Ok,
+            };
+            let (width, height) = (video_info.width() as i32, video_info.height() as i32);
+            let amount = match layout {
+                Layout::SideBySide => width / 2,
+                Layout::TopBottom => height / 2,
+            };
+            let caps = gst::Caps::builder("video/x-raw")
+                .field("width", &width)
+                .field("height", &height)
+                .build();
+            let applied = crop
+                .set_property(property, &amount)
+                .and_then(|_| filter.set_property("caps", &caps));
+            if let Err(err) = applied {
+                warn!("Failed to set up the stereo view for {}x{}: {}", width, height, err);
+            }
+            gst::PadProbeReturn::Ok
+        });
+
+        Ok(Stereo {
+            anaglyph,
+            elements: vec![videocrop, videoscale, capsfilter],
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        self.anaglyph
+            .iter()
+            .map(BgrxFilter::element)
+            .chain(self.elements.iter())
+            .collect()
+    }
+}
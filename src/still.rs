@@ -0,0 +1,121 @@
+//! Skipping of repeated frames, for `--still-frames`.
+//!
+//! Static content, such as a slideshow through `imagefreeze` or the frames
+//! `videorate` duplicates, hands the sink the same picture over and over,
+//! each of which it attaches and commits, waking the compositor up for
+//! nothing. A probe on the sink pad recognizes a frame made of the same
+//! memory as the previous one, waits for its time as the sink would, so that
+//! upstream keeps its pace, and drops it: the last attached buffer stays on
+//! screen, and commits resume with the next frame that differs.
+//!
+//! Frames are compared by memory rather than by content, which costs nothing
+//! on moving video. Frames held by the sink while paused, and the first one
+//! after a flush, always go through.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use gst::prelude::*;
+
+/// The running time of `buffer` on its way through `pad`.
+pub fn running_time(pad: &gst::Pad, buffer: &gst::BufferRef) -> Option<gst::ClockTime> {
+    pad.get_sticky_event(gst::EventType::Segment, 0)
+        .and_then(|event| match event.view() {
+            gst::EventView::Segment(segment) => segment
+                .get_segment()
+                .downcast_ref::<gst::format::Time>()
+                .map(|segment| segment.to_running_time(buffer.get_pts())),
+            _ => None,
+        })
+        .filter(|time| time.is_some())
+}
+
+/// Waits for the time of the frames dropped before a sink, as the sink
+/// would have, so that upstream keeps its pace. A flush cuts the wait short.
+#[derive(Clone, Default)]
+pub struct FrameWait(Arc<Mutex<Option<gst::ClockId>>>);
+
+impl FrameWait {
+    /// Wait for the time `sink` would render `buffer`, coming through `pad`.
+    pub fn wait(&self, sink: &gst::Element, pad: &gst::Pad, buffer: &gst::BufferRef) {
+        let id = match (sink.get_clock(), running_time(pad, buffer)) {
+            (Some(clock), Some(running_time)) => {
+                clock.new_single_shot_id(sink.get_base_time() + running_time).ok()
+            }
+            _ => None,
+        };
+        if let Some(id) = id {
+            *self.0.lock().unwrap() = Some(id.clone());
+            let _ = id.wait();
+            *self.0.lock().unwrap() = None;
+        }
+    }
+
+    /// Cut the wait in progress short, on a flush.
+    pub fn interrupt(&self) {
+        if let Some(id) = self.0.lock().unwrap().as_ref() {
+            id.unschedule();
+        }
+    }
+}
+
+/// Whether `buffer` holds the same memory as `last`.
+fn is_repeated(buffer: &gst::BufferRef, last: &gst::Buffer) -> bool {
+    buffer.n_memory() > 0
+        && buffer.n_memory() == last.n_memory()
+        && (0..buffer.n_memory()).all(|i| {
+            buffer.peek_memory(i).as_ptr() == last.peek_memory(i).as_ptr()
+        })
+}
+
+pub struct StillFrames {
+    skipped: Arc<AtomicU64>,
+}
+
+impl StillFrames {
+    /// Skip the repeated frames handed to `sink`.
+    pub fn new(sink: &gst::Element) -> Self {
+        let skipped = Arc::new(AtomicU64::new(0));
+        let count = skipped.clone();
+        // The last frame let through, which also keeps its memory from
+        // being reused for another one
+        let last = Arc::new(Mutex::new(None::<gst::Buffer>));
+        let waiting = FrameWait::default();
+        let element = sink.clone();
+        let pad = sink.get_static_pad("sink").unwrap();
+        let mask = gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_FLUSH;
+        pad.add_probe(mask, move |pad, info| {
+            let buffer = match &info.data {
+                Some(gst::PadProbeData::Buffer(buffer)) => buffer,
+                Some(gst::PadProbeData::Event(event)) => {
+                    if let gst::EventView::FlushStart(_) = event.view() {
+                        waiting.interrupt();
+                    }
+                    *last.lock().unwrap() = None;
+                    return gst::PadProbeReturn::Ok;
+                }
+                _ => return gst::PadProbeReturn::Ok,
+            };
+
+            let repeated = last
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map_or(false, |last| is_repeated(buffer, last));
+            let (_, state, _) = element.get_state(gst::ClockTime::from_seconds(0));
+            if !repeated || state != gst::State::Playing {
+                *last.lock().unwrap() = Some(buffer.clone());
+                return gst::PadProbeReturn::Ok;
+            }
+
+            waiting.wait(&element, pad, buffer);
+            count.fetch_add(1, Ordering::Relaxed);
+            gst::PadProbeReturn::Drop
+        });
+        StillFrames { skipped }
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{} repeated frames skipped", self.skipped.load(Ordering::Relaxed))
+    }
+}
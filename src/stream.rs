@@ -0,0 +1,202 @@
+use std::str::FromStr;
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+
+use crate::make;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Invalid stream destination {}, expected host:port", _0)]
+pub struct InvalidDestination(#[error(not(source))] String);
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Unknown stream codec {}, expected h264 or raw", _0)]
+pub struct UnknownCodec(#[error(not(source))] String);
+
+const PAYLOAD_TYPE: u32 = 96;
+
+/// How the video is packed into RTP.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    /// `x264enc ! rtph264pay`, for constrained networks.
+    H264,
+    /// `rtpvrawpay`, uncompressed RFC 4175 video for local networks.
+    Raw,
+}
+
+impl FromStr for Codec {
+    type Err = UnknownCodec;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "h264" => Ok(Codec::H264),
+            "raw" => Ok(Codec::Raw),
+            _ => Err(UnknownCodec(s.to_owned())),
+        }
+    }
+}
+
+/// Where the RTP stream is sent.
+#[derive(Debug, Clone)]
+pub struct Destination {
+    pub host: String,
+    pub port: u16,
+}
+
+impl FromStr for Destination {
+    type Err = InvalidDestination;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidDestination(s.to_owned());
+        let pos = s.rfind(':').ok_or_else(invalid)?;
+        let port = s[pos + 1..].parse().map_err(|_| invalid())?;
+
+        Ok(Destination {
+            host: s[..pos].to_owned(),
+            port,
+        })
+    }
+}
+
+/// Add a branch to `tee` sending the video as RTP over UDP, handing the
+/// session description to `publish`. Raw video is described once its size
+/// is known, and again when it changes.
+pub fn add_branch(
+    bin: &gst::Bin,
+    tee: &gst::Element,
+    destination: &Destination,
+    codec: Codec,
+    publish: impl Fn(String) + Send + Sync + 'static,
+) -> Result<(), Error> {
+    let queue = make("queue")?;
+    let videoconvert = make("videoconvert")?;
+    let udpsink = make("udpsink")?;
+
+    udpsink.set_property("host", &destination.host)?;
+    udpsink.set_property("port", &(destination.port as i32))?;
+    // Frames are timestamped for display, the network side should not wait on them
+    udpsink.set_property("sync", &false)?;
+
+    let mut branch = vec![queue, videoconvert];
+    match codec {
+        Codec::H264 => {
+            let encoder = make("x264enc")?;
+            encoder.set_property_from_str("tune", "zerolatency");
+            encoder.set_property("key-int-max", &30u32)?;
+            let payloader = make("rtph264pay")?;
+            // Resend SPS/PPS with every keyframe so receivers can join at any time
+            payloader.set_property("config-interval", &-1i32)?;
+            payloader.set_property("pt", &PAYLOAD_TYPE)?;
+            branch.push(encoder);
+            branch.push(payloader);
+            publish(sdp(destination, codec, None));
+        }
+        Codec::Raw => {
+            // rtpvrawpay doesn't take BGRx, send plain RGB
+            let capsfilter = make("capsfilter")?;
+            let caps = gst::Caps::builder("video/x-raw")
+                .field("format", &"RGB")
+                .build();
+            capsfilter.set_property("caps", &caps)?;
+            let payloader = make("rtpvrawpay")?;
+            payloader.set_property("pt", &PAYLOAD_TYPE)?;
+
+            let destination = destination.clone();
+            let pad = payloader.get_static_pad("sink").unwrap();
+            pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+                if let Some(gst::PadProbeData::Event(event)) = &info.data {
+                    if let gst::EventView::Caps(caps) = event.view() {
+                        if let Ok(video_info) = gst_video::VideoInfo::from_caps(caps.get_caps()) {
+                            let size = (video_info.width(), video_info.height());
+                            publish(sdp(&destination, codec, Some(size)));
+                        }
+                    }
+                }
+                gst::PadProbeReturn::Ok
+            });
+            branch.push(capsfilter);
+            branch.push(payloader);
+        }
+    }
+    branch.push(udpsink);
+
+    let branch: Vec<&gst::Element> = branch.iter().collect();
+    bin.add_many(&branch)?;
+    gst::Element::link_many(&branch)?;
+    tee.link(branch[0])?;
+
+    Ok(())
+}
+
+/// Session description a receiver can be started with, e.g.
+/// `ffplay -protocol_whitelist file,udp,rtp stream.sdp`. Raw video is
+/// described with its `size`.
+pub fn sdp(destination: &Destination, codec: Codec, size: Option<(u32, u32)>) -> String {
+    let ip_version = if destination.host.contains(':') { "IP6" } else { "IP4" };
+
+    let mut sdp = format!(
+        "v=0\r\n\
+         o=- 0 0 IN {ip} {host}\r\n\
+         s=waylandsink-with-video-overlay\r\n\
+         c=IN {ip} {host}\r\n\
+         t=0 0\r\n\
+         m=video {port} RTP/AVP {pt}\r\n",
+        ip = ip_version,
+        host = destination.host,
+        port = destination.port,
+        pt = PAYLOAD_TYPE,
+    );
+
+    match codec {
+        Codec::H264 => {
+            sdp += &format!("a=rtpmap:{} H264/90000\r\n", PAYLOAD_TYPE);
+            sdp += &format!("a=fmtp:{} packetization-mode=1\r\n", PAYLOAD_TYPE);
+        }
+        Codec::Raw => {
+            sdp += &format!("a=rtpmap:{} raw/90000\r\n", PAYLOAD_TYPE);
+            let (width, height) = size.unwrap_or_default();
+            sdp += &format!(
+                "a=fmtp:{} sampling=RGB; width={}; height={}; depth=8; colorimetry=SMPTE240M\r\n",
+                PAYLOAD_TYPE, width, height
+            );
+        }
+    }
+
+    sdp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_destinations() {
+        let destination: Destination = "::1:5000".parse().unwrap();
+        assert_eq!((destination.host.as_str(), destination.port), ("::1", 5000));
+        assert!("localhost".parse::<Destination>().is_err());
+        assert!("localhost:port".parse::<Destination>().is_err());
+        assert_eq!("raw".parse::<Codec>().unwrap(), Codec::Raw);
+        assert!("vp8".parse::<Codec>().is_err());
+    }
+
+    #[test]
+    fn describes_h264_streams() {
+        let destination = "192.168.1.2:5000".parse().unwrap();
+        let sdp = sdp(&destination, Codec::H264, None);
+        assert!(sdp.starts_with("v=0\r\n"));
+        assert!(sdp.contains("c=IN IP4 192.168.1.2\r\n"));
+        assert!(sdp.contains("m=video 5000 RTP/AVP 96\r\n"));
+        assert!(sdp.contains("a=rtpmap:96 H264/90000\r\n"));
+        assert!(sdp.contains("a=fmtp:96 packetization-mode=1\r\n"));
+    }
+
+    #[test]
+    fn describes_raw_streams_with_their_size() {
+        let destination = "ff02::1:5004".parse().unwrap();
+        let sdp = sdp(&destination, Codec::Raw, Some((1280, 720)));
+        assert!(sdp.contains("c=IN IP6 ff02::1\r\n"));
+        assert!(sdp.contains("a=rtpmap:96 raw/90000\r\n"));
+        assert!(sdp.contains("width=1280; height=720;"));
+    }
+}
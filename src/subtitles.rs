@@ -0,0 +1,69 @@
+//! Subtitle track selection, for files played through `playbin`.
+//!
+//! `playbin` renders embedded subtitle tracks, and external `.srt` and
+//! similar files given as its `suburi`, with `subtitleoverlay`.
+
+use anyhow::{anyhow, Error};
+use gst::glib;
+use gst::prelude::*;
+
+// GstPlayFlags
+const TEXT_FLAG: &str = "text";
+
+pub struct Subtitles {
+    playbin: gst::Element,
+}
+
+impl Subtitles {
+    pub fn new(playbin: gst::Element) -> Self {
+        Subtitles { playbin }
+    }
+
+    /// Show the next subtitle track, returns its index and the track count.
+    pub fn cycle(&self) -> Result<(i32, i32), Error> {
+        let count = self.playbin.get_property("n-text")?.get_some::<i32>()?;
+        if count == 0 {
+            return Ok((-1, 0));
+        }
+
+        let current = self.playbin.get_property("current-text")?.get_some::<i32>()?;
+        let next = (current + 1) % count;
+        self.playbin.set_property("current-text", &next)?;
+        self.set_shown(true)?;
+
+        Ok((next, count))
+    }
+
+    /// Switch subtitles off and on, returns whether they are now shown.
+    pub fn toggle(&self) -> Result<bool, Error> {
+        let shown = !self.is_shown()?;
+        self.set_shown(shown)?;
+        Ok(shown)
+    }
+
+    fn flags(&self) -> Result<(glib::FlagsClass, glib::Value), Error> {
+        let flags = self.playbin.get_property("flags")?;
+        let class = glib::FlagsClass::new(flags.type_()).ok_or_else(|| anyhow!("Invalid flags"))?;
+        Ok((class, flags))
+    }
+
+    fn is_shown(&self) -> Result<bool, Error> {
+        let (class, flags) = self.flags()?;
+        Ok(class.is_set_by_nick(&flags, TEXT_FLAG))
+    }
+
+    fn set_shown(&self, shown: bool) -> Result<(), Error> {
+        let (class, flags) = self.flags()?;
+        let builder = class
+            .builder_with_value(flags)
+            .ok_or_else(|| anyhow!("Invalid flags"))?;
+        let builder = if shown {
+            builder.set_by_nick(TEXT_FLAG)
+        } else {
+            builder.unset_by_nick(TEXT_FLAG)
+        };
+        let flags = builder.build().ok_or_else(|| anyhow!("Invalid flags"))?;
+        self.playbin.set_property_generic("flags", &flags)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! Switching to another source at the same playback position, such as from
+//! a proxy to the full resolution media of the same content.
+//!
+//! playbin only takes a new URI from the ready state, which drops the
+//! position. It is queried beforehand, and once the new source has
+//! prerolled in the paused state, a flushing seek brings it back there
+//! before playback resumes.
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+use tracing::warn;
+
+use crate::sink::SurfaceGuard;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "The synthetic source can't be swapped")]
+pub struct NotSwappable;
+
+enum Step {
+    /// The new source is prerolling, to be seeked to `position`.
+    Prerolling {
+        position: Option<gst::ClockTime>,
+        resume: bool,
+    },
+    /// The seek is done once the pipeline has prerolled again.
+    Seeking { resume: bool },
+}
+
+pub struct SourceSwap {
+    step: Option<Step>,
+}
+
+impl SourceSwap {
+    pub fn new() -> Self {
+        SourceSwap { step: None }
+    }
+
+    /// Play `uri` in place of the current source of `pipeline`, from the
+    /// current position.
+    pub fn swap(&mut self, pipeline: &gst::Pipeline, uri: &str) -> Result<(), Error> {
+        if pipeline.find_property("uri").is_none() {
+            return Err(NotSwappable.into());
+        }
+
+        let position = pipeline.query_position::<gst::ClockTime>();
+        let (_, current, pending) = pipeline.get_state(gst::ClockTime::from_seconds(0));
+        let resume = current == gst::State::Playing || pending == gst::State::Playing;
+
+        pipeline.set_state(gst::State::Ready)?;
+        pipeline.set_property("uri", &uri)?;
+        pipeline.set_state(gst::State::Paused)?;
+        self.step = Some(Step::Prerolling { position, resume });
+        Ok(())
+    }
+
+    /// Carry on with the swap as the pipeline prerolls, from its bus
+    /// messages, resuming playback through `surface_guard`.
+    pub fn on_message(
+        &mut self,
+        msg: &gst::Message,
+        pipeline: &gst::Pipeline,
+        surface_guard: &SurfaceGuard,
+    ) {
+        let from_pipeline = msg.get_src().as_ref() == Some(pipeline.upcast_ref::<gst::Object>());
+        if !from_pipeline || msg.get_type() != gst::MessageType::AsyncDone {
+            return;
+        }
+
+        self.step = match self.step.take() {
+            Some(Step::Prerolling {
+                position: Some(position),
+                resume,
+            }) => {
+                let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE;
+                match pipeline.seek_simple(flags, position) {
+                    Ok(()) => Some(Step::Seeking { resume }),
+                    Err(err) => {
+                        warn!("Failed to seek the new source to {}: {}", position, err);
+                        Self::resume(pipeline, resume, surface_guard);
+                        None
+                    }
+                }
+            }
+            Some(Step::Prerolling { position: None, resume })
+            | Some(Step::Seeking { resume }) => {
+                Self::resume(pipeline, resume, surface_guard);
+                None
+            }
+            None => None,
+        };
+    }
+
+    fn resume(pipeline: &gst::Pipeline, resume: bool, surface_guard: &SurfaceGuard) {
+        if !resume {
+            return;
+        }
+        if let Err(err) = surface_guard.set_state(pipeline, gst::State::Playing) {
+            warn!("Failed to play the new source: {}", err);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Commit throttling, for `--swap-interval N`.
+//!
+//! The sink attaches and commits every frame it is handed, which on a high
+//! refresh rate display can be close to every refresh. With a swap interval
+//! of N, frames are let through to the sink at most every Nth refresh of the
+//! output the window is on, at 60 Hz until it is known, and the ones in
+//! between are dropped at their time, as for `--still-frames`. The first
+//! frame after a flush, and the ones of a paused pipeline, always go through.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use gst::prelude::*;
+use tracing::debug;
+
+use crate::still::{self, FrameWait};
+
+/// The refresh period assumed until the output is known, in nanoseconds.
+const DEFAULT_REFRESH: u64 = 1_000_000_000 / 60;
+
+pub struct SwapInterval {
+    interval: u64,
+    /// Shortest time between two frames let through, in nanoseconds.
+    period: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl SwapInterval {
+    /// Let frames through to `sink` at most every `interval` refreshes.
+    pub fn new(sink: &gst::Element, interval: u32) -> Self {
+        let interval = u64::from(interval.max(1));
+        let period = Arc::new(AtomicU64::new(interval * DEFAULT_REFRESH));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (probe_period, count) = (period.clone(), dropped.clone());
+        // Running time of the last frame let through
+        let last = Arc::new(Mutex::new(None::<gst::ClockTime>));
+        let waiting = FrameWait::default();
+        let element = sink.clone();
+        let pad = sink.get_static_pad("sink").unwrap();
+        let mask = gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_FLUSH;
+        pad.add_probe(mask, move |pad, info| {
+            let buffer = match &info.data {
+                Some(gst::PadProbeData::Buffer(buffer)) => buffer,
+                Some(gst::PadProbeData::Event(event)) => {
+                    if let gst::EventView::FlushStart(_) = event.view() {
+                        waiting.interrupt();
+                    }
+                    *last.lock().unwrap() = None;
+                    return gst::PadProbeReturn::Ok;
+                }
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            let running_time = match still::running_time(pad, buffer) {
+                Some(running_time) => running_time,
+                None => return gst::PadProbeReturn::Ok,
+            };
+
+            let period = probe_period.load(Ordering::Relaxed);
+            // Half a refresh of slack, for the jitter of the timestamps
+            let slack = period / interval / 2;
+            let too_soon = last.lock().unwrap().map_or(false, |last| {
+                running_time.nseconds().unwrap_or(0) + slack
+                    < last.nseconds().unwrap_or(0) + period
+            });
+            let (_, state, _) = element.get_state(gst::ClockTime::from_seconds(0));
+            if !too_soon || state != gst::State::Playing {
+                *last.lock().unwrap() = Some(running_time);
+                return gst::PadProbeReturn::Ok;
+            }
+
+            waiting.wait(&element, pad, buffer);
+            count.fetch_add(1, Ordering::Relaxed);
+            gst::PadProbeReturn::Drop
+        });
+        SwapInterval {
+            interval,
+            period,
+            dropped,
+        }
+    }
+
+    /// Follow the refresh rate of the output, in mHz as advertised.
+    pub fn set_refresh_rate(&self, refresh_rate: i32) {
+        if refresh_rate <= 0 {
+            return;
+        }
+        let refresh = 1_000_000_000_000 / refresh_rate as u64;
+        debug!(refresh, interval = self.interval, "Throttling commits");
+        self.period.store(self.interval * refresh, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{} frames dropped", self.dropped.load(Ordering::Relaxed))
+    }
+}
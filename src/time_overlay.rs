@@ -0,0 +1,65 @@
+//! Timestamp and wall clock overlays, for debugging latency and pacing.
+//!
+//! `timeoverlay` writes the timestamp of each frame in the top right corner,
+//! and `clockoverlay` the local time it went through, in the bottom right
+//! one, both rendered into the frame itself. Filming the screen along with
+//! a clock, or comparing with the timestamps of the sink, then tells how
+//! long frames take to be shown and how evenly.
+
+use anyhow::Error;
+use gst::prelude::*;
+
+use crate::make;
+
+pub struct TimeOverlays {
+    time: gst::Element,
+    clock: gst::Element,
+    time_shown: bool,
+    clock_shown: bool,
+}
+
+impl TimeOverlays {
+    /// The overlays, with the timestamps shown if `time` and the wall clock
+    /// if `clock`.
+    pub fn new(time: bool, clock: bool) -> Result<Self, Error> {
+        let time_overlay = make("timeoverlay")?;
+        time_overlay.set_property_from_str("halignment", "right");
+        time_overlay.set_property_from_str("valignment", "top");
+        let clock_overlay = make("clockoverlay")?;
+        clock_overlay.set_property_from_str("halignment", "right");
+        clock_overlay.set_property_from_str("valignment", "bottom");
+        clock_overlay.set_property("time-format", &"%H:%M:%S")?;
+        for overlay in &[&time_overlay, &clock_overlay] {
+            overlay.set_property("font-desc", &"Monospace 16")?;
+            overlay.set_property("shaded-background", &true)?;
+        }
+        time_overlay.set_property("silent", &!time)?;
+        clock_overlay.set_property("silent", &!clock)?;
+
+        Ok(TimeOverlays {
+            time: time_overlay,
+            clock: clock_overlay,
+            time_shown: time,
+            clock_shown: clock,
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.time, &self.clock]
+    }
+
+    /// Hide or show the timestamps, returns whether they are shown.
+    pub fn toggle_time(&mut self) -> bool {
+        self.time_shown = !self.time_shown;
+        let _ = self.time.set_property("silent", &!self.time_shown);
+        self.time_shown
+    }
+
+    /// Hide or show the wall clock, returns whether it is shown.
+    pub fn toggle_clock(&mut self) -> bool {
+        self.clock_shown = !self.clock_shown;
+        let _ = self.clock.set_property("silent", &!self.clock_shown);
+        self.clock_shown
+    }
+}
@@ -0,0 +1,78 @@
+//! SMPTE timecode, shown over the video and stamped on the synthetic frames.
+//!
+//! Timecodes travel along the buffers as `GstVideoTimeCodeMeta`, put there
+//! by demuxers of formats carrying them or by capture cards. The overlay
+//! reads the meta of each buffer right before rendering its text over it,
+//! so that the timecode shown is the one of the frame.
+//!
+//! The synthetic source can stamp its frames as well, starting from the local
+//! time of day: the timecode is jam-synced to the wall clock when the source
+//! is created, and counts frames from there.
+
+use anyhow::Error;
+use gst::prelude::*;
+use gst_video::{ValidVideoTimeCode, VideoTimeCodeFlags, VideoTimeCodeMeta};
+
+use crate::{make, MissingElement};
+
+/// A timecode of `fps` for the current local time of day.
+pub fn jam_synced(fps: gst::Fraction) -> Result<ValidVideoTimeCode, Error> {
+    let now = gst::glib::DateTime::new_now_local();
+    let (numer, denom) = (*fps.numer() as i64, *fps.denom() as i64);
+    let frames = now.get_microsecond() as i64 * numer / denom / 1_000_000;
+    Ok(ValidVideoTimeCode::new(
+        fps,
+        Some(&now),
+        VideoTimeCodeFlags::NONE,
+        now.get_hour() as u32,
+        now.get_minute() as u32,
+        now.get_second() as u32,
+        frames as u32,
+        0,
+    )?)
+}
+
+/// Stamp `buffer` with `timecode`, and move it to the next frame.
+pub fn stamp(buffer: &mut gst::BufferRef, timecode: &mut ValidVideoTimeCode) {
+    VideoTimeCodeMeta::add(buffer, timecode);
+    timecode.increment_frame();
+}
+
+/// The timecode of each frame, in the top left corner of the video.
+pub struct TimecodeOverlay {
+    overlay: gst::Element,
+}
+
+impl TimecodeOverlay {
+    pub fn new() -> Result<Self, Error> {
+        let overlay = make("textoverlay")?;
+        overlay.set_property_from_str("halignment", "left");
+        overlay.set_property_from_str("valignment", "top");
+        overlay.set_property("font-desc", &"Monospace 16")?;
+        overlay.set_property("shaded-background", &true)?;
+
+        let pad = overlay
+            .get_static_pad("video_sink")
+            .ok_or(MissingElement("textoverlay video_sink pad"))?;
+        let text_overlay = overlay.downgrade();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+            let timecode = match &info.data {
+                Some(gst::PadProbeData::Buffer(buffer)) => buffer
+                    .get_meta::<VideoTimeCodeMeta>()
+                    .map(|meta| meta.get_tc().to_string()),
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            if let Some(overlay) = text_overlay.upgrade() {
+                let _ = overlay.set_property("text", &timecode.unwrap_or_default());
+            }
+            gst::PadProbeReturn::Ok
+        });
+
+        Ok(TimecodeOverlay { overlay })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.overlay]
+    }
+}
@@ -0,0 +1,246 @@
+//! Time-shifting of live sources, for `--timeshift MINUTES`.
+//!
+//! A live RTSP or UDP input is recorded as MPEG-TS into a `queue2` ring
+//! buffer backed by a temporary file, and decodebin plays it from there in
+//! pull mode, so that it can seek within what was recorded. The recording
+//! elements are in a bin whose state is locked: they keep recording while
+//! the rest of the pipeline is paused, and rewinding goes back up to the
+//! size of the ring buffer, `MINUTES` at `MAX_BITRATE`.
+//!
+//! The last seconds of the recording are also kept in memory, to save as an
+//! instant replay with `Timeshift::save_replay`.
+//!
+//! UDP inputs are taken to carry MPEG-TS already. The H.264, H.265 and AAC
+//! streams of RTSP inputs are muxed into it, their other streams are left
+//! out.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::{debug, info, warn};
+
+use crate::replay::Replay;
+use crate::make;
+
+/// Highest bitrate the ring buffer is sized for, in bits per second.
+const MAX_BITRATE: u64 = 20_000_000;
+
+/// Whether `uri` is a live input that can be time-shifted.
+pub fn is_live(uri: &str) -> bool {
+    uri.starts_with("rtsp://") || uri.starts_with("rtsps://") || uri.starts_with("udp://")
+}
+
+/// The depayloader and parser for an RTP stream of `encoding`, if it is
+/// one muxed into the recording.
+fn depayloader(encoding: &str) -> Option<(&'static str, &'static str)> {
+    match encoding {
+        "H264" => Some(("rtph264depay", "h264parse")),
+        "H265" => Some(("rtph265depay", "h265parse")),
+        "MPEG4-GENERIC" => Some(("rtpmp4gdepay", "aacparse")),
+        _ => None,
+    }
+}
+
+/// The recording elements for `uri`, in a bin with a `src` ghost pad
+/// producing MPEG-TS, and the replay keeping the last `replay` of it.
+fn create_source(uri: &str, replay: Duration) -> Result<(gst::Bin, Replay), Error> {
+    let bin = gst::Bin::new(Some("timeshift-source"));
+    let src = if uri.starts_with("udp://") {
+        let udpsrc = make("udpsrc")?;
+        udpsrc.set_property("uri", &uri)?;
+        let caps = gst::Caps::builder("video/mpegts")
+            .field("systemstream", &true)
+            .field("packetsize", &188i32)
+            .build();
+        udpsrc.set_property("caps", &caps)?;
+        let tsparse = make("tsparse")?;
+        tsparse.set_property("set-timestamps", &true)?;
+        bin.add_many(&[&udpsrc, &tsparse])?;
+        udpsrc.link(&tsparse)?;
+        tsparse
+    } else {
+        let rtspsrc = make("rtspsrc")?;
+        rtspsrc.set_property("location", &uri)?;
+        let mux = make("mpegtsmux")?;
+        bin.add_many(&[&rtspsrc, &mux])?;
+
+        let bin_weak = bin.downgrade();
+        let mux_weak = mux.downgrade();
+        rtspsrc.connect_pad_added(move |_, pad| {
+            let (bin, mux) = match (bin_weak.upgrade(), mux_weak.upgrade()) {
+                (Some(bin), Some(mux)) => (bin, mux),
+                _ => return,
+            };
+            let encoding = pad
+                .get_current_caps()
+                .and_then(|caps| {
+                    caps.get_structure(0)?
+                        .get::<String>("encoding-name")
+                        .ok()
+                        .flatten()
+                })
+                .unwrap_or_default();
+            match depayloader(&encoding) {
+                Some(names) => {
+                    if let Err(err) = add_stream(&bin, &mux, pad, names) {
+                        warn!("Failed to record the {} stream: {}", encoding, err);
+                    }
+                }
+                None => info!("Not recording the {} stream", encoding),
+            }
+        });
+        mux
+    };
+
+    let tee = make("tee")?;
+    let queue = make("queue")?;
+    bin.add_many(&[&tee, &queue])?;
+    gst::Element::link_many(&[&src, &tee, &queue])?;
+    let replay = Replay::add_branch(&bin, &tee, replay)?;
+
+    let pad = queue.get_static_pad("src").unwrap();
+    bin.add_pad(&gst::GhostPad::with_target(Some("src"), &pad)?)?;
+    Ok((bin, replay))
+}
+
+/// Mux the RTP stream of `pad` into the recording, with the depayloader
+/// and parser `names`.
+fn add_stream(
+    bin: &gst::Bin,
+    mux: &gst::Element,
+    pad: &gst::Pad,
+    (depay, parse): (&'static str, &'static str),
+) -> Result<(), Error> {
+    let depay = make(depay)?;
+    let parse = make(parse)?;
+    bin.add_many(&[&depay, &parse])?;
+    depay.link(&parse)?;
+    parse.link(mux)?;
+    depay.sync_state_with_parent()?;
+    parse.sync_state_with_parent()?;
+    pad.link(&depay.get_static_pad("sink").unwrap())?;
+    Ok(())
+}
+
+/// The recording of a live input, which runs on its own.
+pub struct Timeshift {
+    source: gst::Bin,
+    replay: Replay,
+    started: bool,
+}
+
+impl Timeshift {
+    /// Start recording once the pipeline is on its way to paused, from
+    /// which decodebin waits for data. The recording is left running from
+    /// then on, until dropped.
+    pub fn update(&mut self, pipeline: &gst::Pipeline) {
+        if self.started {
+            return;
+        }
+        let (_, current, pending) = pipeline.get_state(gst::ClockTime::from_seconds(0));
+        let is_prerolling = |state| matches!(state, gst::State::Paused | gst::State::Playing);
+        if !is_prerolling(current) && !is_prerolling(pending) {
+            return;
+        }
+        self.started = true;
+        if let Err(err) = self.source.set_state(gst::State::Playing) {
+            warn!("Failed to start recording the live input: {}", err);
+        }
+    }
+
+    /// Save the last seconds of the recording, returns the path of the file.
+    pub fn save_replay(&self) -> Result<PathBuf, Error> {
+        self.replay.save()
+    }
+}
+
+impl Drop for Timeshift {
+    fn drop(&mut self) {
+        let _ = self.source.set_state(gst::State::Null);
+    }
+}
+
+/// The pipeline playing the live `uri` from a ring buffer of `minutes`,
+/// into the `video` and `audio` bins, keeping the last `replay` of it for
+/// instant replays.
+pub fn create_pipeline(
+    uri: &str,
+    minutes: u32,
+    replay: Duration,
+    video: &gst::Bin,
+    audio: &gst::Bin,
+) -> Result<(gst::Pipeline, Timeshift), Error> {
+    let pipeline = gst::Pipeline::new(None);
+    let (source, replay) = create_source(uri, replay)?;
+    // Recording goes on whatever the state of the pipeline
+    source.set_locked_state(true);
+
+    let queue = make("queue2")?;
+    let size = u64::from(minutes) * 60 * MAX_BITRATE / 8;
+    queue.set_property("ring-buffer-max-size", &size)?;
+    let template = std::env::temp_dir().join("waylandsink-timeshift-XXXXXX");
+    queue.set_property("temp-template", &template.to_string_lossy().into_owned())?;
+    for name in &["max-size-buffers", "max-size-bytes"] {
+        queue.set_property(*name, &0u32)?;
+    }
+    queue.set_property("max-size-time", &0u64)?;
+    let decodebin = make("decodebin")?;
+
+    pipeline.add_many(&[source.upcast_ref(), &queue, &decodebin, video.upcast_ref()])?;
+    gst::Element::link_many(&[source.upcast_ref(), &queue, &decodebin])?;
+
+    // The audio bin only joins once there is audio, or it would never preroll
+    let pipeline_weak = pipeline.downgrade();
+    let video_weak = video.downgrade();
+    let audio_weak = audio.downgrade();
+    decodebin.connect_pad_added(move |_, pad| {
+        let is_video = pad
+            .get_current_caps()
+            .and_then(|caps| caps.get_structure(0).map(|s| s.get_name().starts_with("video/")))
+            .unwrap_or(false);
+        let bin = if is_video {
+            video_weak.upgrade()
+        } else {
+            audio_weak.upgrade()
+        };
+        let (pipeline, bin) = match (pipeline_weak.upgrade(), bin) {
+            (Some(pipeline), Some(bin)) => (pipeline, bin),
+            _ => return,
+        };
+        if bin.get_parent().is_none() {
+            if let Err(err) = pipeline.add(&bin) {
+                warn!("Failed to play the time-shifted audio: {}", err);
+                return;
+            }
+            if let Err(err) = bin.sync_state_with_parent() {
+                warn!("Failed to play the time-shifted audio: {}", err);
+                return;
+            }
+        }
+        let sink = match bin.get_static_pad("sink") {
+            Some(sink) if !sink.is_linked() => sink,
+            _ => return,
+        };
+        match pad.link(&sink) {
+            Ok(_) => debug!(video = is_video, "Linked the time-shifted stream"),
+            Err(err) => warn!("Failed to link the time-shifted stream: {:?}", err),
+        }
+    });
+
+    info!(
+        "Time-shifting {} up to {} minutes, in up to {} MB",
+        uri,
+        minutes,
+        size / 1_000_000
+    );
+    Ok((
+        pipeline,
+        Timeshift {
+            source,
+            replay,
+            started: false,
+        },
+    ))
+}
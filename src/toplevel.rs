@@ -0,0 +1,80 @@
+//! The surface the video is shown on, an sctk window or a layer surface.
+//!
+//! The event loop works with either through the same calls. Those that only
+//! make sense for windows, such as titles or fullscreen, are ignored by layer
+//! surfaces.
+
+use sctk::environment::Environment;
+use sctk::reexports::client::protocol::wl_surface;
+use sctk::window::{ConceptConfig, ConceptFrame, Window};
+
+use crate::layer_shell::LayerSurface;
+use crate::ThemedFrameExample;
+
+pub enum Toplevel {
+    Window(Window<ConceptFrame>),
+    Layer(LayerSurface),
+}
+
+impl Toplevel {
+    pub fn surface(&self) -> &wl_surface::WlSurface {
+        match self {
+            Toplevel::Window(window) => window.surface(),
+            Toplevel::Layer(layer) => layer.surface(),
+        }
+    }
+
+    /// Whether the surface waits for a configure event before its first
+    /// buffer, which is all but windows of the legacy `wl_shell`.
+    pub fn needs_configure(&self, env: &Environment<ThemedFrameExample>) -> bool {
+        match self {
+            Toplevel::Window(_) => env.get_shell().unwrap().needs_configure(),
+            Toplevel::Layer(_) => true,
+        }
+    }
+
+    pub fn set_title(&self, title: String) {
+        if let Toplevel::Window(window) = self {
+            window.set_title(title);
+        }
+    }
+
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if let Toplevel::Window(window) = self {
+            if fullscreen {
+                window.set_fullscreen(None);
+            } else {
+                window.unset_fullscreen();
+            }
+        }
+    }
+
+    /// Keep the size of the window between `min` and `max` while floating.
+    pub fn set_size_limits(&mut self, min: (u32, u32), max: (u32, u32)) {
+        if let Toplevel::Window(window) = self {
+            window.set_min_size(Some(min));
+            window.set_max_size(Some(max));
+        }
+    }
+
+    /// Follow a new size, the compositor's for layer surfaces.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if let Toplevel::Window(window) = self {
+            window.resize(width, height);
+        }
+    }
+
+    /// Draw the decorations with `config` from their next refresh.
+    pub fn set_frame_config(&mut self, config: ConceptConfig) {
+        if let Toplevel::Window(window) = self {
+            window.set_frame_config(config);
+        }
+    }
+
+    /// Redraw the decorations.
+    pub fn refresh(&mut self) {
+        if let Toplevel::Window(window) = self {
+            window.refresh();
+        }
+    }
+}
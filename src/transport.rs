@@ -0,0 +1,161 @@
+//! Playback rate changes and frame stepping.
+
+use gst::prelude::*;
+use tracing::warn;
+
+pub const RATES: [f64; 7] = [0.25, 0.5, 1.0, 1.5, 2.0, 3.0, 4.0];
+const NORMAL: usize = 2;
+
+pub struct Transport {
+    rate: usize,
+    // Frames are being stepped backwards, with a reverse segment
+    reverse: bool,
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Transport {
+            rate: NORMAL,
+            reverse: false,
+        }
+    }
+
+    /// The current playback rate.
+    pub fn rate(&self) -> f64 {
+        RATES[self.rate]
+    }
+
+    /// Follow the pipeline playing at `rate`, the closest of `RATES`, in
+    /// reverse if negative.
+    pub fn set_rate(&mut self, rate: f64) {
+        let distance = |index: &usize| ((RATES[*index] - rate.abs()).abs() * 1000.0) as i64;
+        self.rate = (0..RATES.len()).min_by_key(distance).unwrap();
+        self.reverse = rate < 0.0;
+    }
+
+    /// Play faster, forward.
+    pub fn speed_up(&mut self, pipeline: &gst::Pipeline) {
+        if self.rate + 1 < RATES.len() {
+            self.rate += 1;
+            self.reverse = false;
+            self.apply(pipeline);
+        }
+    }
+
+    /// Play slower, forward.
+    pub fn slow_down(&mut self, pipeline: &gst::Pipeline) {
+        if self.rate > 0 {
+            self.rate -= 1;
+            self.reverse = false;
+            self.apply(pipeline);
+        }
+    }
+
+    /// Go back to a forward segment after frames were stepped backwards, for
+    /// anything but stepping.
+    pub fn forward(&mut self, pipeline: &gst::Pipeline) {
+        if self.turn(true) {
+            self.apply(pipeline);
+        }
+    }
+
+    /// Go back to a forward segment as `pipeline` leaves paused, in case it
+    /// is resumed by other means than a command.
+    pub fn on_message(&mut self, msg: &gst::Message, pipeline: &gst::Pipeline) {
+        if let gst::MessageView::StateChanged(state) = msg.view() {
+            let from_pipeline = msg
+                .get_src()
+                .map_or(false, |src| &src == pipeline.upcast_ref::<gst::Object>());
+            if from_pipeline && state.get_old() == gst::State::Paused {
+                self.forward(pipeline);
+            }
+        }
+    }
+
+    /// Switch to stepping `forward` or backwards, returns whether this is a
+    /// change of direction, which takes a new segment.
+    fn turn(&mut self, forward: bool) -> bool {
+        let turned = self.reverse == forward;
+        self.reverse = !forward;
+        turned
+    }
+
+    /// Step one frame while paused. Stepping backwards first switches to a
+    /// reverse segment ending at the current position, and back again when
+    /// stepping forward.
+    pub fn step(&mut self, pipeline: &gst::Pipeline, forward: bool) {
+        let (_, current, _) = pipeline.get_state(gst::ClockTime::from_seconds(0));
+        if current != gst::State::Paused {
+            return;
+        }
+
+        if self.turn(forward) {
+            self.apply(pipeline);
+        }
+
+        let step = gst::event::Step::new(gst::format::Buffers(Some(1)), 1.0, true, false);
+        if !pipeline.send_event(step) {
+            warn!("Failed to step a frame");
+        }
+    }
+
+    /// Start a segment at the current position with the current rate and direction.
+    fn apply(&self, pipeline: &gst::Pipeline) {
+        let position = match pipeline.query_position::<gst::ClockTime>() {
+            Some(position) => position,
+            None => return,
+        };
+
+        let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE;
+        let result = if self.reverse {
+            pipeline.seek(
+                -self.rate(),
+                flags,
+                gst::SeekType::Set,
+                gst::ClockTime::from_seconds(0),
+                gst::SeekType::Set,
+                position,
+            )
+        } else {
+            pipeline.seek(
+                self.rate(),
+                flags,
+                gst::SeekType::Set,
+                position,
+                gst::SeekType::None,
+                gst::ClockTime::none(),
+            )
+        };
+        if let Err(err) = result {
+            warn!("Failed to change the playback rate to {}: {}", self.rate(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_only_on_a_change_of_direction() {
+        let mut transport = Transport::new();
+        assert!(!transport.turn(true));
+        assert!(transport.turn(false));
+        assert!(!transport.turn(false));
+        assert!(transport.reverse);
+        assert!(transport.turn(true));
+        assert!(!transport.reverse);
+    }
+
+    #[test]
+    fn follows_the_sign_of_the_rate() {
+        let mut transport = Transport::new();
+        transport.set_rate(-0.5);
+        assert_eq!(transport.rate(), 0.5);
+        assert!(transport.reverse);
+
+        transport.set_rate(2.1);
+        assert_eq!(transport.rate(), 2.0);
+        assert!(!transport.reverse);
+    }
+}
@@ -0,0 +1,136 @@
+//! Writing wl_shm buffers off the event loop.
+//!
+//! Sizing the file of a buffer and filling it in takes time in proportion
+//! to its size, which the event loop would otherwise spend instead of
+//! dispatching input and the bus. A worker thread does both, into a new
+//! file for every upload, and hands the file back through a calloop
+//! channel: the event loop only makes the pool and buffer requests, which
+//! don't touch the pixels.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc;
+use std::thread;
+
+use sctk::reexports::calloop::channel::Sender;
+use sctk::reexports::client::protocol::{wl_buffer, wl_shm};
+use tracing::warn;
+use wayland_sink_with_video_overlay::canvas;
+
+/// Pixels drawn by the worker thread, ready to be attached.
+pub struct Upload {
+    /// What `Uploader::upload` returned for it.
+    pub id: u64,
+    file: File,
+    width: u32,
+    height: u32,
+    format: wl_shm::Format,
+}
+
+impl Upload {
+    /// A buffer of the pixels, destroyed once the compositor released it.
+    /// The file stays with the upload, so that buffers can be made again.
+    pub fn buffer(&self, shm: &wl_shm::WlShm) -> wl_buffer::WlBuffer {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let pool = shm.create_pool(self.file.as_raw_fd(), 4 * width * height);
+        let buffer = pool.create_buffer(0, width, height, 4 * width, self.format);
+        // The buffer keeps the memory of the pool
+        pool.destroy();
+        buffer.quick_assign(|buffer, event, _| {
+            if let wl_buffer::Event::Release = event {
+                buffer.destroy();
+            }
+        });
+        buffer.detach()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+struct Job {
+    id: u64,
+    width: u32,
+    height: u32,
+    format: wl_shm::Format,
+    pixel: Box<dyn Fn(u32, u32) -> u32 + Send>,
+}
+
+impl Job {
+    fn run(self) -> io::Result<Upload> {
+        let file = tempfile::tempfile()?;
+        file.set_len(4 * self.width as u64 * self.height as u64)?;
+        let mut writer = BufWriter::new(&file);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                writer.write_all(&canvas::pack(self.format, (self.pixel)(x, y)))?;
+            }
+        }
+        writer.flush()?;
+        drop(writer);
+
+        Ok(Upload {
+            id: self.id,
+            file,
+            width: self.width,
+            height: self.height,
+            format: self.format,
+        })
+    }
+}
+
+pub struct Uploader {
+    jobs: mpsc::Sender<Job>,
+    next_id: u64,
+}
+
+impl Uploader {
+    /// Start the worker thread, which sends the finished uploads to `done`.
+    pub fn new(done: Sender<Upload>) -> Self {
+        let (jobs, pending) = mpsc::channel::<Job>();
+        thread::spawn(move || {
+            // Ends once the uploader is dropped and the queue is empty
+            for job in pending {
+                match job.run() {
+                    Ok(upload) => {
+                        if done.send(upload).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => warn!("Failed to upload a buffer: {}", err),
+                }
+            }
+        });
+
+        Uploader { jobs, next_id: 0 }
+    }
+
+    /// Queue a `width` by `height` buffer in `format`, one of the 32-bit
+    /// RGB ones, with the colors given by `pixel`. Returns the id of the
+    /// `Upload` to come.
+    pub fn upload(
+        &mut self,
+        (width, height): (u32, u32),
+        format: wl_shm::Format,
+        pixel: impl Fn(u32, u32) -> u32 + Send + 'static,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let job = Job {
+            id,
+            width,
+            height,
+            format,
+            pixel: Box::new(pixel),
+        };
+        // The worker only stops once this is dropped
+        let _ = self.jobs.send(job);
+        id
+    }
+}
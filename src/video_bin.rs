@@ -0,0 +1,69 @@
+//! The video path as a bin, to embed into larger pipelines.
+//!
+//! The example builds its whole video path, from the converter through the
+//! display filters and branches to the sink, with `create`. Embedders can do
+//! the same with a sink they set up, such as a waylandsink rendering on their
+//! connection with `gst_ctx::set_wayland_display`, add the bin to a pipeline
+//! of theirs and feed its `sink` ghost pad, while they keep driving the
+//! window and overlay side.
+//!
+//! ```ignore
+//! let video = video_bin::create("video", &[&videoconvert, &sink])?;
+//! pipeline.add(&video)?;
+//! decodebin.link(&video)?;
+//! ```
+
+use anyhow::Error;
+use derive_more::{Display, Error};
+use gst::prelude::*;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "The video path has no elements")]
+pub struct EmptyChain;
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "{} has no sink pad", _0)]
+pub struct NoSinkPad(#[error(not(source))] String);
+
+/// The elements of `chain`, linked in order in a bin named `name`, whose
+/// `sink` ghost pad feeds the first one.
+pub fn create(name: &str, chain: &[&gst::Element]) -> Result<gst::Bin, Error> {
+    let first = chain.first().ok_or(EmptyChain)?;
+    let pad = first
+        .get_static_pad("sink")
+        .ok_or_else(|| NoSinkPad(first.get_name().to_string()))?;
+
+    let bin = gst::Bin::new(Some(name));
+    bin.add_many(chain)?;
+    gst::Element::link_many(chain)?;
+    bin.add_pad(&gst::GhostPad::with_target(Some("sink"), &pad)?)?;
+    Ok(bin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ghosts_the_sink_pad_of_the_first_element() {
+        gst::init().unwrap();
+        let identity = gst::ElementFactory::make("identity", None).unwrap();
+        let sink = gst::ElementFactory::make("fakesink", None).unwrap();
+        let bin = create("video", &[&identity, &sink]).unwrap();
+
+        assert_eq!(bin.get_name(), "video");
+        assert_eq!(bin.get_children().len(), 2);
+        let pad = bin.get_static_pad("sink").unwrap();
+        let ghost = pad.downcast::<gst::GhostPad>().unwrap();
+        let target = ghost.get_target().unwrap();
+        assert_eq!(target.get_parent_element().unwrap(), identity);
+        assert!(identity.get_static_pad("src").unwrap().is_linked());
+    }
+
+    #[test]
+    fn rejects_an_empty_chain() {
+        gst::init().unwrap();
+        let err = create("video", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "The video path has no elements");
+    }
+}
@@ -0,0 +1,148 @@
+//! Windows playing a URI of their own, next to the main window.
+//!
+//! An application opens as many as it wants, each with its own surface,
+//! shared memory pool and pipeline, a playbin with its own sink and video
+//! overlay, over a single Wayland connection and event loop. It creates the
+//! `Window` itself, routing its events to `handle` and its bus to `update`,
+//! and hands the sink already rendering on the surface of the window.
+//! Dropping a `VideoWindow` stops its pipeline and destroys the window.
+//!
+//! ```ignore
+//! let window = env.create_window::<ConceptFrame, _>(surface, None, size, callback)?;
+//! let overlay = set_window(&sink, &display, window.surface())?;
+//! let pools = env.create_double_pool(|_| {})?;
+//! let needs_configure = env.get_shell().unwrap().needs_configure();
+//! let video_window = VideoWindow::new(window, pools, uri, overlay, None, size, needs_configure)?;
+//! ```
+
+use anyhow::Error;
+use gst::prelude::*;
+use gst_video::prelude::*;
+use sctk::shm::DoubleMemPool;
+use sctk::window::{ConceptFrame, Event as WEvent, State, Window};
+use tracing::{info, warn};
+
+use crate::canvas::Canvas;
+
+const BACKGROUND: u32 = 0xFF00_0000;
+
+pub struct VideoWindow {
+    window: Window<ConceptFrame>,
+    pools: DoubleMemPool,
+    pipeline: gst::Pipeline,
+    overlay: gst_video::VideoOverlay,
+    dimensions: (u32, u32),
+    activated: bool,
+}
+
+impl VideoWindow {
+    /// Play `uri` in `window` of `dimensions`, its video through `overlay`,
+    /// a sink rendering on the surface of `window`, and its audio through
+    /// `audio_sink` if given. With `needs_configure`, as for xdg-shell, the
+    /// window is drawn once configured.
+    pub fn new(
+        window: Window<ConceptFrame>,
+        pools: DoubleMemPool,
+        uri: &str,
+        overlay: gst_video::VideoOverlay,
+        audio_sink: Option<&gst::Element>,
+        dimensions: (u32, u32),
+        needs_configure: bool,
+    ) -> Result<Self, Error> {
+        let playbin = gst::ElementFactory::make("playbin", None)?;
+        playbin.set_property("uri", &uri)?;
+        playbin.set_property("video-sink", overlay.upcast_ref::<gst::Element>())?;
+        if let Some(audio_sink) = audio_sink {
+            playbin.set_property("audio-sink", audio_sink)?;
+        }
+        let pipeline = playbin.downcast::<gst::Pipeline>().unwrap();
+
+        let mut video_window = VideoWindow {
+            window,
+            pools,
+            pipeline,
+            overlay,
+            dimensions,
+            activated: false,
+        };
+        if !needs_configure {
+            video_window.draw();
+        }
+        video_window.pipeline.set_state(gst::State::Playing)?;
+        Ok(video_window)
+    }
+
+    /// Whether the window had the keyboard focus at its last configure.
+    pub fn is_activated(&self) -> bool {
+        self.activated
+    }
+
+    /// Handle an event of the window. Its owner drops the window on `Close`.
+    pub fn handle(&mut self, event: WEvent) {
+        match event {
+            WEvent::Close => {}
+            WEvent::Refresh => {
+                self.window.refresh();
+                self.window.surface().commit();
+            }
+            WEvent::Configure { new_size, states } => {
+                if let Some((w, h)) = new_size {
+                    self.window.resize(w, h);
+                    self.dimensions = (w, h);
+                }
+                self.activated = states.contains(&State::Activated);
+                self.window.refresh();
+                self.draw();
+            }
+        }
+    }
+
+    /// Fill the window under the video, which takes all of it.
+    fn draw(&mut self) {
+        let pool = match self.pools.pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let (width, height) = self.dimensions;
+        match Canvas::new(pool, width.max(1), height.max(1)) {
+            Ok(mut canvas) => {
+                canvas.fill(BACKGROUND);
+                canvas.attach(self.window.surface());
+            }
+            Err(err) => warn!("Failed to draw a window: {}", err),
+        }
+        let (width, height) = (width as i32, height as i32);
+        if let Err(err) = self.overlay.set_render_rectangle(0, 0, width, height) {
+            warn!("Failed to place the video of a window: {}", err);
+        }
+        self.window.surface().commit();
+    }
+
+    /// Handle the messages of the pipeline of the window, which stops at the
+    /// end of its URI.
+    pub fn update(&self) {
+        let bus = match self.pipeline.get_bus() {
+            Some(bus) => bus,
+            None => return,
+        };
+        while let Some(msg) = bus.pop() {
+            match msg.view() {
+                gst::MessageView::Eos(_) => {
+                    info!("A window finished playing");
+                    let _ = self.pipeline.set_state(gst::State::Paused);
+                }
+                gst::MessageView::Error(err) => {
+                    warn!("Window: {}", err.get_error());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for VideoWindow {
+    fn drop(&mut self) {
+        // The sink stops rendering before the window and its surface go away
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
@@ -0,0 +1,83 @@
+//! Pausing the video while the window can't be seen.
+//!
+//! xdg-shell has no minimized state, but compositors stop sending frame
+//! callbacks to surfaces that aren't shown, whether minimized, on another
+//! workspace or fully covered. A frame callback is kept pending on the
+//! window surface: when it doesn't come back for a while during playback,
+//! the pipeline is paused, which stops the production of frames, and it
+//! plays again once the callback comes back or the window gets activated.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use gst::prelude::*;
+use sctk::reexports::client::protocol::wl_surface;
+use tracing::{info, warn};
+
+use crate::sink::SurfaceGuard;
+
+/// How long without frame callbacks before the window is deemed hidden.
+const HIDDEN_AFTER: Duration = Duration::from_secs(2);
+
+pub struct AutoPause {
+    surface: wl_surface::WlSurface,
+    /// When the pending frame callback was requested, if there is one.
+    requested: Option<Instant>,
+    done: Rc<Cell<bool>>,
+    paused: bool,
+}
+
+impl AutoPause {
+    pub fn new(surface: &wl_surface::WlSurface) -> Self {
+        AutoPause {
+            surface: surface.clone(),
+            requested: None,
+            done: Rc::new(Cell::new(false)),
+            paused: false,
+        }
+    }
+
+    /// The window got activated, it is shown.
+    pub fn activated(&mut self, pipeline: &gst::Pipeline, surface_guard: &SurfaceGuard) {
+        self.resume(pipeline, surface_guard);
+    }
+
+    /// Pause or resume `pipeline` as the window gets hidden or shown.
+    pub fn update(&mut self, pipeline: &gst::Pipeline, surface_guard: &SurfaceGuard) {
+        if self.done.replace(false) {
+            self.requested = None;
+            self.resume(pipeline, surface_guard);
+        }
+
+        match self.requested {
+            None => {
+                let done = self.done.clone();
+                self.surface.frame().quick_assign(move |_, _, _| done.set(true));
+                self.surface.commit();
+                self.requested = Some(Instant::now());
+            }
+            Some(requested) if !self.paused && requested.elapsed() >= HIDDEN_AFTER => {
+                let (_, current, _) = pipeline.get_state(gst::ClockTime::from_seconds(0));
+                if current == gst::State::Playing
+                    && pipeline.set_state(gst::State::Paused).is_ok()
+                {
+                    info!("Window hidden, pausing");
+                    self.paused = true;
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn resume(&mut self, pipeline: &gst::Pipeline, surface_guard: &SurfaceGuard) {
+        if !self.paused {
+            return;
+        }
+        self.paused = false;
+        info!("Window shown, resuming");
+        if let Err(err) = surface_guard.set_state(pipeline, gst::State::Playing) {
+            warn!("Failed to resume: {}", err);
+        }
+    }
+}
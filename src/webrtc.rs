@@ -0,0 +1,173 @@
+//! Experimental WebRTC publishing of the video through `webrtcbin`.
+//!
+//! Signalling uses the protocol of the GStreamer `webrtc/signalling` example
+//! server: `HELLO <id>`, `SESSION <peer>`, then JSON `sdp` and `ice` messages.
+//! Only plain `ws://` servers are supported.
+
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Error};
+use gst::prelude::*;
+use serde_json::json;
+use tracing::warn;
+use tungstenite::Message;
+
+use crate::make;
+
+const STUN_SERVER: &str = "stun://stun.l.google.com:19302";
+
+/// Add a branch to `tee` publishing the video to `peer` through the
+/// signalling server at `server`.
+pub fn add_branch(
+    bin: &gst::Bin,
+    tee: &gst::Element,
+    server: &str,
+    peer: &str,
+) -> Result<(), Error> {
+    let queue = make("queue")?;
+    let videoconvert = make("videoconvert")?;
+    let encoder = make("vp8enc")?;
+    let payloader = make("rtpvp8pay")?;
+    let webrtcbin = make("webrtcbin")?;
+
+    encoder.set_property("deadline", &1i64)?;
+    webrtcbin.set_property_from_str("bundle-policy", "max-bundle");
+    webrtcbin.set_property("stun-server", &STUN_SERVER)?;
+
+    let branch = [&queue, &videoconvert, &encoder, &payloader, &webrtcbin];
+    bin.add_many(&branch)?;
+    gst::Element::link_many(&branch)?;
+    tee.link(&queue)?;
+
+    // Messages from the GStreamer threads to the signalling connection
+    let (tx, rx) = mpsc::channel::<String>();
+
+    let ice_tx = tx.clone();
+    webrtcbin.connect("on-ice-candidate", false, move |values| {
+        let mline_index = values[1].get_some::<u32>().expect("Invalid argument");
+        let candidate = values[2].get::<String>().expect("Invalid argument").unwrap();
+        let message = json!({
+            "ice": { "candidate": candidate, "sdpMLineIndex": mline_index }
+        });
+        let _ = ice_tx.send(message.to_string());
+        None
+    })?;
+
+    let server = server.to_owned();
+    let peer = peer.to_owned();
+    thread::spawn(move || {
+        if let Err(err) = run_signalling(&server, &peer, &webrtcbin, tx, rx) {
+            warn!("WebRTC signalling failed: {}", err);
+        }
+    });
+
+    Ok(())
+}
+
+fn create_offer(webrtcbin: &gst::Element, tx: mpsc::Sender<String>) -> Result<(), Error> {
+    let element = webrtcbin.clone();
+    let promise = gst::Promise::with_change_func(move |reply| {
+        let offer = match reply {
+            Ok(reply) => reply
+                .get_value("offer")
+                .expect("Invalid reply")
+                .get::<gst_webrtc::WebRTCSessionDescription>()
+                .expect("Invalid reply")
+                .unwrap(),
+            Err(err) => {
+                warn!("Failed to create the WebRTC offer: {:?}", err);
+                return;
+            }
+        };
+
+        let _ = element.emit("set-local-description", &[&offer, &None::<gst::Promise>]);
+
+        let message = json!({
+            "sdp": { "type": "offer", "sdp": offer.get_sdp().as_text().unwrap() }
+        });
+        let _ = tx.send(message.to_string());
+    });
+
+    webrtcbin.emit("create-offer", &[&None::<gst::Structure>, &promise])?;
+    Ok(())
+}
+
+fn handle_message(webrtcbin: &gst::Element, message: &serde_json::Value) -> Result<(), Error> {
+    if let Some(sdp) = message.get("sdp") {
+        if sdp["type"] != "answer" {
+            bail!("Unexpected SDP {}", sdp["type"]);
+        }
+
+        let text = sdp["sdp"].as_str().unwrap_or_default();
+        let message = gst_sdp::SDPMessage::parse_buffer(text.as_bytes())
+            .map_err(|_| anyhow::anyhow!("Invalid SDP answer"))?;
+        let answer =
+            gst_webrtc::WebRTCSessionDescription::new(gst_webrtc::WebRTCSDPType::Answer, message);
+        webrtcbin.emit("set-remote-description", &[&answer, &None::<gst::Promise>])?;
+    } else if let Some(ice) = message.get("ice") {
+        let candidate = ice["candidate"].as_str().unwrap_or_default();
+        let mline_index = ice["sdpMLineIndex"].as_u64().unwrap_or_default() as u32;
+        webrtcbin.emit("add-ice-candidate", &[&mline_index, &candidate])?;
+    }
+
+    Ok(())
+}
+
+fn run_signalling(
+    server: &str,
+    peer: &str,
+    webrtcbin: &gst::Element,
+    tx: mpsc::Sender<String>,
+    rx: mpsc::Receiver<String>,
+) -> Result<(), Error> {
+    let url = url::Url::parse(server)?;
+    let address = url
+        .socket_addrs(|| Some(80))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Cannot resolve {}", server))?;
+
+    let (mut socket, _) = tungstenite::client(url, TcpStream::connect(address)?)
+        .map_err(|err| anyhow::anyhow!("WebSocket handshake failed: {}", err))?;
+    // Poll the socket so that outgoing messages aren't stuck behind a blocking read
+    socket
+        .get_mut()
+        .set_read_timeout(Some(Duration::from_millis(100)))?;
+
+    let our_id = std::process::id();
+    socket.write_message(Message::Text(format!("HELLO {}", our_id)))?;
+
+    let mut tx = Some(tx);
+    loop {
+        while let Ok(text) = rx.try_recv() {
+            socket.write_message(Message::Text(text))?;
+        }
+
+        let text = match socket.read_message() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(err))
+                if err.kind() == std::io::ErrorKind::WouldBlock
+                    || err.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        match text.as_str() {
+            "HELLO" => socket.write_message(Message::Text(format!("SESSION {}", peer)))?,
+            "SESSION_OK" => {
+                if let Some(tx) = tx.take() {
+                    create_offer(webrtcbin, tx)?;
+                }
+            }
+            text if text.starts_with("ERROR") => bail!("Signalling server error: {}", text),
+            text => handle_message(webrtcbin, &serde_json::from_str(text)?)?,
+        }
+    }
+}
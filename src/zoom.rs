@@ -0,0 +1,141 @@
+//! Digital zoom into a region of the video, panned by dragging.
+//!
+//! A `videocrop` among the display filters keeps the region shown, which the
+//! sink scales up to the render rectangle. The region keeps the aspect ratio
+//! of the video and stays within it, and the overlays after it are drawn at
+//! the size of the region, so they stay legible when zoomed in.
+
+use anyhow::Error;
+use gst::prelude::*;
+use tracing::{debug, warn};
+
+use crate::make;
+
+const MAX_FACTOR: f64 = 8.0;
+/// Zoom factor of a scroll wheel click.
+const STEP: f64 = 1.25;
+
+pub struct Zoom {
+    videocrop: gst::Element,
+    factor: f64,
+    /// Center of the region, in fractions of the video size.
+    center: (f64, f64),
+}
+
+impl Zoom {
+    pub fn new() -> Result<Self, Error> {
+        let videocrop = make("videocrop")?;
+
+        Ok(Zoom {
+            videocrop,
+            factor: 1.0,
+            center: (0.5, 0.5),
+        })
+    }
+
+    /// The elements to link in front of the sink.
+    pub fn elements(&self) -> Vec<&gst::Element> {
+        vec![&self.videocrop]
+    }
+
+    /// Zoom in by `steps` wheel clicks, or out if negative.
+    pub fn zoom(&mut self, steps: f64) {
+        self.factor = (self.factor * STEP.powf(steps)).clamp(1.0, MAX_FACTOR);
+        self.apply();
+    }
+
+    /// Zoom in by `ratio`, or out if below 1, as a pinch spreading the
+    /// fingers that much further apart.
+    pub fn scale(&mut self, ratio: f64) {
+        self.factor = (self.factor * ratio).clamp(1.0, MAX_FACTOR);
+        self.apply();
+    }
+
+    /// Drag the region along by `(dx, dy)`, in fractions of the render
+    /// rectangle.
+    pub fn pan(&mut self, (dx, dy): (f64, f64)) {
+        // The video follows the pointer, so the region goes the other way
+        self.center.0 -= dx / self.factor;
+        self.center.1 -= dy / self.factor;
+        self.apply();
+    }
+
+    /// The zoom factor, and the center of the region.
+    pub fn get(&self) -> (f64, (f64, f64)) {
+        (self.factor, self.center)
+    }
+
+    pub fn set(&mut self, factor: f64, center: (f64, f64)) {
+        self.factor = factor.clamp(1.0, MAX_FACTOR);
+        self.center = center;
+        self.apply();
+    }
+
+    /// Show the whole video again.
+    pub fn reset(&mut self) {
+        self.factor = 1.0;
+        self.center = (0.5, 0.5);
+        self.apply();
+    }
+
+    /// The size of the video coming in, once it is negotiated.
+    fn video_size(&self) -> Option<(i32, i32)> {
+        let caps = self.videocrop.get_static_pad("sink")?.get_current_caps()?;
+        let info = gst_video::VideoInfo::from_caps(&caps).ok()?;
+        Some((info.width() as i32, info.height() as i32))
+    }
+
+    fn apply(&mut self) {
+        let (width, height) = match self.video_size() {
+            Some(size) => size,
+            None => return,
+        };
+        // The center is clamped so that the region stays within the video
+        let half = 0.5 / self.factor;
+        self.center.0 = self.center.0.clamp(half, 1.0 - half);
+        self.center.1 = self.center.1.clamp(half, 1.0 - half);
+
+        let crop_width = (width as f64 / self.factor).round() as i32;
+        let crop_height = (height as f64 / self.factor).round() as i32;
+        let left = ((self.center.0 - half) * width as f64).round() as i32;
+        let top = ((self.center.1 - half) * height as f64).round() as i32;
+        let right = (width - crop_width - left).max(0);
+        let bottom = (height - crop_height - top).max(0);
+        debug!(factor = self.factor, left, top, right, bottom, "Zoom");
+        let crop = [("left", left), ("right", right), ("top", top), ("bottom", bottom)];
+        for (property, value) in &crop {
+            if let Err(err) = self.videocrop.set_property(property, value) {
+                warn!("Failed to zoom: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_the_zoom_factor() {
+        gst::init().unwrap();
+        let mut zoom = Zoom::new().unwrap();
+        zoom.zoom(1.0);
+        assert_eq!(zoom.factor, STEP);
+        zoom.zoom(100.0);
+        assert_eq!(zoom.factor, MAX_FACTOR);
+        zoom.zoom(-100.0);
+        assert_eq!(zoom.factor, 1.0);
+    }
+
+    #[test]
+    fn pans_against_the_drag() {
+        gst::init().unwrap();
+        let mut zoom = Zoom::new().unwrap();
+        zoom.factor = 2.0;
+        zoom.pan((0.5, -0.25));
+        assert_eq!(zoom.center, (0.25, 0.625));
+
+        zoom.reset();
+        assert_eq!((zoom.factor, zoom.center), (1.0, (0.5, 0.5)));
+    }
+}